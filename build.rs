@@ -0,0 +1,13 @@
+// Generates the gRPC service of the grpc feature from its .proto, with the
+// protoc that comes with protoc-bin-vendored.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        tonic_build::compile_protos("proto/transacto.proto")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::digest::hex;
+use super::transactions::Transaction;
+
+#[cfg(test)]
+#[path = "audit_tests.rs"]
+mod audit_tests;
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("invalid audit log entry on line {line}, err={err}")]
+    Invalid { line: u64, err: serde_json::Error },
+    #[error("audit log entry {0} is out of sequence")]
+    Sequence(u64),
+    #[error("audit log entry {0} doesn't chain to the one before it")]
+    Chain(u64),
+    #[error("audit log entry {0} doesn't match its hash")]
+    Hash(u64),
+}
+
+/// What the first entry chains to.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An applied transaction in an audit log. Sequence numbers start at 0 and
+/// increase by one for every entry. `previous` is the hash of the entry
+/// before, and `hash` the hex encoded SHA-256 of `previous` followed by the
+/// JSON of the sequence number and transaction.
+#[derive(Deserialize)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    pub transaction: Transaction,
+    pub previous: String,
+    pub hash: String,
+}
+
+// Same layout as `AuditEntry`, without taking the transaction.
+#[derive(Serialize)]
+struct AuditEntryRef<'a> {
+    sequence: u64,
+    transaction: &'a Transaction,
+    previous: &'a str,
+    hash: &'a str,
+}
+
+// What an entry's hash covers, besides the previous hash.
+#[derive(Serialize)]
+struct Hashed<'a> {
+    sequence: u64,
+    transaction: &'a Transaction,
+}
+
+/// The end of an audit log, the entry all the others chain to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditHead {
+    pub entries: u64,
+    /// The hash of the last entry.
+    pub hash: String,
+}
+
+/// A tamper-evident trail of the transactions a ledger applied, see
+/// `Ledger::use_audit_log`. Every entry is a line of JSON chained to the one
+/// before by its hash, so altering, removing or reordering entries breaks
+/// the chain from there on, see `verify_audit_log`. Cutting entries off the
+/// end doesn't, keep the head elsewhere to catch that.
+pub struct AuditLog {
+    file: File,
+    head: AuditHead,
+}
+
+impl AuditLog {
+    /// Opens the audit log at this path, creating it if needed. The entries
+    /// already in it are verified, new ones continue their chain.
+    pub fn open(path: impl AsRef<Path>) -> Result<AuditLog, AuditError> {
+        let file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let head = verify_audit_log(BufReader::new(&file))?;
+
+        Ok(AuditLog { file, head })
+    }
+
+    pub fn append(&mut self, transaction: &Transaction) -> Result<(), AuditError> {
+        let sequence = self.head.entries;
+        let hash = hash(&self.head.hash, sequence, transaction)?;
+        let entry = AuditEntryRef {
+            sequence,
+            transaction,
+            previous: &self.head.hash,
+            hash: &hash,
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(io::Error::from)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+
+        self.head = AuditHead {
+            entries: sequence + 1,
+            hash,
+        };
+        Ok(())
+    }
+
+    pub fn head(&self) -> &AuditHead {
+        &self.head
+    }
+}
+
+/// Checks that every entry of an audit log chains to the one before it and
+/// matches its hash, failing at the first one that doesn't. Returns the head
+/// of the log, to be compared with one kept elsewhere.
+pub fn verify_audit_log(reader: impl BufRead) -> Result<AuditHead, AuditError> {
+    let mut head = AuditHead {
+        entries: 0,
+        hash: GENESIS.to_string(),
+    };
+    for line in reader.lines() {
+        let entry: AuditEntry = serde_json::from_str(&line?).map_err(|err| AuditError::Invalid {
+            line: head.entries + 1,
+            err,
+        })?;
+        if entry.sequence != head.entries {
+            return Err(AuditError::Sequence(head.entries));
+        }
+        if entry.previous != head.hash {
+            return Err(AuditError::Chain(entry.sequence));
+        }
+        if hash(&entry.previous, entry.sequence, &entry.transaction)? != entry.hash {
+            return Err(AuditError::Hash(entry.sequence));
+        }
+
+        head = AuditHead {
+            entries: entry.sequence + 1,
+            hash: entry.hash,
+        };
+    }
+
+    Ok(head)
+}
+
+fn hash(previous: &str, sequence: u64, transaction: &Transaction) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(serde_json::to_vec(&Hashed { sequence, transaction })?);
+
+    Ok(hex(&hasher.finalize()))
+}
@@ -0,0 +1,51 @@
+use std::fs;
+
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Deposit, Dispute, Withdrawal};
+
+#[test]
+fn test_audit_log() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("audit.jsonl");
+
+    let mut ledger = Ledger::new();
+    ledger.use_audit_log(AuditLog::open(&path)?);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    // Neither failed nor repeated transactions are logged.
+    assert!(ledger
+        .execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(20))?))
+        .is_err());
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    drop(ledger);
+
+    // Reopened, the chain continues.
+    let mut audit = AuditLog::open(&path)?;
+    assert_eq!(audit.head().entries, 2);
+    audit.append(&Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(1))?))?;
+    let head = audit.head().clone();
+    drop(audit);
+    assert_eq!(verify_audit_log(BufReader::new(File::open(&path)?))?, head);
+
+    let log = fs::read_to_string(&path)?;
+    let altered = log.replacen("\"10\"", "\"100\"", 1);
+    if let Err(err) = verify_audit_log(altered.as_bytes()) {
+        assert!(matches!(err, AuditError::Hash(0)), "{}", err);
+    } else {
+        bail!("altered entry should not verify");
+    }
+
+    let removed = &log[log.find('\n').unwrap() + 1..];
+    if let Err(err) = verify_audit_log(removed.as_bytes()) {
+        assert!(matches!(err, AuditError::Sequence(0)), "{}", err);
+    } else {
+        bail!("log without its first entry should not verify");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::snapshot::SnapshotOptions;
+
+#[cfg(test)]
+#[path = "checkpoint_tests.rs"]
+mod checkpoint_tests;
+
+/// When the ledger saves a checkpoint, see `Ledger::use_checkpoints`. A
+/// checkpoint is due as soon as either limit is reached.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckpointPolicy {
+    /// Number of transactions executed since the last checkpoint.
+    pub every_transactions: Option<u64>,
+    /// Time since the last checkpoint.
+    pub every: Option<Duration>,
+}
+
+impl Default for CheckpointPolicy {
+    fn default() -> Self {
+        CheckpointPolicy {
+            every_transactions: Some(100_000),
+            every: None,
+        }
+    }
+}
+
+pub(super) struct Checkpoints {
+    pub(super) path: PathBuf,
+    pub(super) policy: CheckpointPolicy,
+    pub(super) options: SnapshotOptions,
+    pub(super) executed: u64,
+    pub(super) last: Instant,
+}
+
+impl Checkpoints {
+    pub(super) fn new(path: PathBuf, policy: CheckpointPolicy, options: SnapshotOptions) -> Checkpoints {
+        Checkpoints {
+            path,
+            policy,
+            options,
+            executed: 0,
+            last: Instant::now(),
+        }
+    }
+
+    pub(super) fn is_due(&self) -> bool {
+        self.policy
+            .every_transactions
+            .map_or(false, |every_transactions| self.executed >= every_transactions)
+            || self.policy.every.map_or(false, |every| self.last.elapsed() >= every)
+    }
+
+    pub(super) fn reset(&mut self) {
+        self.executed = 0;
+        self.last = Instant::now();
+    }
+}
@@ -0,0 +1,73 @@
+use std::thread;
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Deposit, Transaction};
+use crate::accounting::wal::Wal;
+
+#[test]
+fn test_policy() {
+    let mut checkpoints = Checkpoints::new(
+        PathBuf::new(),
+        CheckpointPolicy {
+            every_transactions: Some(2),
+            every: None,
+        },
+        SnapshotOptions::default(),
+    );
+    assert_eq!(checkpoints.is_due(), false);
+    checkpoints.executed = 2;
+    assert_eq!(checkpoints.is_due(), true);
+    checkpoints.reset();
+    assert_eq!(checkpoints.is_due(), false);
+
+    checkpoints.policy = CheckpointPolicy {
+        every_transactions: None,
+        every: Some(Duration::from_millis(10)),
+    };
+    assert_eq!(checkpoints.is_due(), false);
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(checkpoints.is_due(), true);
+}
+
+#[test]
+fn test_checkpoints_truncate_wal() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.checkpoint");
+    let wal_dir = dir.path().join("wal");
+
+    let mut ledger = Ledger::new();
+    ledger.use_wal(Wal::open(&wal_dir)?);
+    let policy = CheckpointPolicy {
+        every_transactions: Some(3),
+        every: None,
+    };
+    assert_eq!(ledger.is_checkpointed(), false);
+    ledger.use_checkpoints(&path, policy, SnapshotOptions::default());
+
+    let mut saved = Vec::new();
+    for id in 0..7 {
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(id, 0, dec!(1))?))?;
+        saved.push(ledger.checkpoint_if_due()?);
+        assert_eq!(ledger.is_checkpointed(), saved[id as usize]);
+    }
+    assert_eq!(saved, vec![false, false, true, false, false, true, false]);
+
+    // Only what was journaled since the last checkpoint is left.
+    let checkpoint: Ledger = Ledger::load(&path)?;
+    assert_eq!(checkpoint.wal_sequence(), 6);
+    assert_eq!(checkpoint.clients.get(&0).unwrap().available(), dec!(6));
+    let entries = Wal::read(&wal_dir)?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(entries.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), vec![6]);
+
+    ledger.checkpoint()?;
+    assert_eq!(Wal::read(&wal_dir)?.count(), 0);
+    let checkpoint: Ledger = Ledger::load(&path)?;
+    assert_eq!(checkpoint.wal_sequence(), 7);
+
+    Ok(())
+}
@@ -1,64 +1,345 @@
 use anyhow::Result;
-use getset::CopyGetters;
+use getset::{CopyGetters, Getters};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
-use super::TransactionError;
+use super::policy::LedgerPolicy;
+use super::{ClientId, TransactionError};
 
-const PRECISION: u32 = 4;
-
-#[derive(CopyGetters)]
+#[derive(Clone, Debug, PartialEq, CopyGetters, Getters, Serialize, Deserialize)]
 pub struct Client {
     #[get_copy = "pub"]
-    id: u16,
+    id: ClientId,
     #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str")]
     available: Decimal,
     #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str")]
     held: Decimal,
     #[get_copy = "pub"]
     locked: bool,
+    /// Set by a `Freeze`, e.g. while the account is reviewed for fraud.
+    /// Unlike a lock it can be taken back.
+    #[get_copy = "pub"]
+    frozen: bool,
+    /// Set by a `CloseAccount`, for good.
+    #[get_copy = "pub"]
+    closed: bool,
+    /// How far withdrawals may take the available funds below zero, see
+    /// `SetCreditLimit`.
+    #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str")]
+    credit_limit: Decimal,
+    #[get = "pub"]
+    #[serde(default)]
+    metadata: ClientMetadata,
+}
+
+/// Who a client is, for the reports, see `Ledger::set_client_metadata`. The
+/// ledger itself doesn't use it, so it's kept as given, e.g. `opened_at`
+/// isn't parsed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientMetadata {
+    pub display_name: Option<String>,
+    /// The client's id in another system, e.g. a CRM.
+    pub external_ref: Option<String>,
+    pub opened_at: Option<String>,
+}
+
+/// The layout of `Client` in snapshots of version 7.
+#[derive(Deserialize)]
+pub(super) struct ClientV7 {
+    id: ClientId,
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+    locked: bool,
+    frozen: bool,
+    closed: bool,
+    #[serde(with = "rust_decimal::serde::str")]
+    credit_limit: Decimal,
+}
+
+/// The layout of `Client` in snapshots of version 6.
+#[derive(Deserialize)]
+pub(super) struct ClientV6 {
+    id: ClientId,
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+    locked: bool,
+    frozen: bool,
+    closed: bool,
+}
+
+/// The layout of `Client` in snapshots of version 5.
+#[derive(Deserialize)]
+pub(super) struct ClientV5 {
+    id: ClientId,
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+    locked: bool,
+    frozen: bool,
+}
+
+/// The layout of `Client` in snapshots before version 5, see
+/// `snapshot::read`.
+#[derive(Deserialize)]
+pub(super) struct ClientV4 {
+    id: ClientId,
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+    locked: bool,
+}
+
+impl From<ClientV4> for Client {
+    fn from(client: ClientV4) -> Self {
+        Client {
+            id: client.id,
+            available: client.available,
+            held: client.held,
+            locked: client.locked,
+            frozen: false,
+            closed: false,
+            credit_limit: Decimal::ZERO,
+            metadata: ClientMetadata::default(),
+        }
+    }
+}
+
+impl From<ClientV7> for Client {
+    fn from(client: ClientV7) -> Self {
+        Client {
+            id: client.id,
+            available: client.available,
+            held: client.held,
+            locked: client.locked,
+            frozen: client.frozen,
+            closed: client.closed,
+            credit_limit: client.credit_limit,
+            metadata: ClientMetadata::default(),
+        }
+    }
+}
+
+impl From<ClientV6> for Client {
+    fn from(client: ClientV6) -> Self {
+        Client {
+            id: client.id,
+            available: client.available,
+            held: client.held,
+            locked: client.locked,
+            frozen: client.frozen,
+            closed: client.closed,
+            credit_limit: Decimal::ZERO,
+            metadata: ClientMetadata::default(),
+        }
+    }
+}
+
+impl From<ClientV5> for Client {
+    fn from(client: ClientV5) -> Self {
+        Client {
+            id: client.id,
+            available: client.available,
+            held: client.held,
+            locked: client.locked,
+            frozen: client.frozen,
+            closed: false,
+            credit_limit: Decimal::ZERO,
+            metadata: ClientMetadata::default(),
+        }
+    }
 }
 
 impl Client {
-    pub fn new(id: u16) -> Client {
+    pub fn new(id: ClientId) -> Client {
         Client {
             id,
             available: Decimal::ZERO,
             held: Decimal::ZERO,
             locked: false,
+            frozen: false,
+            closed: false,
+            credit_limit: Decimal::ZERO,
+            metadata: ClientMetadata::default(),
         }
     }
 
-    pub fn deposit(&mut self, amount: Decimal) {
-        self.available = (self.available + amount).round_dp(PRECISION);
+    /// Rebuilds a client taken apart by a storage backend, no validation is
+    /// done. The metadata is set apart, see `set_metadata`.
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    pub(crate) fn from_parts(
+        id: ClientId,
+        available: Decimal,
+        held: Decimal,
+        locked: bool,
+        frozen: bool,
+        closed: bool,
+        credit_limit: Decimal,
+    ) -> Client {
+        Client {
+            id,
+            available,
+            held,
+            locked,
+            frozen,
+            closed,
+            credit_limit,
+            metadata: ClientMetadata::default(),
+        }
+    }
+
+    /// Rounds the funds as the policy says, see `LedgerPolicy::round`. The
+    /// other functions don't round, the ledger does once a transaction
+    /// changed the client.
+    pub fn round(&mut self, policy: &LedgerPolicy) {
+        self.available = policy.round(self.available);
+        self.held = policy.round(self.held);
+    }
+
+    /// Sets the funds, failing with AmountOverflow (and changing nothing) if
+    /// either of them, or their total, is out of a Decimal's range.
+    fn set_funds(&mut self, available: Option<Decimal>, held: Option<Decimal>) -> Result<(), TransactionError> {
+        match (available, held) {
+            (Some(available), Some(held)) if available.checked_add(held).is_some() => {
+                self.available = available;
+                self.held = held;
+                Ok(())
+            },
+            _ => Err(TransactionError::AmountOverflow),
+        }
     }
 
-    pub fn withdraw(&mut self, amount: Decimal) -> Result<(), TransactionError> {
+    pub fn deposit(&mut self, amount: Decimal) -> Result<(), TransactionError> {
+        self.set_funds(self.available.checked_add(amount), Some(self.held))
+    }
+
+    /// Fails with BelowMinimumBalance if it would leave less than
+    /// `minimum_balance` available.
+    pub fn withdraw(&mut self, amount: Decimal, minimum_balance: Option<Decimal>) -> Result<(), TransactionError> {
+        if self.closed {
+            return Err(TransactionError::AccountClosed);
+        }
+
         if self.locked {
             return Err(TransactionError::AccountLocked);
         }
 
-        if self.available < amount {
+        if self.frozen {
+            return Err(TransactionError::AccountFrozen);
+        }
+
+        // Both are positive, so this can't overflow.
+        if self.available < amount - self.credit_limit {
             return Err(TransactionError::InsufficientFunds);
         }
 
-        self.available = (self.available - amount).round_dp(PRECISION);
+        let available = self.available.checked_sub(amount);
+        if minimum_balance
+            .zip(available)
+            .map_or(false, |(minimum, available)| available < minimum)
+        {
+            return Err(TransactionError::BelowMinimumBalance);
+        }
 
-        Ok(())
+        self.set_funds(available, Some(self.held))
+    }
+
+    pub fn hold_funds(&mut self, amount: Decimal) -> Result<(), TransactionError> {
+        self.set_funds(self.available.checked_sub(amount), self.held.checked_add(amount))
     }
 
-    pub fn hold_funds(&mut self, amount: Decimal) {
-        self.available = (self.available - amount).round_dp(PRECISION);
-        self.held = (self.held + amount).round_dp(PRECISION);
+    pub fn release_funds(&mut self, amount: Decimal) -> Result<(), TransactionError> {
+        self.set_funds(self.available.checked_add(amount), self.held.checked_sub(amount))
     }
 
-    pub fn release_funds(&mut self, amount: Decimal) {
-        self.held = (self.held - amount).round_dp(PRECISION);
-        self.available = (self.available + amount).round_dp(PRECISION);
+    pub fn chargeback(&mut self, amount: Decimal) -> Result<(), TransactionError> {
+        self.set_funds(Some(self.available), self.held.checked_sub(amount))?;
+        self.locked = true;
+
+        Ok(())
     }
 
-    pub fn chargeback(&mut self, amount: Decimal) {
-        self.held = (self.held - amount).round_dp(PRECISION);
+    /// Credits back a disputed withdrawal, see
+    /// `LedgerPolicy::withdrawal_disputes`. Nothing was held for it.
+    pub fn chargeback_withdrawal(&mut self, amount: Decimal) -> Result<(), TransactionError> {
+        self.set_funds(self.available.checked_add(amount), Some(self.held))?;
         self.locked = true;
+
+        Ok(())
+    }
+
+    /// Takes a fee from the available funds, see `FeePolicy`. Like a
+    /// chargeback this works on a locked account, and may leave it with
+    /// negative funds.
+    pub fn charge_fee(&mut self, fee: Decimal) -> Result<(), TransactionError> {
+        self.set_funds(self.available.checked_sub(fee), Some(self.held))
+    }
+
+    /// Takes back a deposit. Unlike a withdrawal this works on a locked
+    /// account, and may leave it with negative funds.
+    pub fn reverse_deposit(&mut self, amount: Decimal) -> Result<(), TransactionError> {
+        self.set_funds(self.available.checked_sub(amount), Some(self.held))
+    }
+
+    pub fn set_metadata(&mut self, metadata: ClientMetadata) {
+        self.metadata = metadata;
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    /// Lets withdrawals take the available funds down to `-limit`. A negative
+    /// limit fails with InvalidAmount. Lowering the limit doesn't change the
+    /// available funds, even if they're below it.
+    pub fn set_credit_limit(&mut self, limit: Decimal) -> Result<(), TransactionError> {
+        if limit.is_sign_negative() {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        self.credit_limit = limit;
+
+        Ok(())
+    }
+
+    /// Fails with FundsHeld while a dispute still holds funds. Whatever is
+    /// available stays on the account.
+    pub fn close(&mut self) -> Result<(), TransactionError> {
+        if !self.held.is_zero() {
+            return Err(TransactionError::FundsHeld);
+        }
+
+        self.closed = true;
+
+        Ok(())
+    }
+
+    /// Adds the funds of the same client's account in another ledger, see
+    /// `Ledger::merge`. Locked, frozen or closed if either account is, with
+    /// the higher of their credit limits.
+    pub fn merge(&mut self, other: &Client) -> Result<(), TransactionError> {
+        self.set_funds(
+            self.available.checked_add(other.available),
+            self.held.checked_add(other.held),
+        )?;
+        self.locked |= other.locked;
+        self.frozen |= other.frozen;
+        self.closed |= other.closed;
+        self.credit_limit = self.credit_limit.max(other.credit_limit);
+
+        Ok(())
     }
 
     pub fn get_total(&self) -> Decimal {
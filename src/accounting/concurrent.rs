@@ -0,0 +1,156 @@
+use dashmap::iter::Iter;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+
+use super::client::Client;
+use super::ledger::Ledger;
+use super::policy::LedgerPolicy;
+use super::transactions::Transaction;
+use super::{BuildHasher, ClientId, ExecutableTransaction, LedgerAccess, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "concurrent_tests.rs"]
+mod concurrent_tests;
+
+/// A ledger that can be shared between threads (e.g. behind an `Arc`) and
+/// executed against concurrently. Clients and transactions are kept in sharded
+/// maps, so transactions for different clients rarely contend with each other.
+///
+/// `execute_transaction` executes transactions like `Ledger`'s does with
+/// none of its `use_*` options, by the policy set with `use_policy`. It
+/// differs in that:
+/// - the policy's `daily_withdrawal_limit` isn't applied, as the day's
+///   totals aren't kept,
+/// - every transaction with an id stays stored, as there's no `compact` and
+///   settled ids aren't tracked (e.g. by `Ledger::use_id_filter`),
+/// - open disputes aren't counted, they are once it's turned into a
+///   `Ledger`.
+#[derive(Default)]
+pub struct ConcurrentLedger {
+    clients: DashMap<ClientId, Client, BuildHasher>,
+    transactions: DashMap<TxId, Transaction, BuildHasher>,
+    policy: LedgerPolicy,
+}
+
+impl ConcurrentLedger {
+    pub fn new() -> ConcurrentLedger {
+        ConcurrentLedger {
+            clients: DashMap::default(),
+            transactions: DashMap::default(),
+            policy: LedgerPolicy::default(),
+        }
+    }
+
+    /// Sets the rules transactions are executed by from now on, see
+    /// `Ledger::use_policy`. It's kept when turned into a `Ledger`.
+    pub fn use_policy(&mut self, policy: LedgerPolicy) {
+        self.policy = policy;
+    }
+
+    /// Transactions that have their own global unique id will be stored.
+    /// If the id already exists then the transaction is discarded.
+    pub fn execute_transaction(&self, transaction: Transaction) -> Result<(), TransactionError> {
+        let Some(id) = transaction.id() else {
+            return transaction.execute(&mut ConcurrentAccess(self));
+        };
+
+        // Holding on to the entry makes any concurrent transaction with the
+        // same id wait until this one is either stored or rejected.
+        let entry = match self.transactions.entry(id) {
+            // The transaction has already been processed, ignore.
+            Entry::Occupied(_) => return Ok(()),
+            Entry::Vacant(entry) => entry,
+        };
+
+        transaction.execute(&mut ConcurrentAccess(self))?;
+        entry.insert(transaction);
+
+        Ok(())
+    }
+
+    pub fn clients_iter(&self) -> Iter<ClientId, Client, BuildHasher> {
+        self.clients.iter()
+    }
+
+    pub fn clients_len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn transactions_len(&self) -> usize {
+        self.transactions.len()
+    }
+}
+
+impl From<ConcurrentLedger> for Ledger {
+    fn from(ledger: ConcurrentLedger) -> Self {
+        let mut result = Ledger::new();
+        result.use_policy(ledger.policy);
+        result.clients = ledger.clients.into_iter().collect();
+        result.transactions = ledger.transactions.into_iter().collect();
+        let open_disputes = result
+            .transactions
+            .values()
+            .filter(|transaction| transaction.under_dispute())
+            .count();
+        result.set_open_disputes(open_disputes);
+        result
+    }
+}
+
+/// To avoid deadlocks the transactions map is always locked before the clients
+/// map, and a client is never held while looking up a transaction. Transactions
+/// that carry their own id execute while their (vacant) entry is held, so they
+/// must not look up other stored transactions.
+struct ConcurrentAccess<'a>(&'a ConcurrentLedger);
+
+impl LedgerAccess for ConcurrentAccess<'_> {
+    fn policy(&self) -> LedgerPolicy {
+        self.0.policy
+    }
+
+    fn client_or_insert(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let mut client = self.0.clients.entry(client_id).or_insert(Client::new(client_id));
+        f(&mut client)?;
+        client.round(&self.policy());
+        Ok(())
+    }
+
+    fn client(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        if let Some(mut client) = self.0.clients.get_mut(&client_id) {
+            f(&mut client)?;
+            client.round(&self.policy());
+            Ok(())
+        } else {
+            Err(TransactionError::ClientNotFound)
+        }
+    }
+
+    fn client_and_transaction(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        f: impl FnOnce(&mut Client, &mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let transaction = self.0.transactions.get_mut(&tx_id);
+
+        if let Some(mut client) = self.0.clients.get_mut(&client_id) {
+            if let Some(mut transaction) = transaction {
+                f(&mut client, &mut transaction)?;
+                client.round(&self.policy());
+                Ok(())
+            } else {
+                Err(TransactionError::TransactionNotFound)
+            }
+        } else {
+            Err(TransactionError::ClientNotFound)
+        }
+    }
+}
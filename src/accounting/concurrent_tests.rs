@@ -0,0 +1,115 @@
+use std::thread;
+
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Withdrawal};
+
+#[test]
+fn test_same_semantics_as_ledger() -> Result<()> {
+    let ledger = ConcurrentLedger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(4))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(1))?)) {
+        assert_eq!(err, TransactionError::ClientNotFound);
+    } else {
+        bail!("withdrawal should not create a new client");
+    }
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(7, 0))) {
+        assert_eq!(err, TransactionError::TransactionNotFound);
+    } else {
+        bail!("dispute should fail for unknown transactions");
+    }
+
+    let ledger: Ledger = ledger.into();
+    let client = ledger.clients.get(&0).unwrap();
+    assert_eq!(client.available(), dec!(-4));
+    assert_eq!(client.held(), dec!(0));
+    assert_eq!(client.locked(), true);
+    assert_eq!(ledger.transactions.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_deposits() -> Result<()> {
+    let ledger = ConcurrentLedger::new();
+
+    thread::scope(|scope| {
+        for thread_id in 0..8 as TxId {
+            let ledger = &ledger;
+            scope.spawn(move || {
+                for i in 0..1000 as TxId {
+                    let id = thread_id * 1000 + i;
+                    let deposit = Deposit::new(id, (id % 4) as ClientId, dec!(1)).unwrap();
+                    ledger.execute_transaction(Transaction::Deposit(deposit)).unwrap();
+                }
+            });
+        }
+    });
+
+    assert_eq!(ledger.clients_len(), 4);
+    assert_eq!(ledger.transactions_len(), 8000);
+    for client in ledger.clients_iter() {
+        assert_eq!(client.available(), dec!(2000));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_duplicates_applied_once() -> Result<()> {
+    let ledger = ConcurrentLedger::new();
+
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            let ledger = &ledger;
+            scope.spawn(move || {
+                for id in 0..500 as TxId {
+                    let deposit = Deposit::new(id, 0, dec!(1.5)).unwrap();
+                    ledger.execute_transaction(Transaction::Deposit(deposit)).unwrap();
+                }
+            });
+        }
+    });
+
+    assert_eq!(ledger.transactions_len(), 500);
+    assert_eq!(ledger.clients_iter().next().unwrap().available(), dec!(750));
+
+    Ok(())
+}
+
+#[test]
+fn test_policy() -> Result<()> {
+    let mut ledger = ConcurrentLedger::new();
+    ledger.use_policy(LedgerPolicy {
+        max_amount: Some(dec!(100)),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(101))?)) {
+        assert_eq!(err, TransactionError::AmountTooLarge);
+    } else {
+        bail!("deposit should be over the policy's max amount");
+    }
+
+    // The policy and the open disputes carry over.
+    let mut ledger: Ledger = ledger.into();
+    assert_eq!(ledger.stats().open_disputes, 1);
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(101))?)) {
+        assert_eq!(err, TransactionError::AmountTooLarge);
+    } else {
+        bail!("deposit should be over the policy's max amount");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::{self, FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::ledger::Ledger;
+use super::policy::LedgerPolicy;
+
+#[cfg(test)]
+#[path = "currency_tests.rs"]
+mod currency_tests;
+
+/// An ISO 4217 currency code, e.g. USD. Lowercase codes are accepted and
+/// kept uppercase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency([u8; 3]);
+
+#[derive(Clone, Debug, PartialEq, Error)]
+#[error("invalid currency code: {0}")]
+pub struct InvalidCurrency(String);
+
+impl Currency {
+    /// XXX, the code of transactions without a currency.
+    pub const NONE: Currency = Currency(*b"XXX");
+
+    pub fn as_str(&self) -> &str {
+        // Only ever made of ASCII letters.
+        str::from_utf8(&self.0).expect("currency code is ASCII")
+    }
+}
+
+impl FromStr for Currency {
+    type Err = InvalidCurrency;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        match code.as_bytes() {
+            [a, b, c] if code.bytes().all(|letter| letter.is_ascii_alphabetic()) => Ok(Currency([
+                a.to_ascii_uppercase(),
+                b.to_ascii_uppercase(),
+                c.to_ascii_uppercase(),
+            ])),
+            _ => Err(InvalidCurrency(code.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = InvalidCurrency;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        code.parse()
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.as_str().to_string()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A ledger per currency, so that a client can hold balances in several.
+/// Transactions without a currency are in the default one. Disputes,
+/// resolves and chargebacks only find transactions of their own currency,
+/// and transaction ids only have to be unique within a currency. Iteration
+/// is ordered by currency.
+pub struct CurrencyLedgers {
+    default: Currency,
+    policy: LedgerPolicy,
+    policies: BTreeMap<Currency, LedgerPolicy>,
+    ledgers: BTreeMap<Currency, Ledger>,
+}
+
+impl CurrencyLedgers {
+    pub fn new(default: Currency) -> CurrencyLedgers {
+        CurrencyLedgers {
+            default,
+            policy: LedgerPolicy::default(),
+            policies: BTreeMap::new(),
+            ledgers: BTreeMap::new(),
+        }
+    }
+
+    pub fn default_currency(&self) -> Currency {
+        self.default
+    }
+
+    /// Sets the policy of every currency's ledger, including those created
+    /// later, see `Ledger::use_policy`. Currencies with a policy of their own
+    /// keep it.
+    pub fn use_policy(&mut self, policy: LedgerPolicy) {
+        self.policy = policy;
+        for (currency, ledger) in &mut self.ledgers {
+            if !self.policies.contains_key(currency) {
+                ledger.use_policy(policy);
+            }
+        }
+    }
+
+    /// Sets the policy of one currency's ledger, e.g. for its `precision`.
+    pub fn use_currency_policy(&mut self, currency: Currency, policy: LedgerPolicy) {
+        self.policies.insert(currency, policy);
+        if let Some(ledger) = self.ledgers.get_mut(&currency) {
+            ledger.use_policy(policy);
+        }
+    }
+
+    /// The currency's ledger, a new one if the currency wasn't seen before.
+    /// None is the default currency.
+    pub fn ledger(&mut self, currency: Option<Currency>) -> &mut Ledger {
+        let currency = currency.unwrap_or(self.default);
+        let policy = self.policies.get(&currency).copied().unwrap_or(self.policy);
+        self.ledgers.entry(currency).or_insert_with(|| {
+            let mut ledger = Ledger::new();
+            ledger.use_policy(policy);
+            ledger.use_currency(currency);
+            ledger
+        })
+    }
+
+    pub fn get(&self, currency: Currency) -> Option<&Ledger> {
+        self.ledgers.get(&currency)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Currency, &Ledger)> {
+        self.ledgers.iter().map(|(currency, ledger)| (*currency, ledger))
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::transactions::{Deposit, Transaction, Withdrawal};
+use crate::accounting::TransactionError;
+
+#[test]
+fn test_parse() -> Result<()> {
+    assert_eq!("usd".parse::<Currency>()?.to_string(), "USD");
+    assert_eq!(serde_json::from_str::<Currency>("\"JPY\"")?, "JPY".parse()?);
+    assert_eq!(serde_json::to_string(&"eur".parse::<Currency>()?)?, "\"EUR\"");
+    assert!("US".parse::<Currency>().is_err());
+    assert!("US1".parse::<Currency>().is_err());
+    assert!("EURO".parse::<Currency>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_ledgers() -> Result<()> {
+    let usd: Currency = "USD".parse()?;
+    let eur: Currency = "EUR".parse()?;
+    let mut ledgers = CurrencyLedgers::new(usd);
+    ledgers.use_policy(LedgerPolicy {
+        max_amount: Some(dec!(100)),
+        ..LedgerPolicy::default()
+    });
+
+    ledgers
+        .ledger(None)
+        .execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledgers
+        .ledger(Some(eur))
+        .execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(5))?))?;
+    assert_eq!(ledgers.get(usd).unwrap().clients[&1].available(), dec!(10));
+    assert_eq!(ledgers.get(eur).unwrap().clients[&1].available(), dec!(5));
+
+    // The policy applies to the ledgers created after it was set too.
+    let result = ledgers
+        .ledger(Some(eur))
+        .execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(200))?));
+    assert_eq!(result, Err(TransactionError::AmountTooLarge));
+
+    let jpy: Currency = "JPY".parse()?;
+    ledgers.use_currency_policy(
+        jpy,
+        LedgerPolicy {
+            precision: Some(0),
+            ..LedgerPolicy::default()
+        },
+    );
+    ledgers
+        .ledger(Some(jpy))
+        .execute_transaction(Transaction::Deposit(Deposit::new(4, 1, dec!(150.5))?))?;
+    assert_eq!(ledgers.get(jpy).unwrap().clients[&1].available(), dec!(150));
+
+    let currencies: Vec<Currency> = ledgers.iter().map(|(currency, _ledger)| currency).collect();
+    assert_eq!(currencies, vec![eur, jpy, usd]);
+
+    Ok(())
+}
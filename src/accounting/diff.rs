@@ -0,0 +1,200 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
+
+use super::client::Client;
+use super::ledger::Ledger;
+use super::ClientId;
+
+#[cfg(test)]
+#[path = "diff_tests.rs"]
+mod diff_tests;
+
+/// How the accounts of one ledger differ from those of another, see
+/// `Ledger::diff`. Printing it gives a report with a line per client.
+#[derive(Debug, Default, PartialEq)]
+pub struct LedgerDiff {
+    /// Ordered by client id.
+    pub clients: Vec<ClientDelta>,
+}
+
+/// A client whose account differs, as it was and as it is.
+#[derive(Debug, PartialEq)]
+pub struct ClientDelta {
+    pub client: ClientId,
+    /// `None` for a new client.
+    pub before: Option<Client>,
+    /// `None` for a client that is missing from the other ledger.
+    pub after: Option<Client>,
+}
+
+impl<T> Ledger<T> {
+    /// Compares the accounts with those of `other`, taking this ledger as
+    /// the earlier one, e.g. yesterday's snapshot against today's.
+    pub fn diff<U>(&self, other: &Ledger<U>) -> LedgerDiff {
+        let mut clients: Vec<ClientDelta> = other
+            .clients
+            .iter()
+            .filter_map(|(id, after)| {
+                let before = self.clients.get(id);
+                (before != Some(after)).then(|| ClientDelta {
+                    client: *id,
+                    before: before.cloned(),
+                    after: Some(after.clone()),
+                })
+            })
+            .collect();
+        clients.extend(
+            self.clients
+                .iter()
+                .filter(|(id, _before)| !other.clients.contains_key(id))
+                .map(|(id, before)| ClientDelta {
+                    client: *id,
+                    before: Some(before.clone()),
+                    after: None,
+                }),
+        );
+        clients.sort_by_key(|delta| delta.client);
+
+        LedgerDiff { clients }
+    }
+}
+
+impl LedgerDiff {
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// Change of the available funds over all clients. Every client's
+    /// funds fit in a Decimal, but all of them together may not, so the
+    /// change stops at its limits.
+    pub fn available(&self) -> Decimal {
+        self.clients
+            .iter()
+            .fold(Decimal::ZERO, |sum, delta| sum.saturating_add(delta.available()))
+    }
+
+    /// Change of the held funds over all clients, stopping at the limits of
+    /// a Decimal like `available`.
+    pub fn held(&self) -> Decimal {
+        self.clients
+            .iter()
+            .fold(Decimal::ZERO, |sum, delta| sum.saturating_add(delta.held()))
+    }
+
+    pub fn new_clients(&self) -> usize {
+        self.clients.iter().filter(|delta| delta.before.is_none()).count()
+    }
+}
+
+impl ClientDelta {
+    /// Change of the available funds, a missing client counts as having
+    /// none.
+    pub fn available(&self) -> Decimal {
+        self.after
+            .as_ref()
+            .map_or(Decimal::ZERO, Client::available)
+            .saturating_sub(self.before.as_ref().map_or(Decimal::ZERO, Client::available))
+    }
+
+    pub fn held(&self) -> Decimal {
+        self.after
+            .as_ref()
+            .map_or(Decimal::ZERO, Client::held)
+            .saturating_sub(self.before.as_ref().map_or(Decimal::ZERO, Client::held))
+    }
+
+    /// Whether the account is locked now, if that changed.
+    pub fn locked(&self) -> Option<bool> {
+        let before = self.before.as_ref().map_or(false, Client::locked);
+        let after = self.after.as_ref().map_or(false, Client::locked);
+        (before != after).then_some(after)
+    }
+}
+
+impl fmt::Display for LedgerDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for delta in &self.clients {
+            writeln!(f, "{}", delta)?;
+        }
+
+        if self.is_empty() {
+            write!(f, "no changes")
+        } else {
+            write!(
+                f,
+                "{} clients changed, {} new, available {}, held {}",
+                self.clients.len(),
+                self.new_clients(),
+                Signed(self.available()),
+                Signed(self.held())
+            )
+        }
+    }
+}
+
+impl fmt::Display for ClientDelta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (before, after) = match (&self.before, &self.after) {
+            (None, Some(after)) => {
+                write!(
+                    f,
+                    "client {}: new, available {}, held {}",
+                    self.client,
+                    after.available(),
+                    after.held()
+                )?;
+                if after.locked() {
+                    write!(f, ", locked")?;
+                }
+                return Ok(());
+            },
+            (Some(_), None) => return write!(f, "client {}: missing", self.client),
+            (Some(before), Some(after)) => (before, after),
+            (None, None) => return write!(f, "client {}: unchanged", self.client),
+        };
+
+        write!(f, "client {}:", self.client)?;
+        let mut separator = " ";
+        if before.available() != after.available() {
+            write!(
+                f,
+                "{}available {} -> {} ({})",
+                separator,
+                before.available(),
+                after.available(),
+                Signed(self.available())
+            )?;
+            separator = ", ";
+        }
+        if before.held() != after.held() {
+            write!(
+                f,
+                "{}held {} -> {} ({})",
+                separator,
+                before.held(),
+                after.held(),
+                Signed(self.held())
+            )?;
+            separator = ", ";
+        }
+        match self.locked() {
+            Some(true) => write!(f, "{}locked", separator),
+            Some(false) => write!(f, "{}unlocked", separator),
+            None => Ok(()),
+        }
+    }
+}
+
+// A change, with its sign even if it's positive.
+struct Signed(Decimal);
+
+impl fmt::Display for Signed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_sign_positive() {
+            write!(f, "+{}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Transaction, Withdrawal};
+
+#[test]
+fn test_diff() -> Result<()> {
+    let mut before = Ledger::new();
+    before.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    before.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(5))?))?;
+    before.execute_transaction(Transaction::Deposit(Deposit::new(3, 4, dec!(2))?))?;
+
+    let mut after = before.fork();
+    after.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    after.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 1)))?;
+    after.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 2, dec!(1.5))?))?;
+    after.execute_transaction(Transaction::Deposit(Deposit::new(5, 3, dec!(1))?))?;
+
+    let diff = before.diff(&after);
+    assert_eq!(
+        diff.clients.iter().map(|delta| delta.client).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(diff.clients[0].available(), dec!(-10));
+    assert_eq!(diff.clients[0].held(), dec!(0));
+    assert_eq!(diff.clients[0].locked(), Some(true));
+    assert_eq!(diff.clients[1].locked(), None);
+    assert_eq!(diff.available(), dec!(-10.5));
+    assert_eq!(diff.new_clients(), 1);
+
+    assert_eq!(
+        diff.to_string(),
+        "client 1: available 10 -> 0 (-10), locked
+client 2: available 5 -> 3.5 (-1.5)
+client 3: new, available 1, held 0
+3 clients changed, 1 new, available -10.5, held +0"
+    );
+
+    // The other way around.
+    let diff = after.diff(&before);
+    assert_eq!(diff.clients[0].locked(), Some(false));
+    assert_eq!(diff.clients[2].after, None);
+    assert_eq!(diff.clients[2].to_string(), "client 3: missing");
+
+    assert!(before.diff(&before.fork()).is_empty());
+    assert_eq!(LedgerDiff::default().to_string(), "no changes");
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_saturates() -> Result<()> {
+    let before = Ledger::new();
+    let mut after = Ledger::new();
+    after.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, Decimal::MAX)?))?;
+    after.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, Decimal::MAX)?))?;
+
+    let diff = before.diff(&after);
+    assert_eq!(diff.available(), Decimal::MAX);
+    assert!(diff
+        .to_string()
+        .ends_with("2 clients changed, 2 new, available +79228162514264337593543950335, held +0"));
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use sha2::{Digest, Sha256};
+
+use super::client::Client;
+use super::ledger::Ledger;
+use super::ClientId;
+
+#[cfg(test)]
+#[path = "digest_tests.rs"]
+mod digest_tests;
+
+pub type Hash = [u8; 32];
+
+/// A Merkle tree over the accounts of a ledger, see `Ledger::state_digest`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateDigest {
+    /// The hash of every account, ordered by client id.
+    leaves: Vec<(ClientId, Hash)>,
+    root: Hash,
+}
+
+impl StateDigest {
+    /// Hex encoded, the same for any two ledgers with the same accounts.
+    pub fn root(&self) -> String {
+        hex(&self.root)
+    }
+
+    /// The hex encoded hash of the client's account.
+    pub fn client(&self, client_id: ClientId) -> Option<String> {
+        self.leaves
+            .binary_search_by_key(&client_id, |(id, _hash)| *id)
+            .ok()
+            .map(|index| hex(&self.leaves[index].1))
+    }
+
+    /// The clients whose accounts differ from those in `other`, including
+    /// those only one of them has, ordered by id.
+    pub fn differing_clients(&self, other: &StateDigest) -> Vec<ClientId> {
+        let mut clients = Vec::new();
+        if self.root == other.root {
+            return clients;
+        }
+
+        let (mut ours, mut theirs) = (self.leaves.iter().peekable(), other.leaves.iter().peekable());
+        loop {
+            match (ours.peek(), theirs.peek()) {
+                (Some((id, hash)), Some((other_id, other_hash))) => match id.cmp(other_id) {
+                    Ordering::Less => {
+                        clients.push(*id);
+                        ours.next();
+                    },
+                    Ordering::Greater => {
+                        clients.push(*other_id);
+                        theirs.next();
+                    },
+                    Ordering::Equal => {
+                        if hash != other_hash {
+                            clients.push(*id);
+                        }
+                        ours.next();
+                        theirs.next();
+                    },
+                },
+                (Some((id, _hash)), None) => {
+                    clients.push(*id);
+                    ours.next();
+                },
+                (None, Some((id, _hash))) => {
+                    clients.push(*id);
+                    theirs.next();
+                },
+                (None, None) => return clients,
+            }
+        }
+    }
+}
+
+impl<T> Ledger<T> {
+    /// A deterministic digest of the clients' accounts, so that ledgers
+    /// built separately, e.g. two runs over the same file, can be compared by
+    /// their roots alone. The leaves are the SHA-256 of every account, in
+    /// order of client id, and each node hashes the two below it; an odd one
+    /// out moves up a level as is. Amounts are normalized, 1.50 hashes like
+    /// 1.5.
+    pub fn state_digest(&self) -> StateDigest {
+        let mut clients: Vec<&Client> = self.clients.values().collect();
+        clients.sort_by_key(|client| client.id());
+        let leaves: Vec<(ClientId, Hash)> = clients.into_iter().map(|client| (client.id(), leaf(client))).collect();
+
+        let mut level: Vec<Hash> = leaves.iter().map(|(_id, hash)| *hash).collect();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node(left, right),
+                    [single] => *single,
+                    _ => unreachable!("chunks of 2"),
+                })
+                .collect();
+        }
+        let root = level.pop().unwrap_or_else(|| Sha256::digest([]).into());
+
+        StateDigest { leaves, root }
+    }
+}
+
+// Leaves and nodes are prefixed differently, so that neither can pass for
+// the other.
+fn leaf(client: &Client) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0]);
+    hasher.update(client.id().to_le_bytes());
+    for amount in [client.available(), client.held(), client.credit_limit()] {
+        hasher.update(amount.normalize().serialize());
+    }
+    hasher.update([client.locked() as u8, client.frozen() as u8, client.closed() as u8]);
+    hasher.finalize().into()
+}
+
+fn node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+pub(super) fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).expect("writing to a String doesn't fail");
+    }
+    hex
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use pretty_assertions::{assert_eq, assert_ne};
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::transactions::{Deposit, Dispute, Transaction, Withdrawal};
+
+#[test]
+fn test_state_digest() -> Result<()> {
+    assert_eq!(Ledger::new().state_digest(), Ledger::new().state_digest());
+
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 3, dec!(1.50))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 2)))?;
+
+    // Built in another order, with differently scaled amounts.
+    let mut other = Ledger::new();
+    other.execute_transaction(Transaction::Deposit(Deposit::new(3, 3, dec!(1.5))?))?;
+    other.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(5))?))?;
+    other.execute_transaction(Transaction::Dispute(Dispute::new(2, 2)))?;
+    other.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    let digest = ledger.state_digest();
+    assert_eq!(other.state_digest().root(), digest.root());
+    assert_eq!(digest.root().len(), 64);
+    assert_eq!(other.state_digest().differing_clients(&digest), Vec::<ClientId>::new());
+
+    other.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 3, dec!(1))?))?;
+    other.execute_transaction(Transaction::Deposit(Deposit::new(5, 4, dec!(1))?))?;
+    let other_digest = other.state_digest();
+    assert_ne!(other_digest.root(), digest.root());
+    assert_eq!(other_digest.client(1), digest.client(1));
+    assert_ne!(other_digest.client(3), digest.client(3));
+    assert_eq!(digest.client(4), None);
+    assert_eq!(digest.differing_clients(&other_digest), vec![3, 4]);
+
+    Ok(())
+}
@@ -1,21 +1,430 @@
-use std::collections::hash_map::Iter;
-use std::collections::HashMap;
+use std::collections::hash_map::{Entry, Iter};
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::Hash;
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
 
-use super::client::Client;
-use super::transactions::Transaction;
-use super::{ExecutableTransaction, TransactionError};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
+use super::audit::AuditLog;
+use super::checkpoint::{CheckpointPolicy, Checkpoints};
+use super::client::{Client, ClientMetadata, ClientV4};
+use super::currency::Currency;
+use super::lifetime::LifetimeTotals;
+use super::merge::{ClientPolicy, DuplicatePolicy, MergeError, MergePolicy, MergeReport};
+use super::monitoring::{AlertHook, AlertThreshold, ChargebackMonitor, ChargebackRate};
+use super::policy::LedgerPolicy;
+use super::scoring::{Score, Scoring, TransactionScorer};
+use super::settlement::{Settlement, SettlementRecord};
+use super::snapshot::{SnapshotError, SnapshotOptions};
+use super::store::bloom::BloomFilter;
+use super::store::{ClientStore, TransactionStore};
+use super::timestamp::Timestamp;
+use super::transactions::{Transaction, TransactionV9};
+use super::undo::{Recorder, UndoEntry, UndoLog};
+use super::wal::Wal;
+use super::{ClientId, ExecutableTransaction, LedgerAccess, Map, Set, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "ledger_tests.rs"]
+mod ledger_tests;
+
+/// By default clients and transactions are kept in memory, a different
+/// `TransactionStore` can be used with `Ledger::with_store`, and a different
+/// `ClientStore` as well with `Ledger::with_storage`.
+///
+/// Executing transactions works with any stores. Merging, comparing and
+/// saving snapshots need the clients in memory.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Ledger<T = Map<TxId, Transaction>, C = Map<ClientId, Client>> {
+    pub clients: C,
+    pub transactions: T,
+
+    settled_ids: SettledIds,
+    open_disputes: usize,
+    wal_sequence: u64,
+    position: u64,
+
+    #[serde(skip)]
+    wal: Option<Wal>,
+    #[serde(skip)]
+    checkpoints: Option<Checkpoints>,
+    // An explicit default, or serde would want `T: Default` to skip it.
+    #[serde(skip, default = "Option::default")]
+    save_checkpoint: Option<SaveCheckpoint<T, C>>,
+    #[serde(skip)]
+    undo: Option<UndoLog>,
+    #[serde(skip)]
+    policy: LedgerPolicy,
+    #[serde(skip)]
+    scoring: Option<Scoring>,
+    #[serde(skip)]
+    monitor: Option<ChargebackMonitor>,
+    #[serde(skip)]
+    audit: Option<AuditLog>,
+    #[serde(skip)]
+    settlement: Option<Settlement>,
+    #[serde(skip)]
+    client_index: Option<ClientIndex>,
+    #[serde(skip)]
+    lifetime_totals: Option<Map<ClientId, LifetimeTotals>>,
+    #[serde(skip)]
+    currency: Option<Currency>,
+    #[serde(skip)]
+    daily_withdrawals: Map<ClientId, BTreeMap<NaiveDate, Decimal>>,
+}
+
+// How a ledger saves its checkpoints, set by `use_checkpoints` for the
+// ledgers that can be saved at all.
+type SaveCheckpoint<T, C> = fn(&Ledger<T, C>, &Path, &SnapshotOptions) -> Result<(), SnapshotError>;
+
+/// The layout of `Ledger` in snapshots before version 3, see
+/// `snapshot::read`. Snapshots before version 9 had 32 bit transaction ids.
+#[derive(Deserialize)]
+pub(super) struct LedgerV2 {
+    clients: Map<ClientId, ClientV4>,
+    transactions: Map<u32, TransactionV9<u32>>,
+    settled_ids: SettledIdsV8,
+    open_disputes: usize,
+}
+
+/// The layout of `Ledger` in snapshots of version 3.
+#[derive(Deserialize)]
+pub(super) struct LedgerV3 {
+    clients: Map<ClientId, ClientV4>,
+    transactions: Map<u32, TransactionV9<u32>>,
+    settled_ids: SettledIdsV8,
+    open_disputes: usize,
+    wal_sequence: u64,
+}
+
+/// The layout of `Ledger` in snapshots of version 4. Later versions up to 9
+/// only changed the layout of its clients, `C`, and the width of transaction
+/// ids, `I`, which became u64 in version 9.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "C: Deserialize<'de>, I: Deserialize<'de> + Eq + Hash"))]
+pub(super) struct LedgerV4<C = ClientV4, I = u32> {
+    clients: Map<ClientId, C>,
+    transactions: Map<I, TransactionV9<I>>,
+    settled_ids: SettledIdsV8<I>,
+    open_disputes: usize,
+    wal_sequence: u64,
+    position: u64,
+}
+
+/// A deposit with funds held for a dispute, see `Ledger::open_disputes`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct OpenDispute {
+    pub tx: TxId,
+    pub client: ClientId,
+    /// All of the deposit, or the disputed part of a partial dispute.
+    #[serde(with = "rust_decimal::serde::str")]
+    pub held: Decimal,
+}
+
+impl OpenDispute {
+    fn of(id: TxId, transaction: &Transaction) -> Option<OpenDispute> {
+        let Transaction::Deposit(deposit) = transaction else {
+            return None;
+        };
+
+        let held = deposit.dispute_status().held(deposit.amount());
+        (!held.is_zero()).then_some(OpenDispute {
+            tx: id,
+            client: deposit.client_id(),
+            held,
+        })
+    }
+}
+
+/// The ids of every client's stored transactions, see
+/// `Ledger::use_client_index`.
 #[derive(Default)]
-pub struct Ledger {
-    pub clients: HashMap<u16, Client>,
-    pub transactions: HashMap<u32, Transaction>,
+struct ClientIndex(Map<ClientId, BTreeSet<TxId>>);
+
+impl ClientIndex {
+    fn insert(&mut self, client_id: ClientId, id: TxId) {
+        self.0.entry(client_id).or_default().insert(id);
+    }
+
+    fn remove(&mut self, client_id: ClientId, id: TxId) {
+        if let Entry::Occupied(mut entry) = self.0.entry(client_id) {
+            entry.get_mut().remove(&id);
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+}
+
+/// Aggregated figures over the whole ledger.
+#[derive(Debug, Default, PartialEq)]
+pub struct LedgerStats {
+    pub clients: usize,
+    pub locked_clients: usize,
+    pub total_available: Decimal,
+    pub total_held: Decimal,
+    pub stored_transactions: usize,
+    pub open_disputes: usize,
+}
+
+/// Ids of settled transactions that are no longer kept in the store.
+#[derive(Clone, Serialize, Deserialize)]
+enum SettledIds {
+    Exact(Set<TxId>),
+    Filter(BloomFilter),
+}
+
+impl SettledIds {
+    fn insert(&mut self, id: TxId) {
+        match self {
+            SettledIds::Exact(ids) => {
+                ids.insert(id);
+            },
+            SettledIds::Filter(filter) => filter.insert(id),
+        }
+    }
+
+    fn contains(&self, id: TxId) -> bool {
+        match self {
+            SettledIds::Exact(ids) => ids.contains(&id),
+            SettledIds::Filter(filter) => filter.contains(id),
+        }
+    }
+}
+
+impl Default for SettledIds {
+    fn default() -> Self {
+        SettledIds::Exact(Set::default())
+    }
+}
+
+/// The layout of `SettledIds` in snapshots before version 9, with `I` a u32.
+/// `LedgerV4` reads the ones of version 9 with it too.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "I: Deserialize<'de> + Eq + Hash"))]
+enum SettledIdsV8<I = u32> {
+    Exact(Set<I>),
+    Filter(BloomFilter),
+}
+
+impl<I: Into<TxId>> From<SettledIdsV8<I>> for SettledIds {
+    fn from(ids: SettledIdsV8<I>) -> Self {
+        match ids {
+            SettledIdsV8::Exact(ids) => SettledIds::Exact(ids.into_iter().map(Into::into).collect()),
+            SettledIdsV8::Filter(filter) => SettledIds::Filter(filter),
+        }
+    }
+}
+
+impl<T: FromIterator<(TxId, Transaction)>> From<LedgerV2> for Ledger<T> {
+    fn from(ledger: LedgerV2) -> Self {
+        Ledger {
+            clients: legacy_clients(ledger.clients),
+            transactions: legacy_transactions(ledger.transactions),
+            settled_ids: ledger.settled_ids.into(),
+            open_disputes: ledger.open_disputes,
+            wal_sequence: 0,
+            position: 0,
+            wal: None,
+            checkpoints: None,
+            save_checkpoint: None,
+            undo: None,
+            policy: LedgerPolicy::default(),
+            scoring: None,
+            monitor: None,
+            audit: None,
+            settlement: None,
+            client_index: None,
+            lifetime_totals: None,
+            currency: None,
+            daily_withdrawals: Map::default(),
+        }
+    }
+}
+
+impl<T: FromIterator<(TxId, Transaction)>> From<LedgerV3> for Ledger<T> {
+    fn from(ledger: LedgerV3) -> Self {
+        Ledger {
+            clients: legacy_clients(ledger.clients),
+            transactions: legacy_transactions(ledger.transactions),
+            settled_ids: ledger.settled_ids.into(),
+            open_disputes: ledger.open_disputes,
+            wal_sequence: ledger.wal_sequence,
+            position: 0,
+            wal: None,
+            checkpoints: None,
+            save_checkpoint: None,
+            undo: None,
+            policy: LedgerPolicy::default(),
+            scoring: None,
+            monitor: None,
+            audit: None,
+            settlement: None,
+            client_index: None,
+            lifetime_totals: None,
+            currency: None,
+            daily_withdrawals: Map::default(),
+        }
+    }
+}
+
+impl<T: FromIterator<(TxId, Transaction)>, C: Into<Client>, I: Into<TxId>> From<LedgerV4<C, I>> for Ledger<T> {
+    fn from(ledger: LedgerV4<C, I>) -> Self {
+        Ledger {
+            clients: legacy_clients(ledger.clients),
+            transactions: legacy_transactions(ledger.transactions),
+            settled_ids: ledger.settled_ids.into(),
+            open_disputes: ledger.open_disputes,
+            wal_sequence: ledger.wal_sequence,
+            position: ledger.position,
+            wal: None,
+            checkpoints: None,
+            save_checkpoint: None,
+            undo: None,
+            policy: LedgerPolicy::default(),
+            scoring: None,
+            monitor: None,
+            audit: None,
+            settlement: None,
+            client_index: None,
+            lifetime_totals: None,
+            currency: None,
+            daily_withdrawals: Map::default(),
+        }
+    }
+}
+
+fn legacy_clients<C: Into<Client>>(clients: Map<ClientId, C>) -> Map<ClientId, Client> {
+    clients.into_iter().map(|(id, client)| (id, client.into())).collect()
+}
+
+fn legacy_transactions<T: FromIterator<(TxId, Transaction)>, I: Into<TxId>>(
+    transactions: Map<I, TransactionV9<I>>,
+) -> T {
+    transactions
+        .into_iter()
+        .map(|(id, transaction)| (id.into(), transaction.into()))
+        .collect()
 }
 
 impl Ledger {
     pub fn new() -> Ledger {
         Ledger {
-            clients: HashMap::new(),
-            transactions: HashMap::new(),
+            clients: Map::default(),
+            transactions: Map::default(),
+            settled_ids: SettledIds::default(),
+            open_disputes: 0,
+            wal_sequence: 0,
+            position: 0,
+            wal: None,
+            checkpoints: None,
+            save_checkpoint: None,
+            undo: None,
+            policy: LedgerPolicy::default(),
+            scoring: None,
+            monitor: None,
+            audit: None,
+            settlement: None,
+            client_index: None,
+            lifetime_totals: None,
+            currency: None,
+            daily_withdrawals: Map::default(),
+        }
+    }
+
+    /// Keeps the ids of every client's stored transactions from now on, so
+    /// that `transactions_for` doesn't have to go through all of them. The
+    /// index isn't saved in snapshots, and changing `transactions` directly
+    /// isn't tracked by it.
+    pub fn use_client_index(&mut self) {
+        let mut index = ClientIndex::default();
+        for (id, transaction) in &self.transactions {
+            index.insert(transaction.client_id(), *id);
+        }
+
+        self.client_index = Some(index);
+    }
+
+    /// The client's stored transactions, ordered by id with a client index,
+    /// see `use_client_index`, or else in no particular order.
+    pub fn transactions_for(&self, client_id: ClientId) -> impl Iterator<Item = (TxId, &Transaction)> {
+        let indexed = self.client_index.as_ref().map(|index| {
+            index
+                .0
+                .get(&client_id)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| self.transactions.get(id).map(|transaction| (*id, transaction)))
+        });
+        let scanned = self.client_index.is_none().then(|| {
+            self.transactions
+                .iter()
+                .filter(move |(_, transaction)| transaction.client_id() == client_id)
+                .map(|(id, transaction)| (*id, transaction))
+        });
+
+        indexed.into_iter().flatten().chain(scanned.into_iter().flatten())
+    }
+
+    /// The deposits under dispute, in no particular order.
+    pub fn open_disputes(&self) -> impl Iterator<Item = OpenDispute> + '_ {
+        self.transactions
+            .iter()
+            .filter_map(|(id, transaction)| OpenDispute::of(*id, transaction))
+    }
+
+    /// The client's deposits under dispute, in the order of
+    /// `transactions_for`.
+    pub fn open_disputes_for(&self, client_id: ClientId) -> impl Iterator<Item = OpenDispute> + '_ {
+        self.transactions_for(client_id)
+            .filter_map(|(id, transaction)| OpenDispute::of(id, transaction))
+    }
+
+    /// The stored transactions that happened within `range`, e.g. a day, in
+    /// no particular order. Those without a timestamp are in none.
+    pub fn transactions_between(
+        &self,
+        range: impl RangeBounds<Timestamp>,
+    ) -> impl Iterator<Item = (TxId, &Transaction)> {
+        self.transactions.iter().filter_map(move |(id, transaction)| {
+            let timestamp = transaction.timestamp()?;
+            range.contains(&timestamp).then_some((*id, transaction))
+        })
+    }
+}
+
+impl<T: TransactionStore> Ledger<T> {
+    pub fn with_store(transactions: T) -> Ledger<T> {
+        Ledger::with_storage(transactions, Map::default())
+    }
+}
+
+impl<T: TransactionStore, C: ClientStore> Ledger<T, C> {
+    pub fn with_storage(transactions: T, clients: C) -> Ledger<T, C> {
+        Ledger {
+            clients,
+            transactions,
+            settled_ids: SettledIds::default(),
+            open_disputes: 0,
+            wal_sequence: 0,
+            position: 0,
+            wal: None,
+            checkpoints: None,
+            save_checkpoint: None,
+            undo: None,
+            policy: LedgerPolicy::default(),
+            scoring: None,
+            monitor: None,
+            audit: None,
+            settlement: None,
+            client_index: None,
+            lifetime_totals: None,
+            currency: None,
+            daily_withdrawals: Map::default(),
         }
     }
 
@@ -23,24 +432,723 @@ impl Ledger {
     /// If the id already exists then the transaction is discarded.
     pub fn execute_transaction(&mut self, transaction: Transaction) -> Result<(), TransactionError> {
         if let Some(id) = transaction.id() {
-            if self.transactions.contains_key(&id) {
+            if self.transactions.contains(id)? || self.settled_ids.contains(id) {
                 // The transaction has already been processed, ignore.
                 return Ok(());
             }
         }
 
-        transaction.execute(self)?;
+        if let Some(scoring) = &mut self.scoring {
+            let client = self.clients.get(transaction.client_id())?;
+            if scoring.scorer.score(&transaction, client.as_ref()) > scoring.threshold {
+                scoring.quarantined.push(transaction);
+                return Err(TransactionError::Quarantined);
+            }
+        }
+
+        // Checked before it's journaled, the totals aren't rebuilt from the
+        // journal's failures.
+        let daily_withdrawal = self.check_daily_withdrawal(&transaction)?;
+
+        let sequence = match &mut self.wal {
+            Some(wal) => Some(
+                wal.append(self.position, &transaction)
+                    .map_err(|err| TransactionError::StorageFailure(err.to_string()))?,
+            ),
+            None => None,
+        };
+        if let Some(checkpoints) = &mut self.checkpoints {
+            checkpoints.executed += 1;
+        }
+
+        let total_before = match &mut self.settlement {
+            Some(settlement) => {
+                settlement.roll_over(&transaction);
+                self.clients
+                    .get(transaction.client_id())?
+                    .map_or(Decimal::ZERO, |client| client.get_total())
+            },
+            None => Decimal::ZERO,
+        };
+
+        let mut entry = self.undo.as_ref().map(|_| UndoEntry {
+            open_disputes: self.open_disputes,
+            ..UndoEntry::default()
+        });
+        let result = match &mut entry {
+            Some(entry) => transaction.execute(&mut Recorder { ledger: self, entry }),
+            None => transaction.execute(self),
+        };
+        // Executing it again fails the same way, so it's done either way.
+        if let Some(sequence) = sequence {
+            self.wal_sequence = sequence + 1;
+        }
+        result?;
+
+        // Logged before it's stored, but only failed once it is.
+        let audited = match &mut self.audit {
+            Some(audit) => audit
+                .append(&transaction)
+                .map_err(|err| TransactionError::StorageFailure(err.to_string())),
+            None => Ok(()),
+        };
+
+        if let (Some((day, total)), Transaction::Withdrawal(withdrawal)) = (daily_withdrawal, &transaction) {
+            self.daily_withdrawals
+                .entry(withdrawal.client_id())
+                .or_default()
+                .insert(day, total);
+        }
+
+        if let Some(monitor) = &mut self.monitor {
+            monitor.count(&transaction);
+        }
+        if let Some(lifetime_totals) = &mut self.lifetime_totals {
+            lifetime_totals
+                .entry(transaction.client_id())
+                .or_default()
+                .count(&transaction);
+        }
+        if self.settlement.is_some() {
+            let total_after = self
+                .clients
+                .get(transaction.client_id())?
+                .map_or(Decimal::ZERO, |client| client.get_total());
+            if let Some(settlement) = &mut self.settlement {
+                settlement.count(&transaction, total_after - total_before);
+            }
+        }
 
         // Transactions that contain their own id could potentially be reversed,
         // so we should store them.
         if let Some(id) = transaction.id() {
-            self.transactions.insert(id, transaction);
+            if transaction.settled(&self.policy) && matches!(self.settled_ids, SettledIds::Filter(_)) {
+                self.settled_ids.insert(id);
+            } else {
+                let client_id = transaction.client_id();
+                self.transactions.insert(id, transaction)?;
+                if let Some(index) = &mut self.client_index {
+                    index.insert(client_id, id);
+                }
+                if let Some(entry) = &mut entry {
+                    entry.stored = Some((id, client_id));
+                }
+            }
+        }
+
+        if let (Some(undo), Some(entry)) = (&mut self.undo, entry) {
+            undo.push(entry);
+        }
+
+        audited
+    }
+
+    // The day of a timestamped withdrawal and the client's total for it with
+    // the withdrawal, if the policy has a daily limit. Fails with
+    // WithdrawalLimitExceeded if the total would be over it.
+    fn check_daily_withdrawal(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<Option<(NaiveDate, Decimal)>, TransactionError> {
+        let (Transaction::Withdrawal(withdrawal), Some(limit)) = (transaction, self.policy.daily_withdrawal_limit)
+        else {
+            return Ok(None);
+        };
+        let Some(day) = withdrawal.timestamp().map(|timestamp| timestamp.date()) else {
+            return Ok(None);
+        };
+
+        match self
+            .daily_withdrawals(withdrawal.client_id(), day)
+            .checked_add(withdrawal.amount())
+        {
+            Some(total) if total <= limit => Ok(Some((day, total))),
+            _ => Err(TransactionError::WithdrawalLimitExceeded),
+        }
+    }
+
+    /// Tracks the ids of settled transactions in a Bloom filter instead of
+    /// storing them. Transactions that are settled as soon as they execute
+    /// (e.g. withdrawals) are never stored, and neither are the ids of those
+    /// removed by `compact`.
+    ///
+    /// The filter can have false positives. When a new transaction's id is
+    /// reported as already present it is handled like a repeated transaction,
+    /// i.e. it is discarded. The filter should therefore be sized so that the
+    /// expected number of lost transactions is acceptable. Disputes are not
+    /// affected, as stored transactions are always looked up first.
+    pub fn use_id_filter(&mut self, mut filter: BloomFilter) {
+        if let SettledIds::Exact(ids) = &self.settled_ids {
+            for id in ids {
+                filter.insert(*id);
+            }
+        }
+
+        self.settled_ids = SettledIds::Filter(filter);
+    }
+
+    /// Journals every transaction before it's executed, see `Wal`. Repeated
+    /// transactions are discarded before being journaled. A transaction that
+    /// can't be journaled fails with StorageFailure and isn't executed.
+    pub fn use_wal(&mut self, wal: Wal) {
+        self.wal = Some(wal);
+    }
+
+    /// Appends every applied transaction to a hash-chained audit log, see
+    /// `AuditLog`. Failed and repeated transactions aren't logged, and
+    /// neither is undoing. A transaction that was applied but couldn't be
+    /// logged still fails with StorageFailure.
+    pub fn use_audit_log(&mut self, audit: AuditLog) {
+        self.audit = Some(audit);
+    }
+
+    /// Sets the rules transactions are executed by from now on. The policy
+    /// isn't saved in snapshots, it's configuration rather than state.
+    pub fn use_policy(&mut self, policy: LedgerPolicy) {
+        self.policy = policy;
+    }
+
+    /// Sets the currency of the ledger's transactions. A ledger only keeps
+    /// one, `data::process_records` rejects records in another. Without it,
+    /// the first record with a currency sets it. Like the policy, it isn't
+    /// saved in snapshots.
+    pub fn use_currency(&mut self, currency: Currency) {
+        self.currency = Some(currency);
+    }
+
+    pub fn currency(&self) -> Option<Currency> {
+        self.currency
+    }
+
+    /// Scores every transaction before it's executed. Those scored above
+    /// `threshold` fail with Quarantined instead, and are kept for review
+    /// until taken with `take_quarantined`. Repeated transactions are
+    /// discarded before being scored. The transactions held for review
+    /// aren't saved in snapshots nor journaled.
+    pub fn use_scorer(&mut self, scorer: impl TransactionScorer + Send + Sync + 'static, threshold: Score) {
+        self.scoring = Some(Scoring {
+            scorer: Box::new(scorer),
+            threshold,
+            quarantined: Vec::new(),
+        });
+    }
+
+    /// The transactions held for review so far, oldest first. They're no
+    /// longer kept by the ledger.
+    pub fn take_quarantined(&mut self) -> Vec<Transaction> {
+        self.scoring
+            .as_mut()
+            .map_or_else(Vec::new, |scoring| std::mem::take(&mut scoring.quarantined))
+    }
+
+    /// Executes a transaction that passed review, without scoring it.
+    pub fn approve(&mut self, transaction: Transaction) -> Result<(), TransactionError> {
+        let scoring = self.scoring.take();
+        let result = self.execute_transaction(transaction);
+        self.scoring = scoring;
+        result
+    }
+
+    /// Keeps the chargeback rate of every client and of the whole ledger from
+    /// now on, counting executed deposits and chargebacks. `hook` is alerted
+    /// whenever one goes over the threshold, and again if it went back under
+    /// it in between. Undone transactions aren't taken back from the rates.
+    pub fn use_chargeback_monitor(&mut self, threshold: AlertThreshold, hook: impl AlertHook + Send + Sync + 'static) {
+        self.monitor = Some(ChargebackMonitor::new(threshold, hook));
+    }
+
+    /// The client's chargeback rate since the ledger is monitored, None if it
+    /// isn't.
+    pub fn chargeback_rate(&self, client_id: ClientId) -> Option<ChargebackRate> {
+        self.monitor.as_ref().map(|monitor| monitor.rate(client_id))
+    }
+
+    /// The chargeback rate of all clients since the ledger is monitored, None
+    /// if it isn't.
+    pub fn global_chargeback_rate(&self) -> Option<ChargebackRate> {
+        self.monitor.as_ref().map(ChargebackMonitor::global_rate)
+    }
+
+    /// Settles the executed deposits, withdrawals, chargebacks and reversals
+    /// of every client from now on, by period. A period is closed by
+    /// `close_day`, or as soon as a transaction happened on a later day than
+    /// the period's first one, and the next one starts. Transactions
+    /// without a timestamp, e.g. chargebacks, are settled in the open
+    /// period. The open period isn't saved in snapshots, nor are undone
+    /// transactions taken back from it.
+    pub fn use_settlement(&mut self) {
+        self.settlement = Some(Settlement::new());
+    }
+
+    /// Closes the open settlement period, see `use_settlement`. Does
+    /// nothing if the ledger doesn't settle.
+    pub fn close_day(&mut self) {
+        if let Some(settlement) = &mut self.settlement {
+            settlement.close();
+        }
+    }
+
+    /// The records of the settlement periods closed so far, oldest first and
+    /// by client. They're no longer kept by the ledger.
+    pub fn take_settlements(&mut self) -> Vec<SettlementRecord> {
+        self.settlement.as_mut().map_or_else(Vec::new, Settlement::take)
+    }
+
+    /// Keeps what every client deposited, withdrew, disputed and charged back
+    /// from now on, see `LifetimeTotals`. They aren't saved in snapshots, nor
+    /// are undone transactions taken back from them.
+    pub fn use_lifetime_totals(&mut self) {
+        self.lifetime_totals = Some(Map::default());
+    }
+
+    /// The client's lifetime totals, None if the ledger doesn't keep them.
+    pub fn lifetime_totals(&self, client_id: ClientId) -> Option<LifetimeTotals> {
+        self.lifetime_totals
+            .as_ref()
+            .map(|lifetime_totals| lifetime_totals.get(&client_id).copied().unwrap_or_default())
+    }
+
+    /// Remembers the last `limit` executed transactions, so that they can be
+    /// taken back with `undo`. Failed and repeated transactions aren't
+    /// remembered, as they didn't change anything.
+    pub fn use_undo(&mut self, limit: usize) {
+        self.undo = Some(UndoLog::new(limit));
+    }
+
+    /// Takes back the last `count` executed transactions, newest first, as
+    /// if they were never executed. Returns how many were, which is fewer if
+    /// fewer are remembered.
+    ///
+    /// Undoing isn't journaled: with a write-ahead log, save a checkpoint
+    /// right after, or recovering executes the transactions again. The
+    /// position isn't changed, so resuming doesn't either. With an id filter,
+    /// the ids of undone withdrawals stay in it, executing them again is
+    /// discarded as a repeat.
+    pub fn undo(&mut self, count: usize) -> Result<usize, TransactionError> {
+        let mut undone = 0;
+        while undone < count {
+            let Some(entry) = self.undo.as_mut().and_then(|undo| undo.entries.pop_back()) else {
+                break;
+            };
+
+            if let Some((id, client_id)) = entry.stored {
+                self.transactions.remove(id)?;
+                if let Some(index) = &mut self.client_index {
+                    index.remove(client_id, id);
+                }
+            }
+            for (id, transaction) in entry.referenced.into_iter().rev() {
+                self.transactions.update(id, |stored| {
+                    *stored = transaction;
+                    Ok(())
+                })?;
+            }
+            for (client_id, client) in entry.clients.into_iter().rev() {
+                match client {
+                    Some(client) => self.clients.insert(client_id, client)?,
+                    None => {
+                        self.clients.remove(client_id)?;
+                    },
+                }
+            }
+            self.open_disputes = entry.open_disputes;
+
+            undone += 1;
+        }
+
+        Ok(undone)
+    }
+
+    /// Number of transactions `undo` can take back.
+    pub fn undoable(&self) -> usize {
+        self.undo.as_ref().map_or(0, |undo| undo.entries.len())
+    }
+
+    /// Removes settled transactions from the store to bound its memory usage,
+    /// passing each of them to `archive`. Only their ids are kept, so repeated
+    /// transactions are still discarded. Disputing a removed transaction
+    /// fails with TransactionSettled. The transactions executed so far can't
+    /// be undone anymore.
+    /// Returns the number of transactions removed.
+    pub fn compact(&mut self, mut archive: impl FnMut(Transaction)) -> Result<usize, TransactionError> {
+        if let Some(undo) = &mut self.undo {
+            undo.entries.clear();
+        }
+
+        let policy = self.policy;
+        let mut removed = 0;
+        self.transactions.evict(
+            |transaction| transaction.settled(&policy),
+            |id, transaction| {
+                self.settled_ids.insert(id);
+                if let Some(index) = &mut self.client_index {
+                    index.remove(transaction.client_id(), id);
+                }
+                archive(transaction);
+                removed += 1;
+            },
+        )?;
+
+        Ok(removed)
+    }
+
+    /// Whether the transaction was settled and is no longer in the store,
+    /// e.g. after `compact`. With an id filter this can be a false positive.
+    pub fn is_settled(&self, id: TxId) -> bool {
+        self.settled_ids.contains(id)
+    }
+
+    /// Number of journaled transactions already executed on this ledger,
+    /// i.e. the sequence number of the first entry in the write-ahead log
+    /// that isn't in a checkpoint yet.
+    pub fn wal_sequence(&self) -> u64 {
+        self.wal_sequence
+    }
+
+    pub(super) fn set_wal_sequence(&mut self, wal_sequence: u64) {
+        self.wal_sequence = wal_sequence;
+    }
+
+    pub(super) fn set_open_disputes(&mut self, open_disputes: usize) {
+        self.open_disputes = open_disputes;
+    }
+
+    /// Where in its input the last transaction came from, e.g. its line.
+    /// Saved in snapshots and the write-ahead log so that processing can
+    /// resume after it, see `set_position`.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Sets the position of the transactions executed from now on.
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// What the client withdrew on the day, in UTC, see
+    /// `LedgerPolicy::daily_withdrawal_limit`. Only withdrawals with a
+    /// timestamp executed under a limit count. The totals aren't saved in
+    /// snapshots, nor are reversed or undone withdrawals taken back from them.
+    pub fn daily_withdrawals(&self, client_id: ClientId, day: NaiveDate) -> Decimal {
+        self.daily_withdrawals
+            .get(&client_id)
+            .and_then(|days| days.get(&day))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The client's daily withdrawal totals, oldest day first, see
+    /// `daily_withdrawals`.
+    pub fn daily_withdrawals_for(&self, client_id: ClientId) -> impl Iterator<Item = (NaiveDate, Decimal)> + '_ {
+        self.daily_withdrawals
+            .get(&client_id)
+            .into_iter()
+            .flat_map(|days| days.iter().map(|(day, total)| (*day, *total)))
+    }
+
+    /// Clients the store fails to read are left out of the totals.
+    pub fn stats(&self) -> LedgerStats {
+        let mut stats = LedgerStats {
+            clients: self.clients.len(),
+            stored_transactions: self.transactions.len(),
+            open_disputes: self.open_disputes,
+            ..LedgerStats::default()
+        };
+
+        for client in self.clients.clients().flatten() {
+            // Every client fits in a Decimal, but all of them together may
+            // not.
+            stats.total_available = stats.total_available.saturating_add(client.available());
+            stats.total_held = stats.total_held.saturating_add(client.held());
+            if client.locked() {
+                stats.locked_clients += 1;
+            }
+        }
+
+        stats
+    }
+
+    /// Goes through the deposits under dispute like `open_disputes`, with
+    /// any store.
+    pub fn for_each_open_dispute(&self, mut f: impl FnMut(OpenDispute)) -> Result<(), TransactionError> {
+        self.transactions.for_each(|id, transaction| {
+            if let Some(dispute) = OpenDispute::of(id, transaction) {
+                f(dispute);
+            }
+        })
+    }
+
+    /// Saves a checkpoint if one is due. The ledger doesn't check on its own,
+    /// whoever executes the transactions should call this after each one.
+    /// Returns whether a checkpoint was saved.
+    pub fn checkpoint_if_due(&mut self) -> Result<bool, SnapshotError> {
+        if !self.checkpoints.as_ref().map_or(false, Checkpoints::is_due) {
+            return Ok(false);
+        }
+
+        self.checkpoint()?;
+        Ok(true)
+    }
+
+    /// Saves a checkpoint now, if checkpoints are used at all.
+    pub fn checkpoint(&mut self) -> Result<(), SnapshotError> {
+        let (Some(checkpoints), Some(save)) = (&self.checkpoints, self.save_checkpoint) else {
+            return Ok(());
+        };
+
+        save(self, &checkpoints.path, &checkpoints.options)?;
+
+        // Everything journaled so far is in the checkpoint.
+        if let Some(wal) = &mut self.wal {
+            wal.truncate()?;
+        }
+        if let Some(checkpoints) = &mut self.checkpoints {
+            checkpoints.reset();
         }
 
         Ok(())
     }
 
-    pub fn clients_iter(&self) -> Iter<u16, Client> {
+    /// Sets who the client is, for the reports. A client that doesn't exist
+    /// yet is added without any funds. It isn't journaled, but it's saved in
+    /// snapshots.
+    pub fn set_client_metadata(
+        &mut self,
+        client_id: ClientId,
+        metadata: ClientMetadata,
+    ) -> Result<(), TransactionError> {
+        self.clients.update_or_insert(client_id, |client| {
+            client.set_metadata(metadata);
+            Ok(())
+        })
+    }
+}
+
+impl<T: TransactionStore> Ledger<T> {
+    /// Adds the clients and transactions of `other` to this ledger, e.g. to
+    /// combine ledgers built from separate parts of the input. What is in
+    /// both ledgers is handled according to the policy. If the merge fails
+    /// this ledger isn't changed, and with an id filter a false positive can
+    /// make it fail with DuplicateIds. The transactions executed so far can't
+    /// be undone anymore.
+    ///
+    /// The position and journal of this ledger are kept, the other's aren't
+    /// meaningful here.
+    pub fn merge<U: TransactionStore>(
+        &mut self,
+        mut other: Ledger<U>,
+        policy: &MergePolicy,
+    ) -> Result<MergeReport, MergeError> {
+        let mut transactions = Vec::with_capacity(other.transactions.len());
+        other.transactions.evict(
+            |_transaction| true,
+            |id, transaction| transactions.push((id, transaction)),
+        )?;
+
+        let mut duplicate_ids = Vec::new();
+        for (id, _transaction) in &transactions {
+            if self.transactions.contains(*id)? || self.settled_ids.contains(*id) {
+                duplicate_ids.push(*id);
+            }
+        }
+        // Settled ids in a filter can't be listed.
+        if let SettledIds::Exact(ids) = &other.settled_ids {
+            for id in ids {
+                if self.transactions.contains(*id)? || self.settled_ids.contains(*id) {
+                    duplicate_ids.push(*id);
+                }
+            }
+        }
+        duplicate_ids.sort_unstable();
+        if !duplicate_ids.is_empty() && policy.duplicate_ids == DuplicatePolicy::Fail {
+            return Err(MergeError::DuplicateIds(duplicate_ids));
+        }
+
+        let mut overlapping: Vec<ClientId> = other
+            .clients
+            .keys()
+            .filter(|id| self.clients.contains_key(id))
+            .copied()
+            .collect();
+        overlapping.sort_unstable();
+        if !overlapping.is_empty() && policy.clients == ClientPolicy::Fail {
+            return Err(MergeError::OverlappingClients(overlapping));
+        }
+        let mut merged = Vec::with_capacity(overlapping.len());
+        for id in &overlapping {
+            let mut client = self.clients[id].clone();
+            client.merge(&other.clients[id])?;
+            merged.push(client);
+        }
+
+        // The last thing that can fail.
+        match (&mut self.settled_ids, other.settled_ids) {
+            (SettledIds::Exact(ids), SettledIds::Exact(other_ids)) => ids.extend(other_ids),
+            (SettledIds::Filter(filter), SettledIds::Exact(other_ids)) => {
+                for id in other_ids {
+                    filter.insert(id);
+                }
+            },
+            (SettledIds::Exact(_), SettledIds::Filter(other_filter)) => self.use_id_filter(other_filter),
+            (SettledIds::Filter(filter), SettledIds::Filter(other_filter)) => {
+                if !filter.union(&other_filter) {
+                    return Err(MergeError::IncompatibleFilters);
+                }
+            },
+        }
+
+        let mut report = MergeReport {
+            clients_merged: overlapping.len(),
+            ..MergeReport::default()
+        };
+        for (id, client) in other.clients {
+            if let Entry::Vacant(entry) = self.clients.entry(id) {
+                entry.insert(client);
+                report.clients_added += 1;
+            }
+        }
+        for client in merged {
+            self.clients.insert(client.id(), client);
+        }
+
+        for (id, transaction) in transactions {
+            if duplicate_ids.binary_search(&id).is_ok() {
+                continue;
+            }
+
+            if transaction.under_dispute() {
+                self.open_disputes += 1;
+            }
+            if let Some(index) = &mut self.client_index {
+                index.insert(transaction.client_id(), id);
+            }
+            self.transactions.insert(id, transaction)?;
+            report.transactions_added += 1;
+        }
+        report.duplicate_ids = duplicate_ids;
+
+        if let Some(undo) = &mut self.undo {
+            undo.entries.clear();
+        }
+
+        Ok(report)
+    }
+
+    pub fn clients_iter(&self) -> Iter<ClientId, Client> {
         self.clients.iter()
     }
 }
+
+impl<T: TransactionStore + Clone, C: ClientStore + Clone> Ledger<T, C> {
+    /// An independent copy of the ledger, e.g. to try out transactions
+    /// without executing them on this one, see `simulate`. The fork doesn't
+    /// journal, checkpoint, score or remember anything to undo.
+    pub fn fork(&self) -> Ledger<T, C> {
+        Ledger {
+            clients: self.clients.clone(),
+            transactions: self.transactions.clone(),
+            settled_ids: self.settled_ids.clone(),
+            open_disputes: self.open_disputes,
+            wal_sequence: self.wal_sequence,
+            position: self.position,
+            wal: None,
+            checkpoints: None,
+            save_checkpoint: None,
+            undo: None,
+            policy: self.policy,
+            scoring: None,
+            monitor: None,
+            audit: None,
+            settlement: None,
+            client_index: None,
+            lifetime_totals: None,
+            currency: self.currency,
+            daily_withdrawals: self.daily_withdrawals.clone(),
+        }
+    }
+}
+
+impl<T: TransactionStore + Serialize + DeserializeOwned + FromIterator<(TxId, Transaction)>> Ledger<T> {
+    /// Saves a snapshot of the ledger to this path whenever the policy says
+    /// so, see `checkpoint_if_due`. With a write-ahead log, the journal is
+    /// truncated after every checkpoint, so recovering never has to execute
+    /// more than the transactions since the last one.
+    pub fn use_checkpoints(&mut self, path: impl Into<PathBuf>, policy: CheckpointPolicy, options: SnapshotOptions) {
+        self.checkpoints = Some(Checkpoints::new(path.into(), policy, options));
+        self.save_checkpoint = Some(|ledger, path, options| ledger.save_with(path, options));
+    }
+
+    /// Whether every transaction executed so far is in the last checkpoint,
+    /// e.g. to acknowledge the input it came from. Always false without
+    /// checkpoints.
+    pub fn is_checkpointed(&self) -> bool {
+        self.checkpoints
+            .as_ref()
+            .map_or(false, |checkpoints| checkpoints.executed == 0)
+    }
+}
+
+impl<T: TransactionStore, C: ClientStore> LedgerAccess for Ledger<T, C> {
+    fn policy(&self) -> LedgerPolicy {
+        self.policy
+    }
+
+    fn client_or_insert(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let policy = self.policy;
+        self.clients.update_or_insert(client_id, |client| {
+            f(client)?;
+            client.round(&policy);
+            Ok(())
+        })
+    }
+
+    fn client(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let policy = self.policy;
+        self.clients.update(client_id, |client| {
+            f(client)?;
+            client.round(&policy);
+            Ok(())
+        })
+    }
+
+    fn client_and_transaction(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        f: impl FnOnce(&mut Client, &mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let policy = self.policy;
+        let transactions = &mut self.transactions;
+        let open_disputes = &mut self.open_disputes;
+        let result = self.clients.update(client_id, |client| {
+            transactions.update(tx_id, |transaction| {
+                let was_under_dispute = transaction.under_dispute();
+                f(client, transaction)?;
+                client.round(&policy);
+
+                match (was_under_dispute, transaction.under_dispute()) {
+                    (false, true) => *open_disputes += 1,
+                    (true, false) => *open_disputes -= 1,
+                    _ => {},
+                }
+
+                Ok(())
+            })
+        });
+
+        match result {
+            Err(TransactionError::TransactionNotFound) if self.settled_ids.contains(tx_id) => {
+                Err(TransactionError::TransactionSettled)
+            },
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::policy::LedgerPolicy;
+use crate::accounting::timestamp::Timestamp;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+
+#[test]
+fn test_compact_removes_settled_transactions() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(20))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(30))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 0, dec!(40))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 0)))?;
+
+    let mut archived = Vec::new();
+    assert_eq!(ledger.compact(|transaction| archived.push(transaction.id()))?, 3);
+    archived.sort();
+
+    assert_eq!(archived, vec![Some(0), Some(1), Some(4)]);
+    assert_eq!(ledger.transactions.len(), 2);
+    assert_eq!(ledger.transactions.contains_key(&2), true);
+    assert_eq!(ledger.transactions.contains_key(&3), true);
+
+    Ok(())
+}
+
+#[test]
+fn test_compacted_transactions_are_remembered() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+    ledger.compact(|_transaction| {})?;
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    assert_eq!(ledger.clients.get(&0).unwrap().available(), dec!(10));
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionSettled);
+    } else {
+        bail!("compacted transaction should not be disputed again");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_id_filter() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_id_filter(BloomFilter::new(100, 0.001));
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(3))?))?;
+
+    // Withdrawals are only remembered by the filter.
+    assert_eq!(ledger.transactions.len(), 2);
+    assert_eq!(ledger.clients.get(&0).unwrap().available(), dec!(17));
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+    assert_eq!(ledger.compact(|_transaction| {})?, 1);
+    assert_eq!(ledger.transactions.len(), 1);
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    assert_eq!(ledger.clients.get(&0).unwrap().available(), dec!(17));
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 0))) {
+        assert_eq!(err, TransactionError::TransactionSettled);
+    } else {
+        bail!("withdrawal should not be disputed");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_stats() -> Result<()> {
+    let mut ledger = Ledger::new();
+    assert_eq!(ledger.stats(), LedgerStats::default());
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(20))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(30))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 2, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 2)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(2, 2)))?;
+
+    assert_eq!(
+        ledger.stats(),
+        LedgerStats {
+            clients: 3,
+            locked_clients: 1,
+            total_available: dec!(25),
+            total_held: dec!(10),
+            stored_transactions: 4,
+            open_disputes: 1,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_transactions_between() -> Result<()> {
+    let at = |timestamp: &str| timestamp.parse::<Timestamp>().map(Some);
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(0, 0, dec!(10))?).with_timestamp(at("2024-05-01T10:00:00Z")?),
+    )?;
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(1, 0, dec!(20))?).with_timestamp(at("2024-05-02T09:00:00Z")?),
+    )?;
+    ledger.execute_transaction(
+        Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(5))?).with_timestamp(at("2024-05-02T18:00:00Z")?),
+    )?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 0, dec!(1))?))?;
+    // A dispute keeps the deposit's timestamp.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+
+    let day = "2024-05-02T00:00:00Z".parse::<Timestamp>()?.."2024-05-03T00:00:00Z".parse()?;
+    let mut ids: Vec<TxId> = ledger.transactions_between(day).map(|(id, _)| id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![1, 2]);
+    assert_eq!(ledger.transactions_between(..).count(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_transactions_for() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 2, dec!(20))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(30))?))?;
+
+    let mut ids: Vec<TxId> = ledger.transactions_for(1).map(|(id, _)| id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![2, 3]);
+
+    ledger.use_client_index();
+    ledger.use_undo(10);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 1, dec!(40))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(4, 1, dec!(50))?))?;
+    let ids =
+        |ledger: &Ledger, client_id| -> Vec<TxId> { ledger.transactions_for(client_id).map(|(id, _)| id).collect() };
+    assert_eq!(ids(&ledger, 1), vec![0, 2, 3, 4]);
+    assert_eq!(ids(&ledger, 2), vec![1]);
+    assert_eq!(ids(&ledger, 3), Vec::<TxId>::new());
+
+    ledger.undo(1)?;
+    assert_eq!(ids(&ledger, 1), vec![0, 2, 3]);
+
+    // A resolved deposit is settled.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(3, 1)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(3, 1)))?;
+    assert_eq!(ledger.compact(|_| {})?, 1);
+    assert_eq!(ids(&ledger, 1), vec![0, 2]);
+
+    Ok(())
+}
+
+#[test]
+fn test_open_disputes() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(20))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(30))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 2, dec!(40))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 1)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(2, 2, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(3, 2)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(3, 2)))?;
+
+    let mut disputes: Vec<OpenDispute> = ledger.open_disputes().collect();
+    disputes.sort_by_key(|dispute| dispute.tx);
+    assert_eq!(
+        disputes,
+        vec![
+            OpenDispute {
+                tx: 0,
+                client: 1,
+                held: dec!(10),
+            },
+            OpenDispute {
+                tx: 2,
+                client: 2,
+                held: dec!(5),
+            },
+        ]
+    );
+
+    ledger.use_client_index();
+    assert_eq!(ledger.open_disputes_for(2).collect::<Vec<_>>(), vec![disputes[1]]);
+    assert_eq!(ledger.open_disputes_for(3).count(), 0);
+
+    Ok(())
+}
+
+// Like a store backed by a database, clients are copied out and only written
+// back when `f` succeeds.
+#[derive(Default)]
+struct CopyingClients(BTreeMap<ClientId, Client>);
+
+impl ClientStore for CopyingClients {
+    fn get(&self, id: ClientId) -> Result<Option<Client>, TransactionError> {
+        Ok(self.0.get(&id).cloned())
+    }
+
+    fn insert(&mut self, id: ClientId, client: Client) -> Result<(), TransactionError> {
+        self.0.insert(id, client);
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let mut client = self.get(id)?.ok_or(TransactionError::ClientNotFound)?;
+        f(&mut client)?;
+        self.insert(id, client)
+    }
+
+    fn update_or_insert(
+        &mut self,
+        id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let mut client = self.get(id)?.unwrap_or(Client::new(id));
+        f(&mut client)?;
+        self.insert(id, client)
+    }
+
+    fn remove(&mut self, id: ClientId) -> Result<bool, TransactionError> {
+        Ok(self.0.remove(&id).is_some())
+    }
+
+    fn clients(&self) -> impl Iterator<Item = Result<Cow<'_, Client>, TransactionError>> {
+        self.0.values().map(|client| Ok(Cow::Owned(client.clone())))
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[test]
+fn test_client_store() -> Result<()> {
+    let mut ledger = Ledger::with_storage(Map::default(), CopyingClients::default());
+    ledger.use_undo(10);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(1))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 0, dec!(6))?)) {
+        assert!(matches!(err, TransactionError::InsufficientFunds));
+    } else {
+        bail!("withdrawal should fail for insufficient funds");
+    }
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 2))) {
+        assert!(matches!(err, TransactionError::ClientNotFound));
+    } else {
+        bail!("dispute of a missing client should fail");
+    }
+
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+    assert_eq!(ledger.clients.len(), 2);
+    assert_eq!(ledger.clients.0[&0].available(), dec!(5));
+    assert_eq!(ledger.clients.0[&0].held(), dec!(0));
+    assert_eq!(ledger.clients.0[&0].locked(), true);
+
+    // Undoing writes the old clients back, and removes the new ones.
+    assert_eq!(ledger.undo(3)?, 3);
+    assert_eq!(ledger.clients.len(), 1);
+    assert_eq!(ledger.clients.0[&0].available(), dec!(15));
+    assert_eq!(ledger.clients.0[&0].locked(), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_client_changes_are_dropped() -> Result<()> {
+    let mut clients: Map<ClientId, Client> = Map::default();
+    let failing = |client: &mut Client| {
+        client.deposit(dec!(5))?;
+        Err(TransactionError::AmountTooLarge)
+    };
+
+    // A client isn't added by a first transaction that fails.
+    assert_eq!(
+        clients.update_or_insert(0, failing),
+        Err(TransactionError::AmountTooLarge)
+    );
+    assert_eq!(clients.len(), 0);
+
+    clients.update_or_insert(0, |client| client.deposit(dec!(10)))?;
+    assert_eq!(clients.update(0, failing), Err(TransactionError::AmountTooLarge));
+    assert_eq!(
+        clients.update_or_insert(0, failing),
+        Err(TransactionError::AmountTooLarge)
+    );
+    assert_eq!(
+        ClientStore::get(&clients, 0)?.map(|client| client.available()),
+        Some(dec!(10))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_daily_withdrawal_limit() -> Result<()> {
+    let at = |timestamp: &str| timestamp.parse::<Timestamp>().map(Some);
+    let withdrawal = |id, amount, timestamp: &str| -> Result<Transaction> {
+        Ok(Transaction::Withdrawal(Withdrawal::new(id, 0, amount)?).with_timestamp(at(timestamp)?))
+    };
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        daily_withdrawal_limit: Some(dec!(100)),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(1000))?))?;
+
+    ledger.execute_transaction(withdrawal(1, dec!(60), "2024-05-01T09:00:00Z")?)?;
+    ledger.execute_transaction(withdrawal(2, dec!(40), "2024-05-01T23:59:59Z")?)?;
+    assert_eq!(
+        ledger.execute_transaction(withdrawal(3, dec!(0.01), "2024-05-01T23:59:59Z")?),
+        Err(TransactionError::WithdrawalLimitExceeded)
+    );
+    // The next day starts over, and untimestamped withdrawals aren't limited.
+    ledger.execute_transaction(withdrawal(4, dec!(30), "2024-05-02T00:00:00Z")?)?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(5, 0, dec!(500))?))?;
+
+    let day = |date: &str| date.parse::<NaiveDate>();
+    assert_eq!(ledger.daily_withdrawals(0, day("2024-05-01")?), dec!(100));
+    assert_eq!(ledger.daily_withdrawals(1, day("2024-05-01")?), dec!(0));
+    assert_eq!(
+        ledger.daily_withdrawals_for(0).collect::<Vec<_>>(),
+        vec![(day("2024-05-01")?, dec!(100)), (day("2024-05-02")?, dec!(30))]
+    );
+    assert_eq!(ledger.clients.get(&0).map(Client::available), Some(dec!(370)));
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use rust_decimal::Decimal;
+
+use super::transactions::Transaction;
+
+#[cfg(test)]
+#[path = "lifetime_tests.rs"]
+mod lifetime_tests;
+
+/// What a client did since the ledger keeps lifetime totals, see
+/// `Ledger::use_lifetime_totals`. The amounts are those of the transactions,
+/// without fees, and nothing is taken back from them by reversals or
+/// chargebacks. A client can deposit and withdraw more in total than fits in
+/// a `Decimal`, so the amounts stop at its limits.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LifetimeTotals {
+    pub deposited: Decimal,
+    pub withdrawn: Decimal,
+    pub disputes: u64,
+    pub chargebacks: u64,
+}
+
+impl LifetimeTotals {
+    /// Counts an executed transaction of the client.
+    pub(super) fn count(&mut self, transaction: &Transaction) {
+        match transaction {
+            Transaction::Deposit(deposit) => self.deposited = self.deposited.saturating_add(deposit.amount()),
+            Transaction::Withdrawal(withdrawal) => self.withdrawn = self.withdrawn.saturating_add(withdrawal.amount()),
+            Transaction::Dispute(_) => self.disputes += 1,
+            Transaction::Chargeback(_) => self.chargebacks += 1,
+            _ => {},
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Resolve, Reversal, Withdrawal};
+
+#[test]
+fn test_lifetime_totals() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    assert_eq!(ledger.lifetime_totals(1), None);
+
+    ledger.use_lifetime_totals();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(4))?))?;
+    // Failed transactions aren't counted.
+    assert!(ledger
+        .execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 1, dec!(100))?))
+        .is_err());
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 1)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(2, 1)))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(5, 2, dec!(7))?))?;
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(5, 2)))?;
+
+    assert_eq!(
+        ledger.lifetime_totals(1),
+        Some(LifetimeTotals {
+            deposited: dec!(5),
+            withdrawn: dec!(4),
+            disputes: 2,
+            chargebacks: 1,
+        })
+    );
+    assert_eq!(
+        ledger.lifetime_totals(2),
+        Some(LifetimeTotals {
+            deposited: dec!(7),
+            ..LifetimeTotals::default()
+        })
+    );
+    assert_eq!(ledger.lifetime_totals(3), Some(LifetimeTotals::default()));
+
+    Ok(())
+}
+
+#[test]
+fn test_lifetime_totals_saturate() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_lifetime_totals();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, Decimal::MAX)?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, Decimal::MAX)?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 1, Decimal::MAX)?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 1, Decimal::MAX)?))?;
+
+    assert_eq!(
+        ledger.lifetime_totals(1),
+        Some(LifetimeTotals {
+            deposited: Decimal::MAX,
+            withdrawn: Decimal::MAX,
+            ..LifetimeTotals::default()
+        })
+    );
+
+    Ok(())
+}
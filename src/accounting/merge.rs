@@ -0,0 +1,62 @@
+use thiserror::Error;
+
+use super::{ClientId, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "merge_tests.rs"]
+mod merge_tests;
+
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("{} transaction ids are in both ledgers, the first is {}", .0.len(), .0[0])]
+    DuplicateIds(Vec<TxId>),
+    #[error("{} clients are in both ledgers, the first is {}", .0.len(), .0[0])]
+    OverlappingClients(Vec<ClientId>),
+    #[error("the id filters of the ledgers aren't the same size")]
+    IncompatibleFilters,
+    #[error("{0}")]
+    Transaction(#[from] TransactionError),
+}
+
+/// How `Ledger::merge` handles what is in both ledgers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MergePolicy {
+    pub duplicate_ids: DuplicatePolicy,
+    pub clients: ClientPolicy,
+}
+
+/// For transaction ids that are in both ledgers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Fails with DuplicateIds, the same transaction was most likely
+    /// executed by both.
+    #[default]
+    Fail,
+    /// Keeps the transaction of the ledger merged into and drops the other.
+    /// The accounts aren't adjusted, they still include what both executed,
+    /// so this is only right for ids that collide between unrelated
+    /// transactions.
+    KeepExisting,
+}
+
+/// For clients that are in both ledgers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ClientPolicy {
+    /// Adds up their funds, the account is locked if either is. Right when
+    /// the input was split by time, as long as no dispute refers to a
+    /// deposit in another part.
+    #[default]
+    Sum,
+    /// Fails with OverlappingClients, for input that was split by client.
+    Fail,
+}
+
+/// What `Ledger::merge` did.
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeReport {
+    pub clients_added: usize,
+    pub clients_merged: usize,
+    pub transactions_added: usize,
+    /// Ids in both ledgers, with DuplicatePolicy::KeepExisting.
+    pub duplicate_ids: Vec<TxId>,
+}
@@ -0,0 +1,114 @@
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::store::bloom::BloomFilter;
+use crate::accounting::store::compact::CompactStore;
+use crate::accounting::transactions::{Deposit, Dispute, Transaction, Withdrawal};
+
+#[test]
+fn test_merge() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(5))?))?;
+
+    let mut other = Ledger::with_store(CompactStore::new());
+    other.execute_transaction(Transaction::Deposit(Deposit::new(3, 2, dec!(1))?))?;
+    other.execute_transaction(Transaction::Deposit(Deposit::new(4, 3, dec!(7))?))?;
+    other.execute_transaction(Transaction::Dispute(Dispute::new(4, 3)))?;
+
+    let report = ledger.merge(other, &MergePolicy::default())?;
+    assert_eq!(
+        report,
+        MergeReport {
+            clients_added: 1,
+            clients_merged: 1,
+            transactions_added: 2,
+            duplicate_ids: vec![],
+        }
+    );
+
+    assert_eq!(ledger.clients[&2].available(), dec!(6));
+    assert_eq!(ledger.clients[&3].held(), dec!(7));
+    let stats = ledger.stats();
+    assert_eq!(stats.stored_transactions, 4);
+    assert_eq!(stats.open_disputes, 1);
+
+    // The merged transactions can be disputed, and aren't executed again.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(3, 2)))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(4, 3, dec!(7))?))?;
+    assert_eq!(ledger.clients[&2].held(), dec!(1));
+    assert_eq!(ledger.clients[&3].held(), dec!(7));
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_conflicts() -> Result<()> {
+    let ledger = || -> Result<Ledger> {
+        let mut ledger = Ledger::new();
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+        Ok(ledger)
+    };
+    let mut other = Ledger::new();
+    other.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    other.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(3))?))?;
+
+    let mut merged = ledger()?;
+    if let Err(err) = merged.merge(other.fork(), &MergePolicy::default()) {
+        assert!(matches!(err, MergeError::DuplicateIds(ids) if ids == vec![1]));
+    } else {
+        bail!("duplicate ids should fail the merge");
+    }
+    assert_eq!(merged.stats(), ledger()?.stats());
+
+    let policy = MergePolicy {
+        duplicate_ids: DuplicatePolicy::KeepExisting,
+        clients: ClientPolicy::Fail,
+    };
+    if let Err(err) = merged.merge(other.fork(), &policy) {
+        assert!(matches!(err, MergeError::OverlappingClients(ids) if ids == vec![1]));
+    } else {
+        bail!("overlapping clients should fail the merge");
+    }
+
+    let policy = MergePolicy {
+        duplicate_ids: DuplicatePolicy::KeepExisting,
+        ..MergePolicy::default()
+    };
+    let report = merged.merge(other, &policy)?;
+    assert_eq!(report.duplicate_ids, vec![1]);
+    assert_eq!(report.transactions_added, 1);
+    // Both deposits are in the account.
+    assert_eq!(merged.clients[&1].available(), dec!(20));
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_id_filters() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_id_filter(BloomFilter::new(100, 0.001));
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(1))?))?;
+
+    let mut other = Ledger::new();
+    other.use_id_filter(BloomFilter::new(100, 0.001));
+    other.execute_transaction(Transaction::Deposit(Deposit::new(3, 2, dec!(10))?))?;
+    other.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 2, dec!(1))?))?;
+
+    let mut different = Ledger::new();
+    different.use_id_filter(BloomFilter::new(1000, 0.001));
+    assert!(matches!(
+        ledger.merge(different, &MergePolicy::default()),
+        Err(MergeError::IncompatibleFilters)
+    ));
+
+    ledger.merge(other, &MergePolicy::default())?;
+    assert!(ledger.is_settled(2));
+    assert!(ledger.is_settled(4));
+
+    Ok(())
+}
@@ -1,21 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
 use anyhow::Result;
 use enum_dispatch::enum_dispatch;
+use rust_decimal::Decimal;
 use thiserror::Error;
 
+pub mod audit;
+pub mod checkpoint;
 pub mod client;
+pub mod concurrent;
+pub mod currency;
+pub mod diff;
+pub mod digest;
 pub mod ledger;
+pub mod lifetime;
+pub mod merge;
+pub mod monitoring;
+pub mod policy;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod recovery;
+pub mod scoring;
+pub mod settlement;
+pub mod shared;
+pub mod simulation;
+pub mod snapshot;
+pub mod store;
+pub mod tenants;
+pub mod timestamp;
 pub mod transactions;
+mod undo;
+pub mod verify;
+pub mod wal;
+
+use policy::LedgerPolicy;
+use transactions::{
+    Accrue, Chargeback, CloseAccount, Deposit, Dispute, DisputedWithdrawal, Freeze, Resolve, Reversal, SetCreditLimit,
+    Transaction, Unfreeze, Withdrawal,
+};
+
+/// The hasher used by all of the ledger's maps. The keys are small integers,
+/// for which SipHash (the default) is mostly overhead. The `fxhash` or `ahash`
+/// features replace it with a faster hasher. Note that FxHash has no
+/// protection against inputs crafted to collide.
+#[cfg(feature = "fxhash")]
+pub type BuildHasher = rustc_hash::FxBuildHasher;
+#[cfg(all(feature = "ahash", not(feature = "fxhash")))]
+pub type BuildHasher = ahash::RandomState;
+#[cfg(not(any(feature = "fxhash", feature = "ahash")))]
+pub type BuildHasher = std::collections::hash_map::RandomState;
 
-use transactions::{Chargeback, Deposit, Dispute, Resolve, Transaction, Withdrawal};
+/// The ids of clients. They're only as wide as the clients in the input by
+/// default, which keeps the ledger's maps small. The `wide-ids` feature
+/// widens them, for more than 65,536 clients.
+#[cfg(not(feature = "wide-ids"))]
+pub type ClientId = u16;
+#[cfg(feature = "wide-ids")]
+pub type ClientId = u32;
 
-#[derive(Debug, PartialEq, Error)]
+/// The ids of transactions. Snapshots before version 9 had 32 bit ids, see
+/// `snapshot::read`.
+pub type TxId = u64;
+
+pub type Map<K, V> = HashMap<K, V, BuildHasher>;
+pub type Set<T> = HashSet<T, BuildHasher>;
+
+#[derive(Clone, Debug, PartialEq, Error)]
 pub enum TransactionError {
     #[error("insufficient funds")]
     InsufficientFunds,
     #[error("account is locked")]
     AccountLocked,
+    #[error("account is frozen")]
+    AccountFrozen,
+    #[error("account is closed")]
+    AccountClosed,
+    #[error("account has held funds")]
+    FundsHeld,
+    #[error("withdrawal would leave less than the minimum balance")]
+    BelowMinimumBalance,
     #[error("invalid amount")]
     InvalidAmount,
+    #[error("amount is over the maximum")]
+    AmountTooLarge,
+    #[error("amount overflow")]
+    AmountOverflow,
     #[error("client not found")]
     ClientNotFound,
     #[error("transaction not found")]
@@ -28,23 +97,112 @@ pub enum TransactionError {
     TransactionAlreadyDisputed,
     #[error("transaction is not under a dispute")]
     TransactionNotDisputed,
+    #[error("transaction is settled and can no longer be changed")]
+    TransactionSettled,
+    #[error("transaction was reversed")]
+    TransactionReversed,
+    #[error("transaction is held for review")]
+    Quarantined,
+    #[error("transaction is too old to be disputed")]
+    DisputeWindowExpired,
+    #[error("withdrawal is over the daily limit")]
+    WithdrawalLimitExceeded,
+    #[error("transaction storage failure: {0}")]
+    StorageFailure(String),
+}
+
+impl TransactionError {
+    /// A stable identifier for the error, meant for machines rather than
+    /// people.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TransactionError::InsufficientFunds => "insufficient_funds",
+            TransactionError::AccountLocked => "account_locked",
+            TransactionError::AccountFrozen => "account_frozen",
+            TransactionError::AccountClosed => "account_closed",
+            TransactionError::FundsHeld => "funds_held",
+            TransactionError::BelowMinimumBalance => "below_minimum_balance",
+            TransactionError::InvalidAmount => "invalid_amount",
+            TransactionError::AmountTooLarge => "amount_too_large",
+            TransactionError::AmountOverflow => "amount_overflow",
+            TransactionError::ClientNotFound => "client_not_found",
+            TransactionError::TransactionNotFound => "transaction_not_found",
+            TransactionError::DisputeNotSupported => "dispute_not_supported",
+            TransactionError::TransactionUnderDispute => "transaction_under_dispute",
+            TransactionError::TransactionAlreadyDisputed => "transaction_already_disputed",
+            TransactionError::TransactionNotDisputed => "transaction_not_disputed",
+            TransactionError::TransactionSettled => "transaction_settled",
+            TransactionError::TransactionReversed => "transaction_reversed",
+            TransactionError::Quarantined => "quarantined",
+            TransactionError::DisputeWindowExpired => "dispute_window_expired",
+            TransactionError::WithdrawalLimitExceeded => "withdrawal_limit_exceeded",
+            TransactionError::StorageFailure(_) => "storage_failure",
+        }
+    }
 }
 
 /// Every transaction should implement this trait. The execute function will
 /// determine the transaction's behavior.
 /// The functions dispute, resolve and chargeback should return an unsupported
 /// error (like DisputeNotSupported) if this is not supported by the
-/// transaction.
+/// transaction. The same goes for reverse, which otherwise takes back what
+/// execute did to the client and returns the amount it reversed.
+/// The amount given to dispute, resolve and chargeback is the part of the
+/// transaction they're about, None for all of it (or all that's left).
 /// The id function should return the transaction id if it contains its own
 /// globally unique id. Transactions that only reference others should return
 /// None.
+/// The settled function should return true once no other transaction can
+/// change the stored transaction anymore under the ledger's policy (e.g. its
+/// dispute was resolved) and
+/// under_dispute whether it currently has an open dispute.
 #[enum_dispatch]
 pub trait ExecutableTransaction {
-    fn execute(&self, ledger: &mut ledger::Ledger) -> Result<(), TransactionError>;
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError>;
+
+    fn dispute(
+        &mut self,
+        client: &mut client::Client,
+        amount: Option<Decimal>,
+        policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError>;
+    fn resolve(&mut self, client: &mut client::Client, amount: Option<Decimal>) -> Result<(), TransactionError>;
+    fn chargeback(&mut self, client: &mut client::Client, amount: Option<Decimal>) -> Result<(), TransactionError>;
+    fn reverse(&mut self, client: &mut client::Client) -> Result<Decimal, TransactionError>;
+
+    fn id(&self) -> Option<TxId>;
+    fn settled(&self, policy: &LedgerPolicy) -> bool;
+    fn under_dispute(&self) -> bool;
+}
+
+/// The state a transaction needs in order to execute. Every ledger flavour
+/// implements this so that transactions don't need to know how (or where) the
+/// clients and stored transactions are kept.
+/// `client` should fail with ClientNotFound if the client doesn't exist and
+/// `client_and_transaction` should additionally fail with TransactionNotFound
+/// if the referenced transaction was never stored.
+/// Ledgers that can't be given a policy use the default one.
+pub trait LedgerAccess {
+    fn policy(&self) -> LedgerPolicy {
+        LedgerPolicy::default()
+    }
+
+    fn client_or_insert(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut client::Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError>;
 
-    fn dispute(&mut self, client: &mut client::Client) -> Result<(), TransactionError>;
-    fn resolve(&mut self, client: &mut client::Client) -> Result<(), TransactionError>;
-    fn chargeback(&mut self, client: &mut client::Client) -> Result<(), TransactionError>;
+    fn client(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut client::Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError>;
 
-    fn id(&self) -> Option<u32>;
+    fn client_and_transaction(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        f: impl FnOnce(&mut client::Client, &mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError>;
 }
@@ -0,0 +1,145 @@
+use std::sync::mpsc::Sender;
+
+use serde::Deserialize;
+
+use super::transactions::Transaction;
+use super::{ClientId, Map};
+
+#[cfg(test)]
+#[path = "monitoring_tests.rs"]
+mod monitoring_tests;
+
+/// The chargebacks and deposits of a client, or of the whole ledger, since
+/// it's monitored, see `Ledger::use_chargeback_monitor`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChargebackRate {
+    pub deposits: u64,
+    pub chargebacks: u64,
+}
+
+impl ChargebackRate {
+    /// Chargebacks per deposit, 0 without deposits.
+    pub fn rate(&self) -> f64 {
+        if self.deposits == 0 {
+            0.0
+        } else {
+            self.chargebacks as f64 / self.deposits as f64
+        }
+    }
+}
+
+/// When the chargeback rate of a client, or of the whole ledger, is alerted
+/// on. Card networks penalize rates above 1%, i.e. a `rate` of 0.01.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertThreshold {
+    pub rate: f64,
+    /// Rates of fewer deposits aren't alerted on, a single chargeback
+    /// shouldn't count as 100%.
+    #[serde(default)]
+    pub min_deposits: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChargebackAlert {
+    /// None for the rate of the whole ledger.
+    pub client_id: Option<ClientId>,
+    pub rate: ChargebackRate,
+}
+
+/// Told about every chargeback rate that goes over the threshold, e.g. to
+/// log it, call a webhook or send it on a channel.
+pub trait AlertHook {
+    fn alert(&self, alert: ChargebackAlert);
+}
+
+impl<F: Fn(ChargebackAlert)> AlertHook for F {
+    fn alert(&self, alert: ChargebackAlert) {
+        self(alert)
+    }
+}
+
+/// Alerts nobody listens to anymore are dropped.
+impl AlertHook for Sender<ChargebackAlert> {
+    fn alert(&self, alert: ChargebackAlert) {
+        let _ = self.send(alert);
+    }
+}
+
+#[derive(Default)]
+struct Tracked {
+    rate: ChargebackRate,
+    /// Whether the rate is over the threshold, so that it's only alerted on
+    /// when it goes over it.
+    over: bool,
+}
+
+impl Tracked {
+    // Returns whether the rate just went over the threshold.
+    fn count(&mut self, chargeback: bool, threshold: &AlertThreshold) -> bool {
+        if chargeback {
+            self.rate.chargebacks += 1;
+        } else {
+            self.rate.deposits += 1;
+        }
+
+        let over = self.rate.deposits >= threshold.min_deposits && self.rate.rate() > threshold.rate;
+        let crossed = over && !self.over;
+        self.over = over;
+        crossed
+    }
+}
+
+/// The chargeback rates of a ledger and who to alert about them.
+pub(super) struct ChargebackMonitor {
+    hook: Box<dyn AlertHook + Send + Sync>,
+    threshold: AlertThreshold,
+    clients: Map<ClientId, Tracked>,
+    global: Tracked,
+}
+
+impl ChargebackMonitor {
+    pub(super) fn new(threshold: AlertThreshold, hook: impl AlertHook + Send + Sync + 'static) -> ChargebackMonitor {
+        ChargebackMonitor {
+            hook: Box::new(hook),
+            threshold,
+            clients: Map::default(),
+            global: Tracked::default(),
+        }
+    }
+
+    /// Counts an executed transaction, alerting if it takes a rate over the
+    /// threshold.
+    pub(super) fn count(&mut self, transaction: &Transaction) {
+        let chargeback = match transaction {
+            Transaction::Deposit(_) => false,
+            Transaction::Chargeback(_) => true,
+            _ => return,
+        };
+
+        let client_id = transaction.client_id();
+        let client = self.clients.entry(client_id).or_default();
+        if client.count(chargeback, &self.threshold) {
+            self.hook.alert(ChargebackAlert {
+                client_id: Some(client_id),
+                rate: client.rate,
+            });
+        }
+        if self.global.count(chargeback, &self.threshold) {
+            self.hook.alert(ChargebackAlert {
+                client_id: None,
+                rate: self.global.rate,
+            });
+        }
+    }
+
+    pub(super) fn rate(&self, client_id: ClientId) -> ChargebackRate {
+        self.clients
+            .get(&client_id)
+            .map_or_else(ChargebackRate::default, |client| client.rate)
+    }
+
+    pub(super) fn global_rate(&self) -> ChargebackRate {
+        self.global.rate
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::mpsc;
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute};
+
+#[test]
+fn test_chargeback_alerts() -> Result<()> {
+    let (sender, alerts) = mpsc::channel();
+    let mut ledger = Ledger::new();
+    assert_eq!(ledger.global_chargeback_rate(), None);
+
+    let threshold = AlertThreshold {
+        rate: 0.25,
+        min_deposits: 2,
+    };
+    ledger.use_chargeback_monitor(threshold, sender);
+    for (id, client_id) in [(1, 1), (2, 1), (3, 2), (4, 2), (5, 2), (6, 2)] {
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(id, client_id, dec!(1))?))?;
+    }
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 1)))?;
+    let client_rate = ChargebackRate {
+        deposits: 2,
+        chargebacks: 1,
+    };
+    assert_eq!(
+        alerts.try_iter().collect::<Vec<_>>(),
+        vec![ChargebackAlert {
+            client_id: Some(1),
+            rate: client_rate,
+        }]
+    );
+    assert_eq!(ledger.chargeback_rate(1), Some(client_rate));
+
+    // A failed chargeback isn't counted.
+    assert!(ledger
+        .execute_transaction(Transaction::Chargeback(Chargeback::new(3, 2)))
+        .is_err());
+
+    // Exactly at the threshold isn't over it.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(3, 2)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(3, 2)))?;
+    let global_rate = ChargebackRate {
+        deposits: 6,
+        chargebacks: 2,
+    };
+    assert_eq!(
+        alerts.try_iter().collect::<Vec<_>>(),
+        vec![ChargebackAlert {
+            client_id: None,
+            rate: global_rate,
+        }]
+    );
+    assert_eq!(ledger.chargeback_rate(2).map(|rate| rate.rate()), Some(0.25));
+    assert_eq!(ledger.global_chargeback_rate(), Some(global_rate));
+
+    Ok(())
+}
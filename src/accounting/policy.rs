@@ -0,0 +1,210 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+
+use super::timestamp::Timestamp;
+use super::{ClientId, TransactionError};
+
+/// Decimals the funds are rounded to unless the policy says otherwise.
+pub const DEFAULT_PRECISION: u32 = 4;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Rules that differ between payment partners, see `Ledger::use_policy`.
+/// The default is the behaviour the ledger always had.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LedgerPolicy {
+    /// Lets withdrawals be disputed. Nothing is held while the dispute is
+    /// open, the withdrawn funds are already gone. A chargeback credits them
+    /// back to the client and locks the account, a resolve changes nothing.
+    /// Withdrawals are then only settled once their dispute is, so they're
+    /// kept in the store like deposits.
+    pub withdrawal_disputes: bool,
+    /// Lets a resolved dispute be followed by another one, e.g. for a second
+    /// presentment. Charged back amounts can never be disputed again. Resolved
+    /// transactions are then only settled by a chargeback.
+    pub redispute_after_resolve: bool,
+    /// Rejects disputes filed more than this many days after the disputed
+    /// transaction with DisputeWindowExpired. Only disputes and transactions
+    /// that both have a timestamp are checked.
+    pub dispute_window_days: Option<u32>,
+    /// Rejects deposits to frozen accounts with AccountFrozen, see
+    /// `Freeze`. They're accepted by default, only withdrawals are blocked.
+    pub reject_deposits_when_frozen: bool,
+    /// Rejects deposits to locked accounts with AccountLocked. Withdrawals
+    /// always are, deposits are accepted by default.
+    pub reject_deposits_when_locked: bool,
+    /// Rejects disputes on locked accounts with AccountLocked, they're
+    /// accepted by default. Resolves and chargebacks of disputes opened
+    /// before the lock are still accepted.
+    pub reject_disputes_when_locked: bool,
+    /// What a dispute of a deposit does when the client no longer has the
+    /// funds to hold, e.g. because they were withdrawn.
+    pub negative_balance: NegativeBalancePolicy,
+    /// The least every client has to keep available, e.g. for prepaid
+    /// products. Withdrawals that would leave less fail with
+    /// BelowMinimumBalance, whatever their credit limit. Disputes and
+    /// chargebacks can still take the funds below it.
+    pub minimum_balance: Option<Decimal>,
+    /// The most a client can withdraw on a calendar day, in UTC, going by
+    /// the withdrawals' timestamps. Withdrawals that would take the day's
+    /// total over it fail with WithdrawalLimitExceeded. Withdrawals without a
+    /// timestamp aren't limited. See `Ledger::daily_withdrawals`.
+    pub daily_withdrawal_limit: Option<Decimal>,
+    /// The largest deposit or withdrawal accepted, larger ones fail with
+    /// AmountTooLarge. Meant to catch fat fingers and shifted exponents in
+    /// feeds rather than to limit clients.
+    pub max_amount: Option<Decimal>,
+    /// Percent of its available funds credited to a client by each `accrue`
+    /// record, e.g. 0.5 for 0.5%, rounded to `precision`. None by default, so
+    /// accruing credits nothing.
+    pub interest_rate: Option<Decimal>,
+    /// Fees charged to clients, none by default.
+    pub fees: Option<FeePolicy>,
+    /// Decimals the funds are rounded to after every transaction,
+    /// `DEFAULT_PRECISION` by default. E.g. 0 for JPY or 3 for BHD, see
+    /// `CurrencyLedgers::use_currency_policy`.
+    pub precision: Option<u32>,
+    /// How the funds are rounded to `precision`.
+    pub rounding: Rounding,
+    /// Rejects deposits and withdrawals with more decimals than `precision`
+    /// with InvalidAmount, instead of rounding them. Trailing zeros don't
+    /// count.
+    pub strict_precision: bool,
+}
+
+impl LedgerPolicy {
+    pub(crate) fn check_amount(&self, amount: Decimal) -> Result<(), TransactionError> {
+        if self.max_amount.map_or(false, |max| amount > max) {
+            return Err(TransactionError::AmountTooLarge);
+        }
+
+        if self.strict_precision && amount.normalize().scale() > self.precision() {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        Ok(())
+    }
+
+    /// Whether a dispute filed at `filed` is within `dispute_window_days` of
+    /// the transaction, which happened at `happened`.
+    pub fn within_dispute_window(&self, happened: Timestamp, filed: Timestamp) -> bool {
+        self.dispute_window_days.map_or(true, |days| {
+            filed.unix() - happened.unix() <= i64::from(days) * SECONDS_PER_DAY
+        })
+    }
+
+    pub fn precision(&self) -> u32 {
+        self.precision.unwrap_or(DEFAULT_PRECISION)
+    }
+
+    /// Rounds an amount to `precision` decimals, the `rounding` way.
+    pub fn round(&self, amount: Decimal) -> Decimal {
+        self.rounding.round(amount, self.precision())
+    }
+}
+
+/// See `LedgerPolicy::fees`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeePolicy {
+    /// The client the fees are credited to, e.g. a house account. It's
+    /// created by the first fee and exported like any other.
+    pub account: ClientId,
+    /// Taken together with every withdrawal, which fails with
+    /// InsufficientFunds unless both are available. Reversing the withdrawal
+    /// doesn't refund it.
+    pub withdrawal: Option<Fee>,
+    /// Taken on every chargeback, even if that leaves the client with
+    /// negative funds. Percentages are of the amount charged back.
+    pub chargeback: Option<Fee>,
+}
+
+/// Negative fees are rejected, they'd pay clients out of the fees account.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "UncheckedFee")]
+pub enum Fee {
+    Flat(Decimal),
+    /// Of the transaction's amount, e.g. 1.5 for 1.5%.
+    Percent(Decimal),
+}
+
+// A fee as written in a config file, before it's checked.
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum UncheckedFee {
+    Flat(Decimal),
+    Percent(Decimal),
+}
+
+impl TryFrom<UncheckedFee> for Fee {
+    type Error = String;
+
+    fn try_from(fee: UncheckedFee) -> Result<Self, Self::Error> {
+        let fee = match fee {
+            UncheckedFee::Flat(fee) => Fee::Flat(fee),
+            UncheckedFee::Percent(percent) => Fee::Percent(percent),
+        };
+        match fee {
+            Fee::Flat(amount) | Fee::Percent(amount) if amount < Decimal::ZERO => {
+                Err(format!("fees can't be negative, got {}", amount))
+            },
+            _ => Ok(fee),
+        }
+    }
+}
+
+impl Fee {
+    /// Percentages are rounded as the policy says. Fails with AmountOverflow
+    /// if the fee is out of a Decimal's range.
+    pub fn amount(&self, of: Decimal, policy: &LedgerPolicy) -> Result<Decimal, TransactionError> {
+        match self {
+            Fee::Flat(fee) => Ok(*fee),
+            Fee::Percent(percent) => of
+                .checked_mul(*percent)
+                .map(|fee| policy.round(fee / dec!(100)))
+                .ok_or(TransactionError::AmountOverflow),
+        }
+    }
+}
+
+/// See `LedgerPolicy::rounding`. Half-way amounts are rounded to even by
+/// default, e.g. 0.00005 to 0.0000 and 0.00015 to 0.0002.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    #[default]
+    HalfEven,
+    /// Away from zero, e.g. 0.00005 to 0.0001 and -0.00005 to -0.0001.
+    HalfUp,
+    /// Towards zero, dropping the extra decimals.
+    Truncate,
+}
+
+impl Rounding {
+    /// Rounds an amount to `places` decimals this way.
+    pub fn round(self, amount: Decimal, places: u32) -> Decimal {
+        let strategy = match self {
+            Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::Truncate => RoundingStrategy::ToZero,
+        };
+        amount.round_dp_with_strategy(places, strategy)
+    }
+}
+
+/// See `LedgerPolicy::negative_balance`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NegativeBalancePolicy {
+    /// Hold the whole amount anyway, leaving the available funds negative.
+    #[default]
+    Allow,
+    /// Only dispute what's available, like a partial dispute of that amount.
+    /// The rest of the deposit can still be disputed later. Fails with
+    /// InsufficientFunds if nothing is available.
+    Cap,
+    /// Fail with InsufficientFunds.
+    Reject,
+}
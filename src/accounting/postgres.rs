@@ -0,0 +1,384 @@
+use postgres::{NoTls, Row};
+use rust_decimal::Decimal;
+
+use super::client::{Client, ClientMetadata};
+use super::timestamp::Timestamp;
+use super::transactions::{Deposit, DisputeStatus, DisputedWithdrawal, Reversal, Transaction, Withdrawal};
+use super::{ClientId, ExecutableTransaction, LedgerAccess, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "postgres_tests.rs"]
+mod postgres_tests;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS clients (
+    id INTEGER PRIMARY KEY,
+    available NUMERIC NOT NULL,
+    held NUMERIC NOT NULL,
+    locked BOOLEAN NOT NULL,
+    frozen BOOLEAN NOT NULL DEFAULT FALSE,
+    closed BOOLEAN NOT NULL DEFAULT FALSE,
+    credit_limit NUMERIC NOT NULL DEFAULT 0,
+    display_name TEXT,
+    external_ref TEXT,
+    opened_at TEXT
+);
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS frozen BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS closed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS credit_limit NUMERIC NOT NULL DEFAULT 0;
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS display_name TEXT;
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS external_ref TEXT;
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS opened_at TEXT;
+CREATE TABLE IF NOT EXISTS transactions (
+    id BIGINT PRIMARY KEY,
+    type TEXT NOT NULL,
+    client INTEGER NOT NULL,
+    amount NUMERIC NOT NULL,
+    dispute_status TEXT NOT NULL,
+    timestamp BIGINT
+);
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS timestamp BIGINT;
+";
+
+/// A ledger kept in a PostgreSQL database, so several processes can execute
+/// against the same accounts, each with its own connection.
+///
+/// Every transaction is executed in a database transaction of its own. The
+/// row of the client it changes is locked (`SELECT ... FOR UPDATE`) until it
+/// commits, so transactions for the same client are serialized while those
+/// for different clients run in parallel. Transactions with their own id are
+/// inserted first, if the id is already there (`ON CONFLICT DO NOTHING`) the
+/// transaction is ignored, no matter which process executed it.
+///
+/// The semantics of `execute_transaction` are the same as `Ledger`'s.
+pub struct PostgresLedger {
+    connection: postgres::Client,
+}
+
+impl PostgresLedger {
+    /// Connects with a libpq style configuration, e.g. `host=localhost
+    /// user=transacto dbname=ledger` or a `postgresql://` URL, and creates
+    /// the tables if needed.
+    pub fn connect(params: &str) -> Result<PostgresLedger, TransactionError> {
+        let mut connection = postgres::Client::connect(params, NoTls).map_err(storage_failure)?;
+        connection.batch_execute(SCHEMA).map_err(storage_failure)?;
+
+        Ok(PostgresLedger { connection })
+    }
+
+    /// Transactions that have their own global unique id will be stored.
+    /// If the id already exists then the transaction is discarded.
+    pub fn execute_transaction(&mut self, transaction: Transaction) -> Result<(), TransactionError> {
+        // Dropping `db` without committing rolls everything back.
+        let mut db = self.connection.transaction().map_err(storage_failure)?;
+
+        if let Some(id) = transaction.id() {
+            let (type_, client_id, amount, dispute_status) = parts(&transaction)?;
+            let inserted = db
+                .execute(
+                    "INSERT INTO transactions (id, type, client, amount, dispute_status, timestamp)
+                     VALUES ($1, $2, $3, $4, $5, $6)
+                     ON CONFLICT (id) DO NOTHING",
+                    &[
+                        &tx_param(id)?,
+                        &type_,
+                        &client_param(client_id)?,
+                        &amount,
+                        &dispute_status.name(),
+                        &transaction.timestamp().map(|timestamp| timestamp.unix()),
+                    ],
+                )
+                .map_err(storage_failure)?;
+            // The transaction has already been processed, ignore.
+            if inserted == 0 {
+                return Ok(());
+            }
+        }
+
+        transaction.execute(&mut PostgresAccess(&mut db))?;
+        db.commit().map_err(storage_failure)
+    }
+
+    pub fn client(&mut self, client_id: ClientId) -> Result<Option<Client>, TransactionError> {
+        self.connection
+            .query_opt(
+                "SELECT id, available, held, locked, frozen, closed, credit_limit, display_name, external_ref, opened_at
+                 FROM clients WHERE id = $1",
+                &[&client_param(client_id)?],
+            )
+            .map_err(storage_failure)?
+            .map(|row| client_from_row(&row))
+            .transpose()
+    }
+
+    /// Ordered by client id.
+    pub fn clients(&mut self) -> Result<Vec<Client>, TransactionError> {
+        self.connection
+            .query(
+                "SELECT id, available, held, locked, frozen, closed, credit_limit, display_name, external_ref, opened_at
+                 FROM clients ORDER BY id",
+                &[],
+            )
+            .map_err(storage_failure)?
+            .iter()
+            .map(client_from_row)
+            .collect()
+    }
+
+    /// Sets the client's metadata, see `Ledger::set_client_metadata`.
+    pub fn set_client_metadata(
+        &mut self,
+        client_id: ClientId,
+        metadata: &ClientMetadata,
+    ) -> Result<(), TransactionError> {
+        self.connection
+            .execute(
+                "INSERT INTO clients (id, available, held, locked, display_name, external_ref, opened_at)
+                 VALUES ($1, 0, 0, FALSE, $2, $3, $4)
+                 ON CONFLICT (id) DO UPDATE
+                 SET display_name = $2, external_ref = $3, opened_at = $4",
+                &[
+                    &client_param(client_id)?,
+                    &metadata.display_name,
+                    &metadata.external_ref,
+                    &metadata.opened_at,
+                ],
+            )
+            .map_err(storage_failure)?;
+
+        Ok(())
+    }
+
+    pub fn transactions_len(&mut self) -> Result<usize, TransactionError> {
+        let len: i64 = self
+            .connection
+            .query_one("SELECT COUNT(*) FROM transactions", &[])
+            .map_err(storage_failure)?
+            .get(0);
+
+        Ok(len as usize)
+    }
+}
+
+/// Locks the client before the transaction, both stay locked until the
+/// database transaction ends.
+struct PostgresAccess<'a, 'b>(&'a mut postgres::Transaction<'b>);
+
+impl PostgresAccess<'_, '_> {
+    fn lock_client(&mut self, client_id: ClientId) -> Result<Option<Client>, TransactionError> {
+        self.0
+            .query_opt(
+                "SELECT id, available, held, locked, frozen, closed, credit_limit, display_name, external_ref, opened_at
+                 FROM clients WHERE id = $1 FOR UPDATE",
+                &[&client_param(client_id)?],
+            )
+            .map_err(storage_failure)?
+            .map(|row| client_from_row(&row))
+            .transpose()
+    }
+
+    fn save_client(&mut self, client: &Client) -> Result<(), TransactionError> {
+        self.0
+            .execute(
+                "UPDATE clients SET available = $2, held = $3, locked = $4, frozen = $5, closed = $6, credit_limit = $7
+                 WHERE id = $1",
+                &[
+                    &client_param(client.id())?,
+                    &client.available(),
+                    &client.held(),
+                    &client.locked(),
+                    &client.frozen(),
+                    &client.closed(),
+                    &client.credit_limit(),
+                ],
+            )
+            .map_err(storage_failure)?;
+
+        Ok(())
+    }
+
+    fn with_client(
+        &mut self,
+        client: Option<Client>,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let Some(mut client) = client else {
+            return Err(TransactionError::ClientNotFound);
+        };
+
+        f(&mut client)?;
+        client.round(&self.policy());
+        self.save_client(&client)
+    }
+}
+
+impl LedgerAccess for PostgresAccess<'_, '_> {
+    fn client_or_insert(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        self.0
+            .execute(
+                "INSERT INTO clients (id, available, held, locked) VALUES ($1, 0, 0, FALSE)
+                 ON CONFLICT (id) DO NOTHING",
+                &[&client_param(client_id)?],
+            )
+            .map_err(storage_failure)?;
+
+        let client = self.lock_client(client_id)?;
+        self.with_client(client, f)
+    }
+
+    fn client(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let client = self.lock_client(client_id)?;
+        self.with_client(client, f)
+    }
+
+    fn client_and_transaction(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        f: impl FnOnce(&mut Client, &mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let Some(mut client) = self.lock_client(client_id)? else {
+            return Err(TransactionError::ClientNotFound);
+        };
+        let Some(row) = self
+            .0
+            .query_opt(
+                "SELECT type, client, amount, dispute_status, timestamp FROM transactions WHERE id = $1 FOR UPDATE",
+                &[&tx_param(tx_id)?],
+            )
+            .map_err(storage_failure)?
+        else {
+            return Err(TransactionError::TransactionNotFound);
+        };
+        let mut transaction = transaction_from_row(tx_id, &row)?;
+
+        f(&mut client, &mut transaction)?;
+        client.round(&self.policy());
+
+        self.save_client(&client)?;
+        // A reversal replaces the transaction it reversed.
+        let (type_, _client_id, amount, dispute_status) = parts(&transaction)?;
+        self.0
+            .execute(
+                "UPDATE transactions SET type = $2, amount = $3, dispute_status = $4 WHERE id = $1",
+                &[&tx_param(tx_id)?, &type_, &amount, &dispute_status.name()],
+            )
+            .map_err(storage_failure)?;
+
+        Ok(())
+    }
+}
+
+fn parts(transaction: &Transaction) -> Result<(&'static str, ClientId, Decimal, DisputeStatus), TransactionError> {
+    match transaction {
+        Transaction::Deposit(deposit) => Ok((
+            "deposit",
+            deposit.client_id(),
+            deposit.amount(),
+            deposit.dispute_status(),
+        )),
+        Transaction::Withdrawal(withdrawal) => Ok((
+            "withdrawal",
+            withdrawal.client_id(),
+            withdrawal.amount(),
+            DisputeStatus::NoDispute,
+        )),
+        Transaction::DisputedWithdrawal(withdrawal) => Ok((
+            "disputed_withdrawal",
+            withdrawal.client_id(),
+            withdrawal.amount(),
+            withdrawal.dispute_status(),
+        )),
+        Transaction::Reversal(reversal) => Ok((
+            "reversal",
+            reversal.client_id(),
+            reversal.amount().unwrap_or_default(),
+            DisputeStatus::NoDispute,
+        )),
+        _ => Err(storage_failure(
+            "only deposits, withdrawals and reversals can be stored",
+        )),
+    }
+}
+
+fn client_from_row(row: &Row) -> Result<Client, TransactionError> {
+    let id = ClientId::try_from(row.get::<_, i32>(0)).map_err(storage_failure)?;
+    let mut client = Client::from_parts(
+        id,
+        row.get(1),
+        row.get(2),
+        row.get(3),
+        row.get(4),
+        row.get(5),
+        row.get(6),
+    );
+    client.set_metadata(ClientMetadata {
+        display_name: row.get(7),
+        external_ref: row.get(8),
+        opened_at: row.get(9),
+    });
+
+    Ok(client)
+}
+
+fn transaction_from_row(id: TxId, row: &Row) -> Result<Transaction, TransactionError> {
+    let type_: &str = row.get(0);
+    let client_id = ClientId::try_from(row.get::<_, i32>(1)).map_err(storage_failure)?;
+    let amount: Decimal = row.get(2);
+    let timestamp = row
+        .get::<_, Option<i64>>(4)
+        .map(|seconds| {
+            Timestamp::from_unix(seconds).ok_or_else(|| storage_failure(format!("invalid timestamp {}", seconds)))
+        })
+        .transpose()?;
+
+    let dispute_status = || {
+        let dispute_status: &str = row.get(3);
+        DisputeStatus::from_name(dispute_status)
+            .ok_or_else(|| storage_failure(format!("unknown dispute status {}", dispute_status)))
+    };
+
+    match type_ {
+        "deposit" => Ok(Transaction::Deposit(Deposit::from_parts(
+            id,
+            client_id,
+            amount,
+            dispute_status()?,
+            timestamp,
+        ))),
+        "withdrawal" => Ok(Transaction::Withdrawal(Withdrawal::from_parts(
+            id, client_id, amount, timestamp,
+        ))),
+        "disputed_withdrawal" => Ok(Transaction::DisputedWithdrawal(DisputedWithdrawal::from_parts(
+            id,
+            client_id,
+            amount,
+            dispute_status()?,
+            timestamp,
+        ))),
+        "reversal" => Ok(Transaction::Reversal(Reversal::from_parts(id, client_id, amount))),
+        _ => Err(storage_failure(format!("unknown transaction type {}", type_))),
+    }
+}
+
+fn storage_failure(err: impl ToString) -> TransactionError {
+    TransactionError::StorageFailure(err.to_string())
+}
+
+// The id columns are signed, wide ids (see `ClientId`) may not fit.
+#[allow(clippy::unnecessary_fallible_conversions)]
+fn client_param(client_id: ClientId) -> Result<i32, TransactionError> {
+    i32::try_from(client_id).map_err(storage_failure)
+}
+
+fn tx_param(tx_id: TxId) -> Result<i64, TransactionError> {
+    i64::try_from(tx_id).map_err(storage_failure)
+}
@@ -0,0 +1,145 @@
+use std::thread;
+
+use anyhow::{Context, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::transactions::{Chargeback, Dispute, Resolve, Reversal};
+
+// These need a server, e.g.
+// TRANSACTO_TEST_POSTGRES="host=localhost user=postgres" cargo test --features postgres -- --ignored
+// Every test works in a schema of its own, which it drops first.
+fn connect(schema: &str) -> Result<PostgresLedger> {
+    let params = std::env::var("TRANSACTO_TEST_POSTGRES").context("TRANSACTO_TEST_POSTGRES isn't set")?;
+    let mut connection = postgres::Client::connect(&params, NoTls)?;
+    connection.batch_execute(&format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}", schema))?;
+
+    reconnect(schema)
+}
+
+fn reconnect(schema: &str) -> Result<PostgresLedger> {
+    let params = std::env::var("TRANSACTO_TEST_POSTGRES")?;
+    Ok(PostgresLedger::connect(&format!(
+        "{} options='-c search_path={}'",
+        params, schema
+    ))?)
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL server"]
+fn test_execute_and_reconnect() -> Result<()> {
+    let mut ledger = connect("test_execute_and_reconnect")?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10.5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 2, dec!(1.25))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 2, dec!(1))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    drop(ledger);
+
+    let mut ledger = reconnect("test_execute_and_reconnect")?;
+    assert_eq!(ledger.transactions_len()?, 4);
+    assert_eq!(
+        ledger.clients()?,
+        vec![
+            Client::from_parts(1, dec!(3), dec!(10.5), false, false, false, dec!(0)),
+            Client::from_parts(2, dec!(0.25), dec!(0), false, false, false, dec!(0))
+        ]
+    );
+
+    // Repeated transactions are still discarded, and disputes still work.
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(3, 2)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(3, 2)))?;
+    assert_eq!(
+        ledger.client(1)?,
+        Some(Client::from_parts(1, dec!(13.5), dec!(0), false, false, false, dec!(0)))
+    );
+    assert_eq!(
+        ledger.client(2)?,
+        Some(Client::from_parts(2, dec!(-1), dec!(0), true, false, false, dec!(0)))
+    );
+    assert_eq!(ledger.client(3)?, None);
+
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(4, 2)))?;
+    assert_eq!(
+        ledger.client(2)?,
+        Some(Client::from_parts(2, dec!(0), dec!(0), true, false, false, dec!(0)))
+    );
+    if let Err(err) = ledger.execute_transaction(Transaction::Reversal(Reversal::new(4, 2))) {
+        assert!(matches!(err, TransactionError::TransactionReversed));
+    } else {
+        anyhow::bail!("transaction should not be reversed again");
+    }
+
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL server"]
+fn test_failed_transactions_change_nothing() -> Result<()> {
+    let mut ledger = connect("test_failed_transactions_change_nothing")?;
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 1, dec!(1))?)) {
+        assert!(matches!(err, TransactionError::ClientNotFound));
+    } else {
+        anyhow::bail!("withdrawal from a missing client should fail");
+    }
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(1))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(2))?)) {
+        assert!(matches!(err, TransactionError::InsufficientFunds));
+    } else {
+        anyhow::bail!("withdrawal should fail for insufficient funds");
+    }
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(3, 1))) {
+        assert!(matches!(err, TransactionError::TransactionNotFound));
+    } else {
+        anyhow::bail!("the failed withdrawal shouldn't be stored");
+    }
+    assert_eq!(ledger.transactions_len()?, 1);
+
+    // The ids of failed transactions can still be used.
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 1, dec!(2))?))?;
+    assert_eq!(
+        ledger.client(1)?,
+        Some(Client::from_parts(1, dec!(3), dec!(0), false, false, false, dec!(0)))
+    );
+
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL server"]
+fn test_shared_between_processes() -> Result<()> {
+    let schema = "test_shared_between_processes";
+    drop(connect(schema)?);
+
+    // Every connection executes the same transactions, each should only
+    // count once.
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            thread::spawn(move || -> Result<()> {
+                let mut ledger = reconnect(schema)?;
+                for id in 1..=50 {
+                    let client_id = (id % 5) as ClientId;
+                    ledger.execute_transaction(Transaction::Deposit(Deposit::new(id, client_id, dec!(1))?))?;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    let mut ledger = reconnect(schema)?;
+    assert_eq!(ledger.transactions_len()?, 50);
+    let clients = ledger.clients()?;
+    assert_eq!(clients.len(), 5);
+    for client in clients {
+        assert_eq!(client.available(), dec!(10));
+    }
+
+    Ok(())
+}
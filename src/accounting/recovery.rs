@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::ledger::Ledger;
+use super::policy::LedgerPolicy;
+use super::snapshot::SnapshotError;
+use super::store::TransactionStore;
+use super::transactions::Transaction;
+use super::wal::Wal;
+use super::TxId;
+use crate::encryption::Key;
+
+#[cfg(test)]
+#[path = "recovery_tests.rs"]
+mod recovery_tests;
+
+/// What `Ledger::recover` did with the entries of the write-ahead log.
+#[derive(Debug, Default, PartialEq)]
+pub struct RecoveryReport {
+    /// Entries executed again.
+    pub replayed: usize,
+    /// Entries left out because the checkpoint already had them.
+    pub in_checkpoint: usize,
+    /// Entries that couldn't be read, e.g. because they failed their
+    /// checksum or were cut off in the crash.
+    pub skipped: usize,
+}
+
+impl<T: TransactionStore + Serialize + DeserializeOwned + FromIterator<(TxId, Transaction)> + Default> Ledger<T> {
+    pub fn recover(
+        checkpoint: impl AsRef<Path>,
+        wal: impl AsRef<Path>,
+    ) -> Result<(Ledger<T>, RecoveryReport), SnapshotError> {
+        Ledger::recover_with(checkpoint, wal, None, LedgerPolicy::default())
+    }
+
+    /// Rebuilds the ledger after a crash: loads the checkpoint, decrypting it
+    /// with the key if needed, and executes the transactions journaled since.
+    /// Without a checkpoint (e.g. a crash before the first one) it starts
+    /// from an empty ledger. The transactions are executed with the policy,
+    /// which should be the one they were executed with the first time.
+    ///
+    /// Transactions that fail are counted as replayed, as they failed the
+    /// first time too. Entries that can't be read are skipped, their
+    /// transactions are lost.
+    pub fn recover_with(
+        checkpoint: impl AsRef<Path>,
+        wal: impl AsRef<Path>,
+        key: Option<&Key>,
+        policy: LedgerPolicy,
+    ) -> Result<(Ledger<T>, RecoveryReport), SnapshotError> {
+        let checkpoint = checkpoint.as_ref();
+        let mut ledger = if checkpoint.exists() {
+            Ledger::load_with(checkpoint, key)?
+        } else {
+            Ledger::default()
+        };
+        ledger.use_policy(policy);
+
+        let mut report = RecoveryReport::default();
+        for entry in Wal::read(wal)? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    warn!("skipped wal entry, err={}", err);
+                    report.skipped += 1;
+                    continue;
+                },
+            };
+
+            if entry.sequence < ledger.wal_sequence() {
+                report.in_checkpoint += 1;
+                continue;
+            }
+
+            ledger.set_position(entry.position);
+            let _ = ledger.execute_transaction(entry.transaction);
+            ledger.set_wal_sequence(entry.sequence + 1);
+            report.replayed += 1;
+        }
+
+        Ok((ledger, report))
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs;
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::checkpoint::CheckpointPolicy;
+use crate::accounting::snapshot::SnapshotOptions;
+use crate::accounting::transactions::{Deposit, Dispute, Transaction, Withdrawal};
+
+#[test]
+fn test_recover() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let checkpoint = dir.path().join("ledger.checkpoint");
+    let wal_dir = dir.path().join("wal");
+
+    let mut ledger = Ledger::new();
+    ledger.use_wal(Wal::open(&wal_dir)?);
+    let policy = CheckpointPolicy {
+        every_transactions: Some(3),
+        every: None,
+    };
+    ledger.use_checkpoints(&checkpoint, policy, SnapshotOptions::default());
+
+    let transactions = [
+        Transaction::Deposit(Deposit::new(0, 1, dec!(10))?),
+        Transaction::Deposit(Deposit::new(1, 1, dec!(5))?),
+        Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(3))?),
+        Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(100))?),
+        Transaction::Dispute(Dispute::new(1, 1)),
+    ];
+    for (line, transaction) in transactions.into_iter().enumerate() {
+        ledger.set_position(line as u64 + 2);
+        let _ = ledger.execute_transaction(transaction);
+        ledger.checkpoint_if_due()?;
+    }
+    // Crashed, without a final checkpoint.
+
+    let (recovered, report): (Ledger, _) = Ledger::recover(&checkpoint, &wal_dir)?;
+    assert_eq!(
+        report,
+        RecoveryReport {
+            replayed: 2,
+            in_checkpoint: 0,
+            skipped: 0,
+        }
+    );
+    assert_eq!(recovered.stats(), ledger.stats());
+    assert_eq!(recovered.position(), 6);
+    assert_eq!(recovered.wal_sequence(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_recover_without_checkpoint() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let mut ledger = Ledger::new();
+    ledger.use_wal(Wal::open(dir.path())?);
+    for id in 0..3 {
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(id, 1, dec!(10))?))?;
+    }
+
+    // Corrupts the last entry.
+    let segment = fs::read_dir(dir.path())?.next().unwrap()?.path();
+    let mut data = fs::read(&segment)?;
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    fs::write(&segment, data)?;
+
+    let (recovered, report): (Ledger, _) = Ledger::recover(dir.path().join("missing.checkpoint"), dir.path())?;
+    assert_eq!(
+        report,
+        RecoveryReport {
+            replayed: 2,
+            in_checkpoint: 0,
+            skipped: 1,
+        }
+    );
+    assert_eq!(recovered.clients.get(&1).unwrap().available(), dec!(20));
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use super::client::Client;
+use super::transactions::Transaction;
+
+#[cfg(test)]
+#[path = "scoring_tests.rs"]
+mod scoring_tests;
+
+/// How suspicious a transaction is, the higher the more. The scale is up to
+/// the scorer, the threshold given to `Ledger::use_scorer` has to match it.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Score(pub f64);
+
+/// A fraud model the ledger asks about every transaction before executing
+/// it, see `Ledger::use_scorer`. `client` is the transaction's client as it
+/// is before the transaction, if it exists.
+pub trait TransactionScorer {
+    fn score(&self, transaction: &Transaction, client: Option<&Client>) -> Score;
+}
+
+impl<F: Fn(&Transaction, Option<&Client>) -> Score> TransactionScorer for F {
+    fn score(&self, transaction: &Transaction, client: Option<&Client>) -> Score {
+        self(transaction, client)
+    }
+}
+
+/// The scorer of a ledger and the transactions it held back.
+pub(super) struct Scoring {
+    pub(super) scorer: Box<dyn TransactionScorer + Send + Sync>,
+    pub(super) threshold: Score,
+    pub(super) quarantined: Vec<Transaction>,
+}
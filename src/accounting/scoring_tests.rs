@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Deposit, Withdrawal};
+use crate::accounting::TransactionError;
+
+// Withdrawals of more than half of what's available are suspicious.
+fn score(transaction: &Transaction, client: Option<&Client>) -> Score {
+    match (transaction, client) {
+        (Transaction::Withdrawal(withdrawal), Some(client)) if withdrawal.amount() * dec!(2) > client.available() => {
+            Score(1.0)
+        },
+        _ => Score(0.0),
+    }
+}
+
+#[test]
+fn test_quarantine() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_scorer(score, Score(0.5));
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(5))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(4))?)) {
+        assert_eq!(err, TransactionError::Quarantined);
+    } else {
+        bail!("the withdrawal should be held for review");
+    }
+    assert_eq!(ledger.clients[&1].available(), dec!(5));
+
+    let quarantined = ledger.take_quarantined();
+    assert_eq!(quarantined.len(), 1);
+    assert!(ledger.take_quarantined().is_empty());
+    for transaction in quarantined {
+        ledger.approve(transaction)?;
+    }
+    assert_eq!(ledger.clients[&1].available(), dec!(1));
+
+    // The scorer is still used after an approval.
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 1, dec!(1))?)) {
+        assert_eq!(err, TransactionError::Quarantined);
+    } else {
+        bail!("the withdrawal should be held for review");
+    }
+    assert_eq!(ledger.clients[&1].available(), dec!(1));
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use super::transactions::Transaction;
+use super::ClientId;
+
+#[cfg(test)]
+#[path = "settlement_tests.rs"]
+mod settlement_tests;
+
+/// A client's figures for a closed settlement period, see
+/// `Ledger::use_settlement`. The amounts are what the transactions changed
+/// the client's total funds by, so fees are included.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SettlementRecord {
+    /// Counted from 1 since the ledger settles.
+    pub period: u64,
+    /// The day of the period's first timestamped transaction, None without
+    /// any.
+    pub date: Option<NaiveDate>,
+    pub client: ClientId,
+    /// Deposited, less the reversed deposits.
+    #[serde(with = "rust_decimal::serde::str")]
+    pub net_deposits: Decimal,
+    /// Withdrawn, less the reversed withdrawals.
+    #[serde(with = "rust_decimal::serde::str")]
+    pub net_withdrawals: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub chargebacks: Decimal,
+}
+
+#[derive(Default)]
+struct Figures {
+    net_deposits: Decimal,
+    net_withdrawals: Decimal,
+    chargebacks: Decimal,
+}
+
+/// The figures of the open period and the closed periods not taken yet.
+pub(super) struct Settlement {
+    period: u64,
+    date: Option<NaiveDate>,
+    clients: BTreeMap<ClientId, Figures>,
+    closed: Vec<SettlementRecord>,
+}
+
+impl Settlement {
+    pub(super) fn new() -> Settlement {
+        Settlement {
+            period: 1,
+            date: None,
+            clients: BTreeMap::new(),
+            closed: Vec::new(),
+        }
+    }
+
+    /// Closes the open period if the transaction happened on a later day
+    /// than it. Transactions from an earlier day are settled in the open
+    /// period anyway.
+    pub(super) fn roll_over(&mut self, transaction: &Transaction) {
+        let Some(date) = transaction.timestamp().map(|timestamp| timestamp.date()) else {
+            return;
+        };
+
+        match self.date {
+            Some(period_date) if date > period_date => {
+                self.close();
+                self.date = Some(date);
+            },
+            Some(_) => {},
+            None => self.date = Some(date),
+        }
+    }
+
+    /// Counts an executed transaction, which changed its client's total
+    /// funds by `change`.
+    pub(super) fn count(&mut self, transaction: &Transaction, change: Decimal) {
+        if !matches!(
+            transaction,
+            Transaction::Deposit(_)
+                | Transaction::Withdrawal(_)
+                | Transaction::Chargeback(_)
+                | Transaction::Reversal(_)
+        ) {
+            return;
+        }
+
+        // A client can move more in a period than fits in a `Decimal`, the
+        // figures stop at its limits.
+        let figures = self.clients.entry(transaction.client_id()).or_default();
+        match transaction {
+            Transaction::Deposit(_) => figures.net_deposits = figures.net_deposits.saturating_add(change),
+            Transaction::Chargeback(_) => figures.chargebacks = figures.chargebacks.saturating_sub(change),
+            // Whichever was reversed gave back what it changed.
+            Transaction::Reversal(_) if change < Decimal::ZERO => {
+                figures.net_deposits = figures.net_deposits.saturating_add(change)
+            },
+            _ => figures.net_withdrawals = figures.net_withdrawals.saturating_sub(change),
+        }
+    }
+
+    /// Closes the open period, with a record for every client that had
+    /// transactions in it, and starts the next one.
+    pub(super) fn close(&mut self) {
+        let period = self.period;
+        let date = self.date.take();
+        self.closed.extend(
+            std::mem::take(&mut self.clients)
+                .into_iter()
+                .map(|(client, figures)| SettlementRecord {
+                    period,
+                    date,
+                    client,
+                    net_deposits: figures.net_deposits,
+                    net_withdrawals: figures.net_withdrawals,
+                    chargebacks: figures.chargebacks,
+                }),
+        );
+        self.period += 1;
+    }
+
+    pub(super) fn take(&mut self) -> Vec<SettlementRecord> {
+        std::mem::take(&mut self.closed)
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::timestamp::Timestamp;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Reversal, Withdrawal};
+
+fn at(timestamp: &str) -> Result<Option<Timestamp>> {
+    Ok(Some(timestamp.parse()?))
+}
+
+#[test]
+fn test_settlement() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.close_day();
+    assert_eq!(ledger.take_settlements(), vec![]);
+
+    ledger.use_settlement();
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(1, 1, dec!(10))?).with_timestamp(at("2024-05-01T09:00:00Z")?),
+    )?;
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(2, 1, dec!(5))?).with_timestamp(at("2024-05-01T10:00:00Z")?),
+    )?;
+    ledger.execute_transaction(
+        Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(4))?).with_timestamp(at("2024-05-01T11:00:00Z")?),
+    )?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 1)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(2, 1)))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(4, 2, dec!(7))?))?;
+    assert_eq!(ledger.take_settlements(), vec![]);
+
+    // The next day closes the first.
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(5, 2, dec!(3))?).with_timestamp(at("2024-05-02T09:00:00Z")?),
+    )?;
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(4, 2)))?;
+    ledger.close_day();
+
+    let day_one = NaiveDate::from_ymd_opt(2024, 5, 1);
+    let day_two = NaiveDate::from_ymd_opt(2024, 5, 2);
+    assert_eq!(
+        ledger.take_settlements(),
+        vec![
+            SettlementRecord {
+                period: 1,
+                date: day_one,
+                client: 1,
+                net_deposits: dec!(15),
+                net_withdrawals: dec!(4),
+                chargebacks: dec!(5),
+            },
+            SettlementRecord {
+                period: 1,
+                date: day_one,
+                client: 2,
+                net_deposits: dec!(7),
+                net_withdrawals: dec!(0),
+                chargebacks: dec!(0),
+            },
+            SettlementRecord {
+                period: 2,
+                date: day_two,
+                client: 2,
+                net_deposits: dec!(-4),
+                net_withdrawals: dec!(0),
+                chargebacks: dec!(0),
+            },
+        ]
+    );
+    assert_eq!(ledger.take_settlements(), vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn test_settlement_saturates() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_settlement();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, Decimal::MAX)?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, Decimal::MAX)?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 1, Decimal::MAX)?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 1, Decimal::MAX)?))?;
+    ledger.close_day();
+
+    assert_eq!(
+        ledger.take_settlements(),
+        vec![SettlementRecord {
+            period: 1,
+            date: None,
+            client: 1,
+            net_deposits: Decimal::MAX,
+            net_withdrawals: Decimal::MAX,
+            chargebacks: dec!(0),
+        }]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use super::client::Client;
+use super::ledger::Ledger;
+use super::transactions::Transaction;
+use super::{ClientId, TransactionError};
+
+#[cfg(test)]
+#[path = "shared_tests.rs"]
+mod shared_tests;
+
+/// A cheaply clonable handle to a single `Ledger` that can be shared between
+/// threads (e.g. request handlers of a web service). Every clone refers to the
+/// same ledger.
+///
+/// Transactions are executed one at a time while readers can look at the
+/// ledger concurrently. Snapshots are always consistent, as they are taken
+/// while no transaction is executing.
+#[derive(Clone, Default)]
+pub struct SharedLedger {
+    inner: Arc<RwLock<Ledger>>,
+}
+
+impl SharedLedger {
+    pub fn new() -> SharedLedger {
+        SharedLedger::default()
+    }
+
+    pub fn execute(&self, transaction: Transaction) -> Result<(), TransactionError> {
+        self.write(|ledger| ledger.execute_transaction(transaction))
+    }
+
+    /// A copy of every client at this point in time.
+    pub fn snapshot(&self) -> Vec<Client> {
+        self.read(|ledger| ledger.clients_iter().map(|(_id, client)| client.clone()).collect())
+    }
+
+    pub fn client(&self, id: ClientId) -> Option<Client> {
+        self.read(|ledger| ledger.clients.get(&id).cloned())
+    }
+
+    /// Runs `f` with shared access to the ledger, for anything not covered by
+    /// the functions above. Transactions are blocked until `f` returns.
+    pub fn read<R>(&self, f: impl FnOnce(&Ledger) -> R) -> R {
+        // Executing a transaction never leaves the ledger half way through an
+        // update, so it's safe to keep using it even if a holder panicked.
+        f(&self.inner.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Runs `f` with exclusive access to the ledger.
+    pub fn write<R>(&self, f: impl FnOnce(&mut Ledger) -> R) -> R {
+        f(&mut self.inner.write().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl From<Ledger> for SharedLedger {
+    fn from(ledger: Ledger) -> Self {
+        SharedLedger {
+            inner: Arc::new(RwLock::new(ledger)),
+        }
+    }
+}
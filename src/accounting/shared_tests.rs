@@ -0,0 +1,53 @@
+use std::thread;
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::transactions::{Deposit, Dispute, Withdrawal};
+use crate::accounting::TxId;
+
+#[test]
+fn test_shared_between_threads() -> Result<()> {
+    let ledger = SharedLedger::new();
+
+    thread::scope(|scope| {
+        for thread_id in 0..4 as TxId {
+            let ledger = ledger.clone();
+            scope.spawn(move || {
+                for i in 0..250 as TxId {
+                    let deposit = Deposit::new(thread_id * 250 + i, thread_id as ClientId, dec!(2)).unwrap();
+                    ledger.execute(Transaction::Deposit(deposit)).unwrap();
+                }
+            });
+        }
+    });
+
+    let mut snapshot = ledger.snapshot();
+    snapshot.sort_by_key(|client| client.id());
+
+    assert_eq!(snapshot.len(), 4);
+    for (id, client) in snapshot.iter().enumerate() {
+        assert_eq!(client.id(), id as ClientId);
+        assert_eq!(client.available(), dec!(500));
+    }
+    assert_eq!(ledger.read(|ledger| ledger.transactions.len()), 1000);
+
+    Ok(())
+}
+
+#[test]
+fn test_client() -> Result<()> {
+    let ledger = SharedLedger::new();
+    ledger.execute(Transaction::Deposit(Deposit::new(0, 3, dec!(10))?))?;
+    ledger.execute(Transaction::Withdrawal(Withdrawal::new(1, 3, dec!(4))?))?;
+    ledger.execute(Transaction::Dispute(Dispute::new(0, 3)))?;
+
+    let client = ledger.client(3).unwrap();
+    assert_eq!(client.available(), dec!(-4));
+    assert_eq!(client.held(), dec!(10));
+    assert_eq!(ledger.client(4).is_none(), true);
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use super::diff::LedgerDiff;
+use super::ledger::Ledger;
+use super::store::TransactionStore;
+use super::transactions::Transaction;
+use super::TransactionError;
+
+#[cfg(test)]
+#[path = "simulation_tests.rs"]
+mod simulation_tests;
+
+/// What a batch of transactions would do to a ledger, see
+/// `Ledger::simulate`.
+pub struct Simulation<T> {
+    /// The fork the batch was executed on.
+    pub ledger: Ledger<T>,
+    /// The transactions that failed, by their index in the batch.
+    pub failed: Vec<(usize, TransactionError)>,
+    /// Every client the batch changed.
+    pub changes: LedgerDiff,
+}
+
+impl<T: TransactionStore + Clone> Ledger<T> {
+    /// Executes the batch on a fork of the ledger and compares the outcome
+    /// with it, e.g. to see what accepting a batch of chargebacks would do.
+    /// This ledger isn't changed.
+    pub fn simulate(&self, batch: impl IntoIterator<Item = Transaction>) -> Simulation<T> {
+        let mut fork = self.fork();
+        let failed = batch
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, transaction)| fork.execute_transaction(transaction).err().map(|err| (index, err)))
+            .collect();
+
+        Simulation {
+            changes: self.diff(&fork),
+            ledger: fork,
+            failed,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::store::compact::CompactStore;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Withdrawal};
+
+#[test]
+fn test_simulate() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    let before = ledger.stats();
+
+    let simulation = ledger.simulate(vec![
+        Transaction::Chargeback(Chargeback::new(1, 1)),
+        Transaction::Withdrawal(Withdrawal::new(3, 2, dec!(50))?),
+        Transaction::Deposit(Deposit::new(4, 3, dec!(1))?),
+    ]);
+
+    assert_eq!(simulation.failed, vec![(1, TransactionError::InsufficientFunds)]);
+    let changes = &simulation.changes.clients;
+    assert_eq!(changes.iter().map(|delta| delta.client).collect::<Vec<_>>(), vec![1, 3]);
+    assert_eq!(changes[0].held(), dec!(-10));
+    assert_eq!(changes[0].locked(), Some(true));
+    assert_eq!(changes[1].before, None);
+    assert_eq!(simulation.ledger.stats().locked_clients, 1);
+
+    // The original is untouched.
+    assert_eq!(ledger.stats(), before);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(4, 3, dec!(1))?))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_fork() -> Result<()> {
+    let mut ledger = Ledger::with_store(CompactStore::new());
+    ledger.use_undo(10);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+
+    let mut fork = ledger.fork();
+    fork.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    fork.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(1))?))?;
+    assert_eq!(fork.undoable(), 0);
+
+    assert_eq!(ledger.clients[&1].held(), dec!(0));
+    assert_eq!(ledger.stats().stored_transactions, 1);
+    assert_eq!(fork.clients[&1].held(), dec!(10));
+    assert_eq!(fork.stats().stored_transactions, 2);
+
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::client::{Client, ClientV5, ClientV6, ClientV7};
+use super::ledger::{Ledger, LedgerV2, LedgerV3, LedgerV4};
+use super::transactions::Transaction;
+use super::wal::WalError;
+use super::TxId;
+use crate::encryption::{is_encrypted, EncryptedReader, EncryptedWriter, Key};
+
+#[cfg(test)]
+#[path = "snapshot_tests.rs"]
+mod snapshot_tests;
+
+/// Snapshots start with these bytes, followed by the format version as a
+/// little endian u32 and, since version 2, the `SnapshotEncoding`.
+///
+/// Version 3 added the ledger's wal sequence, version 4 its position,
+/// version 5 whether clients are frozen, version 6 whether they're closed,
+/// version 7 their credit limit, version 8 their metadata, version 9
+/// widened transaction ids to 64 bits and version 10 added the transactions'
+/// timestamps.
+///
+/// Builds with the `wide-ids` feature use different bytes, as their ids are
+/// wider (see `ClientId`), so that neither reads the other's snapshots.
+#[cfg(not(feature = "wide-ids"))]
+const MAGIC: &[u8; 4] = b"TXSN";
+#[cfg(feature = "wide-ids")]
+const MAGIC: &[u8; 4] = b"TXSW";
+#[cfg(not(feature = "wide-ids"))]
+const OTHER_MAGIC: &[u8; 4] = b"TXSW";
+#[cfg(feature = "wide-ids")]
+const OTHER_MAGIC: &[u8; 4] = b"TXSN";
+
+/// Version of the snapshots written by `write`. It has to be bumped whenever
+/// the layout of anything in a snapshot changes (e.g. a new field in
+/// `Client`). The previous layout then gets its own types, kept only to
+/// read old snapshots and convert them in `read`.
+pub const FORMAT_VERSION: u32 = 10;
+
+/// How the ledger itself is encoded after the header. Both are compact binary
+/// encodings. bincode is the fastest, CBOR is self describing so it can be
+/// read by other tools.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotEncoding {
+    #[default]
+    Bincode,
+    Cbor,
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("failed to encode snapshot, err={0}")]
+    Encode(Box<dyn std::error::Error + Send + Sync>),
+    #[error("failed to decode snapshot, err={0}")]
+    Decode(Box<dyn std::error::Error + Send + Sync>),
+    #[error("unsupported snapshot version {0}, the latest known is {FORMAT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("snapshot was written by a build with ids of a different width, see the wide-ids feature")]
+    IdWidth,
+    #[error("unknown snapshot encoding {0}")]
+    UnknownEncoding(u8),
+    #[error("snapshot is encrypted, a key is required")]
+    Encrypted,
+    #[error("{0}")]
+    Wal(#[from] WalError),
+}
+
+/// How `Ledger::save_with` writes a snapshot.
+#[derive(Clone, Default)]
+pub struct SnapshotOptions {
+    pub encoding: SnapshotEncoding,
+    /// Encrypt the whole snapshot, see `EncryptedWriter`.
+    pub key: Option<Key>,
+}
+
+impl SnapshotEncoding {
+    fn tag(self) -> u8 {
+        match self {
+            SnapshotEncoding::Bincode => 0,
+            SnapshotEncoding::Cbor => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<SnapshotEncoding, SnapshotError> {
+        match tag {
+            0 => Ok(SnapshotEncoding::Bincode),
+            1 => Ok(SnapshotEncoding::Cbor),
+            _ => Err(SnapshotError::UnknownEncoding(tag)),
+        }
+    }
+}
+
+impl FromStr for SnapshotEncoding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<SnapshotEncoding, String> {
+        match value {
+            "bincode" => Ok(SnapshotEncoding::Bincode),
+            "cbor" => Ok(SnapshotEncoding::Cbor),
+            _ => Err(format!("unknown snapshot encoding {}, expected bincode or cbor", value)),
+        }
+    }
+}
+
+/// Writes the whole ledger (clients, stored transactions with their dispute
+/// status and the ids of settled ones) so it can be read back later without
+/// processing the transactions again.
+pub fn write<T: Serialize>(ledger: &Ledger<T>, writer: impl Write) -> Result<(), SnapshotError> {
+    write_with(ledger, SnapshotEncoding::default(), writer)
+}
+
+pub fn write_with<T: Serialize>(
+    ledger: &Ledger<T>,
+    encoding: SnapshotEncoding,
+    mut writer: impl Write,
+) -> Result<(), SnapshotError> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&[encoding.tag()])?;
+
+    match encoding {
+        SnapshotEncoding::Bincode => bincode::serialize_into(writer, ledger).map_err(|err| SnapshotError::Encode(err)),
+        SnapshotEncoding::Cbor => {
+            ciborium::into_writer(ledger, writer).map_err(|err| SnapshotError::Encode(Box::new(err)))
+        },
+    }
+}
+
+/// Reads a snapshot written by this or any earlier version, in any encoding.
+pub fn read<T: DeserializeOwned + FromIterator<(TxId, Transaction)>>(
+    mut reader: impl Read,
+) -> Result<Ledger<T>, SnapshotError> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+
+    if header[..4] == OTHER_MAGIC[..] {
+        return Err(SnapshotError::IdWidth);
+    }
+    if header[..4] != MAGIC[..] {
+        // Snapshots didn't have a header at first, their layout is the same
+        // as version 1. There were no wide ids then.
+        if cfg!(feature = "wide-ids") {
+            return Err(SnapshotError::IdWidth);
+        }
+        return decode::<LedgerV2>(SnapshotEncoding::Bincode, header.chain(reader)).map(Ledger::from);
+    }
+
+    match u32::from_le_bytes([header[4], header[5], header[6], header[7]]) {
+        // Only bincode was supported, so there is no encoding in the header.
+        1 => decode::<LedgerV2>(SnapshotEncoding::Bincode, reader).map(Ledger::from),
+        2 => decode::<LedgerV2>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        3 => decode::<LedgerV3>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        4 => decode::<LedgerV4>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        5 => decode::<LedgerV4<ClientV5>>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        6 => decode::<LedgerV4<ClientV6>>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        7 => decode::<LedgerV4<ClientV7>>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        // Wide ids came with 64 bit transaction ids.
+        8 if cfg!(feature = "wide-ids") => {
+            decode::<LedgerV4<Client, TxId>>(read_encoding(&mut reader)?, reader).map(Ledger::from)
+        },
+        8 => decode::<LedgerV4<Client>>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        9 => decode::<LedgerV4<Client, TxId>>(read_encoding(&mut reader)?, reader).map(Ledger::from),
+        10 => decode(read_encoding(&mut reader)?, reader),
+        version => Err(SnapshotError::UnsupportedVersion(version)),
+    }
+}
+
+fn read_encoding(reader: &mut impl Read) -> Result<SnapshotEncoding, SnapshotError> {
+    let mut tag = [0];
+    reader.read_exact(&mut tag)?;
+    SnapshotEncoding::from_tag(tag[0])
+}
+
+fn decode<T: DeserializeOwned>(encoding: SnapshotEncoding, reader: impl Read) -> Result<T, SnapshotError> {
+    match encoding {
+        SnapshotEncoding::Bincode => bincode::deserialize_from(reader).map_err(|err| SnapshotError::Decode(err)),
+        SnapshotEncoding::Cbor => ciborium::from_reader(reader).map_err(|err| SnapshotError::Decode(Box::new(err))),
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + FromIterator<(TxId, Transaction)>> Ledger<T> {
+    /// Writes a snapshot of the ledger to a file, see `write`. The snapshot is
+    /// written next to it first and then moved in place, so a crash never
+    /// leaves a half written file behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        self.save_with(path, &SnapshotOptions::default())
+    }
+
+    pub fn save_with(&self, path: impl AsRef<Path>, options: &SnapshotOptions) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        if let Some(key) = &options.key {
+            let mut encrypted = EncryptedWriter::new(key, writer)?;
+            write_with(self, options.encoding, &mut encrypted)?;
+            writer = encrypted.finish()?;
+        } else {
+            write_with(self, options.encoding, &mut writer)?;
+        }
+        writer.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+        fs::rename(&partial, path)?;
+
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Ledger<T>, SnapshotError> {
+        Ledger::load_with(path, None)
+    }
+
+    /// Loads a snapshot, decrypting it with the key if it's encrypted.
+    pub fn load_with(path: impl AsRef<Path>, key: Option<&Key>) -> Result<Ledger<T>, SnapshotError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        if !is_encrypted(reader.fill_buf()?) {
+            return read(reader);
+        }
+
+        match key {
+            Some(key) => read(EncryptedReader::new(key, reader)?),
+            None => Err(SnapshotError::Encrypted),
+        }
+    }
+}
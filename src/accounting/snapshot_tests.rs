@@ -0,0 +1,234 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::store::bloom::BloomFilter;
+use crate::accounting::transactions::{Deposit, Dispute, Resolve, Transaction, Withdrawal};
+use crate::accounting::{ExecutableTransaction, TransactionError};
+
+#[test]
+fn test_round_trip() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10.5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(2))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 0, dec!(0.25))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 1)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(2, 1)))?;
+    ledger.compact(|_transaction| {})?;
+
+    let mut buffer = Vec::new();
+    write(&ledger, &mut buffer)?;
+    let mut restored: Ledger = read(buffer.as_slice())?;
+
+    assert_eq!(restored.stats(), ledger.stats());
+    let client = restored.clients.get(&0).unwrap();
+    assert_eq!(client.available(), dec!(10.25));
+    assert_eq!(client.held(), dec!(2));
+    assert_eq!(restored.transactions.get(&1).unwrap().under_dispute(), true);
+    assert_eq!(restored.is_settled(2), true);
+
+    restored.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    assert_eq!(restored.clients.get(&0).unwrap().available(), dec!(12.25));
+
+    if let Err(err) = restored.execute_transaction(Transaction::Dispute(Dispute::new(2, 1))) {
+        assert_eq!(err, TransactionError::TransactionSettled);
+    } else {
+        anyhow::bail!("settled transaction should stay settled");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_round_trip_with_id_filter() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_id_filter(BloomFilter::new(100, 0.001));
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(4))?))?;
+
+    let mut buffer = Vec::new();
+    write(&ledger, &mut buffer)?;
+    let mut restored: Ledger = read(buffer.as_slice())?;
+
+    restored.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(4))?))?;
+    assert_eq!(restored.clients.get(&0).unwrap().available(), dec!(6));
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_snapshot() {
+    let result: Result<Ledger, SnapshotError> = read(&b"not a snapshot"[..]);
+    #[cfg(not(feature = "wide-ids"))]
+    assert!(matches!(result, Err(SnapshotError::Decode(_))));
+    // Without a header it could only be a narrow one.
+    #[cfg(feature = "wide-ids")]
+    assert!(matches!(result, Err(SnapshotError::IdWidth)));
+}
+
+#[test]
+fn test_id_width() -> Result<()> {
+    let mut buffer = Vec::new();
+    write(&Ledger::new(), &mut buffer)?;
+    buffer[..4].copy_from_slice(OTHER_MAGIC);
+
+    let result: Result<Ledger, SnapshotError> = read(buffer.as_slice());
+    assert!(matches!(result, Err(SnapshotError::IdWidth)));
+
+    Ok(())
+}
+
+#[test]
+fn test_save_and_load() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.snapshot");
+
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.save(&path)?;
+
+    // A day later.
+    let mut ledger: Ledger = Ledger::load(&path)?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.set_position(7);
+    ledger.save(&path)?;
+
+    let ledger: Ledger = Ledger::load(&path)?;
+    let client = ledger.clients.get(&0).unwrap();
+    assert_eq!(client.available(), dec!(0));
+    assert_eq!(client.held(), dec!(10));
+    assert_eq!(ledger.position(), 7);
+    assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+
+    Ok(())
+}
+
+// Only narrow ids were ever written with the older versions.
+#[cfg(not(feature = "wide-ids"))]
+#[test]
+fn test_versions() -> Result<()> {
+    let timestamp = crate::accounting::timestamp::Timestamp::from_unix(1714557600);
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?).with_timestamp(timestamp))?;
+
+    let mut buffer = Vec::new();
+    write(&ledger, &mut buffer)?;
+    assert_eq!(&buffer[..4], b"TXSN");
+    assert_eq!(buffer[4..8], FORMAT_VERSION.to_le_bytes());
+    let restored: Ledger = read(buffer.as_slice())?;
+    assert_eq!(restored.transactions[&0].timestamp(), timestamp);
+
+    // Version 9 had no timestamp at the end of the (only) stored transaction.
+    let client_len = bincode::serialize(&ledger.clients[&0])?.len();
+    let transactions_start = 8 + 2 + client_len;
+    let transaction_end = transactions_start + 16 + bincode::serialize(&ledger.transactions[&0])?.len();
+    let mut version_9_ledger = bincode::serialize(&ledger)?;
+    version_9_ledger.drain(transaction_end - 9..transaction_end);
+
+    // Version 8 had 32 bit transaction ids, in the key of the transaction and
+    // in the deposit itself after its variant.
+    let mut version_8_ledger = version_9_ledger.clone();
+    version_8_ledger.drain(transactions_start + 24..transactions_start + 28);
+    version_8_ledger.drain(transactions_start + 12..transactions_start + 16);
+
+    // Version 7 had no metadata at the end of the client, version 6 no credit
+    // limit, version 5 no closed flag and version 4 no frozen flag either.
+    let metadata_len = bincode::serialize(ledger.clients[&0].metadata())?.len();
+    let credit_limit_len = bincode::serialize(&ledger.clients[&0].credit_limit().to_string())?.len();
+    let client_end = 8 + 2 + client_len - metadata_len - credit_limit_len;
+    let mut version_7_ledger = version_8_ledger.clone();
+    version_7_ledger.drain(client_end + credit_limit_len..client_end + credit_limit_len + metadata_len);
+    let mut version_6_ledger = version_7_ledger.clone();
+    version_6_ledger.drain(client_end..client_end + credit_limit_len);
+    let mut version_5_ledger = version_6_ledger.clone();
+    version_5_ledger.remove(client_end - 1);
+    let mut version_4_ledger = version_5_ledger.clone();
+    version_4_ledger.remove(client_end - 2);
+    for (version, bytes) in [
+        (9u32, &version_9_ledger),
+        (8, &version_8_ledger),
+        (7, &version_7_ledger),
+        (6, &version_6_ledger),
+        (5, &version_5_ledger),
+        (4, &version_4_ledger),
+    ] {
+        let mut snapshot = b"TXSN".to_vec();
+        snapshot.extend_from_slice(&version.to_le_bytes());
+        snapshot.push(0);
+        snapshot.extend_from_slice(bytes);
+        let restored: Ledger = read(snapshot.as_slice())?;
+        assert_eq!(restored.stats(), ledger.stats());
+        assert_eq!(restored.transactions[&0].timestamp(), None);
+    }
+
+    // Written before snapshots had a header, nor the wal sequence and
+    // position at the end of the ledger.
+    let mut legacy = version_4_ledger;
+    legacy.truncate(legacy.len() - 16);
+    let restored: Ledger = read(legacy.as_slice())?;
+    assert_eq!(restored.stats(), ledger.stats());
+
+    // Version 1 had no encoding in the header.
+    let mut version_1 = b"TXSN".to_vec();
+    version_1.extend_from_slice(&1u32.to_le_bytes());
+    version_1.extend_from_slice(&legacy);
+    let restored: Ledger = read(version_1.as_slice())?;
+    assert_eq!(restored.stats(), ledger.stats());
+
+    buffer[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    let result: Result<Ledger, SnapshotError> = read(buffer.as_slice());
+    assert!(matches!(result, Err(SnapshotError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1));
+
+    Ok(())
+}
+
+#[test]
+fn test_cbor() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10.5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+
+    let mut buffer = Vec::new();
+    write_with(&ledger, SnapshotEncoding::Cbor, &mut buffer)?;
+    assert_eq!(buffer[8], 1);
+
+    let restored: Ledger = read(buffer.as_slice())?;
+    assert_eq!(restored.stats(), ledger.stats());
+    assert_eq!(restored.transactions.get(&1).unwrap().under_dispute(), true);
+
+    buffer[8] = 7;
+    let result: Result<Ledger, SnapshotError> = read(buffer.as_slice());
+    assert!(matches!(result, Err(SnapshotError::UnknownEncoding(7))));
+
+    Ok(())
+}
+
+#[test]
+fn test_encrypted() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.snapshot");
+    let key = Key::new([3; 32]);
+
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    let options = SnapshotOptions {
+        encoding: SnapshotEncoding::Cbor,
+        key: Some(key.clone()),
+    };
+    ledger.save_with(&path, &options)?;
+
+    let result: Result<Ledger, SnapshotError> = Ledger::load(&path);
+    assert!(matches!(result, Err(SnapshotError::Encrypted)));
+    let result: Result<Ledger, SnapshotError> = Ledger::load_with(&path, Some(&Key::new([4; 32])));
+    assert!(result.is_err());
+
+    let restored: Ledger = Ledger::load_with(&path, Some(&key))?;
+    assert_eq!(restored.stats(), ledger.stats());
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::f64::consts::LN_2;
+use std::hash::Hasher;
+
+use serde::{Deserialize, Serialize};
+use siphasher::sip::SipHasher13;
+
+use crate::accounting::TxId;
+
+#[cfg(test)]
+#[path = "bloom_tests.rs"]
+mod bloom_tests;
+
+/// A Bloom filter over transaction ids. It never forgets an id that was
+/// inserted, but it may claim to contain ids that never were (a false
+/// positive). In exchange it only needs a couple of bytes per id, no matter
+/// how big the transactions are.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    len: usize,
+}
+
+impl BloomFilter {
+    /// A filter sized so that, once `expected_ids` ids have been inserted,
+    /// the chance of a false positive is roughly `false_positive_rate`.
+    pub fn new(expected_ids: usize, false_positive_rate: f64) -> BloomFilter {
+        let expected_ids = expected_ids.max(1) as f64;
+        let false_positive_rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+
+        let num_bits = (-expected_ids * false_positive_rate.ln() / (LN_2 * LN_2))
+            .ceil()
+            .max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / expected_ids) * LN_2).round().max(1.0) as u32;
+
+        BloomFilter {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, id: TxId) {
+        for bit in self.bit_indexes(id) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+
+    pub fn contains(&self, id: TxId) -> bool {
+        self.bit_indexes(id)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Adds every id in `other`, which has to be sized the same, i.e. created
+    /// with the same arguments. Returns false, without changing anything,
+    /// if it isn't.
+    pub fn union(&mut self, other: &BloomFilter) -> bool {
+        if self.num_bits != other.num_bits || self.num_hashes != other.num_hashes {
+            return false;
+        }
+
+        for (bits, other_bits) in self.bits.iter_mut().zip(&other.bits) {
+            *bits |= other_bits;
+        }
+        self.len += other.len;
+
+        true
+    }
+
+    /// Number of insertions, repeated ids are counted every time.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Double hashing, the k indexes are derived from two halves of one hash.
+    // The filters are saved in snapshots, so the hash has to be the same in
+    // every build: SipHash-1-3 with zero keys over the id's little endian
+    // bytes. That's what the standard library's DefaultHasher did on little
+    // endian machines, which older snapshots were made with.
+    fn bit_indexes(&self, id: TxId) -> impl Iterator<Item = u64> {
+        let mut hasher = SipHasher13::new_with_keys(0, 0);
+        // Ids that fit are hashed as u32, like before transaction ids were 64
+        // bits, so that the filters in older snapshots keep working. Builds
+        // with wide ids always had 64 bit ids.
+        #[cfg(not(feature = "wide-ids"))]
+        match u32::try_from(id) {
+            Ok(id) => hasher.write(&id.to_le_bytes()),
+            Err(_) => hasher.write(&id.to_le_bytes()),
+        }
+        #[cfg(feature = "wide-ids")]
+        hasher.write(&id.to_le_bytes());
+        let hash = hasher.finish();
+
+        let h1 = hash & 0xffff_ffff;
+        let h2 = (hash >> 32) | 1;
+        let num_bits = self.num_bits;
+
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+}
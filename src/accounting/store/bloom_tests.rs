@@ -0,0 +1,38 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn test_no_false_negatives() {
+    let mut filter = BloomFilter::new(10_000, 0.01);
+    for id in (0..20_000).step_by(2) {
+        filter.insert(id);
+    }
+
+    assert_eq!(filter.len(), 10_000);
+    assert_eq!((0..20_000).step_by(2).all(|id| filter.contains(id)), true);
+}
+
+#[test]
+fn test_false_positive_rate() {
+    let mut filter = BloomFilter::new(10_000, 0.01);
+    for id in 0..10_000 {
+        filter.insert(id);
+    }
+
+    let false_positives = (1_000_000..1_010_000).filter(|id| filter.contains(*id)).count();
+    assert!(false_positives < 200, "too many false positives: {}", false_positives);
+}
+
+// Wide id builds hash every id as u64.
+#[cfg(not(feature = "wide-ids"))]
+#[test]
+fn test_stable_hash() {
+    // Snapshots keep their filters, so the indexes must never change.
+    let filter = BloomFilter::new(100, 0.01);
+    let indexes = |id| filter.bit_indexes(id).collect::<Vec<_>>();
+
+    assert_eq!(indexes(1), vec![165, 329, 493, 657, 821, 26, 190]);
+    assert_eq!(indexes(42), vec![873, 837, 801, 765, 729, 693, 657]);
+    assert_eq!(indexes(u32::MAX as TxId), vec![440, 203, 925, 688, 451, 214, 936]);
+}
@@ -0,0 +1,201 @@
+use rust_decimal::Decimal;
+
+use super::TransactionStore;
+use crate::accounting::transactions::{Deposit, DisputeStatus, DisputedWithdrawal, Transaction, Withdrawal};
+use crate::accounting::{ClientId, Map, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "compact_tests.rs"]
+mod compact_tests;
+
+const KIND_DEPOSIT: u8 = 0;
+const KIND_WITHDRAWAL: u8 = 1;
+const KIND_DISPUTED_WITHDRAWAL: u8 = 2;
+
+/// A memory efficient store for very large ledgers. Deposits and withdrawals
+/// are taken apart and kept as a 12 byte packed record (14 with wide ids), instead of a full
+/// `Transaction` (plus padding) per entry. The id is only kept as the key.
+///
+/// Anything that can't be packed (e.g. an amount that doesn't fit in 64 bits,
+/// or a timestamp) is kept as is, so the store works for every transaction.
+#[derive(Clone, Default)]
+pub struct CompactStore {
+    packed: Map<TxId, PackedTransaction>,
+    unpacked: Map<TxId, Transaction>,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+struct PackedTransaction {
+    mantissa: i64,
+    client_id: ClientId,
+    scale: u8,
+    // The kind of transaction in the low nibble, its dispute status in the
+    // high nibble.
+    tag: u8,
+}
+
+impl CompactStore {
+    pub fn new() -> CompactStore {
+        CompactStore::default()
+    }
+}
+
+impl PackedTransaction {
+    fn pack(transaction: &Transaction) -> Option<PackedTransaction> {
+        let (kind, status, client_id, amount) = match transaction {
+            Transaction::Deposit(deposit) => (
+                KIND_DEPOSIT,
+                deposit.dispute_status(),
+                deposit.client_id(),
+                deposit.amount(),
+            ),
+            Transaction::Withdrawal(withdrawal) => (
+                KIND_WITHDRAWAL,
+                DisputeStatus::NoDispute,
+                withdrawal.client_id(),
+                withdrawal.amount(),
+            ),
+            Transaction::DisputedWithdrawal(withdrawal) => (
+                KIND_DISPUTED_WITHDRAWAL,
+                withdrawal.dispute_status(),
+                withdrawal.client_id(),
+                withdrawal.amount(),
+            ),
+            _ => return None,
+        };
+        // There's no room for it.
+        if transaction.timestamp().is_some() {
+            return None;
+        }
+
+        let status = match status {
+            DisputeStatus::NoDispute => 0,
+            DisputeStatus::InDispute => 1,
+            DisputeStatus::Resolved => 2,
+            DisputeStatus::Chargedback => 3,
+            // Its parts don't fit in the tag.
+            DisputeStatus::Partial { .. } => return None,
+        };
+
+        Some(PackedTransaction {
+            mantissa: i64::try_from(amount.mantissa()).ok()?,
+            client_id,
+            scale: u8::try_from(amount.scale()).ok()?,
+            tag: kind | status << 4,
+        })
+    }
+
+    fn unpack(self, id: TxId) -> Transaction {
+        let amount = Decimal::from_i128_with_scale(self.mantissa as i128, self.scale as u32);
+
+        if self.tag & 0x0f == KIND_WITHDRAWAL {
+            return Transaction::Withdrawal(Withdrawal::from_parts(id, self.client_id, amount, None));
+        }
+
+        let status = match self.tag >> 4 {
+            0 => DisputeStatus::NoDispute,
+            1 => DisputeStatus::InDispute,
+            2 => DisputeStatus::Resolved,
+            _ => DisputeStatus::Chargedback,
+        };
+
+        if self.tag & 0x0f == KIND_DISPUTED_WITHDRAWAL {
+            return Transaction::DisputedWithdrawal(DisputedWithdrawal::from_parts(
+                id,
+                self.client_id,
+                amount,
+                status,
+                None,
+            ));
+        }
+
+        Transaction::Deposit(Deposit::from_parts(id, self.client_id, amount, status, None))
+    }
+}
+
+impl TransactionStore for CompactStore {
+    fn contains(&self, id: TxId) -> Result<bool, TransactionError> {
+        Ok(self.packed.contains_key(&id) || self.unpacked.contains_key(&id))
+    }
+
+    fn get(&self, id: TxId) -> Result<Option<Transaction>, TransactionError> {
+        match self.packed.get(&id) {
+            Some(packed) => Ok(Some(packed.unpack(id))),
+            None => TransactionStore::get(&self.unpacked, id),
+        }
+    }
+
+    fn for_each(&self, mut f: impl FnMut(TxId, &Transaction)) -> Result<(), TransactionError> {
+        for (id, packed) in &self.packed {
+            f(*id, &packed.unpack(*id));
+        }
+
+        self.unpacked.for_each(f)
+    }
+
+    fn insert(&mut self, id: TxId, transaction: Transaction) -> Result<(), TransactionError> {
+        if let Some(packed) = PackedTransaction::pack(&transaction) {
+            self.unpacked.remove(&id);
+            self.packed.insert(id, packed);
+        } else {
+            self.packed.remove(&id);
+            self.unpacked.insert(id, transaction);
+        }
+
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        id: TxId,
+        f: impl FnOnce(&mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let Some(packed) = self.packed.get_mut(&id) else {
+            return self.unpacked.update(id, f);
+        };
+
+        let mut transaction = packed.unpack(id);
+        f(&mut transaction)?;
+
+        if let Some(repacked) = PackedTransaction::pack(&transaction) {
+            *packed = repacked;
+        } else {
+            self.packed.remove(&id);
+            self.unpacked.insert(id, transaction);
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, id: TxId) -> Result<bool, TransactionError> {
+        Ok(self.packed.remove(&id).is_some() || self.unpacked.remove(&id).is_some())
+    }
+
+    fn evict(
+        &mut self,
+        mut should_evict: impl FnMut(&Transaction) -> bool,
+        mut f: impl FnMut(TxId, Transaction),
+    ) -> Result<(), TransactionError> {
+        self.unpacked.evict(&mut should_evict, &mut f)?;
+
+        let ids: Vec<TxId> = self
+            .packed
+            .iter()
+            .filter(|(id, packed)| should_evict(&packed.unpack(**id)))
+            .map(|(id, _packed)| *id)
+            .collect();
+
+        for id in ids {
+            if let Some(packed) = self.packed.remove(&id) {
+                f(id, packed.unpack(id));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.packed.len() + self.unpacked.len()
+    }
+}
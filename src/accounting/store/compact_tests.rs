@@ -0,0 +1,82 @@
+use std::mem::size_of;
+
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::policy::LedgerPolicy;
+use crate::accounting::transactions::{Chargeback, Dispute, Resolve};
+
+#[test]
+fn test_packed_size() {
+    assert_eq!(size_of::<PackedTransaction>(), 10 + size_of::<ClientId>());
+}
+
+#[test]
+fn test_disputes_on_packed_transactions() -> Result<()> {
+    let mut ledger = Ledger::with_store(CompactStore::new());
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5.5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(3.1415926535))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(1))?))?;
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+
+    assert_eq!(ledger.transactions.len(), 3);
+    let client = ledger.clients.get(&0).unwrap();
+    assert_eq!(client.available(), dec!(2.1416));
+    assert_eq!(client.held(), dec!(0));
+    assert_eq!(client.locked(), true);
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 0))) {
+        assert_eq!(err, TransactionError::DisputeNotSupported);
+    } else {
+        bail!("withdrawal should still be a withdrawal after packing");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_unpackable_amount() -> Result<()> {
+    let mut store = CompactStore::new();
+    let amount = Decimal::from_i128_with_scale(i64::MAX as i128 + 1, 4);
+    store.insert(0, Transaction::Deposit(Deposit::new(0, 0, amount)?))?;
+    store.insert(1, Transaction::Deposit(Deposit::new(1, 0, dec!(1))?))?;
+
+    assert_eq!(store.packed.len(), 1);
+    assert_eq!(store.unpacked.len(), 1);
+    store.update(0, |transaction| match transaction {
+        Transaction::Deposit(deposit) => {
+            assert_eq!(deposit.amount(), amount);
+            Ok(())
+        },
+        _ => Err(TransactionError::TransactionNotFound),
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn test_disputed_withdrawal_is_packed() -> Result<()> {
+    let mut ledger = Ledger::with_store(CompactStore::new());
+    ledger.use_policy(LedgerPolicy {
+        withdrawal_disputes: true,
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(2))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+
+    assert_eq!(ledger.transactions.packed.len(), 2);
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 0)))?;
+    let client = ledger.clients.get(&0).unwrap();
+    assert_eq!(client.available(), dec!(5));
+    assert_eq!(client.locked(), true);
+
+    Ok(())
+}
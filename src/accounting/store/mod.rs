@@ -0,0 +1,194 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use super::client::Client;
+use super::transactions::Transaction;
+use super::{ClientId, TransactionError, TxId};
+
+pub mod bloom;
+pub mod compact;
+#[cfg(feature = "sled")]
+pub mod sled;
+pub mod spill;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Where a ledger keeps the transactions it has executed, so they can be
+/// found again for deduplication and disputes. Stores backed by something
+/// other than memory should report their failures as StorageFailure.
+/// `update` should fail with TransactionNotFound if the id was never stored.
+/// `get` hands over a copy, and `for_each` goes through every transaction in
+/// no particular order. `evict` removes every transaction for which `should_evict` returns true,
+/// handing each of them over to `f`. `remove` returns whether the id was
+/// stored.
+pub trait TransactionStore {
+    fn contains(&self, id: TxId) -> Result<bool, TransactionError>;
+    fn get(&self, id: TxId) -> Result<Option<Transaction>, TransactionError>;
+    fn for_each(&self, f: impl FnMut(TxId, &Transaction)) -> Result<(), TransactionError>;
+    fn insert(&mut self, id: TxId, transaction: Transaction) -> Result<(), TransactionError>;
+    fn update(
+        &mut self,
+        id: TxId,
+        f: impl FnOnce(&mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError>;
+    fn remove(&mut self, id: TxId) -> Result<bool, TransactionError>;
+    fn evict(
+        &mut self,
+        should_evict: impl FnMut(&Transaction) -> bool,
+        f: impl FnMut(TxId, Transaction),
+    ) -> Result<(), TransactionError>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The default store, everything is kept in memory.
+impl<S: BuildHasher> TransactionStore for HashMap<TxId, Transaction, S> {
+    fn contains(&self, id: TxId) -> Result<bool, TransactionError> {
+        Ok(self.contains_key(&id))
+    }
+
+    fn get(&self, id: TxId) -> Result<Option<Transaction>, TransactionError> {
+        Ok(HashMap::get(self, &id).cloned())
+    }
+
+    fn for_each(&self, mut f: impl FnMut(TxId, &Transaction)) -> Result<(), TransactionError> {
+        for (id, transaction) in self {
+            f(*id, transaction);
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, id: TxId, transaction: Transaction) -> Result<(), TransactionError> {
+        HashMap::insert(self, id, transaction);
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        id: TxId,
+        f: impl FnOnce(&mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        if let Some(transaction) = self.get_mut(&id) {
+            f(transaction)
+        } else {
+            Err(TransactionError::TransactionNotFound)
+        }
+    }
+
+    fn remove(&mut self, id: TxId) -> Result<bool, TransactionError> {
+        Ok(HashMap::remove(self, &id).is_some())
+    }
+
+    fn evict(
+        &mut self,
+        mut should_evict: impl FnMut(&Transaction) -> bool,
+        mut f: impl FnMut(TxId, Transaction),
+    ) -> Result<(), TransactionError> {
+        let ids: Vec<TxId> = self
+            .iter()
+            .filter(|(_id, transaction)| should_evict(transaction))
+            .map(|(id, _transaction)| *id)
+            .collect();
+
+        for id in ids {
+            if let Some(transaction) = self.remove(&id) {
+                f(id, transaction);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+/// Where a ledger keeps the accounts of its clients, so they can live
+/// somewhere else than in memory, see `Ledger::with_storage`. As with
+/// `TransactionStore`, failures should be reported as StorageFailure.
+/// `update` should fail with ClientNotFound if there's no such client, while
+/// `update_or_insert` starts from `Client::new`. Either should only keep the
+/// changes if `f` succeeds. `remove` returns whether the client was there.
+/// `clients` goes through every client in no particular order, stores that
+/// copy them out hand over owned ones.
+pub trait ClientStore {
+    fn get(&self, id: ClientId) -> Result<Option<Client>, TransactionError>;
+    fn insert(&mut self, id: ClientId, client: Client) -> Result<(), TransactionError>;
+    fn update(
+        &mut self,
+        id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError>;
+    fn update_or_insert(
+        &mut self,
+        id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError>;
+    fn remove(&mut self, id: ClientId) -> Result<bool, TransactionError>;
+    fn clients(&self) -> impl Iterator<Item = Result<Cow<'_, Client>, TransactionError>>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The default store, everything is kept in memory. `f` works on a copy of
+/// the client, which replaces it only if `f` succeeds.
+impl<S: BuildHasher> ClientStore for HashMap<ClientId, Client, S> {
+    fn get(&self, id: ClientId) -> Result<Option<Client>, TransactionError> {
+        Ok(HashMap::get(self, &id).cloned())
+    }
+
+    fn insert(&mut self, id: ClientId, client: Client) -> Result<(), TransactionError> {
+        HashMap::insert(self, id, client);
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let Some(client) = self.get_mut(&id) else {
+            return Err(TransactionError::ClientNotFound);
+        };
+
+        let mut updated = client.clone();
+        f(&mut updated)?;
+        *client = updated;
+        Ok(())
+    }
+
+    fn update_or_insert(
+        &mut self,
+        id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        // A client that doesn't exist yet is only added if `f` succeeds.
+        let mut client = HashMap::get(self, &id).cloned().unwrap_or_else(|| Client::new(id));
+        f(&mut client)?;
+        HashMap::insert(self, id, client);
+        Ok(())
+    }
+
+    fn remove(&mut self, id: ClientId) -> Result<bool, TransactionError> {
+        Ok(HashMap::remove(self, &id).is_some())
+    }
+
+    fn clients(&self) -> impl Iterator<Item = Result<Cow<'_, Client>, TransactionError>> {
+        self.values().map(|client| Ok(Cow::Borrowed(client)))
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
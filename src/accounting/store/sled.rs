@@ -0,0 +1,130 @@
+use std::path::Path;
+
+use super::TransactionStore;
+use crate::accounting::transactions::Transaction;
+use crate::accounting::{TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "sled_tests.rs"]
+mod sled_tests;
+
+/// A store that keeps the transactions in a sled database on disk, so the
+/// ledger's memory usage no longer grows with its history. The clients stay
+/// in memory.
+///
+/// Lookups go through sled's page cache, so recently used transactions, the
+/// ones most likely to be disputed, are usually found without reading the
+/// disk. Like SpillStore's file, the database is scratch space only, it's
+/// cleared when the store is created.
+pub struct SledStore {
+    db: sled::Db,
+    len: usize,
+}
+
+impl SledStore {
+    pub fn new(path: impl AsRef<Path>) -> sled::Result<SledStore> {
+        // Nothing is kept across runs, so there's no need for sled to flush
+        // in the background.
+        let db = sled::Config::new().path(path).flush_every_ms(None).open()?;
+        db.clear()?;
+
+        Ok(SledStore { db, len: 0 })
+    }
+
+    fn read(&self, id: TxId) -> Result<Option<Transaction>, TransactionError> {
+        self.db
+            .get(id.to_be_bytes())
+            .map_err(storage_failure)?
+            .map(|bytes| bincode::deserialize(&bytes).map_err(storage_failure))
+            .transpose()
+    }
+
+    // Returns whether the id was new.
+    fn put(&mut self, id: TxId, transaction: &Transaction) -> Result<bool, TransactionError> {
+        let bytes = bincode::serialize(transaction).map_err(storage_failure)?;
+        let previous = self.db.insert(id.to_be_bytes(), bytes).map_err(storage_failure)?;
+
+        Ok(previous.is_none())
+    }
+}
+
+impl TransactionStore for SledStore {
+    fn contains(&self, id: TxId) -> Result<bool, TransactionError> {
+        self.db.contains_key(id.to_be_bytes()).map_err(storage_failure)
+    }
+
+    fn get(&self, id: TxId) -> Result<Option<Transaction>, TransactionError> {
+        self.read(id)
+    }
+
+    /// Every transaction has to be read back from disk.
+    fn for_each(&self, mut f: impl FnMut(TxId, &Transaction)) -> Result<(), TransactionError> {
+        for entry in self.db.iter() {
+            let (key, bytes) = entry.map_err(storage_failure)?;
+            let id = TxId::from_be_bytes(key.as_ref().try_into().map_err(storage_failure)?);
+            let transaction: Transaction = bincode::deserialize(&bytes).map_err(storage_failure)?;
+            f(id, &transaction);
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, id: TxId, transaction: Transaction) -> Result<(), TransactionError> {
+        if self.put(id, &transaction)? {
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        id: TxId,
+        f: impl FnOnce(&mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let Some(mut transaction) = self.read(id)? else {
+            return Err(TransactionError::TransactionNotFound);
+        };
+
+        f(&mut transaction)?;
+        self.put(id, &transaction)?;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, id: TxId) -> Result<bool, TransactionError> {
+        let removed = self.db.remove(id.to_be_bytes()).map_err(storage_failure)?.is_some();
+        if removed {
+            self.len -= 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Every transaction has to be read back from disk.
+    fn evict(
+        &mut self,
+        mut should_evict: impl FnMut(&Transaction) -> bool,
+        mut f: impl FnMut(TxId, Transaction),
+    ) -> Result<(), TransactionError> {
+        for entry in self.db.iter() {
+            let (key, bytes) = entry.map_err(storage_failure)?;
+            let transaction: Transaction = bincode::deserialize(&bytes).map_err(storage_failure)?;
+            if should_evict(&transaction) {
+                let id = TxId::from_be_bytes(key.as_ref().try_into().map_err(storage_failure)?);
+                self.remove(id)?;
+                f(id, transaction);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+fn storage_failure(err: impl ToString) -> TransactionError {
+    TransactionError::StorageFailure(err.to_string())
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+use crate::accounting::ExecutableTransaction;
+
+#[test]
+fn test_dispute_stored_transaction() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut ledger = Ledger::with_store(SledStore::new(dir.path().join("transactions"))?);
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(7))?))?;
+    assert_eq!(ledger.transactions.len(), 3);
+
+    // Stored ids are deduplicated.
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5))?))?;
+    assert_eq!(ledger.clients[&0].available(), dec!(8));
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+
+    let client = &ledger.clients[&0];
+    assert_eq!(client.available(), dec!(3));
+    assert_eq!(client.held(), dec!(0));
+    assert_eq!(client.locked(), true);
+
+    // The updated dispute status must have been written back.
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionAlreadyDisputed);
+    } else {
+        anyhow::bail!("chargedback transaction should not be disputed again");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_cleared_on_creation() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("transactions");
+
+    let mut ledger = Ledger::with_store(SledStore::new(&path)?);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(2))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+
+    // Only the disputed deposit isn't settled yet.
+    let mut archived = Vec::new();
+    assert_eq!(ledger.compact(|transaction| archived.push(transaction.id()))?, 1);
+    assert_eq!(archived, vec![Some(1)]);
+    assert_eq!(ledger.transactions.len(), 1);
+    drop(ledger);
+
+    let store = SledStore::new(&path)?;
+    assert_eq!(store.len(), 0);
+    assert_eq!(store.contains(0)?, false);
+
+    Ok(())
+}
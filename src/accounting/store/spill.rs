@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::TransactionStore;
+use crate::accounting::transactions::Transaction;
+use crate::accounting::{Map, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "spill_tests.rs"]
+mod spill_tests;
+
+/// A store with bounded memory usage. The most recently inserted transactions
+/// are kept in memory, as those are the ones most likely to be disputed, while
+/// older ones are spilled to a file on disk.
+///
+/// Only the id and file offset of a spilled transaction stay in memory, so
+/// deduplication never touches the disk. Updating a spilled transaction reads
+/// it back and appends the new version to the end of the file. The file is
+/// scratch space only, it's truncated when the store is created.
+pub struct SpillStore {
+    hot: Map<TxId, Transaction>,
+    hot_order: VecDeque<TxId>,
+    hot_capacity: usize,
+
+    cold: Map<TxId, u64>,
+    writer: BufWriter<File>,
+    reader: Mutex<File>,
+    end: u64,
+}
+
+impl SpillStore {
+    pub fn new(path: impl AsRef<Path>, hot_capacity: usize) -> io::Result<SpillStore> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+
+        Ok(SpillStore {
+            hot: Map::default(),
+            hot_order: VecDeque::new(),
+            hot_capacity,
+            cold: Map::default(),
+            // A cloned handle would share the cursor with the writer.
+            reader: Mutex::new(File::open(path)?),
+            writer: BufWriter::new(file),
+            end: 0,
+        })
+    }
+
+    /// Number of transactions currently held in memory.
+    pub fn hot_len(&self) -> usize {
+        self.hot.len()
+    }
+
+    fn spill_oldest(&mut self) -> Result<(), TransactionError> {
+        while self.hot.len() > self.hot_capacity {
+            let Some(id) = self.hot_order.pop_front() else {
+                break;
+            };
+
+            if let Some(transaction) = self.hot.remove(&id) {
+                let offset = self.append(&transaction)?;
+                self.cold.insert(id, offset);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records are a little endian u32 length followed by the encoded
+    /// transaction.
+    fn append(&mut self, transaction: &Transaction) -> Result<u64, TransactionError> {
+        let bytes = bincode::serialize(transaction).map_err(storage_failure)?;
+        let len = u32::try_from(bytes.len()).map_err(storage_failure)?;
+
+        let offset = self.end;
+        self.writer.write_all(&len.to_le_bytes()).map_err(storage_failure)?;
+        self.writer.write_all(&bytes).map_err(storage_failure)?;
+        self.end += 4 + bytes.len() as u64;
+
+        Ok(offset)
+    }
+
+    // The end of the file may still be in the writer's buffer, the record
+    // is read from wherever it is rather than flushing it.
+    fn read(&self, offset: u64) -> Result<Transaction, TransactionError> {
+        let buffered = self.writer.buffer();
+        let flushed = self.end - buffered.len() as u64;
+        let mut reader = self.reader.lock().map_err(storage_failure)?;
+        reader
+            .seek(SeekFrom::Start(offset.min(flushed)))
+            .map_err(storage_failure)?;
+        let skipped = offset.saturating_sub(flushed) as usize;
+        let mut record = (&mut *reader)
+            .take(flushed.saturating_sub(offset))
+            .chain(&buffered[skipped..]);
+
+        let mut len = [0; 4];
+        record.read_exact(&mut len).map_err(storage_failure)?;
+        let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+        record.read_exact(&mut bytes).map_err(storage_failure)?;
+
+        bincode::deserialize(&bytes).map_err(storage_failure)
+    }
+}
+
+impl TransactionStore for SpillStore {
+    fn contains(&self, id: TxId) -> Result<bool, TransactionError> {
+        Ok(self.hot.contains_key(&id) || self.cold.contains_key(&id))
+    }
+
+    fn get(&self, id: TxId) -> Result<Option<Transaction>, TransactionError> {
+        if let Some(transaction) = self.hot.get(&id) {
+            return Ok(Some(transaction.clone()));
+        }
+
+        self.cold.get(&id).map(|offset| self.read(*offset)).transpose()
+    }
+
+    /// Every spilled transaction has to be read back from disk.
+    fn for_each(&self, mut f: impl FnMut(TxId, &Transaction)) -> Result<(), TransactionError> {
+        self.hot.for_each(&mut f)?;
+        for (id, offset) in &self.cold {
+            f(*id, &self.read(*offset)?);
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, id: TxId, transaction: Transaction) -> Result<(), TransactionError> {
+        if self.hot.insert(id, transaction).is_none() {
+            self.hot_order.push_back(id);
+        }
+        self.cold.remove(&id);
+
+        self.spill_oldest()
+    }
+
+    fn update(
+        &mut self,
+        id: TxId,
+        f: impl FnOnce(&mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        if let Some(transaction) = self.hot.get_mut(&id) {
+            return f(transaction);
+        }
+
+        let Some(&offset) = self.cold.get(&id) else {
+            return Err(TransactionError::TransactionNotFound);
+        };
+
+        // Cold transactions stay cold, the updated version is appended and
+        // the old record is simply never read again.
+        let mut transaction = self.read(offset)?;
+        f(&mut transaction)?;
+        let offset = self.append(&transaction)?;
+        self.cold.insert(id, offset);
+
+        Ok(())
+    }
+
+    /// The record of a spilled transaction stays in the file, it's simply
+    /// never read again.
+    fn remove(&mut self, id: TxId) -> Result<bool, TransactionError> {
+        if self.hot.remove(&id).is_some() {
+            self.hot_order.retain(|other| *other != id);
+            return Ok(true);
+        }
+
+        Ok(self.cold.remove(&id).is_some())
+    }
+
+    /// Every spilled transaction has to be read back from disk.
+    fn evict(
+        &mut self,
+        mut should_evict: impl FnMut(&Transaction) -> bool,
+        mut f: impl FnMut(TxId, Transaction),
+    ) -> Result<(), TransactionError> {
+        self.hot.evict(&mut should_evict, &mut f)?;
+        self.hot_order.retain(|id| self.hot.contains_key(id));
+
+        let cold: Vec<(TxId, u64)> = self.cold.iter().map(|(id, offset)| (*id, *offset)).collect();
+        for (id, offset) in cold {
+            let transaction = self.read(offset)?;
+            if should_evict(&transaction) {
+                self.cold.remove(&id);
+                f(id, transaction);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.hot.len() + self.cold.len()
+    }
+}
+
+fn storage_failure(err: impl ToString) -> TransactionError {
+    TransactionError::StorageFailure(err.to_string())
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, Resolve};
+
+#[test]
+fn test_spills_beyond_capacity() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut ledger = Ledger::with_store(SpillStore::new(dir.path().join("spill"), 2)?);
+
+    for id in 0..10 {
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(id, 0, dec!(1))?))?;
+    }
+
+    assert_eq!(ledger.transactions.len(), 10);
+    assert_eq!(ledger.transactions.hot_len(), 2);
+    assert_eq!(ledger.clients.get(&0).unwrap().available(), dec!(10));
+
+    // Spilled ids are still deduplicated.
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(1))?))?;
+    assert_eq!(ledger.clients.get(&0).unwrap().available(), dec!(10));
+
+    Ok(())
+}
+
+#[test]
+fn test_dispute_spilled_transaction() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut ledger = Ledger::with_store(SpillStore::new(dir.path().join("spill"), 1)?);
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(7))?))?;
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+
+    let client = ledger.clients.get(&0).unwrap();
+    assert_eq!(client.available(), dec!(3));
+    assert_eq!(client.held(), dec!(0));
+    assert_eq!(client.locked(), true);
+
+    // The updated dispute status must have been persisted.
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionAlreadyDisputed);
+    } else {
+        anyhow::bail!("chargedback transaction should not be disputed again");
+    }
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(9, 0))) {
+        assert_eq!(err, TransactionError::TransactionNotFound);
+    } else {
+        anyhow::bail!("unknown transaction should not be found");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_get_spilled_transactions() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut store = SpillStore::new(dir.path().join("spill"), 1)?;
+    for id in 0..3 {
+        store.insert(id, Transaction::Deposit(Deposit::new(id, 0, dec!(1))?))?;
+    }
+
+    // Spilled, but still in the writer's buffer rather than in the file.
+    let Some(Transaction::Deposit(deposit)) = store.get(0)? else {
+        panic!("spilled deposit not found");
+    };
+    assert_eq!(deposit.amount(), dec!(1));
+    assert!(store.get(3)?.is_none());
+
+    let mut ids = Vec::new();
+    store.for_each(|id, _transaction| ids.push(id))?;
+    ids.sort();
+    assert_eq!(ids, vec![0, 1, 2]);
+
+    Ok(())
+}
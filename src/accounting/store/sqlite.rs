@@ -0,0 +1,420 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+
+use super::TransactionStore;
+use crate::accounting::client::{Client, ClientMetadata};
+use crate::accounting::ledger::Ledger;
+use crate::accounting::timestamp::Timestamp;
+use crate::accounting::transactions::{Deposit, DisputeStatus, DisputedWithdrawal, Reversal, Transaction, Withdrawal};
+use crate::accounting::{ClientId, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "sqlite_tests.rs"]
+mod sqlite_tests;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS clients (
+    id INTEGER PRIMARY KEY,
+    available TEXT NOT NULL,
+    held TEXT NOT NULL,
+    locked INTEGER NOT NULL,
+    frozen INTEGER NOT NULL DEFAULT 0,
+    closed INTEGER NOT NULL DEFAULT 0,
+    credit_limit TEXT NOT NULL DEFAULT '0',
+    display_name TEXT,
+    external_ref TEXT,
+    opened_at TEXT
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    id INTEGER PRIMARY KEY,
+    type TEXT NOT NULL,
+    client INTEGER NOT NULL,
+    amount TEXT NOT NULL,
+    dispute_status TEXT NOT NULL,
+    timestamp INTEGER
+);
+CREATE TABLE IF NOT EXISTS ledger (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+";
+
+/// A store that keeps the transactions in a SQLite database, so a ledger
+/// can grow far beyond memory. Open it with `Ledger::open_sqlite`, which
+/// also keeps the clients there.
+///
+/// Writes are grouped in batches: nothing is in the database until
+/// `Ledger::commit`, which writes the batch together with the clients in one
+/// database transaction. If the process stops before, the database still
+/// holds the ledger as of the last commit.
+///
+/// The tables can be queried with plain SQL. Amounts are stored as text,
+/// exactly as they are, e.g. `SELECT SUM(CAST(available AS REAL)) FROM
+/// clients`.
+pub struct SqliteStore {
+    connection: Connection,
+    len: usize,
+}
+
+impl SqliteStore {
+    fn open(path: impl AsRef<Path>) -> rusqlite::Result<SqliteStore> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        // Databases created before clients could be frozen, closed, given
+        // credit or metadata, or before transactions had timestamps.
+        for (table, column, definition) in [
+            ("clients", "frozen", "INTEGER NOT NULL DEFAULT 0"),
+            ("clients", "closed", "INTEGER NOT NULL DEFAULT 0"),
+            ("clients", "credit_limit", "TEXT NOT NULL DEFAULT '0'"),
+            ("clients", "display_name", "TEXT"),
+            ("clients", "external_ref", "TEXT"),
+            ("clients", "opened_at", "TEXT"),
+            ("transactions", "timestamp", "INTEGER"),
+        ] {
+            let exists: i64 = connection.query_row(
+                "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+                [table, column],
+                |row| row.get(0),
+            )?;
+            if exists == 0 {
+                connection.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+            }
+        }
+        let len: i64 = connection.query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))?;
+
+        connection.execute_batch("BEGIN")?;
+
+        Ok(SqliteStore {
+            connection,
+            len: len as usize,
+        })
+    }
+
+    fn read(&self, id: TxId) -> Result<Option<Transaction>, TransactionError> {
+        let row = self
+            .connection
+            .prepare_cached("SELECT type, client, amount, dispute_status, timestamp FROM transactions WHERE id = ?1")
+            .and_then(|mut statement| {
+                statement
+                    .query_row([id], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, ClientId>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, String>(3)?,
+                            row.get::<_, Option<i64>>(4)?,
+                        ))
+                    })
+                    .optional()
+            })
+            .map_err(storage_failure)?;
+
+        row.map(|(type_, client_id, amount, dispute_status, timestamp)| {
+            let amount = Decimal::from_str(&amount).map_err(storage_failure)?;
+            let timestamp = timestamp
+                .map(|seconds| {
+                    Timestamp::from_unix(seconds)
+                        .ok_or_else(|| storage_failure(format!("invalid timestamp {}", seconds)))
+                })
+                .transpose()?;
+            match type_.as_str() {
+                "deposit" => Ok(Transaction::Deposit(Deposit::from_parts(
+                    id,
+                    client_id,
+                    amount,
+                    parse_dispute_status(&dispute_status)?,
+                    timestamp,
+                ))),
+                "withdrawal" => Ok(Transaction::Withdrawal(Withdrawal::from_parts(
+                    id, client_id, amount, timestamp,
+                ))),
+                "disputed_withdrawal" => Ok(Transaction::DisputedWithdrawal(DisputedWithdrawal::from_parts(
+                    id,
+                    client_id,
+                    amount,
+                    parse_dispute_status(&dispute_status)?,
+                    timestamp,
+                ))),
+                "reversal" => Ok(Transaction::Reversal(Reversal::from_parts(id, client_id, amount))),
+                _ => Err(storage_failure(format!("unknown transaction type {}", type_))),
+            }
+        })
+        .transpose()
+    }
+
+    fn put(&mut self, id: TxId, transaction: &Transaction) -> Result<(), TransactionError> {
+        let (type_, client_id, amount, dispute_status) = match transaction {
+            Transaction::Deposit(deposit) => (
+                "deposit",
+                deposit.client_id(),
+                deposit.amount(),
+                deposit.dispute_status(),
+            ),
+            Transaction::Withdrawal(withdrawal) => (
+                "withdrawal",
+                withdrawal.client_id(),
+                withdrawal.amount(),
+                DisputeStatus::NoDispute,
+            ),
+            Transaction::DisputedWithdrawal(withdrawal) => (
+                "disputed_withdrawal",
+                withdrawal.client_id(),
+                withdrawal.amount(),
+                withdrawal.dispute_status(),
+            ),
+            Transaction::Reversal(reversal) => (
+                "reversal",
+                reversal.client_id(),
+                reversal.amount().unwrap_or_default(),
+                DisputeStatus::NoDispute,
+            ),
+            _ => {
+                return Err(storage_failure(
+                    "only deposits, withdrawals and reversals can be stored",
+                ))
+            },
+        };
+
+        self.connection
+            .prepare_cached(
+                "INSERT OR REPLACE INTO transactions (id, type, client, amount, dispute_status, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .and_then(|mut statement| {
+                statement.execute(params![
+                    id,
+                    type_,
+                    client_id,
+                    amount.to_string(),
+                    dispute_status.name(),
+                    transaction.timestamp().map(|timestamp| timestamp.unix()),
+                ])
+            })
+            .map_err(storage_failure)?;
+
+        Ok(())
+    }
+}
+
+impl TransactionStore for SqliteStore {
+    fn contains(&self, id: TxId) -> Result<bool, TransactionError> {
+        self.connection
+            .prepare_cached("SELECT 1 FROM transactions WHERE id = ?1")
+            .and_then(|mut statement| statement.exists([id]))
+            .map_err(storage_failure)
+    }
+
+    fn get(&self, id: TxId) -> Result<Option<Transaction>, TransactionError> {
+        self.read(id)
+    }
+
+    /// Reads every transaction one by one, like `evict`.
+    fn for_each(&self, mut f: impl FnMut(TxId, &Transaction)) -> Result<(), TransactionError> {
+        let ids: Vec<TxId> = self
+            .connection
+            .prepare("SELECT id FROM transactions")
+            .and_then(|mut statement| statement.query_map([], |row| row.get(0))?.collect())
+            .map_err(storage_failure)?;
+
+        for id in ids {
+            if let Some(transaction) = self.read(id)? {
+                f(id, &transaction);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, id: TxId, transaction: Transaction) -> Result<(), TransactionError> {
+        let new = !self.contains(id)?;
+        self.put(id, &transaction)?;
+        if new {
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        id: TxId,
+        f: impl FnOnce(&mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        let Some(mut transaction) = self.read(id)? else {
+            return Err(TransactionError::TransactionNotFound);
+        };
+
+        f(&mut transaction)?;
+        self.put(id, &transaction)
+    }
+
+    fn remove(&mut self, id: TxId) -> Result<bool, TransactionError> {
+        let removed = self
+            .connection
+            .prepare_cached("DELETE FROM transactions WHERE id = ?1")
+            .and_then(|mut statement| statement.execute([id]))
+            .map_err(storage_failure)?;
+        self.len -= removed;
+
+        Ok(removed > 0)
+    }
+
+    /// Reads every transaction, there is no index on what `should_evict`
+    /// looks at.
+    fn evict(
+        &mut self,
+        mut should_evict: impl FnMut(&Transaction) -> bool,
+        mut f: impl FnMut(TxId, Transaction),
+    ) -> Result<(), TransactionError> {
+        let ids: Vec<TxId> = self
+            .connection
+            .prepare("SELECT id FROM transactions")
+            .and_then(|mut statement| statement.query_map([], |row| row.get(0))?.collect())
+            .map_err(storage_failure)?;
+
+        for id in ids {
+            if let Some(transaction) = self.read(id)? {
+                if should_evict(&transaction) {
+                    self.remove(id)?;
+                    f(id, transaction);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Ledger<SqliteStore> {
+    /// Opens the ledger in this database, creating it if needed. Everything
+    /// but the transactions is loaded into memory. The ids of settled
+    /// transactions, see `compact` and `use_id_filter`, aren't saved: there's
+    /// no need to compact a ledger that isn't in memory.
+    pub fn open_sqlite(path: impl AsRef<Path>) -> Result<Ledger<SqliteStore>, TransactionError> {
+        let store = SqliteStore::open(path).map_err(storage_failure)?;
+
+        let clients = store
+            .connection
+            .prepare(
+                "SELECT id, available, held, locked, frozen, closed, credit_limit, display_name, external_ref, opened_at
+                 FROM clients",
+            )
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, ClientId>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, bool>(3)?,
+                            row.get::<_, bool>(4)?,
+                            row.get::<_, bool>(5)?,
+                            row.get::<_, String>(6)?,
+                            ClientMetadata {
+                                display_name: row.get(7)?,
+                                external_ref: row.get(8)?,
+                                opened_at: row.get(9)?,
+                            },
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(storage_failure)?;
+        let statuses = store
+            .connection
+            .prepare("SELECT dispute_status FROM transactions WHERE dispute_status <> 'none'")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(storage_failure)?;
+        let mut open_disputes = 0;
+        for status in statuses {
+            if parse_dispute_status(&status)?.under_dispute() {
+                open_disputes += 1;
+            }
+        }
+        let value = |key: &str| {
+            store
+                .connection
+                .query_row("SELECT value FROM ledger WHERE key = ?1", [key], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .optional()
+                .map(|value| value.unwrap_or(0) as u64)
+                .map_err(storage_failure)
+        };
+        let position = value("position")?;
+        let wal_sequence = value("wal_sequence")?;
+
+        let mut ledger = Ledger::with_store(store);
+        for (id, available, held, locked, frozen, closed, credit_limit, metadata) in clients {
+            let available = Decimal::from_str(&available).map_err(storage_failure)?;
+            let held = Decimal::from_str(&held).map_err(storage_failure)?;
+            let credit_limit = Decimal::from_str(&credit_limit).map_err(storage_failure)?;
+            let mut client = Client::from_parts(id, available, held, locked, frozen, closed, credit_limit);
+            client.set_metadata(metadata);
+            ledger.clients.insert(id, client);
+        }
+        ledger.set_open_disputes(open_disputes);
+        ledger.set_position(position);
+        ledger.set_wal_sequence(wal_sequence);
+
+        Ok(ledger)
+    }
+
+    /// Writes the clients and the transactions executed since the last
+    /// commit to the database, all at once.
+    pub fn commit(&mut self) -> Result<(), TransactionError> {
+        let connection = &self.transactions.connection;
+        let mut upsert = connection
+            .prepare_cached(
+                "INSERT OR REPLACE INTO clients
+                 (id, available, held, locked, frozen, closed, credit_limit, display_name, external_ref, opened_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )
+            .map_err(storage_failure)?;
+        for client in self.clients.values() {
+            upsert
+                .execute(params![
+                    client.id(),
+                    client.available().to_string(),
+                    client.held().to_string(),
+                    client.locked(),
+                    client.frozen(),
+                    client.closed(),
+                    client.credit_limit().to_string(),
+                    client.metadata().display_name,
+                    client.metadata().external_ref,
+                    client.metadata().opened_at
+                ])
+                .map_err(storage_failure)?;
+        }
+        drop(upsert);
+
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO ledger (key, value) VALUES ('position', ?1), ('wal_sequence', ?2)",
+                params![self.position() as i64, self.wal_sequence() as i64],
+            )
+            .map_err(storage_failure)?;
+
+        connection.execute_batch("COMMIT; BEGIN").map_err(storage_failure)
+    }
+}
+
+fn parse_dispute_status(status: &str) -> Result<DisputeStatus, TransactionError> {
+    DisputeStatus::from_name(status).ok_or_else(|| storage_failure(format!("unknown dispute status {}", status)))
+}
+
+fn storage_failure(err: impl ToString) -> TransactionError {
+    TransactionError::StorageFailure(err.to_string())
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::transactions::{Chargeback, CloseAccount, Dispute, Freeze, Resolve, Reversal, SetCreditLimit};
+
+#[test]
+fn test_commit_and_reopen() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.db");
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10.5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(3))?))?;
+    ledger
+        .execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 2, dec!(1))?))
+        .unwrap_err();
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    ledger.set_position(5);
+    ledger.commit()?;
+
+    // Not committed, so lost when the ledger is dropped.
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(4, 3, dec!(1))?))?;
+    drop(ledger);
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    assert_eq!(ledger.position(), 5);
+    let stats = ledger.stats();
+    assert_eq!(stats.clients, 1);
+    assert_eq!(stats.stored_transactions, 2);
+    assert_eq!(stats.open_disputes, 1);
+    assert_eq!(ledger.clients[&1].held(), dec!(10.5));
+
+    // Repeated transactions are still discarded, and disputes still work.
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 1)))?;
+    assert_eq!(ledger.clients[&1].available(), dec!(13.5));
+    assert_eq!(ledger.stats().open_disputes, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_plain_sql() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.db");
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 7, dec!(2.25))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 7, dec!(1))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 7)))?;
+    ledger.commit()?;
+
+    let connection = Connection::open(&path)?;
+    let (available, held): (String, String) =
+        connection.query_row("SELECT available, held FROM clients WHERE id = 7", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    assert_eq!((available.as_str(), held.as_str()), ("-1.00", "2.25"));
+
+    let disputed: Vec<TxId> = connection
+        .prepare("SELECT id FROM transactions WHERE dispute_status = 'disputed'")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    assert_eq!(disputed, vec![1]);
+
+    Ok(())
+}
+
+#[test]
+fn test_timestamp_is_stored() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.db");
+    // Created before transactions had timestamps.
+    Connection::open(&path)?.execute_batch(
+        "CREATE TABLE transactions (
+            id INTEGER PRIMARY KEY,
+            type TEXT NOT NULL,
+            client INTEGER NOT NULL,
+            amount TEXT NOT NULL,
+            dispute_status TEXT NOT NULL
+        );
+        INSERT INTO transactions VALUES (1, 'deposit', 1, '2', 'none');",
+    )?;
+
+    let timestamp = Timestamp::from_unix(1714557600);
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(1))?).with_timestamp(timestamp))?;
+    ledger.commit()?;
+    drop(ledger);
+
+    let ledger = Ledger::open_sqlite(&path)?;
+    assert_eq!(ledger.transactions.get(1)?.and_then(|tx| tx.timestamp()), None);
+    assert_eq!(ledger.transactions.get(2)?.and_then(|tx| tx.timestamp()), timestamp);
+
+    let date: String = Connection::open(&path)?.query_row(
+        "SELECT datetime(timestamp, 'unixepoch') FROM transactions WHERE id = 2",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(date, "2024-05-01 10:00:00");
+
+    Ok(())
+}
+
+#[test]
+fn test_reversal_is_stored() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.db");
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(4))?))?;
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(1, 1)))?;
+    ledger.commit()?;
+    drop(ledger);
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    assert_eq!(ledger.clients[&1].available(), dec!(0));
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1))) {
+        assert_eq!(err, TransactionError::TransactionReversed);
+    } else {
+        anyhow::bail!("reversed transaction should not be disputed");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_partial_dispute_is_stored() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.db");
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(1, 1, dec!(2.5))?))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::with_amount(1, 1, dec!(0.5))?))?;
+    ledger.commit()?;
+    drop(ledger);
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    assert_eq!(ledger.stats().open_disputes, 1);
+    assert_eq!(ledger.clients[&1].held(), dec!(2));
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    assert_eq!(ledger.clients[&1].held(), dec!(9.5));
+
+    Ok(())
+}
+
+#[test]
+fn test_account_state_is_stored() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ledger.db");
+
+    // A database from before clients could be frozen, closed or given credit.
+    let connection = Connection::open(&path)?;
+    connection.execute_batch(
+        "CREATE TABLE clients (id INTEGER PRIMARY KEY, available TEXT NOT NULL, held TEXT NOT NULL, locked INTEGER NOT \
+         NULL);
+         INSERT INTO clients VALUES (1, '4', '0', 0);",
+    )?;
+    drop(connection);
+
+    let mut ledger = Ledger::open_sqlite(&path)?;
+    assert!(!ledger.clients[&1].frozen());
+    ledger.execute_transaction(Transaction::Freeze(Freeze::new(1)))?;
+    ledger.execute_transaction(Transaction::SetCreditLimit(SetCreditLimit::new(1, dec!(2.5))?))?;
+    ledger.execute_transaction(Transaction::CloseAccount(CloseAccount::new(1)))?;
+    ledger.commit()?;
+    drop(ledger);
+
+    let ledger = Ledger::open_sqlite(&path)?;
+    assert!(ledger.clients[&1].frozen());
+    assert!(ledger.clients[&1].closed());
+    assert_eq!(ledger.clients[&1].credit_limit(), dec!(2.5));
+    assert_eq!(ledger.clients[&1].available(), dec!(4));
+
+    Ok(())
+}
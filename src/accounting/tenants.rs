@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use super::ledger::Ledger;
+use super::store::TransactionStore;
+use super::transactions::Transaction;
+use super::{Map, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "tenants_tests.rs"]
+mod tenants_tests;
+
+/// A ledger per tenant, e.g. for each of the payment partners one process
+/// serves. Tenants are isolated from each other: client and transaction ids
+/// only have to be unique within a tenant, and a transaction only ever
+/// touches the ledger of its own. Iteration is ordered by tenant id.
+#[derive(Default)]
+pub struct LedgerSet<T = Map<TxId, Transaction>> {
+    ledgers: BTreeMap<String, Ledger<T>>,
+}
+
+impl LedgerSet {
+    pub fn new() -> LedgerSet {
+        LedgerSet {
+            ledgers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> LedgerSet<T> {
+    /// Adds a tenant with this ledger, e.g. one loaded from a snapshot.
+    /// Returns the ledger it had before, if any.
+    pub fn insert(&mut self, tenant: impl Into<String>, ledger: Ledger<T>) -> Option<Ledger<T>> {
+        self.ledgers.insert(tenant.into(), ledger)
+    }
+
+    pub fn remove(&mut self, tenant: &str) -> Option<Ledger<T>> {
+        self.ledgers.remove(tenant)
+    }
+
+    pub fn get(&self, tenant: &str) -> Option<&Ledger<T>> {
+        self.ledgers.get(tenant)
+    }
+
+    pub fn get_mut(&mut self, tenant: &str) -> Option<&mut Ledger<T>> {
+        self.ledgers.get_mut(tenant)
+    }
+
+    pub fn contains(&self, tenant: &str) -> bool {
+        self.ledgers.contains_key(tenant)
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = &str> {
+        self.ledgers.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Ledger<T>)> {
+        self.ledgers.iter().map(|(tenant, ledger)| (tenant.as_str(), ledger))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Ledger<T>)> {
+        self.ledgers
+            .iter_mut()
+            .map(|(tenant, ledger)| (tenant.as_str(), ledger))
+    }
+
+    pub fn len(&self) -> usize {
+        self.ledgers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ledgers.is_empty()
+    }
+}
+
+impl<T: TransactionStore + Default> LedgerSet<T> {
+    /// The tenant's ledger, a new one if the tenant wasn't seen before. Use
+    /// `get_mut` to only accept known tenants.
+    pub fn ledger(&mut self, tenant: &str) -> &mut Ledger<T> {
+        self.ledgers
+            .entry(tenant.to_string())
+            .or_insert_with(|| Ledger::with_store(T::default()))
+    }
+
+    /// Executes the transaction on the tenant's ledger, see `ledger`.
+    pub fn execute_transaction(&mut self, tenant: &str, transaction: Transaction) -> Result<(), TransactionError> {
+        self.ledger(tenant).execute_transaction(transaction)
+    }
+}
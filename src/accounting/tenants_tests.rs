@@ -0,0 +1,60 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::store::compact::CompactStore;
+use crate::accounting::transactions::{Deposit, Dispute, Withdrawal};
+
+#[test]
+fn test_tenants_are_isolated() -> Result<()> {
+    let mut ledgers = LedgerSet::new();
+    ledgers.execute_transaction("beta", Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    // The same ids, for another tenant.
+    ledgers.execute_transaction("alpha", Transaction::Deposit(Deposit::new(1, 1, dec!(3))?))?;
+    ledgers.execute_transaction("alpha", Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(1))?))?;
+
+    if let Err(err) = ledgers.execute_transaction("alpha", Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(5))?)) {
+        assert!(matches!(err, TransactionError::InsufficientFunds));
+    } else {
+        anyhow::bail!("withdrawal should only see the tenant's own funds");
+    }
+    if let Err(err) = ledgers.execute_transaction("gamma", Transaction::Dispute(Dispute::new(1, 1))) {
+        assert!(matches!(err, TransactionError::ClientNotFound));
+    } else {
+        anyhow::bail!("dispute should only see the tenant's own transactions");
+    }
+
+    assert_eq!(ledgers.tenants().collect::<Vec<_>>(), vec!["alpha", "beta", "gamma"]);
+    let available: Vec<_> = ledgers
+        .iter()
+        .map(|(tenant, ledger)| (tenant, ledger.stats().total_available))
+        .collect();
+    assert_eq!(
+        available,
+        vec![("alpha", dec!(2)), ("beta", dec!(10)), ("gamma", dec!(0))]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_known_tenants_only() -> Result<()> {
+    let mut ledgers: LedgerSet<CompactStore> = LedgerSet::default();
+    assert!(ledgers
+        .insert("alpha", Ledger::with_store(CompactStore::new()))
+        .is_none());
+
+    assert!(ledgers.get_mut("beta").is_none());
+    ledgers
+        .get_mut("alpha")
+        .unwrap()
+        .execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(1))?))?;
+    assert_eq!(ledgers.len(), 1);
+
+    let alpha = ledgers.remove("alpha").unwrap();
+    assert_eq!(alpha.transactions.len(), 1);
+    assert!(ledgers.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+#[cfg(test)]
+#[path = "timestamp_tests.rs"]
+mod timestamp_tests;
+
+/// When a transaction happened, to the second. It's read from an RFC 3339
+/// date and time (e.g. `2024-05-01T12:00:00+02:00`) or the seconds since the
+/// Unix epoch, and written in RFC 3339 in UTC. Binary encodings (e.g.
+/// bincode) keep the seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+#[derive(Clone, Debug, PartialEq, Error)]
+#[error("invalid timestamp: {0}")]
+pub struct InvalidTimestamp(String);
+
+impl Timestamp {
+    /// None if it's beyond the dates that can be written.
+    pub fn from_unix(seconds: i64) -> Option<Timestamp> {
+        DateTime::from_timestamp(seconds, 0).map(|_| Timestamp(seconds))
+    }
+
+    pub fn unix(&self) -> i64 {
+        self.0
+    }
+
+    /// The day it falls on, in UTC.
+    pub fn date(&self) -> NaiveDate {
+        self.date_time().date_naive()
+    }
+
+    fn date_time(&self) -> DateTime<Utc> {
+        // Only ever made of seconds checked by `from_unix`.
+        DateTime::from_timestamp(self.0, 0).expect("timestamp is in range")
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = InvalidTimestamp;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let timestamp = match value.parse::<i64>() {
+            Ok(seconds) => Timestamp::from_unix(seconds),
+            Err(_) => DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|date_time| Timestamp(date_time.timestamp())),
+        };
+
+        timestamp.ok_or_else(|| InvalidTimestamp(value.to_string()))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.date_time().to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_i64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(TimestampVisitor)
+        } else {
+            deserializer.deserialize_i64(TimestampVisitor)
+        }
+    }
+}
+
+struct TimestampVisitor;
+
+impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an RFC 3339 date and time or seconds since the Unix epoch")
+    }
+
+    fn visit_i64<E: de::Error>(self, seconds: i64) -> Result<Timestamp, E> {
+        Timestamp::from_unix(seconds).ok_or_else(|| E::custom(InvalidTimestamp(seconds.to_string())))
+    }
+
+    fn visit_u64<E: de::Error>(self, seconds: u64) -> Result<Timestamp, E> {
+        i64::try_from(seconds)
+            .ok()
+            .and_then(Timestamp::from_unix)
+            .ok_or_else(|| E::custom(InvalidTimestamp(seconds.to_string())))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Timestamp, E> {
+        value.parse().map_err(E::custom)
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn test_parse() -> Result<()> {
+    let timestamp: Timestamp = "2024-05-01T12:00:00+02:00".parse()?;
+    assert_eq!(timestamp.unix(), 1714557600);
+    assert_eq!(timestamp.to_string(), "2024-05-01T10:00:00Z");
+    assert_eq!("1714557600".parse::<Timestamp>()?, timestamp);
+    assert_eq!(timestamp.date(), NaiveDate::from_ymd_opt(2024, 5, 1).unwrap());
+
+    assert!("2024-05-01".parse::<Timestamp>().is_err());
+    assert!("yesterday".parse::<Timestamp>().is_err());
+    assert_eq!(Timestamp::from_unix(i64::MAX), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_serde() -> Result<()> {
+    let timestamp: Timestamp = "2024-05-01T10:00:00Z".parse()?;
+    assert_eq!(serde_json::to_string(&timestamp)?, "\"2024-05-01T10:00:00Z\"");
+    assert_eq!(
+        serde_json::from_str::<Timestamp>("\"2024-05-01T10:00:00Z\"")?,
+        timestamp
+    );
+    assert_eq!(serde_json::from_str::<Timestamp>("1714557600")?, timestamp);
+
+    let bytes = bincode::serialize(&timestamp)?;
+    assert_eq!(bytes, 1714557600i64.to_le_bytes());
+    assert_eq!(bincode::deserialize::<Timestamp>(&bytes)?, timestamp);
+
+    Ok(())
+}
@@ -3,8 +3,11 @@ use pretty_assertions::assert_eq;
 use rust_decimal_macros::dec;
 
 use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::policy::{Fee, FeePolicy, LedgerPolicy, NegativeBalancePolicy, Rounding};
+use crate::accounting::timestamp::Timestamp;
 
-fn assert_client(client: &Client, id: u16, available: Decimal, held: Decimal, locked: bool) {
+fn assert_client(client: &Client, id: ClientId, available: Decimal, held: Decimal, locked: bool) {
     assert_eq!(client.id(), id);
     assert_eq!(client.available(), available);
     assert_eq!(client.held(), held);
@@ -34,6 +37,109 @@ fn test_deposit_keeps_4_decimal() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_precision() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        precision: Some(2),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(3.1415926535))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(1.005))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(1.00), dec!(3.14), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_precision() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        strict_precision: true,
+        ..LedgerPolicy::default()
+    });
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(3.1415926535))?)) {
+        assert_eq!(err, TransactionError::InvalidAmount);
+    } else {
+        bail!("deposit with more than 4 decimals should not be accepted");
+    }
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(3.141500))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(0.00001))?)) {
+        assert_eq!(err, TransactionError::InvalidAmount);
+    } else {
+        bail!("withdrawal with more than 4 decimals should not be accepted");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(3.1415), dec!(0), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_rounding() -> Result<()> {
+    for (rounding, available) in [
+        (Rounding::HalfEven, dec!(1.00)),
+        (Rounding::HalfUp, dec!(1.01)),
+        (Rounding::Truncate, dec!(1.00)),
+    ] {
+        let mut ledger = Ledger::new();
+        ledger.use_policy(LedgerPolicy {
+            precision: Some(2),
+            rounding,
+            ..LedgerPolicy::default()
+        });
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(1.005))?))?;
+        assert_client(ledger.clients.get(&0).unwrap(), 0, available, dec!(0), false);
+    }
+
+    let policy = LedgerPolicy {
+        rounding: Rounding::Truncate,
+        ..LedgerPolicy::default()
+    };
+    assert_eq!(policy.round(dec!(-0.12349)), dec!(-0.1234));
+
+    Ok(())
+}
+
+#[test]
+fn test_amount_overflow() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, Decimal::MAX)?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(1))?)) {
+        assert_eq!(err, TransactionError::AmountOverflow);
+    } else {
+        bail!("deposit over the maximum balance should not be accepted");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, Decimal::MAX, dec!(0), false);
+
+    // The total of the available and held funds has to fit too.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(1))?)) {
+        assert_eq!(err, TransactionError::AmountOverflow);
+    } else {
+        bail!("deposit over the maximum total should not be accepted");
+    }
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, Decimal::MAX, dec!(0), false);
+
+    ledger.use_policy(LedgerPolicy {
+        fees: Some(FeePolicy {
+            account: 1,
+            withdrawal: Some(Fee::Percent(dec!(200))),
+            chargeback: None,
+        }),
+        ..LedgerPolicy::default()
+    });
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 0, Decimal::MAX)?)) {
+        assert_eq!(err, TransactionError::AmountOverflow);
+    } else {
+        bail!("withdrawal with a fee over the maximum should not be accepted");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, Decimal::MAX, dec!(0), false);
+
+    Ok(())
+}
+
 #[test]
 fn test_withdrawal() -> Result<()> {
     let mut ledger = Ledger::new();
@@ -358,6 +464,44 @@ fn test_dispute_negative_funds() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_dispute_negative_funds_policy() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        negative_balance: NegativeBalancePolicy::Reject,
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(3.2))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0))) {
+        assert_eq!(err, TransactionError::InsufficientFunds);
+    } else {
+        bail!("the policy should reject disputes the client can't cover");
+    }
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(0, 0, dec!(1.8))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(0), dec!(1.8), false);
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+
+    ledger.use_policy(LedgerPolicy {
+        negative_balance: NegativeBalancePolicy::Cap,
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(0.8))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(0), dec!(1), false);
+    // The rest stays disputable, once there's something to hold.
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0))) {
+        assert_eq!(err, TransactionError::InsufficientFunds);
+    } else {
+        bail!("nothing is available to hold");
+    }
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(7.8), dec!(3.2), false);
+
+    Ok(())
+}
+
 #[test]
 fn test_transaction_invalid_amount() -> Result<()> {
     if let Err(err) = Deposit::new(0, 0, dec!(-59)) {
@@ -404,3 +548,566 @@ fn test_ignore_repeated_transaction() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_reversal() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(8))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(3))?))?;
+
+    // Reversing the deposit leaves the account negative, but not locked.
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(-8), dec!(0), false);
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(1, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(0), dec!(0), false);
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Reversal(Reversal::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionReversed);
+    } else {
+        bail!("transaction should not be reversed again");
+    }
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionReversed);
+    } else {
+        bail!("reversed transaction should not be disputed");
+    }
+    if let Err(err) = ledger.execute_transaction(Transaction::Reversal(Reversal::new(9, 1))) {
+        assert_eq!(err, TransactionError::TransactionNotFound);
+    } else {
+        bail!("unknown transaction should not be found");
+    }
+    assert_client(ledger.clients.get(&1).unwrap(), 1, dec!(3), dec!(0), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_reversal_of_disputed_deposit() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(7))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(11))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Reversal(Reversal::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionUnderDispute);
+    } else {
+        bail!("transaction under dispute should not be reversed");
+    }
+
+    // A resolved dispute gave the funds back, so they can be taken back.
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(11), dec!(0), true);
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Reversal(Reversal::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionAlreadyDisputed);
+    } else {
+        bail!("chargedback transaction should not be reversed");
+    }
+    // Locked accounts can still be corrected.
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(2, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(0), dec!(0), true);
+
+    Ok(())
+}
+
+#[test]
+fn test_withdrawal_dispute() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(4))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(3))?))?;
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0))) {
+        assert_eq!(err, TransactionError::DisputeNotSupported);
+    } else {
+        bail!("withdrawal should not be disputed by default");
+    }
+
+    ledger.use_policy(LedgerPolicy {
+        withdrawal_disputes: true,
+        ..LedgerPolicy::default()
+    });
+
+    // Nothing is held, the funds were already withdrawn.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(3), dec!(0), false);
+    assert_eq!(ledger.stats().open_disputes, 1);
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0))) {
+        assert_eq!(err, TransactionError::TransactionUnderDispute);
+    } else {
+        bail!("withdrawal should not be disputed twice");
+    }
+
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(3), dec!(0), false);
+
+    // The chargeback credits the withdrawal back.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(2, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(6), dec!(0), true);
+    assert_eq!(ledger.stats().open_disputes, 0);
+    if let Err(err) = ledger.execute_transaction(Transaction::Reversal(Reversal::new(2, 0))) {
+        assert_eq!(err, TransactionError::TransactionAlreadyDisputed);
+    } else {
+        bail!("charged back withdrawal should not be reversed");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_withdrawal_dispute_keeps_withdrawals() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        withdrawal_disputes: true,
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(4))?))?;
+
+    // Withdrawals aren't settled until their dispute is.
+    assert_eq!(ledger.compact(|_transaction| {})?, 0);
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    assert_eq!(ledger.compact(|_transaction| {})?, 0);
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    assert_eq!(ledger.compact(|_transaction| {})?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_partial_dispute() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(0, 0, dec!(4))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(6), dec!(4), false);
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(0, 0, dec!(1))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(5), dec!(5), false);
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(0, 0, dec!(6))?)) {
+        assert_eq!(err, TransactionError::InvalidAmount);
+    } else {
+        bail!("more than the undisputed amount should not be disputed");
+    }
+
+    ledger.execute_transaction(Transaction::Resolve(Resolve::with_amount(0, 0, dec!(2))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(7), dec!(3), false);
+    if let Err(err) = ledger.execute_transaction(Transaction::Chargeback(Chargeback::with_amount(0, 0, dec!(4))?)) {
+        assert_eq!(err, TransactionError::InvalidAmount);
+    } else {
+        bail!("more than the held amount should not be charged back");
+    }
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(7), dec!(0), true);
+    assert_eq!(ledger.stats().open_disputes, 0);
+    assert_eq!(ledger.is_settled(0), false);
+
+    // The rest is still disputable, all of it by default.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(2), dec!(5), true);
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(7), dec!(0), true);
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0))) {
+        assert_eq!(err, TransactionError::TransactionAlreadyDisputed);
+    } else {
+        bail!("settled deposit should not be disputed again");
+    }
+    assert_eq!(ledger.compact(|_transaction| {})?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_partial_dispute_of_whole_amount() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(0, 0, dec!(10))?))?;
+
+    if let Some(Transaction::Deposit(deposit)) = ledger.transactions.get(&0) {
+        assert_eq!(deposit.dispute_status(), DisputeStatus::InDispute);
+    } else {
+        bail!("deposit should be stored");
+    }
+
+    assert!(Dispute::with_amount(0, 0, dec!(0)).is_err());
+    assert!(Resolve::with_amount(0, 0, dec!(-1)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_redispute_after_resolve() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        redispute_after_resolve: true,
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(5))?))?;
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+    assert_eq!(ledger.compact(|_transaction| {})?, 0);
+
+    // The second presentment.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(5), dec!(10), false);
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+
+    // Partially charged back, only the resolved part is disputed again.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(1, 0, dec!(2))?))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::with_amount(1, 0, dec!(1))?))?;
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(10), dec!(4), true);
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 0)))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0))) {
+        assert_eq!(err, TransactionError::TransactionAlreadyDisputed);
+    } else {
+        bail!("charged back deposit should not be disputed again");
+    }
+    assert_eq!(ledger.compact(|_transaction| {})?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_dispute_window() -> Result<()> {
+    let at = |timestamp: &str| timestamp.parse::<Timestamp>().map(Some);
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        dispute_window_days: Some(30),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(0, 0, dec!(10))?).with_timestamp(at("2024-05-01T12:00:00Z")?),
+    )?;
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(1, 0, dec!(5))?).with_timestamp(at("2024-05-01T12:00:00Z")?),
+    )?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(1))?))?;
+
+    if let Err(err) =
+        ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)).with_timestamp(at("2024-05-31T12:00:01Z")?))
+    {
+        assert_eq!(err, TransactionError::DisputeWindowExpired);
+    } else {
+        bail!("dispute after the window should fail");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(16), dec!(0), false);
+
+    // Exactly 30 days is still within the window.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)).with_timestamp(at("2024-05-31T12:00:00Z")?))?;
+    // Without both timestamps there's nothing to check.
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 0)).with_timestamp(at("2025-01-01T00:00:00Z")?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(0), dec!(16), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_freeze() -> Result<()> {
+    let mut ledger = Ledger::new();
+    if let Err(err) = ledger.execute_transaction(Transaction::Freeze(Freeze::new(0))) {
+        assert_eq!(err, TransactionError::ClientNotFound);
+    } else {
+        bail!("a missing client should not be frozen");
+    }
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Freeze(Freeze::new(0)))?;
+    ledger.execute_transaction(Transaction::Freeze(Freeze::new(0)))?;
+    assert!(ledger.clients.get(&0).unwrap().frozen());
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(4))?)) {
+        assert_eq!(err, TransactionError::AccountFrozen);
+    } else {
+        bail!("a frozen account should not accept withdrawals");
+    }
+
+    // Deposits and disputes go through, and nothing is locked.
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(10), dec!(5), false);
+
+    ledger.execute_transaction(Transaction::Unfreeze(Unfreeze::new(0)))?;
+    assert!(!ledger.clients.get(&0).unwrap().frozen());
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 0, dec!(4))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(6), dec!(5), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_freeze_rejects_deposits() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        reject_deposits_when_frozen: true,
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Freeze(Freeze::new(0)))?;
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(5))?)) {
+        assert_eq!(err, TransactionError::AccountFrozen);
+    } else {
+        bail!("the policy should reject deposits to a frozen account");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(10), dec!(0), false);
+
+    // Rejected, so it can be retried once the account is unfrozen.
+    ledger.execute_transaction(Transaction::Unfreeze(Unfreeze::new(0)))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(5))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(15), dec!(0), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_close_account() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::CloseAccount(CloseAccount::new(0))) {
+        assert_eq!(err, TransactionError::FundsHeld);
+    } else {
+        bail!("an account with held funds should not be closed");
+    }
+
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::CloseAccount(CloseAccount::new(0)))?;
+    ledger.execute_transaction(Transaction::CloseAccount(CloseAccount::new(0)))?;
+    assert!(ledger.clients.get(&0).unwrap().closed());
+
+    // The account isn't opened again by a deposit.
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(5))?)) {
+        assert_eq!(err, TransactionError::AccountClosed);
+    } else {
+        bail!("a closed account should not accept deposits");
+    }
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 0, dec!(0.5))?)) {
+        assert_eq!(err, TransactionError::AccountClosed);
+    } else {
+        bail!("a closed account should not accept withdrawals");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(0), dec!(0), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_locked_account_policy() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 0)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(0, 0)))?;
+
+    // Accepted by default.
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(3), dec!(5), true);
+
+    ledger.use_policy(LedgerPolicy {
+        reject_deposits_when_locked: true,
+        reject_disputes_when_locked: true,
+        ..LedgerPolicy::default()
+    });
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 0, dec!(3))?)) {
+        assert_eq!(err, TransactionError::AccountLocked);
+    } else {
+        bail!("the policy should reject deposits to a locked account");
+    }
+    if let Err(err) = ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 0))) {
+        assert_eq!(err, TransactionError::AccountLocked);
+    } else {
+        bail!("the policy should reject disputes on a locked account");
+    }
+
+    // The dispute opened before is still resolved.
+    ledger.execute_transaction(Transaction::Resolve(Resolve::new(1, 0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(8), dec!(0), true);
+
+    Ok(())
+}
+
+#[test]
+fn test_credit_limit() -> Result<()> {
+    let mut ledger = Ledger::new();
+    if let Err(err) = SetCreditLimit::new(0, dec!(-1)) {
+        assert_eq!(err, TransactionError::InvalidAmount);
+    } else {
+        bail!("a negative credit limit should not be accepted");
+    }
+
+    ledger.execute_transaction(Transaction::SetCreditLimit(SetCreditLimit::new(0, dec!(20))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(25))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(-15), dec!(0), false);
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(5.01))?)) {
+        assert_eq!(err, TransactionError::InsufficientFunds);
+    } else {
+        bail!("withdrawal should not go past the credit limit");
+    }
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 0, dec!(5))?))?;
+
+    // Taking the credit away leaves the balance as it is.
+    ledger.execute_transaction(Transaction::SetCreditLimit(SetCreditLimit::new(0, dec!(0))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(-20), dec!(0), false);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(4, 0, dec!(21))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(5, 0, dec!(2))?)) {
+        assert_eq!(err, TransactionError::InsufficientFunds);
+    } else {
+        bail!("withdrawal should not go below zero without credit");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_minimum_balance() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        minimum_balance: Some(dec!(5)),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::SetCreditLimit(SetCreditLimit::new(0, dec!(20))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(5.5))?)) {
+        assert_eq!(err, TransactionError::BelowMinimumBalance);
+    } else {
+        bail!("withdrawal should not go below the minimum balance, whatever the credit");
+    }
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(5))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(5), dec!(0), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_amount() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        max_amount: Some(dec!(1000)),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(1000))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(1000.0001))?)) {
+        assert_eq!(err, TransactionError::AmountTooLarge);
+    } else {
+        bail!("deposit over the maximum should not be accepted");
+    }
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 0, dec!(500))?))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 0, dec!(1500))?)) {
+        assert_eq!(err, TransactionError::AmountTooLarge);
+    } else {
+        bail!("withdrawal over the maximum should not be accepted");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(1500), dec!(0), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_fees() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        fees: Some(FeePolicy {
+            account: 100,
+            withdrawal: Some(Fee::Percent(dec!(1.5))),
+            chargeback: Some(Fee::Flat(dec!(15))),
+        }),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(100))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 0, dec!(10))?))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(89.85), dec!(0), false);
+    assert_client(ledger.clients.get(&100).unwrap(), 100, dec!(0.15), dec!(0), false);
+
+    // Nothing is taken unless the fee is available too.
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(89.85))?)) {
+        assert_eq!(err, TransactionError::InsufficientFunds);
+    } else {
+        bail!("withdrawal without funds for the fee should not be accepted");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(89.85), dec!(0), false);
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(3, 1)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(3, 1)))?;
+    assert_client(ledger.clients.get(&1).unwrap(), 1, dec!(-15), dec!(0), true);
+    assert_client(ledger.clients.get(&100).unwrap(), 100, dec!(15.15), dec!(0), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_fees_account_full() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 100, Decimal::MAX)?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 0, dec!(100))?))?;
+    ledger.use_policy(LedgerPolicy {
+        fees: Some(FeePolicy {
+            account: 100,
+            withdrawal: Some(Fee::Flat(dec!(1))),
+            chargeback: Some(Fee::Flat(dec!(15))),
+        }),
+        ..LedgerPolicy::default()
+    });
+
+    // Nothing changes if the fees account can't take the fee.
+    if let Err(err) = ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 0, dec!(10))?)) {
+        assert_eq!(err, TransactionError::AmountOverflow);
+    } else {
+        bail!("withdrawal with a fee the fees account can't take should not be accepted");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(100), dec!(0), false);
+    assert_client(ledger.clients.get(&100).unwrap(), 100, Decimal::MAX, dec!(0), false);
+
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 0)))?;
+    if let Err(err) = ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 0))) {
+        assert_eq!(err, TransactionError::AmountOverflow);
+    } else {
+        bail!("chargeback with a fee the fees account can't take should not be accepted");
+    }
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(0), dec!(100), false);
+    assert_eq!(ledger.open_disputes().count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_accrue() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 0, dec!(10.01))?))?;
+    ledger.execute_transaction(Transaction::Accrue(Accrue::new(0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(10.01), dec!(0), false);
+
+    ledger.use_policy(LedgerPolicy {
+        interest_rate: Some(dec!(1.5)),
+        ..LedgerPolicy::default()
+    });
+    // 0.15015 is rounded to even.
+    ledger.execute_transaction(Transaction::Accrue(Accrue::new(0)))?;
+    assert_client(ledger.clients.get(&0).unwrap(), 0, dec!(10.1602), dec!(0), false);
+
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Accrue(Accrue::new(1)))?;
+    assert_client(ledger.clients.get(&1).unwrap(), 1, dec!(-10), dec!(10), false);
+
+    if let Err(err) = ledger.execute_transaction(Transaction::Accrue(Accrue::new(2))) {
+        assert_eq!(err, TransactionError::ClientNotFound);
+    } else {
+        bail!("accruing for an unknown client should fail");
+    }
+
+    Ok(())
+}
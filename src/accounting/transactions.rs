@@ -1,52 +1,400 @@
 use anyhow::Result;
 use enum_dispatch::enum_dispatch;
+use getset::CopyGetters;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
 
-use super::{client, ExecutableTransaction, TransactionError};
-use super::{client::Client, ledger::Ledger};
+use super::client::Client;
+use super::policy::{FeePolicy, LedgerPolicy, NegativeBalancePolicy};
+use super::timestamp::Timestamp;
+use super::{client, ClientId, ExecutableTransaction, LedgerAccess, TransactionError, TxId};
 
 #[cfg(test)]
 #[path = "transaction_tests.rs"]
 mod transaction_tests;
 
 #[enum_dispatch(ExecutableTransaction)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Transaction {
     Deposit,
     Withdrawal,
     Dispute,
     Resolve,
     Chargeback,
+    Reversal,
+    DisputedWithdrawal,
+    Freeze,
+    Unfreeze,
+    CloseAccount,
+    SetCreditLimit,
+    Accrue,
 }
 
-#[derive(Debug, PartialEq)]
+impl Transaction {
+    /// The client whose account the transaction is about.
+    pub fn client_id(&self) -> ClientId {
+        match self {
+            Transaction::Deposit(deposit) => deposit.client_id,
+            Transaction::Withdrawal(withdrawal) => withdrawal.client_id,
+            Transaction::Dispute(dispute) => dispute.client_id,
+            Transaction::Resolve(resolve) => resolve.client_id,
+            Transaction::Chargeback(chargeback) => chargeback.client_id,
+            Transaction::Reversal(reversal) => reversal.client_id,
+            Transaction::DisputedWithdrawal(withdrawal) => withdrawal.client_id,
+            Transaction::Freeze(freeze) => freeze.client_id,
+            Transaction::Unfreeze(unfreeze) => unfreeze.client_id,
+            Transaction::CloseAccount(close) => close.client_id,
+            Transaction::SetCreditLimit(limit) => limit.client_id,
+            Transaction::Accrue(accrue) => accrue.client_id,
+        }
+    }
+
+    /// When the transaction happened, if it has an id of its own or is a
+    /// dispute, and it was given.
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Transaction::Deposit(deposit) => deposit.timestamp,
+            Transaction::Withdrawal(withdrawal) => withdrawal.timestamp,
+            Transaction::DisputedWithdrawal(withdrawal) => withdrawal.timestamp,
+            Transaction::Dispute(dispute) => dispute.timestamp,
+            _ => None,
+        }
+    }
+
+    /// Sets the timestamp of a transaction with an id of its own or a
+    /// dispute, see `LedgerPolicy::dispute_window_days`. The others are left
+    /// as they are.
+    pub fn with_timestamp(mut self, timestamp: Option<Timestamp>) -> Transaction {
+        match &mut self {
+            Transaction::Deposit(deposit) => deposit.timestamp = timestamp,
+            Transaction::Withdrawal(withdrawal) => withdrawal.timestamp = timestamp,
+            Transaction::DisputedWithdrawal(withdrawal) => withdrawal.timestamp = timestamp,
+            Transaction::Dispute(dispute) => dispute.timestamp = timestamp,
+            _ => {},
+        }
+
+        self
+    }
+}
+
+/// The layout of `Transaction` in snapshots before version 10, without
+/// timestamps. Transaction ids, `I`, were 32 bits before version 9. The
+/// transactions without ids are unchanged.
+#[derive(Deserialize)]
+pub(super) enum TransactionV9<I> {
+    Deposit(DepositV9<I>),
+    Withdrawal(WithdrawalV9<I>),
+    Dispute(ReferenceV9<I>),
+    Resolve(ReferenceV9<I>),
+    Chargeback(ReferenceV9<I>),
+    Reversal(ReferenceV9<I>),
+    DisputedWithdrawal(DepositV9<I>),
+    Freeze(Freeze),
+    Unfreeze(Unfreeze),
+    CloseAccount(CloseAccount),
+    SetCreditLimit(SetCreditLimit),
+    Accrue(Accrue),
+}
+
+/// The layout of `Deposit` and `DisputedWithdrawal` before version 10.
+#[derive(Deserialize)]
+pub(super) struct DepositV9<I> {
+    id: I,
+    client_id: ClientId,
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: Decimal,
+    dispute_status: DisputeStatus,
+}
+
+/// The layout of `Withdrawal` before version 10.
+#[derive(Deserialize)]
+pub(super) struct WithdrawalV9<I> {
+    id: I,
+    client_id: ClientId,
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: Decimal,
+}
+
+/// The layout of the transactions referencing another one before version 9,
+/// later ones only have wider ids.
+#[derive(Deserialize)]
+pub(super) struct ReferenceV9<I> {
+    ref_tx_id: I,
+    client_id: ClientId,
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<Decimal>,
+}
+
+impl<I: Into<TxId>> From<TransactionV9<I>> for Transaction {
+    fn from(transaction: TransactionV9<I>) -> Self {
+        match transaction {
+            TransactionV9::Deposit(deposit) => Transaction::Deposit(Deposit::from_parts(
+                deposit.id.into(),
+                deposit.client_id,
+                deposit.amount,
+                deposit.dispute_status,
+                None,
+            )),
+            TransactionV9::Withdrawal(withdrawal) => Transaction::Withdrawal(Withdrawal::from_parts(
+                withdrawal.id.into(),
+                withdrawal.client_id,
+                withdrawal.amount,
+                None,
+            )),
+            TransactionV9::Dispute(dispute) => Transaction::Dispute(Dispute {
+                ref_tx_id: dispute.ref_tx_id.into(),
+                client_id: dispute.client_id,
+                amount: dispute.amount,
+                timestamp: None,
+            }),
+            TransactionV9::Resolve(resolve) => Transaction::Resolve(Resolve {
+                ref_tx_id: resolve.ref_tx_id.into(),
+                client_id: resolve.client_id,
+                amount: resolve.amount,
+            }),
+            TransactionV9::Chargeback(chargeback) => Transaction::Chargeback(Chargeback {
+                ref_tx_id: chargeback.ref_tx_id.into(),
+                client_id: chargeback.client_id,
+                amount: chargeback.amount,
+            }),
+            TransactionV9::Reversal(reversal) => Transaction::Reversal(Reversal {
+                ref_tx_id: reversal.ref_tx_id.into(),
+                client_id: reversal.client_id,
+                amount: reversal.amount,
+            }),
+            TransactionV9::DisputedWithdrawal(withdrawal) => {
+                Transaction::DisputedWithdrawal(DisputedWithdrawal::from_parts(
+                    withdrawal.id.into(),
+                    withdrawal.client_id,
+                    withdrawal.amount,
+                    withdrawal.dispute_status,
+                    None,
+                ))
+            },
+            TransactionV9::Freeze(freeze) => Transaction::Freeze(freeze),
+            TransactionV9::Unfreeze(unfreeze) => Transaction::Unfreeze(unfreeze),
+            TransactionV9::CloseAccount(close) => Transaction::CloseAccount(close),
+            TransactionV9::SetCreditLimit(limit) => Transaction::SetCreditLimit(limit),
+            TransactionV9::Accrue(accrue) => Transaction::Accrue(accrue),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum DisputeStatus {
     NoDispute,
     InDispute,
     Resolved,
     Chargedback,
+    /// Only part of the amount went through disputes, see
+    /// `Dispute::with_amount`. Whatever is in none of these parts can still
+    /// be disputed.
+    Partial {
+        #[serde(with = "rust_decimal::serde::str")]
+        held: Decimal,
+        #[serde(with = "rust_decimal::serde::str")]
+        resolved: Decimal,
+        #[serde(with = "rust_decimal::serde::str")]
+        charged_back: Decimal,
+    },
 }
 
 impl DisputeStatus {
-    fn under_dispute(&self) -> bool {
-        self == &DisputeStatus::InDispute
+    pub(crate) fn under_dispute(&self) -> bool {
+        match self {
+            DisputeStatus::InDispute => true,
+            DisputeStatus::Partial { held, .. } => !held.is_zero(),
+            _ => false,
+        }
+    }
+
+    /// Whether no part of `total` can be disputed anymore.
+    fn dispute_solved(&self, total: Decimal, policy: &LedgerPolicy) -> bool {
+        let (held, resolved, charged_back) = self.amounts(total);
+        if policy.redispute_after_resolve {
+            return held.is_zero() && charged_back == total;
+        }
+
+        held.is_zero() && resolved + charged_back == total
+    }
+
+    /// The part of `total` that is held.
+    pub(crate) fn held(&self, total: Decimal) -> Decimal {
+        self.amounts(total).0
+    }
+
+    /// The parts of `total` that are held, resolved and charged back.
+    fn amounts(&self, total: Decimal) -> (Decimal, Decimal, Decimal) {
+        match *self {
+            DisputeStatus::NoDispute => (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO),
+            DisputeStatus::InDispute => (total, Decimal::ZERO, Decimal::ZERO),
+            DisputeStatus::Resolved => (Decimal::ZERO, total, Decimal::ZERO),
+            DisputeStatus::Chargedback => (Decimal::ZERO, Decimal::ZERO, total),
+            DisputeStatus::Partial {
+                held,
+                resolved,
+                charged_back,
+            } => (held, resolved, charged_back),
+        }
+    }
+
+    /// Partial only if none of the other statuses covers all of `total`.
+    fn from_amounts(total: Decimal, held: Decimal, resolved: Decimal, charged_back: Decimal) -> DisputeStatus {
+        if held.is_zero() && resolved.is_zero() && charged_back.is_zero() {
+            DisputeStatus::NoDispute
+        } else if held == total {
+            DisputeStatus::InDispute
+        } else if resolved == total {
+            DisputeStatus::Resolved
+        } else if charged_back == total {
+            DisputeStatus::Chargedback
+        } else {
+            DisputeStatus::Partial {
+                held,
+                resolved,
+                charged_back,
+            }
+        }
+    }
+
+    /// Disputes `amount` of `total`, or everything that can be disputed if
+    /// it's None. Returns the amount that should be held. What was never
+    /// disputed goes first, then what was resolved if the policy allows it.
+    fn dispute(
+        &mut self,
+        total: Decimal,
+        amount: Option<Decimal>,
+        policy: &LedgerPolicy,
+    ) -> Result<Decimal, TransactionError> {
+        let (held, resolved, charged_back) = self.amounts(total);
+        let undisputed = total - held - resolved - charged_back;
+        let disputable = if policy.redispute_after_resolve {
+            undisputed + resolved
+        } else {
+            undisputed
+        };
+        if disputable.is_zero() {
+            if held.is_zero() {
+                return Err(TransactionError::TransactionAlreadyDisputed);
+            }
+            return Err(TransactionError::TransactionUnderDispute);
+        }
+
+        let amount = amount.unwrap_or(disputable);
+        if amount > disputable {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        let redisputed = (amount - undisputed).max(Decimal::ZERO);
+        *self = DisputeStatus::from_amounts(total, held + amount, resolved - redisputed, charged_back);
+        Ok(amount)
+    }
+
+    /// Resolves `amount` of what's held, or all of it if it's None. Returns
+    /// the amount that should be released.
+    fn resolve(&mut self, total: Decimal, amount: Option<Decimal>) -> Result<Decimal, TransactionError> {
+        let (held, resolved, charged_back) = self.amounts(total);
+        let amount = DisputeStatus::settle(held, resolved + charged_back == total, amount)?;
+
+        *self = DisputeStatus::from_amounts(total, held - amount, resolved + amount, charged_back);
+        Ok(amount)
     }
 
-    fn dispute_solved(&self) -> bool {
-        self == &DisputeStatus::Resolved || self == &DisputeStatus::Chargedback
+    /// Charges back `amount` of what's held, or all of it if it's None.
+    /// Returns the amount that should be charged back.
+    fn chargeback(&mut self, total: Decimal, amount: Option<Decimal>) -> Result<Decimal, TransactionError> {
+        let (held, resolved, charged_back) = self.amounts(total);
+        let amount = DisputeStatus::settle(held, resolved + charged_back == total, amount)?;
+
+        *self = DisputeStatus::from_amounts(total, held - amount, resolved, charged_back + amount);
+        Ok(amount)
+    }
+
+    fn settle(held: Decimal, solved: bool, amount: Option<Decimal>) -> Result<Decimal, TransactionError> {
+        if held.is_zero() {
+            if solved {
+                return Err(TransactionError::TransactionAlreadyDisputed);
+            }
+            return Err(TransactionError::TransactionNotDisputed);
+        }
+
+        let amount = amount.unwrap_or(held);
+        if amount > held {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        Ok(amount)
+    }
+
+    /// Whether any part of the amount was charged back.
+    fn charged_back(&self) -> bool {
+        match self {
+            DisputeStatus::Chargedback => true,
+            DisputeStatus::Partial { charged_back, .. } => !charged_back.is_zero(),
+            _ => false,
+        }
+    }
+
+    /// The name storage backends keep the status under. A partial status
+    /// keeps its parts as well, e.g. `partial:1.5:0:2`.
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    pub(crate) fn name(self) -> String {
+        match self {
+            DisputeStatus::NoDispute => "none".to_string(),
+            DisputeStatus::InDispute => "disputed".to_string(),
+            DisputeStatus::Resolved => "resolved".to_string(),
+            DisputeStatus::Chargedback => "chargedback".to_string(),
+            DisputeStatus::Partial {
+                held,
+                resolved,
+                charged_back,
+            } => format!(
+                "partial:{}:{}:{}",
+                held.normalize(),
+                resolved.normalize(),
+                charged_back.normalize()
+            ),
+        }
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    pub(crate) fn from_name(name: &str) -> Option<DisputeStatus> {
+        match name {
+            "none" => Some(DisputeStatus::NoDispute),
+            "disputed" => Some(DisputeStatus::InDispute),
+            "resolved" => Some(DisputeStatus::Resolved),
+            "chargedback" => Some(DisputeStatus::Chargedback),
+            _ => {
+                let mut parts = name.strip_prefix("partial:")?.split(':').map(str::parse::<Decimal>);
+                let status = DisputeStatus::Partial {
+                    held: parts.next()?.ok()?,
+                    resolved: parts.next()?.ok()?,
+                    charged_back: parts.next()?.ok()?,
+                };
+                parts.next().is_none().then_some(status)
+            },
+        }
     }
 }
 
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
 pub struct Deposit {
-    id: u32,
-    client_id: u16,
+    id: TxId,
+    #[get_copy = "pub"]
+    client_id: ClientId,
+    #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str")]
     amount: Decimal,
 
+    #[get_copy = "pub"]
     dispute_status: DisputeStatus,
+    #[get_copy = "pub"]
+    timestamp: Option<Timestamp>,
 }
 
 impl Deposit {
-    pub fn new(id: u32, client_id: u16, amount: Decimal) -> Result<Deposit, TransactionError> {
+    pub fn new(id: TxId, client_id: ClientId, amount: Decimal) -> Result<Deposit, TransactionError> {
         if amount <= dec!(0) {
             return Err(TransactionError::InvalidAmount);
         }
@@ -56,233 +404,1036 @@ impl Deposit {
             client_id,
             amount,
             dispute_status: DisputeStatus::NoDispute,
+            timestamp: None,
         })
     }
+
+    /// Rebuilds a deposit taken apart by a transaction store, no validation
+    /// is done.
+    pub(crate) fn from_parts(
+        id: TxId,
+        client_id: ClientId,
+        amount: Decimal,
+        dispute_status: DisputeStatus,
+        timestamp: Option<Timestamp>,
+    ) -> Deposit {
+        Deposit {
+            id,
+            client_id,
+            amount,
+            dispute_status,
+            timestamp,
+        }
+    }
 }
 
 impl ExecutableTransaction for Deposit {
-    fn execute(&self, ledger: &mut Ledger) -> Result<(), TransactionError> {
-        let client = ledger
-            .clients
-            .entry(self.client_id)
-            .or_insert(Client::new(self.client_id));
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        let policy = ledger.policy();
+        policy.check_amount(self.amount)?;
+        ledger.client_or_insert(self.client_id, |client| {
+            if client.closed() {
+                return Err(TransactionError::AccountClosed);
+            }
 
-        client.deposit(self.amount);
+            if client.locked() && policy.reject_deposits_when_locked {
+                return Err(TransactionError::AccountLocked);
+            }
 
-        Ok(())
-    }
+            if client.frozen() && policy.reject_deposits_when_frozen {
+                return Err(TransactionError::AccountFrozen);
+            }
 
-    fn dispute(&mut self, client: &mut Client) -> Result<(), TransactionError> {
-        if self.dispute_status.under_dispute() {
-            return Err(TransactionError::TransactionUnderDispute);
-        }
+            client.deposit(self.amount)
+        })
+    }
 
-        if self.dispute_status.dispute_solved() {
-            return Err(TransactionError::TransactionAlreadyDisputed);
+    fn dispute(
+        &mut self,
+        client: &mut Client,
+        amount: Option<Decimal>,
+        policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        let mut dispute_status = self.dispute_status;
+        let mut held = dispute_status.dispute(self.amount, amount, policy)?;
+        if held > client.available() {
+            match policy.negative_balance {
+                NegativeBalancePolicy::Allow => {},
+                NegativeBalancePolicy::Cap if client.available() > Decimal::ZERO => {
+                    dispute_status = self.dispute_status;
+                    held = dispute_status.dispute(self.amount, Some(client.available()), policy)?;
+                },
+                NegativeBalancePolicy::Cap | NegativeBalancePolicy::Reject => {
+                    return Err(TransactionError::InsufficientFunds);
+                },
+            }
         }
 
-        client.hold_funds(self.amount);
-        self.dispute_status = DisputeStatus::InDispute;
+        client.hold_funds(held)?;
+        self.dispute_status = dispute_status;
 
         Ok(())
     }
 
-    fn resolve(&mut self, client: &mut client::Client) -> Result<(), TransactionError> {
-        if self.dispute_status.dispute_solved() {
-            return Err(TransactionError::TransactionAlreadyDisputed);
-        }
+    fn resolve(&mut self, client: &mut client::Client, amount: Option<Decimal>) -> Result<(), TransactionError> {
+        let mut dispute_status = self.dispute_status;
+        let amount = dispute_status.resolve(self.amount, amount)?;
+        client.release_funds(amount)?;
+        self.dispute_status = dispute_status;
 
-        if !self.dispute_status.under_dispute() {
-            return Err(TransactionError::TransactionNotDisputed);
-        }
+        Ok(())
+    }
 
-        client.release_funds(self.amount);
-        self.dispute_status = DisputeStatus::Resolved;
+    fn chargeback(&mut self, client: &mut Client, amount: Option<Decimal>) -> Result<(), TransactionError> {
+        let mut dispute_status = self.dispute_status;
+        let amount = dispute_status.chargeback(self.amount, amount)?;
+        client.chargeback(amount)?;
+        self.dispute_status = dispute_status;
 
         Ok(())
     }
 
-    fn chargeback(&mut self, client: &mut Client) -> Result<(), TransactionError> {
-        if self.dispute_status.dispute_solved() {
-            return Err(TransactionError::TransactionAlreadyDisputed);
+    fn reverse(&mut self, client: &mut Client) -> Result<Decimal, TransactionError> {
+        if self.dispute_status.under_dispute() {
+            return Err(TransactionError::TransactionUnderDispute);
         }
 
-        if !self.dispute_status.under_dispute() {
-            return Err(TransactionError::TransactionNotDisputed);
+        // The chargeback already took (some of) the funds back.
+        if self.dispute_status.charged_back() {
+            return Err(TransactionError::TransactionAlreadyDisputed);
         }
 
-        client.chargeback(self.amount);
-        self.dispute_status = DisputeStatus::Chargedback;
+        client.reverse_deposit(self.amount)?;
 
-        Ok(())
+        Ok(self.amount)
     }
 
-    fn id(&self) -> Option<u32> {
+    fn id(&self) -> Option<TxId> {
         Some(self.id)
     }
+
+    fn settled(&self, policy: &LedgerPolicy) -> bool {
+        self.dispute_status.dispute_solved(self.amount, policy)
+    }
+
+    fn under_dispute(&self) -> bool {
+        self.dispute_status.under_dispute()
+    }
+}
+
+fn withdrawal_fee(policy: &LedgerPolicy, amount: Decimal) -> Result<Decimal, TransactionError> {
+    policy
+        .fees
+        .and_then(|fees| fees.withdrawal)
+        .map_or(Ok(Decimal::ZERO), |fee| fee.amount(amount, policy))
+}
+
+// Credits a fee taken from a client to the fees account.
+fn collect_fee<L: LedgerAccess>(ledger: &mut L, fees: Option<FeePolicy>, fee: Decimal) -> Result<(), TransactionError> {
+    match fees {
+        Some(fees) if !fee.is_zero() => ledger.client_or_insert(fees.account, |account| account.deposit(fee)),
+        _ => Ok(()),
+    }
+}
+
+// Withdraws an amount and its fee from a client and collects the fee. The
+// client is put back as it was if the fees account can't take the fee, e.g.
+// because it would overflow.
+fn withdraw_with_fee<L: LedgerAccess>(
+    ledger: &mut L,
+    client_id: ClientId,
+    amount: Decimal,
+    policy: &LedgerPolicy,
+) -> Result<(), TransactionError> {
+    let fee = withdrawal_fee(policy, amount)?;
+    let total = amount.checked_add(fee).ok_or(TransactionError::AmountOverflow)?;
+    let mut previous = None;
+    ledger.client(client_id, |client| {
+        if !fee.is_zero() {
+            previous = Some(client.clone());
+        }
+        client.withdraw(total, policy.minimum_balance)
+    })?;
+    collect_fee(ledger, policy.fees, fee).or_else(|error| {
+        if let Some(previous) = previous {
+            ledger.client(client_id, |client| {
+                *client = previous;
+                Ok(())
+            })?;
+        }
+        Err(error)
+    })
 }
 
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
 pub struct Withdrawal {
-    id: u32,
-    client_id: u16,
+    id: TxId,
+    #[get_copy = "pub"]
+    client_id: ClientId,
+    #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str")]
     amount: Decimal,
+    #[get_copy = "pub"]
+    timestamp: Option<Timestamp>,
 }
 
 impl Withdrawal {
-    pub fn new(id: u32, client_id: u16, amount: Decimal) -> Result<Withdrawal, TransactionError> {
+    pub fn new(id: TxId, client_id: ClientId, amount: Decimal) -> Result<Withdrawal, TransactionError> {
         if amount <= dec!(0) {
             return Err(TransactionError::InvalidAmount);
         }
 
-        Ok(Withdrawal { id, client_id, amount })
+        Ok(Withdrawal {
+            id,
+            client_id,
+            amount,
+            timestamp: None,
+        })
+    }
+
+    /// Rebuilds a withdrawal taken apart by a transaction store, no
+    /// validation is done.
+    pub(crate) fn from_parts(
+        id: TxId,
+        client_id: ClientId,
+        amount: Decimal,
+        timestamp: Option<Timestamp>,
+    ) -> Withdrawal {
+        Withdrawal {
+            id,
+            client_id,
+            amount,
+            timestamp,
+        }
     }
 }
 
 impl ExecutableTransaction for Withdrawal {
-    fn execute(&self, ledger: &mut Ledger) -> Result<(), TransactionError> {
-        if let Some(client) = ledger.clients.get_mut(&self.client_id) {
-            client.withdraw(self.amount)
-        } else {
-            Err(TransactionError::ClientNotFound)
-        }
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        let policy = ledger.policy();
+        policy.check_amount(self.amount)?;
+        withdraw_with_fee(ledger, self.client_id, self.amount, &policy)
     }
 
-    fn dispute(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn resolve(&mut self, _client: &mut client::Client) -> Result<(), TransactionError> {
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn chargeback(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn id(&self) -> Option<u32> {
+    fn reverse(&mut self, client: &mut Client) -> Result<Decimal, TransactionError> {
+        client.deposit(self.amount)?;
+
+        Ok(self.amount)
+    }
+
+    fn id(&self) -> Option<TxId> {
         Some(self.id)
     }
+
+    fn settled(&self, policy: &LedgerPolicy) -> bool {
+        // Withdrawals can't be disputed, unless the policy allows it.
+        !policy.withdrawal_disputes
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Dispute {
-    ref_tx_id: u32,
-    client_id: u16,
+    ref_tx_id: TxId,
+    client_id: ClientId,
+    /// None for all of it.
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<Decimal>,
+    /// When it was filed. Disputes are never stored, so snapshots don't have
+    /// it.
+    timestamp: Option<Timestamp>,
 }
 
 impl Dispute {
-    pub fn new(ref_tx_id: u32, client_id: u16) -> Dispute {
-        Dispute { ref_tx_id, client_id }
+    pub fn new(ref_tx_id: TxId, client_id: ClientId) -> Dispute {
+        Dispute {
+            ref_tx_id,
+            client_id,
+            amount: None,
+            timestamp: None,
+        }
+    }
+
+    /// Disputes only `amount` of the referenced transaction, the rest can still
+    /// be disputed later.
+    pub fn with_amount(ref_tx_id: TxId, client_id: ClientId, amount: Decimal) -> Result<Dispute, TransactionError> {
+        if amount <= dec!(0) {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        Ok(Dispute {
+            ref_tx_id,
+            client_id,
+            amount: Some(amount),
+            timestamp: None,
+        })
     }
 }
 
 impl ExecutableTransaction for Dispute {
-    fn execute(&self, ledger: &mut Ledger) -> Result<(), TransactionError> {
-        if let Some(client) = ledger.clients.get_mut(&self.client_id) {
-            if let Some(transaction) = ledger.transactions.get_mut(&self.ref_tx_id) {
-                transaction.dispute(client)
-            } else {
-                Err(TransactionError::TransactionNotFound)
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        let policy = ledger.policy();
+        ledger.client_and_transaction(self.client_id, self.ref_tx_id, |client, transaction| {
+            if client.locked() && policy.reject_disputes_when_locked {
+                return Err(TransactionError::AccountLocked);
             }
-        } else {
-            Err(TransactionError::ClientNotFound)
-        }
+
+            if let (Some(filed), Some(happened)) = (self.timestamp, transaction.timestamp()) {
+                if !policy.within_dispute_window(happened, filed) {
+                    return Err(TransactionError::DisputeWindowExpired);
+                }
+            }
+
+            // A withdrawal only keeps track of its dispute once it has one.
+            if let (Transaction::Withdrawal(withdrawal), true) = (&*transaction, policy.withdrawal_disputes) {
+                *transaction = Transaction::DisputedWithdrawal(DisputedWithdrawal::from(withdrawal));
+            }
+
+            transaction.dispute(client, self.amount, &policy)
+        })
     }
 
-    fn dispute(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn resolve(&mut self, _client: &mut client::Client) -> Result<(), TransactionError> {
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn chargeback(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn id(&self) -> Option<u32> {
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn id(&self) -> Option<TxId> {
         None
     }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Resolve {
-    ref_tx_id: u32,
-    client_id: u16,
+    ref_tx_id: TxId,
+    client_id: ClientId,
+    /// None for all of it.
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<Decimal>,
 }
 
 impl Resolve {
-    pub fn new(ref_tx_id: u32, client_id: u16) -> Resolve {
-        Resolve { ref_tx_id, client_id }
+    pub fn new(ref_tx_id: TxId, client_id: ClientId) -> Resolve {
+        Resolve {
+            ref_tx_id,
+            client_id,
+            amount: None,
+        }
+    }
+
+    /// Resolves only `amount` of what's held for the referenced transaction,
+    /// the rest stays under dispute.
+    pub fn with_amount(ref_tx_id: TxId, client_id: ClientId, amount: Decimal) -> Result<Resolve, TransactionError> {
+        if amount <= dec!(0) {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        Ok(Resolve {
+            ref_tx_id,
+            client_id,
+            amount: Some(amount),
+        })
     }
 }
 
 impl ExecutableTransaction for Resolve {
-    fn execute(&self, ledger: &mut Ledger) -> Result<(), TransactionError> {
-        if let Some(client) = ledger.clients.get_mut(&self.client_id) {
-            if let Some(transaction) = ledger.transactions.get_mut(&self.ref_tx_id) {
-                transaction.resolve(client)
-            } else {
-                Err(TransactionError::TransactionNotFound)
-            }
-        } else {
-            Err(TransactionError::ClientNotFound)
-        }
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        ledger.client_and_transaction(self.client_id, self.ref_tx_id, |client, transaction| {
+            transaction.resolve(client, self.amount)
+        })
     }
 
-    fn dispute(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn resolve(&mut self, _client: &mut client::Client) -> Result<(), TransactionError> {
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn chargeback(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn id(&self) -> Option<u32> {
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn id(&self) -> Option<TxId> {
         None
     }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Chargeback {
-    ref_tx_id: u32,
-    client_id: u16,
+    ref_tx_id: TxId,
+    client_id: ClientId,
+    /// None for all of it.
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<Decimal>,
 }
 
 impl Chargeback {
-    pub fn new(ref_tx_id: u32, client_id: u16) -> Chargeback {
-        Chargeback { ref_tx_id, client_id }
+    pub fn new(ref_tx_id: TxId, client_id: ClientId) -> Chargeback {
+        Chargeback {
+            ref_tx_id,
+            client_id,
+            amount: None,
+        }
+    }
+
+    /// Charges back only `amount` of what's held for the referenced
+    /// transaction, the rest stays under dispute.
+    pub fn with_amount(ref_tx_id: TxId, client_id: ClientId, amount: Decimal) -> Result<Chargeback, TransactionError> {
+        if amount <= dec!(0) {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        Ok(Chargeback {
+            ref_tx_id,
+            client_id,
+            amount: Some(amount),
+        })
     }
 }
 
 impl ExecutableTransaction for Chargeback {
-    fn execute(&self, ledger: &mut Ledger) -> Result<(), TransactionError> {
-        if let Some(client) = ledger.clients.get_mut(&self.client_id) {
-            if let Some(transaction) = ledger.transactions.get_mut(&self.ref_tx_id) {
-                transaction.chargeback(client)
-            } else {
-                Err(TransactionError::TransactionNotFound)
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        let policy = ledger.policy();
+        let fees = policy.fees;
+        let mut fee = Decimal::ZERO;
+        let mut previous = None;
+        ledger.client_and_transaction(self.client_id, self.ref_tx_id, |client, transaction| {
+            let Some(chargeback_fee) = fees.and_then(|fees| fees.chargeback) else {
+                return transaction.chargeback(client, self.amount);
+            };
+
+            // Charged back on copies, so that nothing changes if the fee
+            // can't be charged.
+            let (mut charged, mut charged_transaction) = (client.clone(), transaction.clone());
+            charged_transaction.chargeback(&mut charged, self.amount)?;
+            // Charging back a withdrawal credits the client instead.
+            fee = chargeback_fee.amount((client.get_total() - charged.get_total()).abs(), &policy)?;
+            charged.charge_fee(fee)?;
+            previous = Some((
+                std::mem::replace(client, charged),
+                std::mem::replace(transaction, charged_transaction),
+            ));
+            Ok(())
+        })?;
+        // Undone if the fees account can't take the fee.
+        collect_fee(ledger, fees, fee).or_else(|error| {
+            if let Some((previous_client, previous_transaction)) = previous {
+                ledger.client_and_transaction(self.client_id, self.ref_tx_id, |client, transaction| {
+                    *client = previous_client;
+                    *transaction = previous_transaction;
+                    Ok(())
+                })?;
             }
-        } else {
-            Err(TransactionError::ClientNotFound)
+            Err(error)
+        })
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn id(&self) -> Option<TxId> {
+        None
+    }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
+}
+
+/// Takes back a deposit or withdrawal, e.g. to correct a mistake. Unlike a
+/// chargeback the account isn't locked. The reversed transaction is
+/// replaced in the store by the reversal, together with the amount that was
+/// reversed, so it can't be disputed or reversed again.
+///
+/// Only stored transactions can be reversed. With an id filter or after
+/// `Ledger::compact` settled ones (e.g. withdrawals) no longer are, and
+/// reversing them fails with TransactionSettled.
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
+pub struct Reversal {
+    ref_tx_id: TxId,
+    #[get_copy = "pub"]
+    client_id: ClientId,
+    /// What was reversed, once it was.
+    #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<Decimal>,
+}
+
+impl Reversal {
+    pub fn new(ref_tx_id: TxId, client_id: ClientId) -> Reversal {
+        Reversal {
+            ref_tx_id,
+            client_id,
+            amount: None,
+        }
+    }
+
+    /// Rebuilds the reversal stored for a reversed transaction, taken apart
+    /// by a transaction store.
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    pub(crate) fn from_parts(ref_tx_id: TxId, client_id: ClientId, amount: Decimal) -> Reversal {
+        Reversal {
+            ref_tx_id,
+            client_id,
+            amount: Some(amount),
+        }
+    }
+}
+
+/// A stored reversal stands for a reversed transaction, nothing can be done
+/// with it anymore.
+impl ExecutableTransaction for Reversal {
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        ledger.client_and_transaction(self.client_id, self.ref_tx_id, |client, transaction| {
+            let amount = transaction.reverse(client)?;
+            *transaction = Transaction::Reversal(Reversal {
+                amount: Some(amount),
+                ..self.clone()
+            });
+
+            Ok(())
+        })
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        Err(TransactionError::TransactionReversed)
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::TransactionReversed)
+    }
+
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::TransactionReversed)
+    }
+
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::TransactionReversed)
+    }
+
+    fn id(&self) -> Option<TxId> {
+        None
+    }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
+}
+
+/// A withdrawal that was disputed, which it's replaced by in the store, see
+/// `LedgerPolicy::withdrawal_disputes`. Plain withdrawals have no dispute
+/// status, so that the ones that are never disputed (nearly all of them)
+/// stay small.
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
+pub struct DisputedWithdrawal {
+    id: TxId,
+    #[get_copy = "pub"]
+    client_id: ClientId,
+    #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: Decimal,
+
+    #[get_copy = "pub"]
+    dispute_status: DisputeStatus,
+    #[get_copy = "pub"]
+    timestamp: Option<Timestamp>,
+}
+
+impl DisputedWithdrawal {
+    /// Rebuilds a disputed withdrawal taken apart by a transaction store, no
+    /// validation is done.
+    pub(crate) fn from_parts(
+        id: TxId,
+        client_id: ClientId,
+        amount: Decimal,
+        dispute_status: DisputeStatus,
+        timestamp: Option<Timestamp>,
+    ) -> DisputedWithdrawal {
+        DisputedWithdrawal {
+            id,
+            client_id,
+            amount,
+            dispute_status,
+            timestamp,
+        }
+    }
+}
+
+impl From<&Withdrawal> for DisputedWithdrawal {
+    fn from(withdrawal: &Withdrawal) -> Self {
+        DisputedWithdrawal::from_parts(
+            withdrawal.id,
+            withdrawal.client_id,
+            withdrawal.amount,
+            DisputeStatus::NoDispute,
+            withdrawal.timestamp,
+        )
+    }
+}
+
+/// The withdrawn funds are already gone, so a dispute holds nothing.
+impl ExecutableTransaction for DisputedWithdrawal {
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        let policy = ledger.policy();
+        policy.check_amount(self.amount)?;
+        withdraw_with_fee(ledger, self.client_id, self.amount, &policy)
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        amount: Option<Decimal>,
+        policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        self.dispute_status.dispute(self.amount, amount, policy)?;
+
+        Ok(())
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, amount: Option<Decimal>) -> Result<(), TransactionError> {
+        self.dispute_status.resolve(self.amount, amount)?;
+
+        Ok(())
+    }
+
+    fn chargeback(&mut self, client: &mut Client, amount: Option<Decimal>) -> Result<(), TransactionError> {
+        let mut dispute_status = self.dispute_status;
+        let amount = dispute_status.chargeback(self.amount, amount)?;
+        client.chargeback_withdrawal(amount)?;
+        self.dispute_status = dispute_status;
+
+        Ok(())
+    }
+
+    fn reverse(&mut self, client: &mut Client) -> Result<Decimal, TransactionError> {
+        if self.dispute_status.under_dispute() {
+            return Err(TransactionError::TransactionUnderDispute);
+        }
+
+        // The chargeback already credited (some of) the funds back.
+        if self.dispute_status.charged_back() {
+            return Err(TransactionError::TransactionAlreadyDisputed);
         }
+
+        client.deposit(self.amount)?;
+
+        Ok(self.amount)
+    }
+
+    fn id(&self) -> Option<TxId> {
+        Some(self.id)
+    }
+
+    fn settled(&self, policy: &LedgerPolicy) -> bool {
+        self.dispute_status.dispute_solved(self.amount, policy)
     }
 
-    fn dispute(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn under_dispute(&self) -> bool {
+        self.dispute_status.under_dispute()
+    }
+}
+
+/// Freezes the client's account, e.g. while it's reviewed for fraud, until an
+/// `Unfreeze`. Withdrawals fail with AccountFrozen in the meantime, deposits
+/// are accepted unless the policy rejects them. Unlike a chargeback it
+/// doesn't lock the account, and freezing a frozen account changes nothing.
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
+pub struct Freeze {
+    #[get_copy = "pub"]
+    client_id: ClientId,
+}
+
+impl Freeze {
+    pub fn new(client_id: ClientId) -> Freeze {
+        Freeze { client_id }
+    }
+}
+
+impl ExecutableTransaction for Freeze {
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        ledger.client(self.client_id, |client| {
+            client.freeze();
+            Ok(())
+        })
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn resolve(&mut self, _client: &mut client::Client) -> Result<(), TransactionError> {
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn chargeback(&mut self, _client: &mut Client) -> Result<(), TransactionError> {
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
         Err(TransactionError::DisputeNotSupported)
     }
 
-    fn id(&self) -> Option<u32> {
+    fn id(&self) -> Option<TxId> {
         None
     }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
+}
+
+/// Takes back a `Freeze`. A locked account stays locked.
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
+pub struct Unfreeze {
+    #[get_copy = "pub"]
+    client_id: ClientId,
+}
+
+impl Unfreeze {
+    pub fn new(client_id: ClientId) -> Unfreeze {
+        Unfreeze { client_id }
+    }
+}
+
+impl ExecutableTransaction for Unfreeze {
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        ledger.client(self.client_id, |client| {
+            client.unfreeze();
+            Ok(())
+        })
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn id(&self) -> Option<TxId> {
+        None
+    }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
+}
+
+/// Closes the client's account for good, once no funds are held for it.
+/// Later deposits and withdrawals fail with AccountClosed, so the account
+/// isn't opened again by a deposit. Disputes of its past deposits are still
+/// accepted. Closing a closed account changes nothing.
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
+pub struct CloseAccount {
+    #[get_copy = "pub"]
+    client_id: ClientId,
+}
+
+impl CloseAccount {
+    pub fn new(client_id: ClientId) -> CloseAccount {
+        CloseAccount { client_id }
+    }
+}
+
+impl ExecutableTransaction for CloseAccount {
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        ledger.client(self.client_id, |client| client.close())
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn id(&self) -> Option<TxId> {
+        None
+    }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
+}
+
+/// Sets how far withdrawals may take the client's available funds below
+/// zero, for partners who extend short-term credit. A limit of 0 (the
+/// default) takes the credit away again. The client is created if it
+/// doesn't exist yet, so a limit can be set before the first deposit.
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
+pub struct SetCreditLimit {
+    #[get_copy = "pub"]
+    client_id: ClientId,
+    #[get_copy = "pub"]
+    #[serde(with = "rust_decimal::serde::str")]
+    limit: Decimal,
+}
+
+impl SetCreditLimit {
+    pub fn new(client_id: ClientId, limit: Decimal) -> Result<SetCreditLimit, TransactionError> {
+        if limit.is_sign_negative() {
+            return Err(TransactionError::InvalidAmount);
+        }
+
+        Ok(SetCreditLimit { client_id, limit })
+    }
+}
+
+impl ExecutableTransaction for SetCreditLimit {
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        ledger.client_or_insert(self.client_id, |client| {
+            if client.closed() {
+                return Err(TransactionError::AccountClosed);
+            }
+
+            client.set_credit_limit(self.limit)
+        })
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn id(&self) -> Option<TxId> {
+        None
+    }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
+}
+
+/// Credits the client interest on its available funds, at the policy's
+/// `interest_rate`. Negative funds don't earn (or owe) any, and without a
+/// rate nothing is credited.
+#[derive(Clone, CopyGetters, Serialize, Deserialize)]
+pub struct Accrue {
+    #[get_copy = "pub"]
+    client_id: ClientId,
+}
+
+impl Accrue {
+    pub fn new(client_id: ClientId) -> Accrue {
+        Accrue { client_id }
+    }
+}
+
+impl ExecutableTransaction for Accrue {
+    fn execute<L: LedgerAccess>(&self, ledger: &mut L) -> Result<(), TransactionError> {
+        let policy = ledger.policy();
+        ledger.client(self.client_id, |client| {
+            if client.closed() {
+                return Err(TransactionError::AccountClosed);
+            }
+
+            match policy.interest_rate.filter(|_| client.available().is_sign_positive()) {
+                Some(rate) => match client.available().checked_mul(rate) {
+                    Some(interest) => client.deposit(policy.round(interest / dec!(100))),
+                    None => Err(TransactionError::AmountOverflow),
+                },
+                None => Ok(()),
+            }
+        })
+    }
+
+    fn dispute(
+        &mut self,
+        _client: &mut Client,
+        _amount: Option<Decimal>,
+        _policy: &LedgerPolicy,
+    ) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn resolve(&mut self, _client: &mut client::Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn chargeback(&mut self, _client: &mut Client, _amount: Option<Decimal>) -> Result<(), TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn reverse(&mut self, _client: &mut Client) -> Result<Decimal, TransactionError> {
+        Err(TransactionError::DisputeNotSupported)
+    }
+
+    fn id(&self) -> Option<TxId> {
+        None
+    }
+
+    fn settled(&self, _policy: &LedgerPolicy) -> bool {
+        // Never stored.
+        true
+    }
+
+    fn under_dispute(&self) -> bool {
+        false
+    }
 }
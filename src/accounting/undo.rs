@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+use super::client::Client;
+use super::ledger::Ledger;
+use super::policy::LedgerPolicy;
+use super::store::{ClientStore, TransactionStore};
+use super::transactions::Transaction;
+use super::{ClientId, LedgerAccess, TransactionError, TxId};
+
+#[cfg(test)]
+#[path = "undo_tests.rs"]
+mod undo_tests;
+
+/// The most recently executed transactions, newest last, see
+/// `Ledger::use_undo`.
+pub(super) struct UndoLog {
+    pub(super) entries: VecDeque<UndoEntry>,
+    pub(super) limit: usize,
+}
+
+/// Everything a transaction changed, as it was before.
+#[derive(Default)]
+pub(super) struct UndoEntry {
+    /// `None` for the clients it created.
+    pub(super) clients: Vec<(ClientId, Option<Client>)>,
+    /// Transactions it referenced, e.g. the deposit of a dispute.
+    pub(super) referenced: Vec<(TxId, Transaction)>,
+    /// The id it was stored under and its client, if it was.
+    pub(super) stored: Option<(TxId, ClientId)>,
+    pub(super) open_disputes: usize,
+}
+
+/// Gives a transaction access to the ledger while it executes, saving the
+/// state of everything it touches first.
+pub(super) struct Recorder<'a, T, C> {
+    pub(super) ledger: &'a mut Ledger<T, C>,
+    pub(super) entry: &'a mut UndoEntry,
+}
+
+impl UndoLog {
+    pub(super) fn new(limit: usize) -> UndoLog {
+        UndoLog {
+            entries: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub(super) fn push(&mut self, entry: UndoEntry) {
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+        if self.limit > 0 {
+            self.entries.push_back(entry);
+        }
+    }
+}
+
+impl<'a, T: TransactionStore, C: ClientStore> Recorder<'a, T, C> {
+    fn save_client(&mut self, client_id: ClientId) -> Result<(), TransactionError> {
+        if self.entry.clients.iter().all(|(id, _client)| *id != client_id) {
+            let client = self.ledger.clients.get(client_id)?;
+            self.entry.clients.push((client_id, client));
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, T: TransactionStore, C: ClientStore> LedgerAccess for Recorder<'a, T, C> {
+    fn policy(&self) -> LedgerPolicy {
+        self.ledger.policy()
+    }
+
+    fn client_or_insert(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        self.save_client(client_id)?;
+        self.ledger.client_or_insert(client_id, f)
+    }
+
+    fn client(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce(&mut Client) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        self.save_client(client_id)?;
+        self.ledger.client(client_id, f)
+    }
+
+    fn client_and_transaction(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        f: impl FnOnce(&mut Client, &mut Transaction) -> Result<(), TransactionError>,
+    ) -> Result<(), TransactionError> {
+        self.save_client(client_id)?;
+
+        let referenced = &mut self.entry.referenced;
+        self.ledger
+            .client_and_transaction(client_id, tx_id, |client, transaction| {
+                referenced.push((tx_id, transaction.clone()));
+                f(client, transaction)
+            })
+    }
+}
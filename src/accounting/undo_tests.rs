@@ -0,0 +1,80 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use crate::accounting::ledger::Ledger;
+use crate::accounting::store::compact::CompactStore;
+use crate::accounting::transactions::{Chargeback, Deposit, Dispute, DisputeStatus, Transaction, Withdrawal};
+use crate::accounting::{ClientId, TxId};
+
+fn deposit(id: TxId, client_id: ClientId, amount: rust_decimal::Decimal) -> Result<Transaction> {
+    Ok(Transaction::Deposit(Deposit::new(id, client_id, amount)?))
+}
+
+#[test]
+fn test_undo() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(deposit(1, 1, dec!(10))?)?;
+    ledger.use_undo(10);
+    let before = ledger.stats();
+
+    ledger.execute_transaction(deposit(2, 2, dec!(5))?)?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(4))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Chargeback(Chargeback::new(1, 1)))?;
+    // Neither of these is remembered.
+    ledger.execute_transaction(deposit(2, 2, dec!(5))?)?;
+    ledger
+        .execute_transaction(Transaction::Withdrawal(Withdrawal::new(4, 2, dec!(50))?))
+        .unwrap_err();
+    assert_eq!(ledger.undoable(), 4);
+    assert!(ledger.clients[&1].locked());
+
+    assert_eq!(ledger.undo(2)?, 2);
+    assert!(!ledger.clients[&1].locked());
+    assert_eq!(ledger.clients[&1].available(), dec!(6));
+    assert!(matches!(
+        ledger.transactions.get(&1),
+        Some(Transaction::Deposit(deposit)) if deposit.dispute_status() == DisputeStatus::NoDispute
+    ));
+
+    assert_eq!(ledger.undo(10)?, 2);
+    assert_eq!(ledger.stats(), before);
+    assert!(!ledger.clients.contains_key(&2));
+    assert_eq!(ledger.undo(1)?, 0);
+
+    // The undone transactions aren't repeats anymore.
+    ledger.execute_transaction(deposit(2, 2, dec!(5))?)?;
+    assert_eq!(ledger.clients[&2].available(), dec!(5));
+
+    Ok(())
+}
+
+#[test]
+fn test_undo_limit() -> Result<()> {
+    let mut ledger = Ledger::with_store(CompactStore::new());
+    ledger.use_undo(2);
+    for id in 0..5 {
+        ledger.execute_transaction(deposit(id, 1, dec!(1))?)?;
+    }
+
+    assert_eq!(ledger.undo(5)?, 2);
+    assert_eq!(ledger.clients[&1].available(), dec!(3));
+    assert_eq!(ledger.stats().stored_transactions, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_compact_clears_undo() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_undo(10);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(4))?))?;
+
+    ledger.compact(|_| {})?;
+    assert_eq!(ledger.undoable(), 0);
+    assert_eq!(ledger.undo(1)?, 0);
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+use super::client::Client;
+use super::ledger::Ledger;
+use super::transactions::Transaction;
+use super::{ClientId, ExecutableTransaction, LedgerAccess};
+
+#[cfg(test)]
+#[path = "verify_tests.rs"]
+mod verify_tests;
+
+/// An invariant that doesn't hold for a ledger, see `Ledger::verify`.
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum Violation {
+    #[error("client {client} has negative held funds: {held}")]
+    NegativeHeld { client: ClientId, held: Decimal },
+    #[error("the total funds of client {client} are out of range")]
+    TotalOverflow { client: ClientId },
+    #[error("client {client} has {held} held, but its disputed deposits hold {disputed}")]
+    HeldMismatch {
+        client: ClientId,
+        held: Decimal,
+        disputed: Decimal,
+    },
+    #[error("{counted} open disputes are counted, but {stored} are stored")]
+    OpenDisputes { counted: usize, stored: usize },
+}
+
+impl Ledger {
+    /// Checks the invariants executing transactions keeps, e.g. after the
+    /// ledger was restored from a snapshot or replayed from a log. Returns
+    /// every one that doesn't hold, none if the ledger is consistent:
+    /// - No client has negative held funds, no policy allows it.
+    /// - The total of every client, its available plus held funds, is in a
+    ///   Decimal's range.
+    /// - The held funds of every client are what its disputed deposits hold,
+    ///   rounded as the policy says.
+    /// - The open disputes counted are those of the stored transactions.
+    ///
+    /// Compacted transactions are settled, so they don't hold anything.
+    pub fn verify(&self) -> Vec<Violation> {
+        let policy = self.policy();
+        let mut disputed: BTreeMap<ClientId, Decimal> = BTreeMap::new();
+        let mut open_disputes = 0;
+        for transaction in self.transactions.values() {
+            if transaction.under_dispute() {
+                open_disputes += 1;
+            }
+            if let Transaction::Deposit(deposit) = transaction {
+                let held = deposit.dispute_status().held(deposit.amount());
+                let sum = disputed.entry(deposit.client_id()).or_default();
+                *sum = sum.saturating_add(held);
+            }
+        }
+
+        let mut clients: Vec<&Client> = self.clients.values().collect();
+        clients.sort_by_key(|client| client.id());
+        let mut violations = Vec::new();
+        for client in clients {
+            let (id, held) = (client.id(), client.held());
+            if held < Decimal::ZERO {
+                violations.push(Violation::NegativeHeld { client: id, held });
+            }
+            if client.available().checked_add(held).is_none() {
+                violations.push(Violation::TotalOverflow { client: id });
+            }
+            let disputed = policy.round(disputed.remove(&id).unwrap_or_default());
+            if disputed != held {
+                violations.push(Violation::HeldMismatch {
+                    client: id,
+                    held,
+                    disputed,
+                });
+            }
+        }
+        // Disputed deposits of clients the ledger doesn't have.
+        for (client, disputed) in disputed.into_iter().filter(|(_client, disputed)| !disputed.is_zero()) {
+            violations.push(Violation::HeldMismatch {
+                client,
+                held: Decimal::ZERO,
+                disputed,
+            });
+        }
+
+        let counted = self.stats().open_disputes;
+        if counted != open_disputes {
+            violations.push(Violation::OpenDisputes {
+                counted,
+                stored: open_disputes,
+            });
+        }
+
+        violations
+    }
+}
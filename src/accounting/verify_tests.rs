@@ -0,0 +1,63 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::policy::LedgerPolicy;
+use crate::accounting::transactions::{Deposit, Dispute, DisputeStatus};
+
+#[test]
+fn test_verify() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        precision: Some(2),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(3.14159))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::with_amount(2, 1, dec!(2))?))?;
+    assert_eq!(ledger.verify(), vec![]);
+
+    let mut client = Client::new(2);
+    client.release_funds(dec!(1))?;
+    ledger.clients.insert(2, client);
+    let client = r#"{"id":3,"available":"79228162514264337593543950335","held":"1","locked":false,"frozen":false,"closed":false,"credit_limit":"0"}"#;
+    ledger.clients.insert(3, serde_json::from_str(client)?);
+    let deposit = Deposit::from_parts(4, 5, dec!(1), DisputeStatus::InDispute, None);
+    ledger.transactions.insert(4, Transaction::Deposit(deposit));
+
+    let violations = ledger.verify();
+    assert_eq!(
+        violations,
+        vec![
+            Violation::NegativeHeld {
+                client: 2,
+                held: dec!(-1)
+            },
+            Violation::HeldMismatch {
+                client: 2,
+                held: dec!(-1),
+                disputed: dec!(0)
+            },
+            Violation::TotalOverflow { client: 3 },
+            Violation::HeldMismatch {
+                client: 3,
+                held: dec!(1),
+                disputed: dec!(0)
+            },
+            Violation::HeldMismatch {
+                client: 5,
+                held: dec!(0),
+                disputed: dec!(1)
+            },
+            Violation::OpenDisputes { counted: 2, stored: 3 },
+        ]
+    );
+    assert_eq!(
+        violations[1].to_string(),
+        "client 2 has -1 held, but its disputed deposits hold 0"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::transactions::Transaction;
+
+#[cfg(test)]
+#[path = "wal_tests.rs"]
+mod wal_tests;
+
+#[derive(Debug, Error)]
+pub enum WalError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("failed to encode wal entry, err={0}")]
+    Encode(bincode::Error),
+    #[error("failed to decode wal entry, err={0}")]
+    Decode(bincode::Error),
+    #[error("wal entry doesn't match its checksum")]
+    Checksum,
+    #[error("wal entry has an invalid length {0}")]
+    InvalidLength(u32),
+}
+
+/// No entry comes anywhere near this, a longer one has a corrupt length.
+const MAX_ENTRY_SIZE: u32 = 1 << 20;
+
+/// A journaled transaction. Sequence numbers start at 0 and increase by one
+/// for every entry. The position is where the transaction came from, see
+/// `Ledger::position`.
+#[derive(Deserialize)]
+pub struct WalEntry {
+    pub sequence: u64,
+    pub position: u64,
+    pub transaction: Transaction,
+}
+
+// Same layout as `WalEntry`, without taking the transaction.
+#[derive(Serialize)]
+struct WalEntryRef<'a> {
+    sequence: u64,
+    position: u64,
+    transaction: &'a Transaction,
+}
+
+/// A write-ahead log: every transaction is appended to it, and synced to
+/// disk, before the ledger executes it (see `Ledger::use_wal`). After a crash
+/// the ledger can be rebuilt by executing the journaled transactions again.
+///
+/// The journal is a directory of segment files, each named after the sequence
+/// number of its first entry. Opening the journal always starts a new
+/// segment. Entries are a little endian u32 length and CRC32 checksum,
+/// followed by the bincode encoded `WalEntry`.
+///
+/// Syncing every entry is slow, expect thousands rather than millions of
+/// transactions per second.
+///
+/// Entries journaled before transaction ids were 64 bits, or before
+/// transactions had timestamps, can't be read, the ledger has to be
+/// checkpointed (and the journal truncated) before upgrading.
+pub struct Wal {
+    dir: PathBuf,
+    segment: File,
+    next_sequence: u64,
+}
+
+/// Reads the entries of a journal, oldest first.
+pub struct WalReader {
+    segments: Vec<PathBuf>,
+    reader: Option<BufReader<File>>,
+}
+
+impl Wal {
+    /// Opens the journal in this directory, creating it if needed. New
+    /// entries continue after the last one already in it.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Wal, WalError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let next_sequence = match segments(&dir)?.pop() {
+            Some((first_sequence, path)) => {
+                // An entry that was cut off in a crash was never executed, so
+                // it's left out.
+                let reader = WalReader::new(vec![path]);
+                reader
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.sequence + 1)
+                    .max()
+                    .unwrap_or(first_sequence)
+            },
+            None => 0,
+        };
+
+        Ok(Wal {
+            segment: start_segment(&dir, next_sequence)?,
+            dir,
+            next_sequence,
+        })
+    }
+
+    /// Appends the transaction and waits until it's on disk. Returns its
+    /// sequence number.
+    pub fn append(&mut self, position: u64, transaction: &Transaction) -> Result<u64, WalError> {
+        let sequence = self.next_sequence;
+        let entry = WalEntryRef {
+            sequence,
+            position,
+            transaction,
+        };
+        let entry = bincode::serialize(&entry).map_err(WalError::Encode)?;
+
+        let mut frame = Vec::with_capacity(8 + entry.len());
+        frame.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32fast::hash(&entry).to_le_bytes());
+        frame.extend_from_slice(&entry);
+        self.segment.write_all(&frame)?;
+        self.segment.sync_data()?;
+
+        self.next_sequence += 1;
+        Ok(sequence)
+    }
+
+    /// Starts a new segment and removes all the others. Only to be called
+    /// once every entry so far is in a checkpoint.
+    pub fn truncate(&mut self) -> Result<(), WalError> {
+        self.segment = start_segment(&self.dir, self.next_sequence)?;
+        for (first_sequence, path) in segments(&self.dir)? {
+            if first_sequence < self.next_sequence {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sequence number the next appended entry gets.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reads every entry in the journal in this directory.
+    pub fn read(dir: impl AsRef<Path>) -> Result<WalReader, WalError> {
+        let segments = segments(dir.as_ref())?;
+        Ok(WalReader::new(segments.into_iter().map(|(_, path)| path).collect()))
+    }
+}
+
+impl WalReader {
+    fn new(mut segments: Vec<PathBuf>) -> WalReader {
+        // Taken from the back.
+        segments.reverse();
+        WalReader { segments, reader: None }
+    }
+
+    fn read_entry(reader: &mut BufReader<File>) -> Result<Option<WalEntry>, WalError> {
+        let mut header = [0; 8];
+        let read = reader.read(&mut header)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        reader.read_exact(&mut header[read..])?;
+
+        let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        if length > MAX_ENTRY_SIZE {
+            return Err(WalError::InvalidLength(length));
+        }
+        let mut entry = vec![0; length as usize];
+        reader.read_exact(&mut entry)?;
+
+        if crc32fast::hash(&entry).to_le_bytes() != header[4..] {
+            return Err(WalError::Checksum);
+        }
+        bincode::deserialize(&entry).map(Some).map_err(WalError::Decode)
+    }
+}
+
+impl Iterator for WalReader {
+    type Item = Result<WalEntry, WalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(reader) = &mut self.reader {
+                match WalReader::read_entry(reader) {
+                    Ok(Some(entry)) => return Some(Ok(entry)),
+                    Ok(None) => self.reader = None,
+                    // The next entry can still be found.
+                    Err(err @ (WalError::Checksum | WalError::Decode(_))) => return Some(Err(err)),
+                    Err(err) => {
+                        // The rest of the segment can't be found anymore.
+                        self.reader = None;
+                        return Some(Err(err));
+                    },
+                }
+            }
+
+            let path = self.segments.pop()?;
+            match File::open(path) {
+                Ok(file) => self.reader = Some(BufReader::new(file)),
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+    }
+}
+
+fn segment_path(dir: &Path, first_sequence: u64) -> PathBuf {
+    dir.join(format!("{:020}.wal", first_sequence))
+}
+
+fn start_segment(dir: &Path, first_sequence: u64) -> Result<File, WalError> {
+    let segment = File::create(segment_path(dir, first_sequence))?;
+    // Makes the new file itself durable.
+    File::open(dir)?.sync_all()?;
+
+    Ok(segment)
+}
+
+// The segments in the directory with their first sequence number, oldest
+// first.
+fn segments(dir: &Path) -> Result<Vec<(u64, PathBuf)>, WalError> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |extension| extension != "wal") {
+            continue;
+        }
+
+        if let Some(first_sequence) = path.file_stem().and_then(|stem| stem.to_str()?.parse().ok()) {
+            segments.push((first_sequence, path));
+        }
+    }
+
+    segments.sort();
+    Ok(segments)
+}
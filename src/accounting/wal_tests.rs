@@ -0,0 +1,127 @@
+use std::fs::OpenOptions;
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Deposit, Dispute, Withdrawal};
+use crate::accounting::ExecutableTransaction;
+
+fn sequences(dir: &Path) -> Result<Vec<u64>> {
+    Ok(Wal::read(dir)?
+        .map(|entry| entry.map(|entry| entry.sequence))
+        .collect::<Result<_, _>>()?)
+}
+
+#[test]
+fn test_append_and_read() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let mut wal = Wal::open(dir.path())?;
+    assert_eq!(wal.append(2, &Transaction::Deposit(Deposit::new(0, 1, dec!(10))?))?, 0);
+    assert_eq!(wal.append(3, &Transaction::Dispute(Dispute::new(0, 1)))?, 1);
+    drop(wal);
+
+    // Reopened after a restart.
+    let mut wal = Wal::open(dir.path())?;
+    assert_eq!(wal.next_sequence(), 2);
+    assert_eq!(
+        wal.append(5, &Transaction::Withdrawal(Withdrawal::new(1, 1, dec!(2))?))?,
+        2
+    );
+
+    let entries = Wal::read(dir.path())?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(entries.len(), 3);
+    assert!(matches!(&entries[0].transaction, Transaction::Deposit(deposit) if deposit.amount() == dec!(10)));
+    assert!(matches!(entries[1].transaction, Transaction::Dispute(_)));
+    assert_eq!(entries[2].transaction.id(), Some(1));
+    assert_eq!(
+        entries.iter().map(|entry| entry.position).collect::<Vec<_>>(),
+        vec![2, 3, 5]
+    );
+    assert_eq!(fs::read_dir(dir.path())?.count(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_torn_entry() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let mut wal = Wal::open(dir.path())?;
+    wal.append(0, &Transaction::Deposit(Deposit::new(0, 1, dec!(10))?))?;
+    wal.append(0, &Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    drop(wal);
+
+    // A crash in the middle of writing the next entry.
+    let (_, path) = segments(dir.path())?.pop().unwrap();
+    let mut segment = OpenOptions::new().append(true).open(path)?;
+    segment.write_all(&[20, 0, 0, 0, 1, 2, 3, 4, 5])?;
+
+    let mut entries = Wal::read(dir.path())?;
+    assert_eq!(entries.next().unwrap()?.sequence, 0);
+    assert_eq!(entries.next().unwrap()?.sequence, 1);
+    assert!(entries.next().unwrap().is_err());
+    assert!(entries.next().is_none());
+
+    let mut wal = Wal::open(dir.path())?;
+    assert_eq!(wal.append(0, &Transaction::Deposit(Deposit::new(2, 1, dec!(10))?))?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_ledger_journals_transactions() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let mut ledger = Ledger::new();
+    ledger.use_wal(Wal::open(dir.path())?);
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(0, 1, dec!(10))?))?;
+    ledger
+        .execute_transaction(Transaction::Withdrawal(Withdrawal::new(1, 1, dec!(20))?))
+        .unwrap_err();
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(0, 1)))?;
+
+    // The repeated deposit is left out, the failed withdrawal isn't as it
+    // fails the same way when executed again.
+    assert_eq!(sequences(dir.path())?, vec![0, 1, 2]);
+
+    let mut rebuilt = Ledger::new();
+    for entry in Wal::read(dir.path())? {
+        let _ = rebuilt.execute_transaction(entry?.transaction);
+    }
+    assert_eq!(rebuilt.stats(), ledger.stats());
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_entry() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let mut wal = Wal::open(dir.path())?;
+    for id in 0..3 {
+        wal.append(0, &Transaction::Deposit(Deposit::new(id, 1, dec!(10))?))?;
+    }
+    drop(wal);
+
+    // Flips a bit in the second entry.
+    let (_, path) = segments(dir.path())?.pop().unwrap();
+    let mut data = fs::read(&path)?;
+    let length = u32::from_le_bytes(data[..4].try_into()?) as usize;
+    data[8 + length + 8 + 5] ^= 1;
+    fs::write(&path, data)?;
+
+    let entries: Vec<_> = Wal::read(dir.path())?.collect();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].as_ref().unwrap().sequence, 0);
+    assert!(matches!(entries[1], Err(WalError::Checksum)));
+    assert_eq!(entries[2].as_ref().unwrap().sequence, 2);
+
+    assert_eq!(Wal::open(dir.path())?.next_sequence(), 3);
+
+    Ok(())
+}
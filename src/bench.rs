@@ -0,0 +1,85 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::Transaction;
+use crate::generate::{Generator, GeneratorConfig};
+
+#[cfg(test)]
+#[path = "bench_tests.rs"]
+mod bench_tests;
+
+/// Outcome of `run`. Latencies are per transaction executed by the ledger.
+#[derive(Debug)]
+pub struct BenchReport {
+    pub transactions: usize,
+    pub elapsed: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+    /// Peak resident memory of the process in bytes, where the platform
+    /// tells (Linux only for now).
+    pub peak_memory: Option<u64>,
+}
+
+impl BenchReport {
+    pub fn throughput(&self) -> f64 {
+        self.transactions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Generates the transactions in memory first, so only the ledger is
+/// measured and not the parsing.
+pub fn run(config: GeneratorConfig) -> Result<BenchReport> {
+    let transactions = Generator::new(config)
+        .map(Transaction::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut ledger = Ledger::new();
+    let mut latencies = Vec::with_capacity(transactions.len());
+    let started = Instant::now();
+
+    for transaction in transactions {
+        let executed = Instant::now();
+        // Generated transactions are all valid, a failure would be counted
+        // the same anyway.
+        let _ = ledger.execute_transaction(transaction);
+        latencies.push(executed.elapsed());
+    }
+
+    let elapsed = started.elapsed();
+    latencies.sort_unstable();
+    let percentile = |p: f64| {
+        let index = ((latencies.len() as f64 * p) as usize).min(latencies.len().saturating_sub(1));
+        latencies.get(index).copied().unwrap_or_default()
+    };
+
+    Ok(BenchReport {
+        transactions: latencies.len(),
+        elapsed,
+        p50: percentile(0.5),
+        p90: percentile(0.9),
+        p99: percentile(0.99),
+        p999: percentile(0.999),
+        max: latencies.last().copied().unwrap_or_default(),
+        peak_memory: peak_memory(),
+    })
+}
+
+fn peak_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("VmHWM:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(kilobytes * 1024)
+}
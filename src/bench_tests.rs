@@ -0,0 +1,21 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn test_run() -> Result<()> {
+    let report = run(GeneratorConfig {
+        rows: 5000,
+        ..GeneratorConfig::default()
+    })?;
+
+    assert_eq!(report.transactions, 5000);
+    assert!(report.p50 <= report.p90);
+    assert!(report.p90 <= report.p99);
+    assert!(report.p99 <= report.p999);
+    assert!(report.p999 <= report.max);
+    assert!(report.throughput() > 0.0);
+
+    Ok(())
+}
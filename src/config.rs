@@ -0,0 +1,112 @@
+use std::fs;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use transacto::accounting::currency::Currency;
+use transacto::accounting::monitoring::AlertThreshold;
+use transacto::accounting::policy::LedgerPolicy;
+use transacto::accounting::snapshot::SnapshotEncoding;
+use transacto::accounting::ClientId;
+use transacto::data::rules::Rule;
+use transacto::data::{ErrorPolicy, ExportSpec, FixTags, Format, OrderingCheck, SortKey, VelocityWindow};
+
+#[cfg(test)]
+#[path = "config_tests.rs"]
+mod config_tests;
+
+/// Settings read from a TOML file with `--config`. Flags given on the
+/// command line, or their `TRANSACTO_*` environment variables, take
+/// precedence over the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub input_format: Option<Format>,
+    pub output_format: Option<Format>,
+    pub output: Option<String>,
+    pub rejects: Option<String>,
+    pub events: Option<String>,
+    pub balances: Option<String>,
+    pub risk_report: Option<String>,
+    pub daily_report: Option<String>,
+    pub currency: Option<Currency>,
+    pub camt053: Option<String>,
+    pub camt053_currency: Option<Currency>,
+    pub html_report: Option<String>,
+    pub xlsx: Option<String>,
+    pub xlsx_summary: bool,
+    pub settlements: Option<String>,
+    pub clients: Option<String>,
+    pub with_metadata: bool,
+    pub client_ids: Option<String>,
+    pub strict: bool,
+    pub summary: bool,
+    pub snapshot_encoding: Option<SnapshotEncoding>,
+    pub key_file: Option<String>,
+    pub wal: Option<String>,
+    pub audit_log: Option<String>,
+    pub error_policy: ErrorPolicy,
+    pub policy: LedgerPolicy,
+    pub checkpoint: Checkpoint,
+    pub velocity: Velocity,
+    pub ordering: Option<OrderingCheck>,
+    /// See `Ledger::use_chargeback_monitor`, alerts are logged.
+    pub chargeback_alerts: Option<AlertThreshold>,
+    pub rules: Vec<Rule>,
+    pub performance: Performance,
+    pub export: Export,
+    /// See `FixTags`.
+    pub fix: FixTags,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Checkpoint {
+    pub path: Option<String>,
+    pub every_transactions: Option<u64>,
+    pub every_seconds: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Velocity {
+    /// See `ProcessingOptions::max_transactions_per_client`, the limit is
+    /// per input file.
+    pub max_transactions_per_client: Option<usize>,
+    /// See `ProcessingOptions::velocity_window`.
+    pub window: Option<VelocityWindow>,
+}
+
+/// Which accounts are exported, see `ExportOptions`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Export {
+    pub locked_only: bool,
+    pub non_zero_only: bool,
+    pub clients: Option<Vec<ClientId>>,
+    pub sort: Option<SortKey>,
+    pub columns: Option<ExportSpec>,
+    pub lifetime_totals: bool,
+    pub decimal_places: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Performance {
+    /// Remember settled transactions in a Bloom filter, see
+    /// `Ledger::use_id_filter`.
+    pub id_filter: Option<IdFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdFilter {
+    pub expected_ids: usize,
+    pub false_positive_rate: f64,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Config> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
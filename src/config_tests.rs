@@ -0,0 +1,185 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use transacto::accounting::policy::{Fee, FeePolicy, NegativeBalancePolicy, Rounding};
+use transacto::data::rules::{Condition, RuleAction};
+use transacto::data::{Column, ErrorAction, TransactionType};
+
+#[test]
+fn test_parse() -> Result<()> {
+    let config: Config = toml::from_str(
+        r#"
+        input_format = "jsonl"
+        output = "accounts.parquet"
+        strict = false
+        snapshot_encoding = "cbor"
+        currency = "usd"
+        camt053 = "statements.xml"
+        camt053_currency = "eur"
+
+        [error_policy]
+        parse = "abort"
+        validation = "warn"
+
+        [error_policy.ledger_errors]
+        insufficient_funds = "ignore"
+
+        [policy]
+        withdrawal_disputes = true
+        redispute_after_resolve = true
+        reject_deposits_when_frozen = true
+        reject_deposits_when_locked = true
+        reject_disputes_when_locked = true
+        negative_balance = "cap"
+        minimum_balance = "10.5"
+        max_amount = "1000000"
+        rounding = "half_up"
+
+        [policy.fees]
+        account = 65535
+        withdrawal = { percent = "0.5" }
+
+        [checkpoint]
+        path = "ledger.checkpoint"
+        every_seconds = 60
+
+        [fix]
+        type = 5001
+        amount = 44
+
+        [velocity]
+        max_transactions_per_client = 100
+        window = { max_transactions = 5, seconds = 60 }
+
+        [ordering]
+        tolerance_seconds = 300
+        strict = true
+
+        [chargeback_alerts]
+        rate = 0.01
+        min_deposits = 100
+
+        [[rules]]
+        name = "large withdrawal"
+        when = { kind = "amount_over", amount = "5000", types = ["withdrawal"] }
+        action = "flag"
+
+        [performance.id_filter]
+        expected_ids = 1000000
+        false_positive_rate = 0.001
+
+        [export]
+        locked_only = true
+        clients = [1, 2]
+        sort = "client"
+        columns = ["client", "total", "open_disputes"]
+        lifetime_totals = true
+        decimal_places = 4
+        "#,
+    )?;
+
+    assert_eq!(config.input_format, Some(Format::Jsonl));
+    assert_eq!(config.output_format, None);
+    assert_eq!(config.output.as_deref(), Some("accounts.parquet"));
+    assert_eq!(config.snapshot_encoding, Some(SnapshotEncoding::Cbor));
+    assert_eq!(config.error_policy.parse, ErrorAction::Abort);
+    assert_eq!(config.error_policy.validation, ErrorAction::Warn);
+    assert_eq!(config.error_policy.ledger, ErrorAction::Collect);
+    assert_eq!(
+        config.error_policy.ledger_errors.get("insufficient_funds"),
+        Some(&ErrorAction::Ignore)
+    );
+    assert_eq!(config.policy.withdrawal_disputes, true);
+    assert_eq!(config.policy.redispute_after_resolve, true);
+    assert_eq!(config.policy.reject_deposits_when_frozen, true);
+    assert_eq!(config.policy.reject_deposits_when_locked, true);
+    assert_eq!(config.policy.reject_disputes_when_locked, true);
+    assert_eq!(config.policy.negative_balance, NegativeBalancePolicy::Cap);
+    assert_eq!(config.policy.minimum_balance, Some(dec!(10.5)));
+    assert_eq!(config.policy.max_amount, Some(dec!(1000000)));
+    assert_eq!(config.policy.rounding, Rounding::HalfUp);
+    assert_eq!(
+        config.policy.fees,
+        Some(FeePolicy {
+            account: 65535,
+            withdrawal: Some(Fee::Percent(dec!(0.5))),
+            chargeback: None,
+        })
+    );
+    assert_eq!(config.currency, Some("USD".parse()?));
+    assert_eq!(config.camt053.as_deref(), Some("statements.xml"));
+    assert_eq!(config.camt053_currency, Some("EUR".parse()?));
+    assert_eq!(
+        config.fix,
+        FixTags {
+            type_: 5001,
+            amount: 44,
+            ..FixTags::default()
+        }
+    );
+    assert_eq!(config.checkpoint.path.as_deref(), Some("ledger.checkpoint"));
+    assert_eq!(config.checkpoint.every_transactions, None);
+    assert_eq!(config.checkpoint.every_seconds, Some(60));
+    assert_eq!(config.velocity.max_transactions_per_client, Some(100));
+    assert_eq!(
+        config.velocity.window,
+        Some(VelocityWindow {
+            max_transactions: 5,
+            seconds: 60,
+        })
+    );
+    assert_eq!(
+        config.ordering,
+        Some(OrderingCheck {
+            tolerance_seconds: 300,
+            strict: true,
+        })
+    );
+    assert_eq!(
+        config.chargeback_alerts,
+        Some(AlertThreshold {
+            rate: 0.01,
+            min_deposits: 100,
+        })
+    );
+    assert_eq!(
+        config.rules,
+        vec![Rule {
+            name: "large withdrawal".to_string(),
+            when: Condition::AmountOver {
+                amount: dec!(5000),
+                types: vec![TransactionType::Withdrawal],
+            },
+            action: RuleAction::Flag,
+        }]
+    );
+    assert_eq!(config.performance.id_filter.unwrap().expected_ids, 1_000_000);
+    assert_eq!(config.export.locked_only, true);
+    assert_eq!(config.export.non_zero_only, false);
+    assert_eq!(config.export.clients, Some(vec![1, 2]));
+    assert_eq!(config.export.sort, Some(SortKey::Client));
+    assert_eq!(
+        config.export.columns.map(|spec| spec.columns().to_vec()),
+        Some(vec![Column::Client, Column::Total, Column::OpenDisputes])
+    );
+    assert_eq!(config.export.lifetime_totals, true);
+    assert_eq!(config.export.decimal_places, Some(4));
+
+    Ok(())
+}
+
+#[test]
+fn test_unknown_settings() {
+    assert!(toml::from_str::<Config>("strickt = true").is_err());
+    assert!(toml::from_str::<Config>("[error_policy]\nparse = \"explode\"").is_err());
+    assert!(toml::from_str::<Config>("[export]\ncolumns = [\"client\", \"client\"]").is_err());
+}
+
+#[test]
+fn test_negative_fees() {
+    assert!(toml::from_str::<Config>("[policy.fees]\naccount = 1\nwithdrawal = { flat = \"-1\" }").is_err());
+    assert!(toml::from_str::<Config>("[policy.fees]\naccount = 1\nchargeback = { percent = \"-0.5\" }").is_err());
+    assert!(toml::from_str::<Config>("[policy.fees]\naccount = 1\nwithdrawal = { flat = \"0\" }").is_ok());
+}
@@ -1,19 +1,141 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BinaryHeap, VecDeque};
+use std::fmt::{self, Display};
 use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::{self, FromStr};
 
 use anyhow::Result;
-use log::debug;
+use chrono::NaiveDate;
+use csv::ByteRecord;
+use log::{debug, warn};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::accounting::client::Client;
+use crate::accounting::client::{Client, ClientMetadata};
+use crate::accounting::currency::{Currency, CurrencyLedgers};
 use crate::accounting::ledger::Ledger;
+use crate::accounting::lifetime::LifetimeTotals;
+use crate::accounting::policy::Rounding;
+use crate::accounting::settlement::SettlementRecord;
+use crate::accounting::store::{ClientStore, TransactionStore};
+use crate::accounting::tenants::LedgerSet;
+use crate::accounting::timestamp::Timestamp;
 use crate::accounting::{
-    transactions::{Chargeback, Deposit, Dispute, Resolve, Transaction, Withdrawal},
-    TransactionError,
+    transactions::{
+        Accrue, Chargeback, CloseAccount, Deposit, Dispute, Freeze, Resolve, Reversal, SetCreditLimit, Transaction,
+        Unfreeze, Withdrawal,
+    },
+    ClientId, ExecutableTransaction, LedgerAccess, Map, Set, TransactionError, TxId,
 };
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[cfg(feature = "arrow")]
+pub mod arrow;
+mod avro;
+mod camt053;
+mod client_ids;
+mod fix;
+#[cfg(feature = "html")]
+mod html;
+mod jsonl;
+mod pain001;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod replay;
+pub mod rules;
+mod statement;
+mod table;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetReader;
+pub use avro::AvroReader;
+pub use client_ids::{ClientIdReader, ClientIds, ClientIdsError};
+pub use fix::{FixReader, FixTags};
+pub use jsonl::JsonlReader;
+pub use replay::{AccountMismatch, ReplayError};
+use rules::{Rule, RuleAction, RuleEngine};
+pub use statement::{StatementFormat, StatementReader};
+
+#[cfg(test)]
+#[path = "data_tests.rs"]
+mod data_tests;
+
+// Fields of a transaction, for the formats that don't have headers of their
+// own.
+const HEADERS: [&str; 6] = ["type", "client", "tx", "amount", "currency", "timestamp"];
+
+/// Supported formats, for both the transactions and the exported accounts.
+/// Parquet support needs the `parquet` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl Format {
+    /// The format matching the file's extension, if any.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Format> {
+        match path.as_ref().extension()?.to_str()? {
+            "csv" => Some(Format::Csv),
+            "jsonl" | "ndjson" => Some(Format::Jsonl),
+            "parquet" => Some(Format::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(Format::Csv),
+            "jsonl" | "ndjson" => Ok(Format::Jsonl),
+            "parquet" => Ok(Format::Parquet),
+            _ => Err(format!("unknown format {}, expected csv, jsonl or parquet", format)),
+        }
+    }
+}
+
+/// How the transactions of a message are encoded, see `process_payload`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    /// JSON lines, see `JsonlReader`.
+    #[default]
+    Json,
+    /// Avro records, see `AvroReader`.
+    Avro,
+}
+
+impl FromStr for PayloadFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "json" | "jsonl" => Ok(PayloadFormat::Json),
+            "avro" => Ok(PayloadFormat::Avro),
+            _ => Err(format!("unknown payload format {}, expected json or avro", format)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     Deposit,
@@ -21,36 +143,205 @@ pub enum TransactionType {
     Dispute,
     Resolve,
     Chargeback,
+    Reversal,
+    Freeze,
+    Unfreeze,
+    #[serde(rename = "close_account")]
+    CloseAccount,
+    #[serde(rename = "credit_limit")]
+    CreditLimit,
+    Accrue,
+    /// Closes the ledger's settlement period, see `Ledger::close_day`. It
+    /// isn't a transaction.
+    #[serde(rename = "close_day")]
+    CloseDay,
+}
+
+impl TransactionType {
+    /// The name in the type column.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Reversal => "reversal",
+            TransactionType::Freeze => "freeze",
+            TransactionType::Unfreeze => "unfreeze",
+            TransactionType::CloseAccount => "close_account",
+            TransactionType::CreditLimit => "credit_limit",
+            TransactionType::Accrue => "accrue",
+            TransactionType::CloseDay => "close_day",
+        }
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum TransactionDataError {
     #[error("transaction requires amount")]
     MissingAmount,
+    #[error("duplicate transaction id {0}")]
+    DuplicateId(TxId),
+    #[error("record is not a transaction")]
+    NotATransaction,
+    #[error("client {0} is over its transaction limit")]
+    VelocityExceeded(ClientId),
+    #[error("timestamp {timestamp} is too far before {latest}, out of time order")]
+    OutOfOrder { timestamp: Timestamp, latest: Timestamp },
+    #[error("rejected by rule {0}")]
+    RuleRejected(String),
+    #[error("currency {found} is not the ledger's currency {expected}")]
+    CurrencyMismatch { expected: Currency, found: Currency },
     #[error("{0}")]
     TransactionCreationError(#[from] TransactionError),
 }
 
-#[derive(Debug, Deserialize)]
+impl TransactionDataError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TransactionDataError::MissingAmount => "missing_amount",
+            TransactionDataError::DuplicateId(_) => "duplicate_id",
+            TransactionDataError::NotATransaction => "not_a_transaction",
+            TransactionDataError::VelocityExceeded(_) => "velocity_exceeded",
+            TransactionDataError::OutOfOrder { .. } => "out_of_order",
+            TransactionDataError::RuleRejected(_) => "rule_rejected",
+            TransactionDataError::CurrencyMismatch { .. } => "currency_mismatch",
+            TransactionDataError::TransactionCreationError(err) => err.code(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
 pub struct TransactionRecord {
     #[serde(rename = "tx")]
-    pub id: u32,
+    pub id: TxId,
     #[serde(rename = "type")]
     pub type_: TransactionType,
     #[serde(rename = "client")]
-    pub client_id: u16,
+    pub client_id: ClientId,
     #[serde(default)] // Default to `None` if the field is empty
     pub amount: Option<Decimal>,
+    /// Only used with `CurrencyLedgers`, a single `Ledger` ignores it.
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    /// Kept by the transactions with an id of their own and disputes, see
+    /// `Transaction::timestamp`.
+    #[serde(default)]
+    pub timestamp: Option<Timestamp>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ClientRecord {
     #[serde(rename = "client")]
-    pub id: u16,
+    pub id: ClientId,
     pub available: Decimal,
     pub held: Decimal,
     pub total: Decimal,
     pub locked: bool,
+    /// Only exported by `export_currencies`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    /// The client's lifetime totals, only exported if the ledger keeps them,
+    /// see `Ledger::use_lifetime_totals`. Parquet files don't have them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_deposited: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_withdrawn: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispute_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chargeback_count: Option<u64>,
+}
+
+/// A row of a clients file, see `load_client_metadata`. Only the client
+/// column is required.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ClientMetadataRecord {
+    #[serde(rename = "client")]
+    pub id: ClientId,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub external_ref: Option<String>,
+    #[serde(default)]
+    pub opened_at: Option<String>,
+}
+
+/// A row of `export_with_metadata`, a `ClientRecord` followed by the
+/// client's metadata. The client is its external id with
+/// `export_with_client_ids`.
+#[derive(Debug, Serialize)]
+struct AccountRecord<'a, C> {
+    client: C,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    display_name: Option<Cow<'a, str>>,
+    external_ref: Option<Cow<'a, str>>,
+    opened_at: Option<Cow<'a, str>>,
+}
+
+/// A `ClientRecord` with the client's external id, see
+/// `export_with_client_ids`.
+#[derive(Debug, Serialize)]
+struct ExternalClientRecord<'a> {
+    client: Cow<'a, str>,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+/// A `ClientRecord` as it's exported, with its amounts written with
+/// `ExportOptions::decimal_places`.
+#[derive(Debug, Serialize)]
+struct ExportRecord {
+    #[serde(rename = "client")]
+    id: ClientId,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<Currency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_deposited: Option<Amount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_withdrawn: Option<Amount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dispute_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chargeback_count: Option<u64>,
+}
+
+impl<'a, C> AccountRecord<'a, C> {
+    /// The metadata is borrowed from the client if it is.
+    fn new(client: Cow<'a, Client>, id: C, places: Option<Places>) -> Self {
+        let [display_name, external_ref, opened_at] = match &client {
+            Cow::Borrowed(client) => {
+                let metadata = client.metadata();
+                [&metadata.display_name, &metadata.external_ref, &metadata.opened_at]
+                    .map(|field| field.as_deref().map(Cow::Borrowed))
+            },
+            Cow::Owned(client) => {
+                let metadata = client.metadata();
+                [&metadata.display_name, &metadata.external_ref, &metadata.opened_at]
+                    .map(|field| field.clone().map(Cow::Owned))
+            },
+        };
+        AccountRecord {
+            client: id,
+            available: with_places(client.available(), places),
+            held: with_places(client.held(), places),
+            total: with_places(client.get_total(), places),
+            locked: client.locked(),
+            display_name,
+            external_ref,
+            opened_at,
+        }
+    }
 }
 
 impl From<&Client> for ClientRecord {
@@ -61,10 +352,45 @@ impl From<&Client> for ClientRecord {
             held: client.held(),
             total: client.get_total(),
             locked: client.locked(),
+            currency: None,
+            total_deposited: None,
+            total_withdrawn: None,
+            dispute_count: None,
+            chargeback_count: None,
         }
     }
 }
 
+impl ClientRecord {
+    fn of<T: TransactionStore, C: ClientStore>(ledger: &Ledger<T, C>, client: &Client) -> ClientRecord {
+        let mut record = ClientRecord::from(client);
+        record.set_lifetime_totals(ledger.lifetime_totals(client.id()));
+        record
+    }
+
+    fn with_places(self, places: Option<Places>) -> ExportRecord {
+        ExportRecord {
+            id: self.id,
+            available: with_places(self.available, places),
+            held: with_places(self.held, places),
+            total: with_places(self.total, places),
+            locked: self.locked,
+            currency: self.currency,
+            total_deposited: self.total_deposited.map(|amount| with_places(amount, places)),
+            total_withdrawn: self.total_withdrawn.map(|amount| with_places(amount, places)),
+            dispute_count: self.dispute_count,
+            chargeback_count: self.chargeback_count,
+        }
+    }
+
+    pub(crate) fn set_lifetime_totals(&mut self, totals: Option<LifetimeTotals>) {
+        self.total_deposited = totals.map(|totals| totals.deposited);
+        self.total_withdrawn = totals.map(|totals| totals.withdrawn);
+        self.dispute_count = totals.map(|totals| totals.disputes);
+        self.chargeback_count = totals.map(|totals| totals.chargebacks);
+    }
+}
+
 impl TryFrom<TransactionRecord> for Transaction {
     type Error = TransactionDataError;
 
@@ -72,7 +398,7 @@ impl TryFrom<TransactionRecord> for Transaction {
         match tx.type_ {
             TransactionType::Deposit => {
                 if let Some(amount) = tx.amount {
-                    Ok(Transaction::Deposit(Deposit::new(tx.id, tx.client_id, amount)?))
+                    Ok(Transaction::Deposit(Deposit::new(tx.id, tx.client_id, amount)?).with_timestamp(tx.timestamp))
                 } else {
                     Err(TransactionDataError::MissingAmount)
                 }
@@ -80,47 +406,2178 @@ impl TryFrom<TransactionRecord> for Transaction {
 
             TransactionType::Withdrawal => {
                 if let Some(amount) = tx.amount {
-                    Ok(Transaction::Withdrawal(Withdrawal::new(tx.id, tx.client_id, amount)?))
+                    Ok(Transaction::Withdrawal(Withdrawal::new(tx.id, tx.client_id, amount)?)
+                        .with_timestamp(tx.timestamp))
                 } else {
                     Err(TransactionDataError::MissingAmount)
                 }
             },
-            TransactionType::Dispute => Ok(Transaction::Dispute(Dispute::new(tx.id, tx.client_id))),
-            TransactionType::Resolve => Ok(Transaction::Resolve(Resolve::new(tx.id, tx.client_id))),
-            TransactionType::Chargeback => Ok(Transaction::Chargeback(Chargeback::new(tx.id, tx.client_id))),
+            // An amount makes them partial.
+            TransactionType::Dispute => match tx.amount {
+                Some(amount) => Ok(Transaction::Dispute(Dispute::with_amount(tx.id, tx.client_id, amount)?)
+                    .with_timestamp(tx.timestamp)),
+                None => Ok(Transaction::Dispute(Dispute::new(tx.id, tx.client_id)).with_timestamp(tx.timestamp)),
+            },
+            TransactionType::Resolve => match tx.amount {
+                Some(amount) => Ok(Transaction::Resolve(Resolve::with_amount(tx.id, tx.client_id, amount)?)),
+                None => Ok(Transaction::Resolve(Resolve::new(tx.id, tx.client_id))),
+            },
+            TransactionType::Chargeback => match tx.amount {
+                Some(amount) => Ok(Transaction::Chargeback(Chargeback::with_amount(
+                    tx.id,
+                    tx.client_id,
+                    amount,
+                )?)),
+                None => Ok(Transaction::Chargeback(Chargeback::new(tx.id, tx.client_id))),
+            },
+            TransactionType::Reversal => Ok(Transaction::Reversal(Reversal::new(tx.id, tx.client_id))),
+            // The tx column means nothing to these.
+            TransactionType::Freeze => Ok(Transaction::Freeze(Freeze::new(tx.client_id))),
+            TransactionType::Unfreeze => Ok(Transaction::Unfreeze(Unfreeze::new(tx.client_id))),
+            TransactionType::CloseAccount => Ok(Transaction::CloseAccount(CloseAccount::new(tx.client_id))),
+            TransactionType::CreditLimit => {
+                if let Some(limit) = tx.amount {
+                    Ok(Transaction::SetCreditLimit(SetCreditLimit::new(tx.client_id, limit)?))
+                } else {
+                    Err(TransactionDataError::MissingAmount)
+                }
+            },
+            TransactionType::Accrue => Ok(Transaction::Accrue(Accrue::new(tx.client_id))),
+            TransactionType::CloseDay => Err(TransactionDataError::NotATransaction),
         }
     }
 }
 
-pub fn process_csv(file_path: &str, ledger: &mut Ledger) -> Result<()> {
-    let file = File::open(file_path)?;
-    let mut csv_reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(file);
+/// Most accounts an export holds at once, e.g. in a parquet row group or
+/// while sorting, see `ExportOptions::sort`.
+pub const EXPORT_CHUNK_SIZE: usize = 65_536;
 
-    for record in csv_reader.deserialize::<TransactionRecord>() {
-        match record {
-            Ok(transaction) => match transaction.try_into() {
-                Ok(transaction) => {
-                    if let Err(err) = ledger.execute_transaction(transaction) {
-                        debug!("failed to execute transaction, err={}", err);
-                    }
+/// Maximum number of rejected records kept in a `ProcessingReport`.
+pub const MAX_REPORTED_ERRORS: usize = 100;
+
+/// Why a record couldn't be read.
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "parquet")]
+    #[error("{0}")]
+    Parquet(#[from] ::parquet::errors::ParquetError),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Invalid(&'static str),
+}
+
+impl ParseError {
+    /// Whether the rest of the input can't be read either, as opposed to
+    /// just this record being malformed.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            ParseError::Csv(err) => err.is_io_error(),
+            ParseError::Json(err) => err.is_io(),
+            #[cfg(feature = "parquet")]
+            ParseError::Parquet(_) => true,
+            ParseError::Io(_) => true,
+            ParseError::Invalid(_) => false,
+        }
+    }
+}
+
+/// Why a record was rejected.
+#[derive(Debug, Error)]
+pub enum RecordError {
+    #[error("failed to deserialize record, err={0}")]
+    Parse(ParseError),
+    #[error("invalid transaction, err={0}")]
+    Validation(TransactionDataError),
+    #[error("failed to execute transaction, err={0}")]
+    Ledger(TransactionError),
+}
+
+impl RecordError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RecordError::Parse(_) => "malformed_record",
+            RecordError::Validation(err) => err.code(),
+            RecordError::Ledger(err) => err.code(),
+        }
+    }
+}
+
+/// A rejected record as a JSON event, see `ProcessingOptions::error_events`.
+/// The ids are taken from the raw row, so they are missing if they couldn't
+/// be read.
+#[derive(Debug, Serialize)]
+pub struct ErrorEvent {
+    pub line: u64,
+    pub tx: Option<TxId>,
+    pub client: Option<ClientId>,
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// An executed transaction and the client's account after it, as a JSON
+/// event, see `ProcessingOptions::events`. For disputes, resolves and
+/// chargebacks the amount is the record's for partial ones, or else the
+/// disputed deposit's, if it's still stored. For accruals it's the interest
+/// credited.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEvent {
+    pub line: u64,
+    #[serde(rename = "type")]
+    pub type_: TransactionType,
+    pub client: ClientId,
+    pub tx: TxId,
+    #[serde(with = "rust_decimal::serde::str_option")]
+    pub amount: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub held: Decimal,
+    pub locked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<Timestamp>,
+}
+
+/// A row of `ProcessingOptions::balances`, a `LedgerEvent` with the
+/// client's total funds, e.g. to reconcile against a bank statement without
+/// executing the file again.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BalanceRecord {
+    pub line: u64,
+    #[serde(rename = "type")]
+    pub type_: TransactionType,
+    pub client: ClientId,
+    pub tx: TxId,
+    pub amount: Option<Decimal>,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+    pub currency: Option<Currency>,
+    pub timestamp: Option<Timestamp>,
+}
+
+impl From<&LedgerEvent> for BalanceRecord {
+    fn from(event: &LedgerEvent) -> Self {
+        BalanceRecord {
+            line: event.line,
+            type_: event.type_,
+            client: event.client,
+            tx: event.tx,
+            amount: event.amount,
+            available: event.available,
+            held: event.held,
+            total: event.available + event.held,
+            locked: event.locked,
+            currency: event.currency,
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RejectedRecord {
+    pub line: u64,
+    pub error: RecordError,
+}
+
+/// Returned (within the `anyhow::Error`) when processing stops at a rejected
+/// record, see `ErrorAction::Abort`.
+#[derive(Debug, Error)]
+#[error("processing aborted on line {line}, err={error}")]
+pub struct ProcessingAborted {
+    pub line: u64,
+    pub error: RecordError,
+}
+
+/// Outcome of processing a csv file. Repeated transactions are ignored by the
+/// ledger, so they are counted as accepted. Only the first
+/// `MAX_REPORTED_ERRORS` rejected records are kept in `errors`.
+#[derive(Debug, Default)]
+pub struct ProcessingReport {
+    /// Records left out because of `ProcessingOptions::resume_after`.
+    pub skipped: usize,
+    pub accepted: usize,
+    pub accepted_by_type: TransactionCounts,
+    pub parse_errors: usize,
+    pub validation_errors: usize,
+    pub ledger_errors: usize,
+    /// Number of rejected records by their `RecordError::code`.
+    pub rejected_by_code: BTreeMap<&'static str, usize>,
+    /// Records rejected because of
+    /// `ProcessingOptions::max_transactions_per_client` or
+    /// `ProcessingOptions::velocity_window`, whatever the error policy did
+    /// with them.
+    pub velocity_violations: usize,
+    /// Records out of time order because of `ProcessingOptions::ordering`,
+    /// whether they were only flagged or rejected.
+    pub out_of_order: usize,
+    /// Records that matched a rule with `RuleAction::Flag`.
+    pub flagged: usize,
+    /// Risk indicators by client, only kept with
+    /// `ProcessingOptions::risk_metrics`, see `export_risk`.
+    pub risk: BTreeMap<ClientId, ClientRisk>,
+    /// What every client did by day, only kept with
+    /// `ProcessingOptions::daily_activity`, see `export_daily_activity`.
+    pub daily: BTreeMap<(ClientId, NaiveDate), DailyActivity>,
+    /// What every client's transactions changed its funds by, only kept
+    /// with `ProcessingOptions::client_activity`, see `export_camt053`.
+    pub activity: BTreeMap<ClientId, ClientActivity>,
+    pub errors: Vec<RejectedRecord>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransactionCounts {
+    pub deposits: usize,
+    pub withdrawals: usize,
+    pub disputes: usize,
+    pub resolves: usize,
+    pub chargebacks: usize,
+    pub reversals: usize,
+    pub freezes: usize,
+    pub unfreezes: usize,
+    pub closures: usize,
+    pub credit_limits: usize,
+    pub accruals: usize,
+    pub day_closes: usize,
+}
+
+impl TransactionCounts {
+    fn count(&mut self, type_: TransactionType) {
+        match type_ {
+            TransactionType::Deposit => self.deposits += 1,
+            TransactionType::Withdrawal => self.withdrawals += 1,
+            TransactionType::Dispute => self.disputes += 1,
+            TransactionType::Resolve => self.resolves += 1,
+            TransactionType::Chargeback => self.chargebacks += 1,
+            TransactionType::Reversal => self.reversals += 1,
+            TransactionType::Freeze => self.freezes += 1,
+            TransactionType::Unfreeze => self.unfreezes += 1,
+            TransactionType::CloseAccount => self.closures += 1,
+            TransactionType::CreditLimit => self.credit_limits += 1,
+            TransactionType::Accrue => self.accruals += 1,
+            TransactionType::CloseDay => self.day_closes += 1,
+        }
+    }
+}
+
+/// What a client did in a run that hints at fraud or abuse.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientRisk {
+    /// Executed deposits.
+    pub deposits: usize,
+    /// Executed disputes.
+    pub disputes: usize,
+    /// Executed chargebacks.
+    pub chargebacks: usize,
+    /// Withdrawals rejected for any reason, e.g. insufficient funds.
+    pub rejected_withdrawals: usize,
+}
+
+impl ClientRisk {
+    fn count(&mut self, type_: TransactionType, accepted: bool) {
+        match (type_, accepted) {
+            (TransactionType::Deposit, true) => self.deposits += 1,
+            (TransactionType::Dispute, true) => self.disputes += 1,
+            (TransactionType::Chargeback, true) => self.chargebacks += 1,
+            (TransactionType::Withdrawal, false) => self.rejected_withdrawals += 1,
+            _ => {},
+        }
+    }
+
+    /// Disputes per deposit, 0 without deposits.
+    pub fn dispute_ratio(&self) -> f64 {
+        if self.deposits == 0 {
+            0.0
+        } else {
+            self.disputes as f64 / self.deposits as f64
+        }
+    }
+}
+
+/// What a client did on a day, by the records' timestamps. The amounts are
+/// what the records changed the client's total funds by, so fees are
+/// included and repeated transactions add nothing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DailyActivity {
+    /// Accepted records by type.
+    pub counts: TransactionCounts,
+    pub deposited: Decimal,
+    pub withdrawn: Decimal,
+    /// The change of the client's total funds, whatever changed it.
+    pub net: Decimal,
+}
+
+/// A client's transactions that changed its total funds, in the order they
+/// were executed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientActivity {
+    /// The client's total funds before the first of them.
+    pub opening: Decimal,
+    pub entries: Vec<ActivityEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActivityEntry {
+    pub line: u64,
+    pub type_: TransactionType,
+    pub tx: TxId,
+    /// What it changed the client's total funds by, fees included.
+    pub amount: Decimal,
+    pub timestamp: Option<Timestamp>,
+}
+
+impl DailyActivity {
+    /// The amounts stop at the limits of a `Decimal`, a client can move more
+    /// in a day than fits in one.
+    fn count(&mut self, type_: TransactionType, change: Decimal) {
+        self.counts.count(type_);
+        match type_ {
+            TransactionType::Deposit => self.deposited = self.deposited.saturating_add(change),
+            TransactionType::Withdrawal => self.withdrawn = self.withdrawn.saturating_sub(change),
+            _ => {},
+        }
+        self.net = self.net.saturating_add(change);
+    }
+}
+
+/// A row of `export_daily_activity`.
+#[derive(Debug, Serialize)]
+struct DailyRecord {
+    client: ClientId,
+    date: NaiveDate,
+    deposits: usize,
+    withdrawals: usize,
+    disputes: usize,
+    resolves: usize,
+    chargebacks: usize,
+    reversals: usize,
+    #[serde(with = "rust_decimal::serde::str")]
+    deposited: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    withdrawn: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    net: Decimal,
+}
+
+/// A row of `export_risk`.
+#[derive(Debug, Serialize)]
+struct RiskRecord {
+    client: ClientId,
+    deposits: usize,
+    disputes: usize,
+    chargebacks: usize,
+    rejected_withdrawals: usize,
+    dispute_ratio: f64,
+}
+
+impl ProcessingReport {
+    pub fn rejected(&self) -> usize {
+        self.parse_errors + self.validation_errors + self.ledger_errors
+    }
+
+    fn reject(&mut self, line: u64, error: RecordError, action: ErrorAction) {
+        match error {
+            RecordError::Parse(_) => self.parse_errors += 1,
+            RecordError::Validation(_) => self.validation_errors += 1,
+            RecordError::Ledger(_) => self.ledger_errors += 1,
+        }
+        *self.rejected_by_code.entry(error.code()).or_default() += 1;
+
+        if action != ErrorAction::Warn && self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(RejectedRecord { line, error });
+        }
+    }
+}
+
+/// What to do with a rejected record.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorAction {
+    /// Skip the record without a trace, it isn't counted in the report nor
+    /// written to the rejects file.
+    Ignore,
+    /// Skip the record and log it as a warning (the other actions log at
+    /// debug level). It is counted in the report, but not kept in
+    /// `ProcessingReport::errors`.
+    Warn,
+    /// Skip the record and keep it in the report.
+    #[default]
+    Collect,
+    /// Stop processing with a `ProcessingAborted` error. Whatever was
+    /// executed up to that point stays in the ledger.
+    Abort,
+}
+
+/// How each class of rejected record is handled. Ledger errors can be
+/// handled one by one, e.g. to abort on everything but ignore
+/// `TransactionError::InsufficientFunds`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorPolicy {
+    pub parse: ErrorAction,
+    pub validation: ErrorAction,
+    pub ledger: ErrorAction,
+    /// Actions for specific ledger errors by their `TransactionError::code`,
+    /// taking precedence over `ledger`.
+    pub ledger_errors: Map<String, ErrorAction>,
+}
+
+/// The amount of a stored transaction that others refer to.
+fn stored_amount<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    id: TxId,
+) -> Result<Option<Decimal>, TransactionError> {
+    Ok(match ledger.transactions.get(id)? {
+        Some(Transaction::Deposit(deposit)) => Some(deposit.amount()),
+        Some(Transaction::Reversal(reversal)) => reversal.amount(),
+        Some(Transaction::DisputedWithdrawal(withdrawal)) => Some(withdrawal.amount()),
+        _ => None,
+    })
+}
+
+// A client's funds as `f` takes them from it, 0 for unknown clients.
+fn client_funds<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    client_id: ClientId,
+    f: impl FnOnce(&Client) -> Decimal,
+) -> Result<Decimal, TransactionError> {
+    Ok(ledger.clients.get(client_id)?.as_ref().map_or(Decimal::ZERO, f))
+}
+
+impl LedgerEvent {
+    fn new<T: TransactionStore, C: ClientStore>(
+        line: u64,
+        record: &TransactionRecord,
+        ledger: &Ledger<T, C>,
+    ) -> Result<LedgerEvent, TransactionError> {
+        let amount = match record.type_ {
+            TransactionType::Deposit | TransactionType::Withdrawal => record.amount,
+            _ if record.amount.is_some() => record.amount,
+            _ => stored_amount(ledger, record.id)?,
+        };
+        let client = ledger.clients.get(record.client_id)?;
+
+        Ok(LedgerEvent {
+            line,
+            type_: record.type_,
+            client: record.client_id,
+            tx: record.id,
+            amount,
+            available: client.as_ref().map_or(Decimal::ZERO, Client::available),
+            held: client.as_ref().map_or(Decimal::ZERO, Client::held),
+            locked: client.as_ref().map_or(false, Client::locked),
+            currency: record.currency,
+            timestamp: record.timestamp,
+        })
+    }
+}
+
+impl ErrorPolicy {
+    /// The same action for every rejected record.
+    pub fn all(action: ErrorAction) -> ErrorPolicy {
+        ErrorPolicy {
+            parse: action,
+            validation: action,
+            ledger: action,
+            ledger_errors: Map::default(),
+        }
+    }
+
+    /// Abort at the first rejected record.
+    pub fn strict() -> ErrorPolicy {
+        ErrorPolicy::all(ErrorAction::Abort)
+    }
+
+    pub fn with_ledger_error(mut self, error: TransactionError, action: ErrorAction) -> ErrorPolicy {
+        self.ledger_errors.insert(error.code().to_string(), action);
+        self
+    }
+
+    pub fn action(&self, error: &RecordError) -> ErrorAction {
+        match error {
+            RecordError::Parse(_) => self.parse,
+            RecordError::Validation(_) => self.validation,
+            RecordError::Ledger(err) => self.ledger_errors.get(err.code()).copied().unwrap_or(self.ledger),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ProcessingOptions {
+    /// Every rejected row is written here as csv, as it was read plus an
+    /// `error` column with the reason it was rejected.
+    pub rejects: Option<Box<dyn Write>>,
+    /// Every rejected record is written here as an `ErrorEvent`, one JSON
+    /// object per line, instead of being logged.
+    pub error_events: Option<Box<dyn Write>>,
+    pub error_policy: ErrorPolicy,
+    /// Every executed transaction is written here as a `LedgerEvent`, one
+    /// JSON object per line. Repeated transactions, which are discarded, are
+    /// left out.
+    pub events: Option<Box<dyn Write>>,
+    /// The same transactions as `events`, written here as csv with the
+    /// client's funds after each of them, see `BalanceRecord`.
+    pub balances: Option<Box<dyn Write>>,
+    /// Skip records up to and including this line, e.g. the
+    /// `Ledger::position` of a ledger that already executed them.
+    pub resume_after: Option<u64>,
+    /// The most records a client may have executed per run, e.g. per file.
+    /// The ones after are rejected with `VelocityExceeded` instead, to
+    /// contain runaway feeds.
+    pub max_transactions_per_client: Option<usize>,
+    /// The most records a client may have executed within a time window,
+    /// going by their timestamps. The ones over it are rejected with
+    /// `VelocityExceeded` too.
+    pub velocity_window: Option<VelocityWindow>,
+    /// Checks that timestamped records arrive in time order, to catch
+    /// upstream shuffling.
+    pub ordering: Option<OrderingCheck>,
+    /// Checked against every valid record before it's executed, see
+    /// `RuleEngine`.
+    pub rules: Vec<Rule>,
+    /// Keep `ProcessingReport::risk`.
+    pub risk_metrics: bool,
+    /// Keep `ProcessingReport::daily`. Records without a timestamp are left
+    /// out of it.
+    pub daily_activity: bool,
+    /// Keep `ProcessingReport::activity`.
+    pub client_activity: bool,
+}
+
+/// At most `max_transactions` records of a client within any `seconds`, see
+/// `ProcessingOptions::velocity_window`. Records without a timestamp aren't
+/// counted.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VelocityWindow {
+    pub max_transactions: usize,
+    pub seconds: u64,
+}
+
+/// Records whose timestamp is more than `tolerance_seconds` before the latest
+/// one seen are logged, or rejected with `OutOfOrder` if `strict`. Records
+/// without a timestamp aren't checked.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrderingCheck {
+    pub tolerance_seconds: u64,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// The ledger each record is executed on, see `process_records`. A single
+/// `Ledger` only takes records in its currency, see `Ledger::use_currency`,
+/// and rejects the others with CurrencyMismatch. Records without a currency
+/// are in the ledger's. A `Ledger` with any stores takes records.
+pub trait RecordLedgers {
+    type Transactions: TransactionStore;
+    type Clients: ClientStore;
+
+    fn ledger_for(
+        &mut self,
+        record: &TransactionRecord,
+    ) -> Result<&mut Ledger<Self::Transactions, Self::Clients>, TransactionDataError>;
+}
+
+impl<T: TransactionStore, C: ClientStore> RecordLedgers for Ledger<T, C> {
+    type Transactions = T;
+    type Clients = C;
+
+    fn ledger_for(&mut self, record: &TransactionRecord) -> Result<&mut Ledger<T, C>, TransactionDataError> {
+        if let Some(found) = record.currency {
+            match self.currency() {
+                Some(expected) if expected != found => {
+                    return Err(TransactionDataError::CurrencyMismatch { expected, found })
                 },
-                Err(err) => debug!("invalid transaction, err={}", err),
-            },
-            Err(err) => debug!("failed to deserialize record, err={}", err),
+                Some(_) => {},
+                None => self.use_currency(found),
+            }
         }
+
+        Ok(self)
+    }
+}
+
+impl RecordLedgers for CurrencyLedgers {
+    type Transactions = Map<TxId, Transaction>;
+    type Clients = Map<ClientId, Client>;
+
+    fn ledger_for(&mut self, record: &TransactionRecord) -> Result<&mut Ledger, TransactionDataError> {
+        Ok(self.ledger(record.currency))
     }
+}
 
-    Ok(())
+pub fn process_csv(file_path: &str, ledger: &mut (impl RecordLedgers + ?Sized)) -> Result<ProcessingReport> {
+    process_csv_with(file_path, ledger, ProcessingOptions::default())
+}
+
+pub fn process_csv_with(
+    file_path: &str,
+    ledger: &mut (impl RecordLedgers + ?Sized),
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    process_reader(File::open(file_path)?, ledger, options)
+}
+
+pub fn process_file(
+    file_path: impl AsRef<Path>,
+    format: Format,
+    ledger: &mut (impl RecordLedgers + ?Sized),
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    with_record_reader(file_path.as_ref(), format, |reader| {
+        process_records(reader, ledger, options)
+    })
+}
+
+/// Processes a file of FIX messages, see `FixReader`.
+pub fn process_fix(
+    file_path: impl AsRef<Path>,
+    tags: FixTags,
+    ledger: &mut (impl RecordLedgers + ?Sized),
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    let mut reader = FixReader::new(BufReader::new(File::open(file_path)?), tags);
+    process_records(&mut reader, ledger, options)
+}
+
+/// Processes a bank statement as the transactions of one client, see
+/// `StatementReader`.
+pub fn process_statement(
+    file_path: impl AsRef<Path>,
+    format: StatementFormat,
+    client_id: ClientId,
+    ledger: &mut (impl RecordLedgers + ?Sized),
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    let mut reader = StatementReader::new(File::open(file_path)?, format, client_id)?;
+    process_records(&mut reader, ledger, options)
+}
+
+/// Same as `process_file`, for input with external client ids, see
+/// `ClientIdReader`.
+pub fn process_file_with_client_ids(
+    file_path: impl AsRef<Path>,
+    format: Format,
+    ledger: &mut (impl RecordLedgers + ?Sized),
+    ids: &mut ClientIds,
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    with_record_reader(file_path.as_ref(), format, |reader| {
+        process_records(&mut ClientIdReader::new(reader, ids), ledger, options)
+    })
 }
 
-pub fn export_csv(ledger: &Ledger) -> Result<()> {
-    let mut csv_writer = csv::WriterBuilder::new().from_writer(std::io::stdout());
-    for (_id, client) in ledger.clients_iter() {
-        let record: ClientRecord = client.into();
-        csv_writer.serialize(record)?;
+/// Processes the transactions of a message, e.g. of a Kafka topic. Its
+/// records are numbered from 1, like the lines of a file.
+pub fn process_payload(
+    payload: &[u8],
+    format: PayloadFormat,
+    ledger: &mut (impl RecordLedgers + ?Sized),
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    match format {
+        PayloadFormat::Json => process_records(&mut JsonlReader::new(payload), ledger, options),
+        PayloadFormat::Avro => process_records(&mut AvroReader::new(payload), ledger, options),
     }
+}
 
-    csv_writer.flush()?;
+pub fn process_reader(
+    reader: impl Read,
+    ledger: &mut (impl RecordLedgers + ?Sized),
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    process_records(&mut TransactionReader::new(reader)?, ledger, options)
+}
 
-    Ok(())
+pub fn process_records(
+    reader: &mut (impl RecordReader + ?Sized),
+    ledgers: &mut (impl RecordLedgers + ?Sized),
+    mut options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    let mut events = options.events.take().map(BufWriter::new);
+    let mut balances = options.balances.take().map(csv::Writer::from_writer);
+    let max_transactions_per_client = options.max_transactions_per_client;
+    let mut transactions_per_client: Map<ClientId, usize> = Map::default();
+    let velocity_window = options.velocity_window;
+    let mut windows: Map<ClientId, VecDeque<Timestamp>> = Map::default();
+    let mut velocity_violations = 0;
+    let ordering = options.ordering;
+    let mut latest_timestamp: Option<Timestamp> = None;
+    let mut out_of_order = 0;
+    let mut rules = RuleEngine::new(std::mem::take(&mut options.rules));
+    let mut flagged = 0;
+    let mut risk = options.risk_metrics.then(BTreeMap::new);
+    let mut daily = options.daily_activity.then(BTreeMap::new);
+    let mut activity = options.client_activity.then(BTreeMap::new);
+
+    let mut execute =
+        |line, record: &TransactionRecord, transaction: Option<Transaction>| -> Result<Result<(), RecordError>> {
+            let ledger = match ledgers.ledger_for(record) {
+                Ok(ledger) => ledger,
+                Err(err) => return Ok(Err(RecordError::Validation(err))),
+            };
+            let Some(transaction) = transaction else {
+                ledger.set_position(line);
+                ledger.close_day();
+                return Ok(Ok(()));
+            };
+
+            // Both limits are checked before the record counts towards
+            // either, so one it breaks doesn't use up the other.
+            let over_total = max_transactions_per_client.map_or(false, |max| {
+                transactions_per_client
+                    .get(&record.client_id)
+                    .map_or(false, |count| *count >= max)
+            });
+            let over_window = match (velocity_window, record.timestamp) {
+                (Some(window), Some(timestamp)) => {
+                    let seconds = i64::try_from(window.seconds).unwrap_or(i64::MAX);
+                    let recent = windows.entry(record.client_id).or_default();
+                    // Those that fell out of the window no longer count.
+                    recent.retain(|earlier| timestamp.unix().saturating_sub(earlier.unix()) < seconds);
+                    recent.len() >= window.max_transactions
+                },
+                _ => false,
+            };
+            if over_total || over_window {
+                velocity_violations += 1;
+                return Ok(Err(RecordError::Validation(TransactionDataError::VelocityExceeded(
+                    record.client_id,
+                ))));
+            }
+            if max_transactions_per_client.is_some() {
+                *transactions_per_client.entry(record.client_id).or_default() += 1;
+            }
+            if let (Some(_), Some(timestamp)) = (velocity_window, record.timestamp) {
+                windows.entry(record.client_id).or_default().push_back(timestamp);
+            }
+
+            if let (Some(ordering), Some(timestamp)) = (ordering, record.timestamp) {
+                let tolerance = i64::try_from(ordering.tolerance_seconds).unwrap_or(i64::MAX);
+                match latest_timestamp {
+                    Some(latest) if latest.unix() - timestamp.unix() > tolerance => {
+                        out_of_order += 1;
+                        if ordering.strict {
+                            return Ok(Err(RecordError::Validation(TransactionDataError::OutOfOrder {
+                                timestamp,
+                                latest,
+                            })));
+                        }
+                        warn!(
+                            "record out of time order, line={}, timestamp={}, latest={}",
+                            line, timestamp, latest
+                        );
+                    },
+                    _ => latest_timestamp = latest_timestamp.max(Some(timestamp)),
+                }
+            }
+
+            if !rules.is_empty() {
+                let mut rejected_by = None;
+                let mut freeze = false;
+                let client = ledger.clients.get(record.client_id)?;
+                for rule in rules.evaluate(record, client.as_ref()) {
+                    match rule.action {
+                        RuleAction::Reject => {
+                            rejected_by.get_or_insert_with(|| rule.name.clone());
+                        },
+                        RuleAction::Flag => {
+                            flagged += 1;
+                            warn!("flagged record, line={}, rule={}", line, rule.name);
+                        },
+                        RuleAction::Freeze => freeze = true,
+                    }
+                }
+
+                // Even if the record itself is rejected.
+                if freeze {
+                    match ledger.execute_transaction(Transaction::Freeze(Freeze::new(record.client_id))) {
+                        Ok(()) | Err(TransactionError::ClientNotFound) => {},
+                        Err(err) => return Ok(Err(RecordError::Ledger(err))),
+                    }
+                }
+                if let Some(name) = rejected_by {
+                    return Ok(Err(RecordError::Validation(TransactionDataError::RuleRejected(name))));
+                }
+            }
+
+            let with_events = events.is_some() || balances.is_some();
+            let repeated = match transaction.id().filter(|_| with_events) {
+                Some(id) => ledger.transactions.contains(id)? || ledger.is_settled(id),
+                None => false,
+            };
+
+            let available_before = (with_events && record.type_ == TransactionType::Accrue)
+                .then(|| client_funds(ledger, record.client_id, Client::available))
+                .transpose()?;
+
+            let total = |ledger: &Ledger<_, _>| client_funds(ledger, record.client_id, Client::get_total);
+            let day = record
+                .timestamp
+                .filter(|_| daily.is_some())
+                .map(|timestamp| timestamp.date());
+            let total_before = (day.is_some() || activity.is_some())
+                .then(|| total(ledger))
+                .transpose()?;
+
+            ledger.set_position(line);
+            let result = ledger.execute_transaction(transaction).map_err(RecordError::Ledger);
+
+            if let (Some(daily), Some(day), Some(total_before), Ok(())) = (&mut daily, day, total_before, &result) {
+                daily
+                    .entry((record.client_id, day))
+                    .or_insert_with(DailyActivity::default)
+                    .count(record.type_, total(ledger)? - total_before);
+            }
+
+            if let (Some(activity), Some(total_before), Ok(())) = (&mut activity, total_before, &result) {
+                let amount = total(ledger)? - total_before;
+                if !amount.is_zero() {
+                    activity
+                        .entry(record.client_id)
+                        .or_insert_with(|| ClientActivity {
+                            opening: total_before,
+                            entries: Vec::new(),
+                        })
+                        .entries
+                        .push(ActivityEntry {
+                            line,
+                            type_: record.type_,
+                            tx: record.id,
+                            amount,
+                            timestamp: record.timestamp,
+                        });
+                }
+            }
+
+            if let (true, Ok(()), false) = (with_events, &result, repeated) {
+                let mut event = LedgerEvent::new(line, record, ledger)?;
+                if let Some(available_before) = available_before {
+                    event.amount = Some(event.available - available_before);
+                }
+                if let Some(balances) = &mut balances {
+                    balances.serialize(BalanceRecord::from(&event))?;
+                }
+                if let Some(events) = &mut events {
+                    serde_json::to_writer(&mut *events, &event)?;
+                    events.write_all(b"\n")?;
+                }
+            }
+            ledger.checkpoint_if_due()?;
+
+            Ok(result)
+        };
+
+    let mut report = run(reader, options, |line, record, transaction| {
+        let is_transaction = transaction.is_some();
+        let result = execute(line, record, transaction)?;
+        if let (Some(risk), true) = (&mut risk, is_transaction) {
+            risk.entry(record.client_id)
+                .or_insert_with(ClientRisk::default)
+                .count(record.type_, result.is_ok());
+        }
+
+        Ok(result)
+    })?;
+    report.velocity_violations = velocity_violations;
+    report.out_of_order = out_of_order;
+    report.flagged = flagged;
+    report.risk = risk.unwrap_or_default();
+    report.daily = daily.unwrap_or_default();
+    report.activity = activity.unwrap_or_default();
+
+    if let Some(events) = &mut events {
+        events.flush()?;
+    }
+    if let Some(balances) = &mut balances {
+        balances.flush()?;
+    }
+
+    Ok(report)
+}
+
+/// Checks every record without executing anything: whether it can be read,
+/// whether it is a valid transaction and whether its id was already used.
+/// The report counts valid records as accepted.
+pub fn validate_csv(file_path: &str, options: ProcessingOptions) -> Result<ProcessingReport> {
+    validate_reader(File::open(file_path)?, options)
+}
+
+pub fn validate_file(
+    file_path: impl AsRef<Path>,
+    format: Format,
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    with_record_reader(file_path.as_ref(), format, |reader| validate_records(reader, options))
+}
+
+pub fn validate_reader(reader: impl Read, options: ProcessingOptions) -> Result<ProcessingReport> {
+    validate_records(&mut TransactionReader::new(reader)?, options)
+}
+
+pub fn validate_records(
+    reader: &mut (impl RecordReader + ?Sized),
+    options: ProcessingOptions,
+) -> Result<ProcessingReport> {
+    let mut ids = Set::default();
+    run(reader, options, |_line, _record, transaction| {
+        match transaction.and_then(|transaction| transaction.id()) {
+            Some(id) if !ids.insert(id) => Ok(Err(RecordError::Validation(TransactionDataError::DuplicateId(id)))),
+            _ => Ok(Ok(())),
+        }
+    })
+}
+
+// Opens the file with the reader for its format, which all have a different
+// type.
+fn with_record_reader<T>(
+    file_path: &Path,
+    format: Format,
+    f: impl FnOnce(&mut dyn RecordReader) -> Result<T>,
+) -> Result<T> {
+    match format {
+        Format::Csv => f(&mut TransactionReader::new(File::open(file_path)?)?),
+        Format::Jsonl => f(&mut JsonlReader::new(BufReader::new(File::open(file_path)?))),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => f(&mut ParquetReader::new(File::open(file_path)?)?),
+        #[cfg(not(feature = "parquet"))]
+        Format::Parquet => anyhow::bail!("parquet support is not enabled, build with the parquet feature"),
+    }
+}
+
+// Reads every record and passes the valid ones to `handle`, which either
+// accepts or rejects them. Records that aren't transactions, e.g. a
+// close_day, are passed without one. An error from `handle` itself stops
+// processing.
+fn run(
+    reader: &mut (impl RecordReader + ?Sized),
+    options: ProcessingOptions,
+    mut handle: impl FnMut(u64, &TransactionRecord, Option<Transaction>) -> Result<Result<(), RecordError>>,
+) -> Result<ProcessingReport> {
+    let mut report = ProcessingReport::default();
+
+    let mut error_events = options.error_events.map(BufWriter::new);
+    let mut rejects = match options.rejects {
+        Some(rejects) => {
+            let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(rejects);
+            let mut headers = reader.headers().clone();
+            headers.push_field(b"error");
+            writer.write_byte_record(&headers)?;
+            Some(writer)
+        },
+        None => None,
+    };
+
+    while let Some(record) = reader.read_record() {
+        let line = reader.line();
+        if options.resume_after.map_or(false, |resume_after| line <= resume_after) {
+            report.skipped += 1;
+            continue;
+        }
+
+        let error = match record {
+            Ok(record) => {
+                let type_ = record.type_;
+                let transaction = match type_ {
+                    TransactionType::CloseDay => Ok(None),
+                    _ => Transaction::try_from(record).map(Some),
+                };
+                match transaction {
+                    Ok(transaction) => match handle(line, &record, transaction)? {
+                        Ok(()) => {
+                            report.accepted += 1;
+                            report.accepted_by_type.count(type_);
+                            continue;
+                        },
+                        Err(err) => err,
+                    },
+                    Err(err) => RecordError::Validation(err),
+                }
+            },
+            Err(err) if err.is_fatal() => return Err(err.into()),
+            Err(err) => RecordError::Parse(err),
+        };
+
+        let action = options.error_policy.action(&error);
+        if action == ErrorAction::Ignore {
+            continue;
+        }
+
+        if let Some(error_events) = &mut error_events {
+            let event = ErrorEvent {
+                line,
+                tx: raw_field(reader, b"tx"),
+                client: raw_field(reader, b"client"),
+                code: error.code(),
+                message: error.to_string(),
+            };
+            serde_json::to_writer(&mut *error_events, &event)?;
+            error_events.write_all(b"\n")?;
+
+            if action == ErrorAction::Abort {
+                error_events.flush()?;
+            }
+        } else if action == ErrorAction::Warn {
+            warn!("rejected record, line={}, err={}", line, error);
+        } else {
+            debug!("rejected record, line={}, err={}", line, error);
+        }
+
+        if let Some(rejects) = &mut rejects {
+            let mut row = reader.raw_record().clone();
+            row.push_field(error.to_string().as_bytes());
+            rejects.write_byte_record(&row)?;
+
+            if action == ErrorAction::Abort {
+                rejects.flush()?;
+            }
+        }
+
+        if action == ErrorAction::Abort {
+            return Err(ProcessingAborted { line, error }.into());
+        }
+
+        report.reject(line, error, action);
+    }
+
+    if let Some(rejects) = &mut rejects {
+        rejects.flush()?;
+    }
+    if let Some(error_events) = &mut error_events {
+        error_events.flush()?;
+    }
+
+    Ok(report)
+}
+
+/// A source of `TransactionRecord`s, whatever the input format.
+pub trait RecordReader {
+    /// The next record, or `None` at the end of the input.
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>>;
+
+    /// Line (or row, for formats without lines) on which the last record
+    /// read starts.
+    fn line(&self) -> u64;
+
+    /// Names of the fields in `raw_record`.
+    fn headers(&self) -> &ByteRecord;
+
+    /// The fields of the last record read, as they were in the input (but
+    /// trimmed).
+    fn raw_record(&self) -> &ByteRecord;
+}
+
+// A field of the last record read, by its header.
+fn raw_field<T: FromStr>(reader: &(impl RecordReader + ?Sized), header: &[u8]) -> Option<T> {
+    let position = reader.headers().iter().position(|other| other == header)?;
+    str::from_utf8(reader.raw_record().get(position)?).ok()?.parse().ok()
+}
+
+/// Reads `TransactionRecord`s from csv data. Rows are read into a reused
+/// buffer and their fields parsed in place, which avoids serde's overhead for
+/// the vast majority of rows. Anything the fast path doesn't understand (e.g.
+/// an unknown column layout, or an amount in scientific notation) falls back
+/// to serde, so both paths accept the same input.
+pub struct TransactionReader<R> {
+    reader: csv::Reader<R>,
+    headers: ByteRecord,
+    columns: Option<Columns>,
+    record: ByteRecord,
+    line: u64,
+}
+
+// Position of each field in a row.
+struct Columns {
+    type_: usize,
+    client_id: usize,
+    id: usize,
+    amount: usize,
+    currency: Option<usize>,
+    timestamp: Option<usize>,
+}
+
+impl<R: Read> TransactionReader<R> {
+    pub fn new(reader: R) -> csv::Result<TransactionReader<R>> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+        let headers = reader.byte_headers()?.clone();
+
+        let position = |name: &[u8]| headers.iter().position(|header| header == name);
+        let columns = match (
+            position(b"type"),
+            position(b"client"),
+            position(b"tx"),
+            position(b"amount"),
+        ) {
+            (Some(type_), Some(client_id), Some(id), Some(amount)) => Some(Columns {
+                type_,
+                client_id,
+                id,
+                amount,
+                currency: position(b"currency"),
+                timestamp: position(b"timestamp"),
+            }),
+            _ => None,
+        };
+
+        Ok(TransactionReader {
+            reader,
+            headers,
+            columns,
+            record: ByteRecord::new(),
+            line: 0,
+        })
+    }
+
+    pub fn next_record(&mut self) -> Option<csv::Result<TransactionRecord>> {
+        self.line = self.reader.position().line();
+
+        match self.reader.read_byte_record(&mut self.record) {
+            Ok(true) => {},
+            Ok(false) => return None,
+            Err(err) => return Some(Err(err)),
+        }
+
+        if let Some(transaction) = self.parse_record() {
+            return Some(Ok(transaction));
+        }
+
+        Some(self.record.deserialize(Some(&self.headers)))
+    }
+
+    fn parse_record(&self) -> Option<TransactionRecord> {
+        let columns = self.columns.as_ref()?;
+        if self.record.len() != self.headers.len() {
+            return None;
+        }
+
+        let type_ = match &self.record[columns.type_] {
+            b"deposit" => TransactionType::Deposit,
+            b"withdrawal" => TransactionType::Withdrawal,
+            b"dispute" => TransactionType::Dispute,
+            b"resolve" => TransactionType::Resolve,
+            b"chargeback" => TransactionType::Chargeback,
+            b"reversal" => TransactionType::Reversal,
+            b"freeze" => TransactionType::Freeze,
+            b"unfreeze" => TransactionType::Unfreeze,
+            b"close_account" => TransactionType::CloseAccount,
+            b"credit_limit" => TransactionType::CreditLimit,
+            b"accrue" => TransactionType::Accrue,
+            b"close_day" => TransactionType::CloseDay,
+            _ => return None,
+        };
+
+        let amount = match &self.record[columns.amount] {
+            b"" => None,
+            amount => Some(Decimal::from_str(str::from_utf8(amount).ok()?).ok()?),
+        };
+        let currency = match columns.currency.map(|currency| &self.record[currency]) {
+            None | Some(b"") => None,
+            Some(currency) => Some(str::from_utf8(currency).ok()?.parse().ok()?),
+        };
+        let timestamp = match columns.timestamp.map(|timestamp| &self.record[timestamp]) {
+            None | Some(b"") => None,
+            Some(timestamp) => Some(str::from_utf8(timestamp).ok()?.parse().ok()?),
+        };
+
+        Some(TransactionRecord {
+            id: str::from_utf8(&self.record[columns.id]).ok()?.parse().ok()?,
+            type_,
+            client_id: str::from_utf8(&self.record[columns.client_id]).ok()?.parse().ok()?,
+            amount,
+            currency,
+            timestamp,
+        })
+    }
+}
+
+impl<R: Read> RecordReader for TransactionReader<R> {
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>> {
+        Some(self.next_record()?.map_err(ParseError::from))
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn headers(&self) -> &ByteRecord {
+        &self.headers
+    }
+
+    fn raw_record(&self) -> &ByteRecord {
+        &self.record
+    }
+}
+
+impl<R: Read> Iterator for TransactionReader<R> {
+    type Item = csv::Result<TransactionRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record()
+    }
+}
+
+/// What `export_with` writes. The defaults are what `export` writes: every
+/// account, without metadata.
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions<'a> {
+    /// See `export_with_metadata`.
+    pub with_metadata: bool,
+    /// See `export_with_client_ids`.
+    pub client_ids: Option<&'a ClientIds>,
+    /// Only the locked accounts.
+    pub locked_only: bool,
+    /// Only the accounts whose total isn't 0.
+    pub non_zero_only: bool,
+    /// Only the accounts of these clients, by their own id.
+    pub clients: Option<Set<ClientId>>,
+    /// Without one the accounts are in the order of the ledger's map, which
+    /// changes from run to run. Sorting goes through the clients once per
+    /// `EXPORT_CHUNK_SIZE` of them, so that it holds no more ids than that.
+    pub sort: Option<SortKey>,
+    /// The columns to write instead of those of a `ClientRecord`. The
+    /// metadata has columns of its own, so it can't also be added with
+    /// `with_metadata`.
+    pub columns: Option<ExportSpec>,
+    /// Writes the amounts with exactly this many decimal places, e.g. 4 for
+    /// `5.0000`, rather than with those they have. More are rounded the way
+    /// of the ledger's `LedgerPolicy::rounding`.
+    pub decimal_places: Option<u32>,
+}
+
+/// The order of exported accounts, see `ExportOptions::sort`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Ascending client ids.
+    Client,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        match key {
+            "client" => Ok(SortKey::Client),
+            _ => Err(format!("unknown sort key {}, expected client", key)),
+        }
+    }
+}
+
+/// A column of an account export, see `ExportSpec`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Client,
+    Available,
+    Held,
+    Total,
+    Locked,
+    Closed,
+    /// Only filled in by `export_currencies_with`, a single `Ledger` has no
+    /// currency of its own.
+    Currency,
+    /// The number of the client's deposits under dispute.
+    OpenDisputes,
+    /// The lifetime totals, empty unless the ledger keeps them, see
+    /// `Ledger::use_lifetime_totals`.
+    TotalDeposited,
+    TotalWithdrawn,
+    DisputeCount,
+    ChargebackCount,
+    DisplayName,
+    ExternalRef,
+    OpenedAt,
+}
+
+impl Column {
+    const ALL: [Column; 15] = [
+        Column::Client,
+        Column::Available,
+        Column::Held,
+        Column::Total,
+        Column::Locked,
+        Column::Closed,
+        Column::Currency,
+        Column::OpenDisputes,
+        Column::TotalDeposited,
+        Column::TotalWithdrawn,
+        Column::DisputeCount,
+        Column::ChargebackCount,
+        Column::DisplayName,
+        Column::ExternalRef,
+        Column::OpenedAt,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Client => "client",
+            Column::Available => "available",
+            Column::Held => "held",
+            Column::Total => "total",
+            Column::Locked => "locked",
+            Column::Closed => "closed",
+            Column::Currency => "currency",
+            Column::OpenDisputes => "open_disputes",
+            Column::TotalDeposited => "total_deposited",
+            Column::TotalWithdrawn => "total_withdrawn",
+            Column::DisputeCount => "dispute_count",
+            Column::ChargebackCount => "chargeback_count",
+            Column::DisplayName => "display_name",
+            Column::ExternalRef => "external_ref",
+            Column::OpenedAt => "opened_at",
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Column::ALL
+            .into_iter()
+            .find(|column| column.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Column::ALL.iter().map(Column::name).collect();
+                format!("unknown column {}, expected one of {}", name, names.join(", "))
+            })
+    }
+}
+
+/// The columns of an account export and their order, e.g. for a loader with
+/// a fixed schema. Read from a comma separated list of names like
+/// `client,available,total`, or a list of them in a config file.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<Column>")]
+pub struct ExportSpec {
+    columns: Vec<Column>,
+}
+
+impl ExportSpec {
+    /// Fails without columns or with the same column twice.
+    pub fn new(columns: Vec<Column>) -> Result<ExportSpec, String> {
+        if columns.is_empty() {
+            return Err("no columns to export".to_string());
+        }
+        if let Some((i, column)) = columns
+            .iter()
+            .enumerate()
+            .find(|(i, column)| columns[..*i].contains(column))
+        {
+            return Err(format!("column {} is exported twice, at {}", column.name(), i + 1));
+        }
+
+        Ok(ExportSpec { columns })
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    // Counted in one go rather than per client, and only when needed.
+    fn open_disputes<T: TransactionStore, C: ClientStore>(
+        &self,
+        ledger: &Ledger<T, C>,
+    ) -> Result<Map<ClientId, usize>, TransactionError> {
+        let mut open_disputes = Map::default();
+        if self.columns.contains(&Column::OpenDisputes) {
+            ledger.for_each_open_dispute(|dispute| *open_disputes.entry(dispute.client).or_default() += 1)?;
+        }
+
+        Ok(open_disputes)
+    }
+}
+
+impl TryFrom<Vec<Column>> for ExportSpec {
+    type Error = String;
+
+    fn try_from(columns: Vec<Column>) -> Result<Self, Self::Error> {
+        ExportSpec::new(columns)
+    }
+}
+
+impl FromStr for ExportSpec {
+    type Err = String;
+
+    fn from_str(columns: &str) -> Result<Self, Self::Err> {
+        ExportSpec::new(
+            columns
+                .split(',')
+                .map(|name| name.trim().parse())
+                .collect::<Result<_, _>>()?,
+        )
+    }
+}
+
+/// A row of an export with an `ExportSpec`, with its columns in order.
+struct ColumnsRecord<'a> {
+    columns: &'a [Column],
+    client: Cow<'a, Client>,
+    client_ids: Option<&'a ClientIds>,
+    currency: Option<Currency>,
+    open_disputes: usize,
+    lifetime_totals: Option<LifetimeTotals>,
+    decimal_places: Option<Places>,
+}
+
+impl Serialize for ColumnsRecord<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let client = &self.client;
+        let metadata = client.metadata();
+        let places = self.decimal_places;
+        let mut record = serializer.serialize_struct("Account", self.columns.len())?;
+        for column in self.columns {
+            let name = column.name();
+            match column {
+                Column::Client => match self.client_ids {
+                    Some(ids) => record.serialize_field(name, &ids.display(client.id()))?,
+                    None => record.serialize_field(name, &client.id())?,
+                },
+                Column::Available => record.serialize_field(name, &with_places(client.available(), places))?,
+                Column::Held => record.serialize_field(name, &with_places(client.held(), places))?,
+                Column::Total => record.serialize_field(name, &with_places(client.get_total(), places))?,
+                Column::Locked => record.serialize_field(name, &client.locked())?,
+                Column::Closed => record.serialize_field(name, &client.closed())?,
+                Column::Currency => record.serialize_field(name, &self.currency)?,
+                Column::OpenDisputes => record.serialize_field(name, &self.open_disputes)?,
+                Column::TotalDeposited => record.serialize_field(
+                    name,
+                    &self.lifetime_totals.map(|totals| with_places(totals.deposited, places)),
+                )?,
+                Column::TotalWithdrawn => record.serialize_field(
+                    name,
+                    &self.lifetime_totals.map(|totals| with_places(totals.withdrawn, places)),
+                )?,
+                Column::DisputeCount => {
+                    record.serialize_field(name, &self.lifetime_totals.map(|totals| totals.disputes))?
+                },
+                Column::ChargebackCount => {
+                    record.serialize_field(name, &self.lifetime_totals.map(|totals| totals.chargebacks))?
+                },
+                Column::DisplayName => record.serialize_field(name, &metadata.display_name)?,
+                Column::ExternalRef => record.serialize_field(name, &metadata.external_ref)?,
+                Column::OpenedAt => record.serialize_field(name, &metadata.opened_at)?,
+            }
+        }
+
+        record.end()
+    }
+}
+
+impl ExportOptions<'_> {
+    fn includes(&self, client: &Client) -> bool {
+        (!self.locked_only || client.locked())
+            && (!self.non_zero_only || !client.get_total().is_zero())
+            && self
+                .clients
+                .as_ref()
+                .map_or(true, |clients| clients.contains(&client.id()))
+    }
+}
+
+pub fn export_csv<T: TransactionStore, C: ClientStore>(ledger: &Ledger<T, C>) -> Result<()> {
+    export(ledger, Format::Csv, io::stdout())
+}
+
+/// Same as `export_csv`, with the accounts chosen by `options`.
+pub fn export_csv_with<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    options: &ExportOptions,
+) -> Result<()> {
+    export_with(ledger, options, Format::Csv, io::stdout())
+}
+
+pub fn export<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    format: Format,
+    writer: impl Write + Send,
+) -> Result<()> {
+    export_with(ledger, &ExportOptions::default(), format, writer)
+}
+
+/// Exports the accounts chosen by `options`, with the columns it asks for.
+/// Parquet is only supported without metadata nor external client ids. If
+/// the store fails to read a client, the export stops there with its error.
+pub fn export_with<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    options: &ExportOptions,
+    format: Format,
+    writer: impl Write + Send,
+) -> Result<()> {
+    if options.columns.is_some() {
+        anyhow::ensure!(
+            !options.with_metadata,
+            "accounts with metadata can't be exported with chosen columns, choose the metadata columns instead"
+        );
+        anyhow::ensure!(
+            format != Format::Parquet,
+            "accounts with chosen columns can't be written as parquet"
+        );
+    }
+    let open_disputes = match &options.columns {
+        Some(spec) => spec.open_disputes(ledger)?,
+        None => Map::default(),
+    };
+
+    let sorted = options
+        .sort
+        .map(|SortKey::Client| SortedClients::new(&ledger.clients, EXPORT_CHUNK_SIZE));
+    let unsorted = sorted.is_none().then(|| ledger.clients.clients());
+    let clients = sorted.into_iter().flatten().chain(unsorted.into_iter().flatten());
+    let places = options.decimal_places.map(|places| Places {
+        places,
+        rounding: ledger.policy().rounding,
+    });
+
+    write_clients(clients, |clients| {
+        let clients = clients.filter(|client| options.includes(client));
+        if let Some(spec) = &options.columns {
+            let records = clients.map(|client| ColumnsRecord {
+                columns: spec.columns(),
+                client_ids: options.client_ids,
+                currency: None,
+                open_disputes: open_disputes.get(&client.id()).copied().unwrap_or_default(),
+                lifetime_totals: ledger.lifetime_totals(client.id()),
+                decimal_places: places,
+                client,
+            });
+            return write_rows(records, format, writer);
+        }
+
+        match options.client_ids {
+            Some(ids) => write_with_client_ids(clients, ids, options.with_metadata, places, format, writer),
+            None if options.with_metadata => write_with_metadata(clients, places, format, writer),
+            None => write_accounts(
+                clients.map(|client| ClientRecord::of(ledger, &client).with_places(places)),
+                format,
+                writer,
+            ),
+        }
+    })
+}
+
+// Passes the clients on to `write` until the store fails to read one, whose
+// error is then returned rather than what `write` returns.
+fn write_clients<'a>(
+    clients: impl Iterator<Item = Result<Cow<'a, Client>, TransactionError>>,
+    write: impl FnOnce(&mut dyn Iterator<Item = Cow<'a, Client>>) -> Result<()>,
+) -> Result<()> {
+    let mut failure = None;
+    let written = write(&mut clients.map_while(|client| client.map_err(|err| failure = Some(err)).ok()));
+
+    match failure {
+        Some(err) => Err(err.into()),
+        None => written,
+    }
+}
+
+// How many decimal places exported amounts have, see
+// `ExportOptions::decimal_places`, and how they're rounded to them.
+#[derive(Clone, Copy, Debug)]
+struct Places {
+    places: u32,
+    rounding: Rounding,
+}
+
+// An exported amount. It's written with exactly `places` decimal places if
+// given, even those a `Decimal` can't hold, e.g. 4 for `Decimal::MAX`.
+#[derive(Clone, Copy, Debug)]
+struct Amount {
+    amount: Decimal,
+    places: Option<u32>,
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The amount is rounded to `places` already, it's only missing
+        // trailing zeros.
+        let scale = self.amount.scale();
+        match self.places {
+            Some(places) if places > scale => {
+                let point = if scale == 0 { "." } else { "" };
+                write!(f, "{}{}{}", self.amount, point, "0".repeat((places - scale) as usize))
+            },
+            _ => write!(f, "{}", self.amount),
+        }
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// The amount rounded to `places`, if given.
+fn with_places(amount: Decimal, places: Option<Places>) -> Amount {
+    match places {
+        Some(Places { places, rounding }) => Amount {
+            amount: rounding.round(amount, places),
+            places: Some(places),
+        },
+        None => Amount { amount, places: None },
+    }
+}
+
+/// Exports the accounts like `export_with`, as a table with aligned columns
+/// for people rather than programs. It's only written once all of the
+/// accounts are, to know how wide the columns are. With `color` the locked
+/// accounts are red.
+pub fn export_table<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    options: &ExportOptions,
+    color: bool,
+    writer: impl Write,
+) -> Result<()> {
+    let mut csv = Vec::new();
+    export_with(ledger, options, Format::Csv, &mut csv)?;
+    table::write_table(&csv, color, writer)
+}
+
+/// Exports a page for people to open in a browser: a summary of the ledger
+/// and of the run, the accounts like `export_with` and the locked ones among
+/// them. Like `export_table` it's only written once all of the accounts are.
+#[cfg(feature = "html")]
+pub fn export_html<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    report: &ProcessingReport,
+    options: &ExportOptions,
+    writer: impl Write,
+) -> Result<()> {
+    let mut accounts = Vec::new();
+    export_with(ledger, options, Format::Csv, &mut accounts)?;
+    html::write_report(&summary(ledger, report), &accounts, writer)
+}
+
+#[cfg(not(feature = "html"))]
+pub fn export_html<T: TransactionStore, C: ClientStore>(
+    _ledger: &Ledger<T, C>,
+    _report: &ProcessingReport,
+    _options: &ExportOptions,
+    _writer: impl Write,
+) -> Result<()> {
+    anyhow::bail!("html reports are not enabled, build with the html feature")
+}
+
+/// Exports the accounts like `export_with` to the first sheet of an XLSX
+/// workbook, followed by a sheet with a summary of the ledger and of the run
+/// if there's a report. A sheet has at most 1,048,576 rows, so there can't
+/// be more accounts than one less, and it's only written once all of them
+/// are.
+#[cfg(feature = "xlsx")]
+pub fn export_xlsx<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    options: &ExportOptions,
+    report: Option<&ProcessingReport>,
+    writer: impl Write,
+) -> Result<()> {
+    let mut accounts = Vec::new();
+    export_with(ledger, options, Format::Csv, &mut accounts)?;
+    let mut sheets = vec![xlsx::Sheet {
+        name: "Accounts",
+        csv: &accounts,
+    }];
+
+    let mut summary_csv = Vec::new();
+    if let Some(report) = report {
+        let mut csv_writer = csv::WriterBuilder::new().from_writer(&mut summary_csv);
+        csv_writer.write_record(["figure", "value"])?;
+        for (name, value) in summary(ledger, report) {
+            csv_writer.write_record([name, &value])?;
+        }
+        csv_writer.flush()?;
+        drop(csv_writer);
+
+        sheets.push(xlsx::Sheet {
+            name: "Summary",
+            csv: &summary_csv,
+        });
+    }
+
+    xlsx::write_workbook(&sheets, writer)
+}
+
+#[cfg(not(feature = "xlsx"))]
+pub fn export_xlsx<T: TransactionStore, C: ClientStore>(
+    _ledger: &Ledger<T, C>,
+    _options: &ExportOptions,
+    _report: Option<&ProcessingReport>,
+    _writer: impl Write,
+) -> Result<()> {
+    anyhow::bail!("xlsx workbooks are not enabled, build with the xlsx feature")
+}
+
+// The figures at the top of reports for people.
+#[cfg(any(feature = "html", feature = "xlsx"))]
+fn summary<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    report: &ProcessingReport,
+) -> [(&'static str, String); 8] {
+    let stats = ledger.stats();
+    [
+        ("Accounts", stats.clients.to_string()),
+        ("Locked accounts", stats.locked_clients.to_string()),
+        ("Available funds", stats.total_available.to_string()),
+        ("Held funds", stats.total_held.to_string()),
+        ("Open disputes", stats.open_disputes.to_string()),
+        ("Accepted records", report.accepted.to_string()),
+        ("Rejected records", report.rejected().to_string()),
+        ("Flagged records", report.flagged.to_string()),
+    ]
+}
+
+/// The clients of a store by ascending id, a chunk at a time: every chunk is
+/// the smallest ids after the previous one, found by going through all of
+/// them. A client the store fails to read ends it with the error.
+struct SortedClients<'a, C> {
+    clients: &'a C,
+    chunk_size: usize,
+    chunk: std::vec::IntoIter<Cow<'a, Client>>,
+    after: Option<ClientId>,
+    done: bool,
+}
+
+// Orders the clients of a chunk by id.
+struct ById<'a>(Cow<'a, Client>);
+
+impl PartialEq for ById<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id() == other.0.id()
+    }
+}
+
+impl Eq for ById<'_> {}
+
+impl PartialOrd for ById<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ById<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.id().cmp(&other.0.id())
+    }
+}
+
+impl<'a, C: ClientStore> SortedClients<'a, C> {
+    fn new(clients: &'a C, chunk_size: usize) -> SortedClients<'a, C> {
+        SortedClients {
+            clients,
+            chunk_size,
+            chunk: Vec::new().into_iter(),
+            after: None,
+            done: false,
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<(), TransactionError> {
+        let clients: &'a C = self.clients;
+        let mut smallest = BinaryHeap::with_capacity(self.chunk_size + 1);
+        for client in clients.clients() {
+            let client = client?;
+            if self.after.map_or(true, |after| client.id() > after) {
+                smallest.push(ById(client));
+                if smallest.len() > self.chunk_size {
+                    smallest.pop();
+                }
+            }
+        }
+
+        let chunk: Vec<Cow<Client>> = smallest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|ById(client)| client)
+            .collect();
+        self.done = chunk.len() < self.chunk_size;
+        self.after = chunk.last().map(|client| client.id()).or(self.after);
+        self.chunk = chunk.into_iter();
+
+        Ok(())
+    }
+}
+
+impl<'a, C: ClientStore> Iterator for SortedClients<'a, C> {
+    type Item = Result<Cow<'a, Client>, TransactionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(client) = self.chunk.next() {
+                return Some(Ok(client));
+            }
+            if self.done {
+                return None;
+            }
+
+            if let Err(err) = self.next_chunk() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Exports the accounts like `export`, followed by the metadata of every
+/// client, empty where it has none. Parquet isn't supported.
+pub fn export_with_metadata<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    format: Format,
+    writer: impl Write,
+) -> Result<()> {
+    write_clients(ledger.clients.clients(), |clients| {
+        write_with_metadata(clients, None, format, writer)
+    })
+}
+
+fn write_with_metadata<'a>(
+    clients: impl Iterator<Item = Cow<'a, Client>>,
+    places: Option<Places>,
+    format: Format,
+    writer: impl Write,
+) -> Result<()> {
+    anyhow::ensure!(
+        format != Format::Parquet,
+        "accounts with metadata can't be written as parquet"
+    );
+
+    write_rows(
+        clients.map(|client| {
+            let id = client.id();
+            AccountRecord::new(client, id, places)
+        }),
+        format,
+        writer,
+    )
+}
+
+/// Exports the accounts like `export`, or `export_with_metadata`, with the
+/// external id of every client in the client column, see `ClientIds`.
+/// Clients without one keep their own id. Parquet isn't supported.
+pub fn export_with_client_ids<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    ids: &ClientIds,
+    with_metadata: bool,
+    format: Format,
+    writer: impl Write,
+) -> Result<()> {
+    write_clients(ledger.clients.clients(), |clients| {
+        write_with_client_ids(clients, ids, with_metadata, None, format, writer)
+    })
+}
+
+fn write_with_client_ids<'a>(
+    clients: impl Iterator<Item = Cow<'a, Client>>,
+    ids: &ClientIds,
+    with_metadata: bool,
+    places: Option<Places>,
+    format: Format,
+    writer: impl Write,
+) -> Result<()> {
+    anyhow::ensure!(
+        format != Format::Parquet,
+        "accounts with external client ids can't be written as parquet"
+    );
+
+    if with_metadata {
+        write_rows(
+            clients.map(|client| {
+                let id = ids.display(client.id());
+                AccountRecord::new(client, id, places)
+            }),
+            format,
+            writer,
+        )
+    } else {
+        let records = clients.map(|client| ExternalClientRecord {
+            client: ids.display(client.id()),
+            available: with_places(client.available(), places),
+            held: with_places(client.held(), places),
+            total: with_places(client.get_total(), places),
+            locked: client.locked(),
+        });
+        write_rows(records, format, writer)
+    }
+}
+
+// Writes csv or jsonl rows, for the exports that can't be written as
+// parquet.
+fn write_rows(records: impl Iterator<Item = impl Serialize>, format: Format, writer: impl Write) -> Result<()> {
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::WriterBuilder::new().from_writer(writer);
+            for record in records {
+                csv_writer.serialize(record)?;
+            }
+
+            csv_writer.flush()?;
+        },
+        Format::Jsonl => jsonl::write_records(records, writer)?,
+        Format::Parquet => anyhow::bail!("parquet isn't supported"),
+    }
+
+    Ok(())
+}
+
+/// Exports the accounts of every currency, with a currency column, ordered
+/// by currency. Parquet isn't supported.
+pub fn export_currencies(ledgers: &CurrencyLedgers, format: Format, writer: impl Write + Send) -> Result<()> {
+    anyhow::ensure!(
+        format != Format::Parquet,
+        "accounts in several currencies can't be written as parquet"
+    );
+
+    let records = ledgers.iter().flat_map(|(currency, ledger)| {
+        ledger.clients_iter().map(move |(_id, client)| {
+            ClientRecord {
+                currency: Some(currency),
+                ..ClientRecord::of(ledger, client)
+            }
+            .with_places(None)
+        })
+    });
+    write_accounts(records, format, writer)
+}
+
+/// Exports the accounts like `export_currencies`, with the columns of the
+/// spec. Parquet isn't supported.
+pub fn export_currencies_with(
+    ledgers: &CurrencyLedgers,
+    spec: &ExportSpec,
+    format: Format,
+    writer: impl Write,
+) -> Result<()> {
+    anyhow::ensure!(
+        format != Format::Parquet,
+        "accounts with chosen columns can't be written as parquet"
+    );
+
+    let open_disputes = ledgers
+        .iter()
+        .map(|(_currency, ledger)| spec.open_disputes(ledger))
+        .collect::<Result<Vec<_>, _>>()?;
+    let records = ledgers
+        .iter()
+        .zip(open_disputes)
+        .flat_map(|((currency, ledger), open_disputes)| {
+            ledger.clients_iter().map(move |(_id, client)| ColumnsRecord {
+                columns: spec.columns(),
+                client: Cow::Borrowed(client),
+                client_ids: None,
+                currency: Some(currency),
+                open_disputes: open_disputes.get(&client.id()).copied().unwrap_or_default(),
+                lifetime_totals: ledger.lifetime_totals(client.id()),
+                decimal_places: None,
+            })
+        });
+    write_rows(records, format, writer)
+}
+
+fn write_accounts(
+    records: impl Iterator<Item = ExportRecord>,
+    format: Format,
+    writer: impl Write + Send,
+) -> Result<()> {
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::WriterBuilder::new().from_writer(writer);
+            for record in records {
+                csv_writer.serialize(record)?;
+            }
+
+            csv_writer.flush()?;
+        },
+        Format::Jsonl => jsonl::write_records(records, writer)?,
+        #[cfg(feature = "parquet")]
+        Format::Parquet => self::parquet::write_records(records, writer)?,
+        #[cfg(not(feature = "parquet"))]
+        Format::Parquet => anyhow::bail!("parquet support is not enabled, build with the parquet feature"),
+    }
+
+    Ok(())
+}
+
+/// Writes the risk indicators of a run, see `ProcessingOptions::risk_metrics`,
+/// one row per client.
+pub fn export_risk(risk: &BTreeMap<ClientId, ClientRisk>, format: Format, writer: impl Write) -> Result<()> {
+    let records = risk.iter().map(|(client, risk)| RiskRecord {
+        client: *client,
+        deposits: risk.deposits,
+        disputes: risk.disputes,
+        chargebacks: risk.chargebacks,
+        rejected_withdrawals: risk.rejected_withdrawals,
+        dispute_ratio: risk.dispute_ratio(),
+    });
+
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::WriterBuilder::new().from_writer(writer);
+            for record in records {
+                csv_writer.serialize(record)?;
+            }
+
+            csv_writer.flush()?;
+        },
+        Format::Jsonl => jsonl::write_records(records, writer)?,
+        Format::Parquet => anyhow::bail!("risk reports can't be written as parquet"),
+    }
+
+    Ok(())
+}
+
+/// Writes what every client did by day, ordered by client and date, see
+/// `ProcessingOptions::daily_activity`.
+pub fn export_daily_activity(
+    daily: &BTreeMap<(ClientId, NaiveDate), DailyActivity>,
+    format: Format,
+    writer: impl Write,
+) -> Result<()> {
+    let records = daily.iter().map(|((client, date), activity)| DailyRecord {
+        client: *client,
+        date: *date,
+        deposits: activity.counts.deposits,
+        withdrawals: activity.counts.withdrawals,
+        disputes: activity.counts.disputes,
+        resolves: activity.counts.resolves,
+        chargebacks: activity.counts.chargebacks,
+        reversals: activity.counts.reversals,
+        deposited: activity.deposited,
+        withdrawn: activity.withdrawn,
+        net: activity.net,
+    });
+
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::WriterBuilder::new().from_writer(writer);
+            for record in records {
+                csv_writer.serialize(record)?;
+            }
+
+            csv_writer.flush()?;
+        },
+        Format::Jsonl => jsonl::write_records(records, writer)?,
+        Format::Parquet => anyhow::bail!("daily reports can't be written as parquet"),
+    }
+
+    Ok(())
+}
+
+/// Writes a camt.053 bank to customer statement of every client of the
+/// ledger, ordered by client id, with its activity of the run, see
+/// `ProcessingOptions::client_activity`, and its opening and closing
+/// balances. A client without activity opens with what it closes with.
+/// The ledger doesn't know its currency, so it's given.
+pub fn export_camt053<T: TransactionStore, C: ClientStore>(
+    ledger: &Ledger<T, C>,
+    activity: &BTreeMap<ClientId, ClientActivity>,
+    currency: Currency,
+    created: Timestamp,
+    writer: impl Write,
+) -> Result<()> {
+    let clients = SortedClients::new(&ledger.clients, EXPORT_CHUNK_SIZE);
+    write_clients(clients, |clients| {
+        let statements = clients.map(|client| {
+            let activity = activity.get(&client.id());
+            camt053::Statement {
+                client: client.id(),
+                opening: activity.map_or(client.get_total(), |activity| activity.opening),
+                closing: client.get_total(),
+                available: client.available(),
+                entries: activity.map_or(&[][..], |activity| &activity.entries),
+            }
+        });
+
+        camt053::write_statements(statements, currency, created, writer)
+    })
+}
+
+/// Writes the records of closed settlement periods, see
+/// `Ledger::take_settlements`.
+pub fn export_settlements(records: &[SettlementRecord], format: Format, writer: impl Write) -> Result<()> {
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::WriterBuilder::new().from_writer(writer);
+            for record in records {
+                csv_writer.serialize(record)?;
+            }
+
+            csv_writer.flush()?;
+        },
+        Format::Jsonl => jsonl::write_records(records.iter(), writer)?,
+        Format::Parquet => anyhow::bail!("settlements can't be written as parquet"),
+    }
+
+    Ok(())
+}
+
+/// Exports the accounts of every tenant to a file of its own in `dir`,
+/// named after the tenant, e.g. `acme.csv`. Tenant ids that aren't plain file
+/// names are refused.
+pub fn export_tenants(ledgers: &LedgerSet, dir: impl AsRef<Path>, format: Format) -> Result<()> {
+    for (tenant, ledger) in ledgers.iter() {
+        anyhow::ensure!(
+            Path::new(tenant).file_name().map_or(false, |name| name == tenant),
+            "tenant {} can't be used as a file name",
+            tenant
+        );
+
+        let path = dir.as_ref().join(format!("{}.{}", tenant, format.extension()));
+        export(ledger, format, BufWriter::new(File::create(path)?))?;
+    }
+
+    Ok(())
+}
+
+/// Sets the metadata of the clients in a clients file, see
+/// `Ledger::set_client_metadata`. Returns how many there were.
+pub fn load_client_metadata<T: TransactionStore, C: ClientStore>(
+    ledger: &mut Ledger<T, C>,
+    reader: impl Read,
+    format: Format,
+) -> Result<usize> {
+    let records: Vec<ClientMetadataRecord> = match format {
+        Format::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader)
+            .deserialize()
+            .collect::<csv::Result<_>>()?,
+        Format::Jsonl => serde_json::Deserializer::from_reader(reader)
+            .into_iter()
+            .collect::<serde_json::Result<_>>()?,
+        Format::Parquet => anyhow::bail!("reading clients from parquet is not supported"),
+    };
+
+    let count = records.len();
+    for record in records {
+        let metadata = ClientMetadata {
+            display_name: record.display_name,
+            external_ref: record.external_ref,
+            opened_at: record.opened_at,
+        };
+        ledger.set_client_metadata(record.id, metadata)?;
+    }
+
+    Ok(count)
+}
+
+/// Reads accounts written by `export`, e.g. to check a ledger against them
+/// with `Ledger::verify_accounts`.
+pub fn read_accounts(reader: impl Read, format: Format) -> Result<Vec<ClientRecord>> {
+    match format {
+        Format::Csv => Ok(csv::Reader::from_reader(reader)
+            .deserialize()
+            .collect::<csv::Result<_>>()?),
+        Format::Jsonl => Ok(serde_json::Deserializer::from_reader(reader)
+            .into_iter()
+            .collect::<serde_json::Result<_>>()?),
+        Format::Parquet => anyhow::bail!("reading accounts from parquet is not supported"),
+    }
 }
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Decimal128Array, RecordBatch, StringArray, TimestampSecondArray, UInt64Array,
+};
+use arrow_schema::{Field, Schema};
+use rust_decimal::Decimal;
+
+use crate::accounting::ledger::Ledger;
+use crate::accounting::store::{ClientStore, TransactionStore};
+use crate::accounting::transactions::Transaction;
+use crate::accounting::{ClientId, LedgerAccess, TxId};
+
+#[cfg(test)]
+#[path = "arrow_tests.rs"]
+mod arrow_tests;
+
+#[cfg(not(feature = "wide-ids"))]
+type ClientIdArray = arrow_array::UInt16Array;
+#[cfg(feature = "wide-ids")]
+type ClientIdArray = arrow_array::UInt32Array;
+
+/// Most digits a Decimal128 column can have.
+const MAX_DIGITS: u8 = 38;
+
+/// The accounts of the ledger as an Arrow record batch, by ascending client
+/// id, with the columns of `export`: client, available, held, total and
+/// locked. The amounts are Decimal128 with the ledger's precision as their
+/// scale, see `LedgerPolicy::precision`, or more if an amount has more
+/// decimal places.
+pub fn accounts_batch<T: TransactionStore, C: ClientStore>(ledger: &Ledger<T, C>) -> Result<RecordBatch> {
+    let mut clients = ledger.clients.clients().collect::<Result<Vec<_>, _>>()?;
+    clients.sort_unstable_by_key(|client| client.id());
+
+    let precision = ledger.policy().precision();
+    let ids: ClientIdArray = clients.iter().map(|client| client.id()).collect();
+    let available = decimals(clients.iter().map(|client| Some(client.available())), precision)?;
+    let held = decimals(clients.iter().map(|client| Some(client.held())), precision)?;
+    let total = decimals(clients.iter().map(|client| Some(client.get_total())), precision)?;
+    let locked: BooleanArray = clients.iter().map(|client| Some(client.locked())).collect();
+
+    batch(vec![
+        ("client", Arc::new(ids), false),
+        ("available", Arc::new(available), false),
+        ("held", Arc::new(held), false),
+        ("total", Arc::new(total), false),
+        ("locked", Arc::new(locked), false),
+    ])
+}
+
+/// The stored transactions of the ledger as an Arrow record batch, by
+/// ascending id, with tx, type, client, amount, held and timestamp columns.
+/// The type is that of the stored transaction: deposit, withdrawal,
+/// disputed_withdrawal or reversal. Held is the part of the amount under
+/// dispute. Amounts are Decimal128 like in `accounts_batch`. A reversal's
+/// amount is null until it has reversed something, and so are missing
+/// timestamps.
+pub fn transactions_batch<T: TransactionStore, C: ClientStore>(ledger: &Ledger<T, C>) -> Result<RecordBatch> {
+    let mut rows = Vec::new();
+    ledger
+        .transactions
+        .for_each(|id, transaction| rows.push(TransactionRow::new(id, transaction)))?;
+    let mut rows = rows.into_iter().collect::<Result<Vec<_>>>()?;
+    rows.sort_unstable_by_key(|row| row.id);
+
+    let precision = ledger.policy().precision();
+    let ids: UInt64Array = rows.iter().map(|row| row.id).collect();
+    let types: StringArray = rows.iter().map(|row| Some(row.type_)).collect();
+    let clients: ClientIdArray = rows.iter().map(|row| row.client_id).collect();
+    let amounts = decimals(rows.iter().map(|row| row.amount), precision)?;
+    let held = decimals(rows.iter().map(|row| Some(row.held)), precision)?;
+    let timestamps =
+        TimestampSecondArray::from(rows.iter().map(|row| row.timestamp).collect::<Vec<_>>()).with_timezone_utc();
+
+    batch(vec![
+        ("tx", Arc::new(ids), false),
+        ("type", Arc::new(types), false),
+        ("client", Arc::new(clients), false),
+        ("amount", Arc::new(amounts), true),
+        ("held", Arc::new(held), false),
+        ("timestamp", Arc::new(timestamps), true),
+    ])
+}
+
+struct TransactionRow {
+    id: TxId,
+    type_: &'static str,
+    client_id: ClientId,
+    amount: Option<Decimal>,
+    held: Decimal,
+    timestamp: Option<i64>,
+}
+
+impl TransactionRow {
+    fn new(id: TxId, transaction: &Transaction) -> Result<TransactionRow> {
+        let (type_, amount, held) = match transaction {
+            Transaction::Deposit(deposit) => (
+                "deposit",
+                Some(deposit.amount()),
+                deposit.dispute_status().held(deposit.amount()),
+            ),
+            Transaction::Withdrawal(withdrawal) => ("withdrawal", Some(withdrawal.amount()), Decimal::ZERO),
+            Transaction::DisputedWithdrawal(withdrawal) => (
+                "disputed_withdrawal",
+                Some(withdrawal.amount()),
+                withdrawal.dispute_status().held(withdrawal.amount()),
+            ),
+            Transaction::Reversal(reversal) => ("reversal", reversal.amount(), Decimal::ZERO),
+            _ => anyhow::bail!("only deposits, withdrawals and reversals are stored"),
+        };
+
+        Ok(TransactionRow {
+            id,
+            type_,
+            client_id: transaction.client_id(),
+            amount,
+            held,
+            timestamp: transaction.timestamp().map(|timestamp| timestamp.unix()),
+        })
+    }
+}
+
+fn batch(columns: Vec<(&str, ArrayRef, bool)>) -> Result<RecordBatch> {
+    let fields: Vec<Field> = columns
+        .iter()
+        .map(|(name, column, nullable)| Field::new(*name, column.data_type().clone(), *nullable))
+        .collect();
+    let columns = columns.into_iter().map(|(_name, column, _nullable)| column).collect();
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+}
+
+// The amounts as a Decimal128 column with `precision` decimal places, or as
+// many as the amount with the most has. Fails if an amount then has more
+// digits than the column can hold.
+fn decimals(amounts: impl Iterator<Item = Option<Decimal>>, precision: u32) -> Result<Decimal128Array> {
+    let amounts: Vec<Option<Decimal>> = amounts.collect();
+    let scale = amounts.iter().flatten().map(Decimal::scale).fold(precision, u32::max);
+
+    let limit = 10u128.pow(u32::from(MAX_DIGITS));
+    let values = amounts
+        .iter()
+        .map(|amount| {
+            amount
+                .map(|amount| {
+                    10i128
+                        .checked_pow(scale - amount.scale())
+                        .and_then(|factor| amount.mantissa().checked_mul(factor))
+                        .filter(|value| value.unsigned_abs() < limit)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("{} doesn't fit a Decimal128 with {} decimal places", amount, scale)
+                        })
+                })
+                .transpose()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Decimal128Array::from(values).with_precision_and_scale(MAX_DIGITS, scale as i8)?)
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Decimal128Type, TimestampSecondType, UInt64Type};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::policy::LedgerPolicy;
+use crate::accounting::timestamp::Timestamp;
+use crate::accounting::transactions::{Deposit, Dispute, Reversal, Withdrawal};
+
+fn amounts(batch: &RecordBatch, name: &str) -> Vec<String> {
+    let column = batch
+        .column_by_name(name)
+        .expect("amount column")
+        .as_primitive::<Decimal128Type>();
+    (0..column.len())
+        .map(|row| {
+            if column.is_null(row) {
+                String::new()
+            } else {
+                column.value_as_string(row)
+            }
+        })
+        .collect()
+}
+
+fn ledger() -> Result<Ledger> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(
+        Transaction::Deposit(Deposit::new(2, 2, dec!(3))?).with_timestamp(Timestamp::from_unix(1_700_000_000)),
+    )?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10.5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 2)))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(3, 1, dec!(1.25))?))?;
+    ledger.execute_transaction(Transaction::Reversal(Reversal::new(3, 1)))?;
+    Ok(ledger)
+}
+
+#[test]
+fn test_accounts_batch() -> Result<()> {
+    let batch = accounts_batch(&ledger()?)?;
+
+    let names: Vec<&str> = batch
+        .schema_ref()
+        .fields()
+        .iter()
+        .map(|field| field.name().as_str())
+        .collect();
+    assert_eq!(names, vec!["client", "available", "held", "total", "locked"]);
+    let clients: Vec<ClientId> = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<ClientIdArray>()
+        .expect("client column")
+        .values()
+        .to_vec();
+    assert_eq!(clients, vec![1, 2]);
+    assert_eq!(amounts(&batch, "available"), vec!["10.5000", "0.0000"]);
+    assert_eq!(amounts(&batch, "held"), vec!["0.0000", "3.0000"]);
+    assert_eq!(amounts(&batch, "total"), vec!["10.5000", "3.0000"]);
+    assert_eq!(
+        batch.column(4).as_boolean().values().iter().collect::<Vec<_>>(),
+        vec![false, false]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_transactions_batch() -> Result<()> {
+    let batch = transactions_batch(&ledger()?)?;
+
+    let ids: Vec<TxId> = batch.column(0).as_primitive::<UInt64Type>().values().to_vec();
+    assert_eq!(ids, vec![1, 2, 3]);
+    let types: Vec<&str> = batch.column(1).as_string::<i32>().iter().flatten().collect();
+    assert_eq!(types, vec!["deposit", "deposit", "reversal"]);
+    assert_eq!(amounts(&batch, "amount"), vec!["10.5000", "3.0000", "1.2500"]);
+    assert_eq!(amounts(&batch, "held"), vec!["0.0000", "3.0000", "0.0000"]);
+    let timestamps = batch.column(5).as_primitive::<TimestampSecondType>();
+    assert_eq!(
+        timestamps.iter().collect::<Vec<_>>(),
+        vec![None, Some(1_700_000_000), None]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_scale() -> Result<()> {
+    // More decimals than the precision widen the column, rather than being
+    // rounded away.
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        precision: Some(2),
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(1.005))?))?;
+    let batch = transactions_batch(&ledger)?;
+    assert_eq!(amounts(&batch, "amount"), vec!["1.005"]);
+    assert_eq!(amounts(&accounts_batch(&ledger)?, "available"), vec!["1.00"]);
+
+    // The largest amounts only fit with up to 9 decimal places.
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, Decimal::MAX)?))?;
+    assert!(accounts_batch(&ledger).is_ok());
+    ledger.use_policy(LedgerPolicy {
+        precision: Some(10),
+        ..LedgerPolicy::default()
+    });
+    assert!(accounts_batch(&ledger).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use csv::ByteRecord;
+
+use super::{ParseError, RecordReader, TransactionRecord, HEADERS};
+
+#[cfg(test)]
+#[path = "avro_tests.rs"]
+mod avro_tests;
+
+/// Reads `TransactionRecord`s from Avro binary data: records of the schema
+/// in `avro/transaction.avsc`, one after the other, without the header of an
+/// object container file. Data behind the header of the Confluent wire
+/// format, a zero byte and the id of the schema, is read the same way. A
+/// record can't be empty, as its type can't be, so the two can't be
+/// mistaken for one another.
+///
+/// The fields are turned into a row with the csv headers and parsed like
+/// one. Once a record can't be decoded, neither can the ones after it, so
+/// it's the last one read.
+pub struct AvroReader<'a> {
+    data: &'a [u8],
+    headers: ByteRecord,
+    record: ByteRecord,
+    line: u64,
+}
+
+impl<'a> AvroReader<'a> {
+    pub fn new(data: &'a [u8]) -> AvroReader<'a> {
+        let data = match data {
+            [0, _, _, _, _, rest @ ..] => rest,
+            _ => data,
+        };
+
+        AvroReader {
+            data,
+            headers: ByteRecord::from(HEADERS.to_vec()),
+            record: ByteRecord::new(),
+            line: 0,
+        }
+    }
+
+    // The fields of the next record, in the order of `HEADERS`.
+    fn fields(&mut self) -> Result<[String; 6], &'static str> {
+        let type_ = self.string()?;
+        let client = self.long()?;
+        if i32::try_from(client).is_err() {
+            return Err("client is out of range of an avro int");
+        }
+        let tx = self.long()?;
+        let amount = self.optional(Self::string)?;
+        let currency = self.optional(Self::string)?;
+        let timestamp = self.optional(Self::long)?;
+
+        Ok([
+            type_,
+            client.to_string(),
+            tx.to_string(),
+            amount.unwrap_or_default(),
+            currency.unwrap_or_default(),
+            timestamp.map(|timestamp| timestamp.to_string()).unwrap_or_default(),
+        ])
+    }
+
+    // A zigzag encoded variable length long, which ints are too.
+    fn long(&mut self) -> Result<i64, &'static str> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let Some((&byte, rest)) = self.data.split_first() else {
+                return Err("truncated avro record");
+            };
+            self.data = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+
+        Err("invalid avro long")
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        let length = usize::try_from(self.long()?).map_err(|_| "invalid avro string length")?;
+        if length > self.data.len() {
+            return Err("truncated avro record");
+        }
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| "avro string is not utf-8")
+    }
+
+    // A union of null, the first branch, and a value.
+    fn optional<T>(&mut self, value: fn(&mut Self) -> Result<T, &'static str>) -> Result<Option<T>, &'static str> {
+        match self.long()? {
+            0 => Ok(None),
+            1 => value(self).map(Some),
+            _ => Err("invalid avro union branch"),
+        }
+    }
+}
+
+impl RecordReader for AvroReader<'_> {
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>> {
+        if self.data.is_empty() {
+            return None;
+        }
+        self.line += 1;
+        self.record.clear();
+
+        let fields = match self.fields() {
+            Ok(fields) => fields,
+            Err(err) => {
+                self.data = &[];
+                return Some(Err(ParseError::Invalid(err)));
+            },
+        };
+        for field in &fields {
+            self.record.push_field(field.trim().as_bytes());
+        }
+
+        Some(self.record.deserialize(Some(&self.headers)).map_err(ParseError::from))
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn headers(&self) -> &ByteRecord {
+        &self.headers
+    }
+
+    fn raw_record(&self) -> &ByteRecord {
+        &self.record
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::timestamp::Timestamp;
+use crate::data::{process_payload, PayloadFormat, ProcessingOptions, RecordError, TransactionType};
+
+fn long(value: i64, data: &mut Vec<u8>) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        data.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn string(value: &str, data: &mut Vec<u8>) {
+    long(value.len() as i64, data);
+    data.extend_from_slice(value.as_bytes());
+}
+
+fn record(type_: &str, client: i64, tx: i64, amount: Option<&str>, timestamp: Option<i64>) -> Vec<u8> {
+    let mut data = Vec::new();
+    string(type_, &mut data);
+    long(client, &mut data);
+    long(tx, &mut data);
+    match amount {
+        Some(amount) => {
+            long(1, &mut data);
+            string(amount, &mut data);
+        },
+        None => long(0, &mut data),
+    }
+    long(0, &mut data);
+    match timestamp {
+        Some(timestamp) => {
+            long(1, &mut data);
+            long(timestamp, &mut data);
+        },
+        None => long(0, &mut data),
+    }
+    data
+}
+
+#[test]
+fn test_reader() -> Result<()> {
+    let mut data = record("deposit", 1, 1, Some("1.5"), None);
+    data.extend(record("withdrawal", 1, 300, Some("0.25"), Some(1714557600)));
+    data.extend(record("dispute", 1, 1, None, None));
+
+    let mut reader = AvroReader::new(&data);
+    let mut records = Vec::new();
+    while let Some(record) = reader.read_record() {
+        records.push((reader.line(), record?));
+    }
+
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].0, 1);
+    assert_eq!(records[0].1.type_, TransactionType::Deposit);
+    assert_eq!(records[0].1.amount, Some(dec!(1.5)));
+    assert_eq!(records[1].1.id, 300);
+    assert_eq!(records[1].1.amount, Some(dec!(0.25)));
+    assert_eq!(records[1].1.timestamp, Timestamp::from_unix(1714557600));
+    assert_eq!(records[2].0, 3);
+    assert_eq!(records[2].1.type_, TransactionType::Dispute);
+    assert_eq!(records[2].1.amount, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_confluent_header() -> Result<()> {
+    let mut data = vec![0, 0, 0, 0, 42];
+    data.extend(record("deposit", 2, 7, Some("3"), None));
+
+    let mut reader = AvroReader::new(&data);
+    let record = reader.read_record().unwrap()?;
+    assert_eq!((record.client_id, record.id, record.amount), (2, 7, Some(dec!(3))));
+    assert!(reader.read_record().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid() -> Result<()> {
+    let mut data = record("deposit", 1, 1, Some("1"), None);
+    data.truncate(data.len() - 3);
+    data.extend(record("deposit", 1, 2, Some("1"), None));
+
+    let mut reader = AvroReader::new(&data);
+    if let Some(Err(ParseError::Invalid(err))) = reader.read_record() {
+        assert_eq!(err, "invalid avro union branch");
+    } else {
+        bail!("expected an invalid record");
+    }
+    assert!(reader.read_record().is_none());
+
+    let data = record("deposit", 1, 1, Some("1"), None);
+    let mut reader = AvroReader::new(&data[..data.len() - 1]);
+    if let Some(Err(ParseError::Invalid(err))) = reader.read_record() {
+        assert_eq!(err, "truncated avro record");
+    } else {
+        bail!("expected a truncated record");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_process_payload() -> Result<()> {
+    let mut ledger = Ledger::new();
+    let mut data = record("deposit", 1, 1, Some("10"), None);
+    data.extend(record("withdrawal", 1, 2, Some("50"), None));
+    let report = process_payload(&data, PayloadFormat::Avro, &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 1);
+    assert_eq!(report.errors.len(), 1);
+    assert!(matches!(report.errors[0].error, RecordError::Ledger(_)));
+
+    let data = br#"{"type": "withdrawal", "client": 1, "tx": 3, "amount": 2.5}"#;
+    let report = process_payload(data, PayloadFormat::Json, &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 1);
+    assert_eq!(ledger.clients.get(&1).unwrap().available(), dec!(7.5));
+
+    Ok(())
+}
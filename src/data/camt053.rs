@@ -0,0 +1,148 @@
+use std::io::Write;
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+
+use super::ActivityEntry;
+use crate::accounting::currency::Currency;
+use crate::accounting::timestamp::Timestamp;
+use crate::accounting::ClientId;
+
+#[cfg(test)]
+#[path = "camt053_tests.rs"]
+mod camt053_tests;
+
+const NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:camt.053.001.08";
+
+/// A client's statement, see `data::export_camt053`.
+pub(super) struct Statement<'a> {
+    pub(super) client: ClientId,
+    pub(super) opening: Decimal,
+    pub(super) closing: Decimal,
+    pub(super) available: Decimal,
+    pub(super) entries: &'a [ActivityEntry],
+}
+
+/// Writes a camt.053 message with a statement for every client. The account
+/// of a statement is the client id, its entries are booked and their bank
+/// transaction code is the type of the transaction, proprietary to us.
+pub(super) fn write_statements<'a>(
+    statements: impl Iterator<Item = Statement<'a>>,
+    currency: Currency,
+    created: Timestamp,
+    mut writer: impl Write,
+) -> Result<()> {
+    // At most 35 characters, which the widest client ids leave room for.
+    let message_id = format!("TRANSACTO-{}", created.unix());
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<Document xmlns="{}">"#, NAMESPACE)?;
+    writeln!(writer, "  <BkToCstmrStmt>")?;
+    writeln!(writer, "    <GrpHdr>")?;
+    writeln!(writer, "      <MsgId>{}</MsgId>", message_id)?;
+    writeln!(writer, "      <CreDtTm>{}</CreDtTm>", created)?;
+    writeln!(writer, "    </GrpHdr>")?;
+
+    for statement in statements {
+        writeln!(writer, "    <Stmt>")?;
+        writeln!(writer, "      <Id>{}-{}</Id>", message_id, statement.client)?;
+        writeln!(writer, "      <CreDtTm>{}</CreDtTm>", created)?;
+        writeln!(writer, "      <Acct>")?;
+        writeln!(writer, "        <Id><Othr><Id>{}</Id></Othr></Id>", statement.client)?;
+        writeln!(writer, "        <Ccy>{}</Ccy>", currency.as_str())?;
+        writeln!(writer, "      </Acct>")?;
+
+        let opened = statement.entries.iter().find_map(|entry| entry.timestamp);
+        write_balance(
+            &mut writer,
+            "OPBD",
+            statement.opening,
+            currency,
+            opened.unwrap_or(created),
+        )?;
+        write_balance(&mut writer, "CLBD", statement.closing, currency, created)?;
+        write_balance(&mut writer, "CLAV", statement.available, currency, created)?;
+
+        if !statement.entries.is_empty() {
+            // Every entry fits in a Decimal, but all of them together may
+            // not.
+            let too_large = || anyhow::anyhow!("the entries of client {} are too large to sum up", statement.client);
+            let sum = checked_sum(statement.entries.iter().map(|entry| entry.amount.abs())).ok_or_else(too_large)?;
+            let net = checked_sum(statement.entries.iter().map(|entry| entry.amount)).ok_or_else(too_large)?;
+            writeln!(writer, "      <TxsSummry>")?;
+            writeln!(writer, "        <TtlNtries>")?;
+            writeln!(writer, "          <NbOfNtries>{}</NbOfNtries>", statement.entries.len())?;
+            writeln!(writer, "          <Sum>{}</Sum>", sum)?;
+            writeln!(writer, "          <TtlNetNtry>")?;
+            writeln!(writer, "            <Amt>{}</Amt>", net.abs())?;
+            writeln!(writer, "            <CdtDbtInd>{}</CdtDbtInd>", indicator(net))?;
+            writeln!(writer, "          </TtlNetNtry>")?;
+            writeln!(writer, "        </TtlNtries>")?;
+            writeln!(writer, "      </TxsSummry>")?;
+        }
+
+        for entry in statement.entries {
+            writeln!(writer, "      <Ntry>")?;
+            writeln!(writer, "        <NtryRef>{}</NtryRef>", entry.tx)?;
+            writeln!(
+                writer,
+                r#"        <Amt Ccy="{}">{}</Amt>"#,
+                currency.as_str(),
+                entry.amount.abs()
+            )?;
+            writeln!(writer, "        <CdtDbtInd>{}</CdtDbtInd>", indicator(entry.amount))?;
+            writeln!(writer, "        <Sts><Cd>BOOK</Cd></Sts>")?;
+            if let Some(timestamp) = entry.timestamp {
+                writeln!(writer, "        <BookgDt><DtTm>{}</DtTm></BookgDt>", timestamp)?;
+            }
+            writeln!(
+                writer,
+                "        <BkTxCd><Prtry><Cd>{}</Cd></Prtry></BkTxCd>",
+                entry.type_.name()
+            )?;
+            writeln!(writer, "        <AddtlNtryInf>line {}</AddtlNtryInf>", entry.line)?;
+            writeln!(writer, "      </Ntry>")?;
+        }
+        writeln!(writer, "    </Stmt>")?;
+    }
+
+    writeln!(writer, "  </BkToCstmrStmt>")?;
+    writeln!(writer, "</Document>")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_balance(
+    writer: &mut impl Write,
+    code: &str,
+    amount: Decimal,
+    currency: Currency,
+    at: Timestamp,
+) -> Result<()> {
+    writeln!(writer, "      <Bal>")?;
+    writeln!(writer, "        <Tp><CdOrPrtry><Cd>{}</Cd></CdOrPrtry></Tp>", code)?;
+    writeln!(
+        writer,
+        r#"        <Amt Ccy="{}">{}</Amt>"#,
+        currency.as_str(),
+        amount.abs()
+    )?;
+    writeln!(writer, "        <CdtDbtInd>{}</CdtDbtInd>", indicator(amount))?;
+    writeln!(writer, "        <Dt><DtTm>{}</DtTm></Dt>", at)?;
+    writeln!(writer, "      </Bal>")?;
+
+    Ok(())
+}
+
+fn checked_sum(mut amounts: impl Iterator<Item = Decimal>) -> Option<Decimal> {
+    amounts.try_fold(Decimal::ZERO, |sum, amount| sum.checked_add(amount))
+}
+
+// Amounts are written without a sign, negative ones are debits.
+fn indicator(amount: Decimal) -> &'static str {
+    match amount < Decimal::ZERO {
+        true => "DBIT",
+        false => "CRDT",
+    }
+}
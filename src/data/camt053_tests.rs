@@ -0,0 +1,173 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::data::TransactionType;
+
+#[test]
+fn test_write_statements() -> Result<()> {
+    let entries = [
+        ActivityEntry {
+            line: 2,
+            type_: TransactionType::Deposit,
+            tx: 1,
+            amount: dec!(10),
+            timestamp: Some("2024-05-01T09:00:00Z".parse()?),
+        },
+        ActivityEntry {
+            line: 3,
+            type_: TransactionType::Withdrawal,
+            tx: 2,
+            amount: dec!(-12.5),
+            timestamp: None,
+        },
+    ];
+    let statements = [
+        Statement {
+            client: 1,
+            opening: dec!(5),
+            closing: dec!(2.5),
+            available: dec!(-2.5),
+            entries: &entries,
+        },
+        Statement {
+            client: 2,
+            opening: dec!(0),
+            closing: dec!(0),
+            available: dec!(0),
+            entries: &[],
+        },
+    ];
+
+    let mut output = Vec::new();
+    write_statements(
+        statements.into_iter(),
+        "EUR".parse()?,
+        "2024-05-02T00:00:00Z".parse()?,
+        &mut output,
+    )?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08">
+  <BkToCstmrStmt>
+    <GrpHdr>
+      <MsgId>TRANSACTO-1714608000</MsgId>
+      <CreDtTm>2024-05-02T00:00:00Z</CreDtTm>
+    </GrpHdr>
+    <Stmt>
+      <Id>TRANSACTO-1714608000-1</Id>
+      <CreDtTm>2024-05-02T00:00:00Z</CreDtTm>
+      <Acct>
+        <Id><Othr><Id>1</Id></Othr></Id>
+        <Ccy>EUR</Ccy>
+      </Acct>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">5</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><DtTm>2024-05-01T09:00:00Z</DtTm></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">2.5</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><DtTm>2024-05-02T00:00:00Z</DtTm></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLAV</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">2.5</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Dt><DtTm>2024-05-02T00:00:00Z</DtTm></Dt>
+      </Bal>
+      <TxsSummry>
+        <TtlNtries>
+          <NbOfNtries>2</NbOfNtries>
+          <Sum>22.5</Sum>
+          <TtlNetNtry>
+            <Amt>2.5</Amt>
+            <CdtDbtInd>DBIT</CdtDbtInd>
+          </TtlNetNtry>
+        </TtlNtries>
+      </TxsSummry>
+      <Ntry>
+        <NtryRef>1</NtryRef>
+        <Amt Ccy="EUR">10</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><DtTm>2024-05-01T09:00:00Z</DtTm></BookgDt>
+        <BkTxCd><Prtry><Cd>deposit</Cd></Prtry></BkTxCd>
+        <AddtlNtryInf>line 2</AddtlNtryInf>
+      </Ntry>
+      <Ntry>
+        <NtryRef>2</NtryRef>
+        <Amt Ccy="EUR">12.5</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BkTxCd><Prtry><Cd>withdrawal</Cd></Prtry></BkTxCd>
+        <AddtlNtryInf>line 3</AddtlNtryInf>
+      </Ntry>
+    </Stmt>
+    <Stmt>
+      <Id>TRANSACTO-1714608000-2</Id>
+      <CreDtTm>2024-05-02T00:00:00Z</CreDtTm>
+      <Acct>
+        <Id><Othr><Id>2</Id></Othr></Id>
+        <Ccy>EUR</Ccy>
+      </Acct>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">0</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><DtTm>2024-05-02T00:00:00Z</DtTm></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">0</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><DtTm>2024-05-02T00:00:00Z</DtTm></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLAV</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">0</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><DtTm>2024-05-02T00:00:00Z</DtTm></Dt>
+      </Bal>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>
+"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_write_statements_overflow() -> Result<()> {
+    let entry = |tx, amount| ActivityEntry {
+        line: tx + 1,
+        type_: TransactionType::Deposit,
+        tx,
+        amount,
+        timestamp: None,
+    };
+    let entries = [entry(1, Decimal::MAX), entry(2, -Decimal::MAX)];
+    let statements = [Statement {
+        client: 1,
+        opening: dec!(0),
+        closing: dec!(0),
+        available: dec!(0),
+        entries: &entries,
+    }];
+
+    let result = write_statements(
+        statements.into_iter(),
+        "EUR".parse()?,
+        "2024-05-02T00:00:00Z".parse()?,
+        Vec::new(),
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use csv::ByteRecord;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{ParseError, RecordReader, TransactionRecord};
+use crate::accounting::{ClientId, Map};
+
+#[cfg(test)]
+#[path = "client_ids_tests.rs"]
+mod client_ids_tests;
+
+#[derive(Debug, Error)]
+pub enum ClientIdsError {
+    #[error("out of client ids")]
+    Exhausted,
+    #[error("client id {0} is mapped more than once")]
+    DuplicateId(ClientId),
+    #[error("external client id {0} is mapped more than once")]
+    DuplicateExternalId(String),
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+}
+
+/// The internal ids of clients known by an external id, e.g. the
+/// alphanumeric customer id of an upstream system. New external ids are
+/// given the id after the highest one in use, starting from 0. Clients that
+/// were given a numeric id some other way (e.g. in a loaded snapshot) aren't
+/// known, so the two shouldn't be mixed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientIds {
+    ids: Map<String, ClientId>,
+    external: BTreeMap<ClientId, String>,
+}
+
+// A row of a client ids file.
+#[derive(Serialize, Deserialize)]
+struct ClientIdRecord {
+    client: ClientId,
+    external_id: String,
+}
+
+impl ClientIds {
+    pub fn new() -> ClientIds {
+        ClientIds::default()
+    }
+
+    /// Reads the ids written by `write`, a csv file with client and
+    /// external_id columns.
+    pub fn read(reader: impl Read) -> Result<ClientIds, ClientIdsError> {
+        let mut ids = ClientIds::new();
+        for record in csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader)
+            .deserialize()
+        {
+            let ClientIdRecord { client, external_id } = record?;
+            if ids.external.contains_key(&client) {
+                return Err(ClientIdsError::DuplicateId(client));
+            }
+            if ids.ids.contains_key(&external_id) {
+                return Err(ClientIdsError::DuplicateExternalId(external_id));
+            }
+
+            ids.insert(client, &external_id);
+        }
+
+        Ok(ids)
+    }
+
+    /// Writes the ids as csv, ordered by client id.
+    pub fn write(&self, writer: impl Write) -> Result<(), ClientIdsError> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for (client, external_id) in &self.external {
+            writer.serialize(ClientIdRecord {
+                client: *client,
+                external_id: external_id.clone(),
+            })?;
+        }
+
+        writer.flush().map_err(csv::Error::from)?;
+
+        Ok(())
+    }
+
+    /// The id of the client with this external id, if it has one.
+    pub fn get(&self, external_id: &str) -> Option<ClientId> {
+        self.ids.get(external_id).copied()
+    }
+
+    /// The id of the client with this external id, a new one if it wasn't
+    /// seen before.
+    pub fn intern(&mut self, external_id: &str) -> Result<ClientId, ClientIdsError> {
+        if let Some(id) = self.get(external_id) {
+            return Ok(id);
+        }
+
+        let id = self.next_id().ok_or(ClientIdsError::Exhausted)?;
+        self.insert(id, external_id);
+
+        Ok(id)
+    }
+
+    /// The external id of a client, if it has one.
+    pub fn external_id(&self, id: ClientId) -> Option<&str> {
+        self.external.get(&id).map(String::as_str)
+    }
+
+    /// The client's external id, or else its own id.
+    pub fn display(&self, id: ClientId) -> Cow<'_, str> {
+        self.external_id(id)
+            .map_or_else(|| Cow::Owned(id.to_string()), Cow::Borrowed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.external.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.external.is_empty()
+    }
+
+    fn insert(&mut self, id: ClientId, external_id: &str) {
+        self.ids.insert(external_id.to_string(), id);
+        self.external.insert(id, external_id.to_string());
+    }
+
+    fn next_id(&self) -> Option<ClientId> {
+        match self.external.last_key_value() {
+            Some((id, _)) => id.checked_add(1),
+            None => Some(0),
+        }
+    }
+}
+
+/// Reads records whose client column has external ids, interning them with
+/// `ClientIds` so that the records carry the internal ids. The client of a
+/// record that can't be read isn't interned. `raw_record` keeps the external
+/// ids, so rejects show what was in the input.
+pub struct ClientIdReader<'a, R: ?Sized> {
+    reader: &'a mut R,
+    ids: &'a mut ClientIds,
+    record: ByteRecord,
+}
+
+impl<'a, R: RecordReader + ?Sized> ClientIdReader<'a, R> {
+    pub fn new(reader: &'a mut R, ids: &'a mut ClientIds) -> ClientIdReader<'a, R> {
+        ClientIdReader {
+            reader,
+            ids,
+            record: ByteRecord::new(),
+        }
+    }
+}
+
+impl<'a, R: RecordReader + ?Sized> RecordReader for ClientIdReader<'a, R> {
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>> {
+        // The wrapped reader fails to read external ids as numbers, only
+        // errors about the row itself are final.
+        match self.reader.read_record()? {
+            Err(ParseError::Csv(err)) if !matches!(err.kind(), csv::ErrorKind::Deserialize { .. }) => {
+                return Some(Err(ParseError::Csv(err)))
+            },
+            Err(err @ (ParseError::Json(_) | ParseError::Io(_) | ParseError::Invalid(_))) => return Some(Err(err)),
+            #[cfg(feature = "parquet")]
+            Err(err @ ParseError::Parquet(_)) => return Some(Err(err)),
+            _ => {},
+        }
+
+        let headers = self.reader.headers();
+        let Some(column) = headers.iter().position(|header| header == b"client") else {
+            return Some(Err(ParseError::Invalid("missing client column")));
+        };
+
+        let raw = self.reader.raw_record();
+        let external_id = match raw.get(column).map(std::str::from_utf8) {
+            Some(Ok(external_id)) if !external_id.is_empty() => external_id,
+            _ => return Some(Err(ParseError::Invalid("missing client"))),
+        };
+        let known = self.ids.get(external_id);
+        let Some(id) = known.or_else(|| self.ids.next_id()) else {
+            return Some(Err(ParseError::Invalid("out of client ids")));
+        };
+
+        self.record.clear();
+        for (index, field) in raw.iter().enumerate() {
+            if index == column {
+                self.record.push_field(id.to_string().as_bytes());
+            } else {
+                self.record.push_field(field);
+            }
+        }
+
+        let record = self.record.deserialize::<TransactionRecord>(Some(headers));
+        if record.is_ok() && known.is_none() {
+            self.ids.insert(id, external_id);
+        }
+
+        Some(record.map_err(ParseError::from))
+    }
+
+    fn line(&self) -> u64 {
+        self.reader.line()
+    }
+
+    fn headers(&self) -> &ByteRecord {
+        self.reader.headers()
+    }
+
+    fn raw_record(&self) -> &ByteRecord {
+        self.reader.raw_record()
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::data::{
+    export_with_client_ids, process_records, Format, JsonlReader, ProcessingOptions, RecordError, TransactionReader,
+};
+
+#[test]
+fn test_intern() -> Result<()> {
+    let mut ids = ClientIds::new();
+    assert_eq!(ids.intern("CUST-A")?, 0);
+    assert_eq!(ids.intern("CUST-B")?, 1);
+    assert_eq!(ids.intern("CUST-A")?, 0);
+    assert_eq!(ids.external_id(1), Some("CUST-B"));
+    assert_eq!(ids.display(7), "7");
+
+    let mut written = Vec::new();
+    ids.write(&mut written)?;
+    assert_eq!(
+        std::str::from_utf8(&written)?,
+        "client,external_id\n0,CUST-A\n1,CUST-B\n"
+    );
+    assert_eq!(ClientIds::read(written.as_slice())?, ids);
+
+    let last = format!("client,external_id\n{},CUST-Z\n", ClientId::MAX);
+    let mut ids = ClientIds::read(last.as_bytes())?;
+    if let Err(err) = ids.intern("CUST-Y") {
+        assert_eq!(err.to_string(), "out of client ids");
+    } else {
+        bail!("Interned a client after the last id");
+    }
+
+    if let Err(err) = ClientIds::read("client,external_id\n1,CUST-A\n2,CUST-A\n".as_bytes()) {
+        assert_eq!(err.to_string(), "external client id CUST-A is mapped more than once");
+    } else {
+        bail!("Read an external id mapped twice");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_process_and_export() -> Result<()> {
+    let data = "type, client, tx, amount
+deposit, CUST-A, 1, 10
+deposit, CUST-B, 2, 5
+deposit, CUST-C, 3, x
+withdrawal, CUST-A, 4, 3
+deposit, 42, 5, 1
+";
+
+    let mut ids = ClientIds::new();
+    let mut ledger = Ledger::new();
+    let mut reader = TransactionReader::new(data.as_bytes())?;
+    let report = process_records(
+        &mut ClientIdReader::new(&mut reader, &mut ids),
+        &mut ledger,
+        ProcessingOptions::default(),
+    )?;
+
+    assert_eq!(report.accepted, 4);
+    assert!(matches!(report.errors[0].error, RecordError::Parse(_)));
+    // The malformed row's client isn't interned.
+    assert_eq!(ids.get("CUST-C"), None);
+    assert_eq!(ids.get("42"), Some(2));
+    assert_eq!(ledger.clients[&0].available(), dec!(7));
+
+    let mut output = Vec::new();
+    export_with_client_ids(&ledger, &ids, false, Format::Csv, &mut output)?;
+    let mut lines: Vec<&str> = std::str::from_utf8(&output)?.lines().collect();
+    lines[1..].sort();
+    assert_eq!(
+        lines,
+        vec![
+            "client,available,held,total,locked",
+            "42,1,0,1,false",
+            "CUST-A,7,0,7,false",
+            "CUST-B,5,0,5,false",
+        ]
+    );
+
+    // The ids carry over to the next file, whatever its format.
+    let data = r#"{"type": "deposit", "client": "CUST-D", "tx": 6, "amount": 1}
+{"type": "withdrawal", "client": "CUST-B", "tx": 7, "amount": 2}
+"#;
+    let mut reader = JsonlReader::new(data.as_bytes());
+    process_records(
+        &mut ClientIdReader::new(&mut reader, &mut ids),
+        &mut ledger,
+        ProcessingOptions::default(),
+    )?;
+    assert_eq!(ids.get("CUST-D"), Some(3));
+    assert_eq!(ledger.clients[&1].available(), dec!(3));
+
+    Ok(())
+}
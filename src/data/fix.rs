@@ -0,0 +1,171 @@
+use std::io::BufRead;
+
+use chrono::NaiveDateTime;
+use csv::ByteRecord;
+use serde::Deserialize;
+
+use super::statement::hash;
+use super::{ParseError, RecordReader, TransactionRecord, HEADERS};
+
+#[cfg(test)]
+#[path = "fix_tests.rs"]
+mod fix_tests;
+
+/// The FIX tags `FixReader` reads the columns of a record from. The
+/// defaults are those of an execution report: Side, Account, ExecID,
+/// NetMoney, Currency and TransactTime.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FixTags {
+    #[serde(rename = "type")]
+    pub type_: u32,
+    pub client: u32,
+    pub tx: u32,
+    pub amount: u32,
+    pub currency: u32,
+    pub timestamp: u32,
+}
+
+impl Default for FixTags {
+    fn default() -> Self {
+        FixTags {
+            type_: 54,
+            client: 1,
+            tx: 17,
+            amount: 118,
+            currency: 15,
+            timestamp: 60,
+        }
+    }
+}
+
+// The session messages, e.g. logons and heartbeats, which are skipped.
+const SESSION_MESSAGES: [&str; 7] = ["0", "1", "2", "3", "4", "5", "A"];
+
+/// Reads `TransactionRecord`s from FIX messages, one per line, with their
+/// fields separated by SOH or `|`. The fields of `FixTags` are turned into
+/// a row with the csv headers and parsed like one.
+///
+/// The type is a Side, buys (1) are withdrawals and sells (2) deposits, or
+/// else the name of a type, e.g. `dispute` in a user defined tag. Ids that
+/// aren't numbers, which ExecIDs seldom are, are replaced with a hash of
+/// them, so that a message sent again is still a repeated transaction.
+/// Timestamps are UTCTimestamps, e.g. `20240501-12:00:00.000`. Session
+/// messages and empty lines are skipped.
+pub struct FixReader<R> {
+    reader: R,
+    tags: FixTags,
+    buffer: String,
+    headers: ByteRecord,
+    record: ByteRecord,
+    line: u64,
+}
+
+impl<R: BufRead> FixReader<R> {
+    pub fn new(reader: R, tags: FixTags) -> FixReader<R> {
+        FixReader {
+            reader,
+            tags,
+            buffer: String::new(),
+            headers: ByteRecord::from(HEADERS.to_vec()),
+            record: ByteRecord::new(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> RecordReader for FixReader<R> {
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>> {
+        loop {
+            self.buffer.clear();
+            self.record.clear();
+
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(err) => return Some(Err(err.into())),
+            }
+
+            let message = self.buffer.trim();
+            if message.is_empty() {
+                continue;
+            }
+
+            let fields = message
+                .split(['\x01', '|'])
+                .filter_map(|field| field.split_once('='))
+                .filter_map(|(tag, value)| Some((tag.trim().parse::<u32>().ok()?, value.trim())));
+            let mut row = [""; 6];
+            let mut message_type = None;
+            for (tag, value) in fields {
+                if tag == 35 {
+                    message_type = Some(value);
+                }
+                let column = [
+                    self.tags.type_,
+                    self.tags.client,
+                    self.tags.tx,
+                    self.tags.amount,
+                    self.tags.currency,
+                    self.tags.timestamp,
+                ]
+                .iter()
+                .position(|column_tag| *column_tag == tag);
+                // The first of a repeated tag, e.g. in a repeating group.
+                if let Some(column) = column.filter(|column| row[*column].is_empty()) {
+                    row[column] = value;
+                }
+            }
+            if message_type.map_or(false, |message_type| SESSION_MESSAGES.contains(&message_type)) {
+                continue;
+            }
+
+            let type_ = match row[0] {
+                "1" => "withdrawal",
+                "2" => "deposit",
+                type_ => type_,
+            };
+            let tx = match row[2].parse::<u64>() {
+                Err(_) if !row[2].is_empty() => hash(row[2].as_bytes()).to_string(),
+                _ => row[2].to_string(),
+            };
+            let timestamp = match row[5] {
+                "" => None,
+                value => match parse_utc_timestamp(value) {
+                    Some(seconds) => Some(seconds.to_string()),
+                    None => return Some(Err(ParseError::Invalid("invalid UTCTimestamp"))),
+                },
+            };
+
+            self.record.push_field(type_.as_bytes());
+            self.record.push_field(row[1].as_bytes());
+            self.record.push_field(tx.as_bytes());
+            self.record.push_field(row[3].as_bytes());
+            self.record.push_field(row[4].as_bytes());
+            self.record
+                .push_field(timestamp.as_deref().unwrap_or_default().as_bytes());
+
+            return Some(self.record.deserialize(Some(&self.headers)).map_err(ParseError::from));
+        }
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn headers(&self) -> &ByteRecord {
+        &self.headers
+    }
+
+    fn raw_record(&self) -> &ByteRecord {
+        &self.record
+    }
+}
+
+// E.g. `20240501-12:00:00` or `20240501-12:00:00.000`, always in UTC.
+fn parse_utc_timestamp(value: &str) -> Option<i64> {
+    let seconds = value.split('.').next()?;
+    let date_time = NaiveDateTime::parse_from_str(seconds, "%Y%m%d-%H:%M:%S").ok()?;
+
+    Some(date_time.and_utc().timestamp())
+}
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::data::{process_records, ProcessingOptions, TransactionType};
+
+const FEED: &str = "8=FIX.4.4\x019=60\x0135=A\x0149=DESK\x0156=LEDGER\x0134=1\x0110=000\x01
+8=FIX.4.4|35=8|1=7|17=EX-1|54=2|15=USD|118=100.50|60=20240501-12:00:00.000|10=000|
+
+8=FIX.4.4|35=8|1=7|17=42|54=1|118=20|10=000|
+8=FIX.4.4|35=8|1=7|17=EX-1|54=2|118=100.50|10=000|
+8=FIX.4.4|35=0|10=000|
+8=FIX.4.4|35=8|1=7|17=43|54=1|60=yesterday|10=000|
+";
+
+fn read_all(reader: &mut impl RecordReader) -> Vec<(u64, Result<TransactionRecord, String>)> {
+    let mut records = Vec::new();
+    while let Some(record) = reader.read_record() {
+        records.push((reader.line(), record.map_err(|err| err.to_string())));
+    }
+
+    records
+}
+
+#[test]
+fn test_read() -> Result<()> {
+    let mut reader = FixReader::new(FEED.as_bytes(), FixTags::default());
+    let records = read_all(&mut reader);
+    assert_eq!(records.len(), 4);
+
+    let (line, sell) = &records[0];
+    let sell = sell.as_ref().unwrap();
+    assert_eq!(*line, 2);
+    assert_eq!(sell.type_, TransactionType::Deposit);
+    assert_eq!(sell.client_id, 7);
+    assert_eq!(sell.id, hash(b"EX-1"));
+    assert_eq!(sell.amount, Some(dec!(100.50)));
+    assert_eq!(sell.currency, Some("USD".parse()?));
+    assert_eq!(sell.timestamp, Some("2024-05-01T12:00:00Z".parse()?));
+
+    let (line, buy) = &records[1];
+    let buy = buy.as_ref().unwrap();
+    assert_eq!(*line, 4);
+    assert_eq!(buy.type_, TransactionType::Withdrawal);
+    assert_eq!(buy.id, 42);
+    assert_eq!(buy.amount, Some(dec!(20)));
+    assert_eq!(buy.timestamp, None);
+
+    // Sent again, with the same id.
+    assert_eq!(records[2].1.as_ref().unwrap().id, sell.id);
+
+    assert_eq!(records[3], (7, Err("invalid UTCTimestamp".to_string())));
+
+    Ok(())
+}
+
+#[test]
+fn test_tags() -> Result<()> {
+    let tags = FixTags {
+        type_: 5001,
+        amount: 44,
+        ..FixTags::default()
+    };
+    let feed = "35=8|1=3|17=1|5001=deposit|44=10|44=99\n35=8|1=3|17=1|5001=dispute\n";
+
+    let mut ledger = Ledger::new();
+    let report = process_records(
+        &mut FixReader::new(feed.as_bytes(), tags),
+        &mut ledger,
+        ProcessingOptions::default(),
+    )?;
+    assert_eq!(report.accepted, 2);
+    assert_eq!(ledger.clients.get(&3).unwrap().held(), dec!(10));
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::io::Write;
+
+use anyhow::Result;
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+#[cfg(test)]
+#[path = "html_tests.rs"]
+mod html_tests;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { background: #f0f0f0; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+tr.locked td { background: #fde8e8; }";
+
+/// Writes a page with the summary, the accounts of the csv export and those
+/// of them that are locked.
+pub(super) fn write_report(summary: &[(&str, String)], accounts: &[u8], mut writer: impl Write) -> Result<()> {
+    let rows = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(accounts)
+        .into_records()
+        .collect::<Result<Vec<StringRecord>, _>>()?;
+    let (header, records) = rows
+        .split_first()
+        .map_or((None, &[][..]), |(header, records)| (Some(header), records));
+    let locked_column = header.and_then(|header| header.iter().position(|name| name == "locked"));
+    let is_locked = |record: &StringRecord| locked_column.and_then(|i| record.get(i)) == Some("true");
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>")?;
+    writeln!(writer, "<head>")?;
+    writeln!(writer, "<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>Accounts report</title>")?;
+    writeln!(writer, "<style>\n{}\n</style>", STYLE)?;
+    writeln!(writer, "</head>")?;
+    writeln!(writer, "<body>")?;
+
+    writeln!(writer, "<h1>Summary</h1>")?;
+    writeln!(writer, "<table>")?;
+    for (name, value) in summary {
+        writeln!(
+            writer,
+            "<tr><th>{}</th><td class=\"number\">{}</td></tr>",
+            escape(name),
+            escape(value)
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h1>Accounts</h1>")?;
+    write_table(&mut writer, header, records.iter(), &is_locked)?;
+
+    writeln!(writer, "<h1>Locked accounts</h1>")?;
+    match locked_column {
+        None if header.is_some() => writeln!(
+            writer,
+            "<p>The exported columns don't say which accounts are locked.</p>"
+        )?,
+        _ => write_table(
+            &mut writer,
+            header,
+            records.iter().filter(|record| is_locked(record)),
+            &|_| false,
+        )?,
+    }
+
+    writeln!(writer, "</body>")?;
+    writeln!(writer, "</html>")?;
+
+    Ok(())
+}
+
+fn write_table<'a>(
+    writer: &mut impl Write,
+    header: Option<&StringRecord>,
+    mut records: impl Iterator<Item = &'a StringRecord>,
+    is_locked: &dyn Fn(&StringRecord) -> bool,
+) -> Result<()> {
+    let (Some(header), Some(first)) = (header, records.next()) else {
+        writeln!(writer, "<p>None.</p>")?;
+        return Ok(());
+    };
+
+    writeln!(writer, "<table>")?;
+    write!(writer, "<tr>")?;
+    for name in header {
+        write!(writer, "<th>{}</th>", escape(name))?;
+    }
+    writeln!(writer, "</tr>")?;
+    for record in std::iter::once(first).chain(records) {
+        match is_locked(record) {
+            true => write!(writer, "<tr class=\"locked\">")?,
+            false => write!(writer, "<tr>")?,
+        }
+        for cell in record {
+            match cell.parse::<Decimal>() {
+                Ok(_) => write!(writer, "<td class=\"number\">{}</td>", escape(cell))?,
+                Err(_) => write!(writer, "<td>{}</td>", escape(cell))?,
+            }
+        }
+        writeln!(writer, "</tr>")?;
+    }
+    writeln!(writer, "</table>")?;
+
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
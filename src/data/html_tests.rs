@@ -0,0 +1,34 @@
+use anyhow::Result;
+
+use super::*;
+
+#[test]
+fn test_write_report() -> Result<()> {
+    let accounts = "client,total,locked,display_name
+1,10.5,false,Alice & Bob
+2,0,true,<script>
+";
+    let mut output = Vec::new();
+    write_report(&[("Accounts", "2".to_string())], accounts.as_bytes(), &mut output)?;
+    let html = String::from_utf8(output)?;
+
+    assert!(html.contains("<tr><th>Accounts</th><td class=\"number\">2</td></tr>"));
+    assert!(html.contains(
+        "<tr><td class=\"number\">1</td><td class=\"number\">10.5</td><td>false</td><td>Alice &amp; Bob</td></tr>"
+    ));
+    let locked = "<tr class=\"locked\"><td class=\"number\">2</td>";
+    assert_eq!(html.matches(locked).count(), 1);
+    let (_, locked_section) = html.split_once("<h1>Locked accounts</h1>").unwrap();
+    assert!(locked_section.contains("<td>&lt;script&gt;</td>"));
+    assert!(!locked_section.contains("Alice"));
+
+    let mut output = Vec::new();
+    write_report(&[], b"client,total\n1,5\n", &mut output)?;
+    assert!(String::from_utf8(output)?.contains("<p>The exported columns don't say which accounts are locked.</p>"));
+
+    let mut output = Vec::new();
+    write_report(&[], b"", &mut output)?;
+    assert_eq!(String::from_utf8(output)?.matches("<p>None.</p>").count(), 2);
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+use csv::ByteRecord;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{ParseError, RecordReader, TransactionRecord, HEADERS};
+
+#[cfg(test)]
+#[path = "jsonl_tests.rs"]
+mod jsonl_tests;
+
+/// Reads `TransactionRecord`s from JSON lines, one object per line. Each
+/// object is turned into a row with the csv headers and parsed like one, so
+/// both formats accept the same values (e.g. an amount as a number or a
+/// string). Empty lines are skipped.
+pub struct JsonlReader<R> {
+    reader: R,
+    buffer: String,
+    headers: ByteRecord,
+    record: ByteRecord,
+    line: u64,
+}
+
+impl<R: BufRead> JsonlReader<R> {
+    pub fn new(reader: R) -> JsonlReader<R> {
+        JsonlReader {
+            reader,
+            buffer: String::new(),
+            headers: ByteRecord::from(HEADERS.to_vec()),
+            record: ByteRecord::new(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> RecordReader for JsonlReader<R> {
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>> {
+        loop {
+            self.buffer.clear();
+            self.record.clear();
+
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(err) => return Some(Err(err.into())),
+            }
+
+            if self.buffer.trim().is_empty() {
+                continue;
+            }
+
+            let object = match serde_json::from_str(&self.buffer) {
+                Ok(Value::Object(object)) => object,
+                Ok(_) => return Some(Err(ParseError::Invalid("record is not an object"))),
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            for header in HEADERS {
+                match object.get(header) {
+                    None | Some(Value::Null) => self.record.push_field(b""),
+                    Some(Value::String(field)) => self.record.push_field(field.trim().as_bytes()),
+                    Some(field) => self.record.push_field(field.to_string().as_bytes()),
+                }
+            }
+
+            return Some(self.record.deserialize(Some(&self.headers)).map_err(ParseError::from));
+        }
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn headers(&self) -> &ByteRecord {
+        &self.headers
+    }
+
+    fn raw_record(&self) -> &ByteRecord {
+        &self.record
+    }
+}
+
+pub(super) fn write_records(records: impl Iterator<Item = impl Serialize>, writer: impl Write) -> Result<()> {
+    let mut writer = std::io::BufWriter::new(writer);
+    for record in records {
+        serde_json::to_writer(&mut writer, &record)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
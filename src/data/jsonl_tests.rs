@@ -0,0 +1,66 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::timestamp::Timestamp;
+use crate::data::{export, process_records, Format, ProcessingOptions, RecordError, TransactionType};
+
+#[test]
+fn test_reader() -> Result<()> {
+    let data = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 1.5}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": "0.25", "timestamp": 1714557600}
+
+{"type": "dispute", "client": 1, "tx": 1}
+{"type": "resolve", "client": 1, "tx": 1, "amount": null}
+"#;
+
+    let mut reader = JsonlReader::new(data.as_bytes());
+    let mut records = Vec::new();
+    while let Some(record) = reader.read_record() {
+        records.push((reader.line(), record?));
+    }
+
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0].0, 1);
+    assert_eq!(records[0].1.type_, TransactionType::Deposit);
+    assert_eq!(records[0].1.amount, Some(dec!(1.5)));
+    assert_eq!(records[1].1.amount, Some(dec!(0.25)));
+    assert_eq!(records[1].1.timestamp, Timestamp::from_unix(1714557600));
+    assert_eq!(records[2].0, 4);
+    assert_eq!(records[2].1.type_, TransactionType::Dispute);
+    assert_eq!(records[2].1.amount, None);
+    assert_eq!(records[3].1.amount, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_process_and_export() -> Result<()> {
+    let data = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 10}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": 50}
+[1, 2, 3]
+{"type": "withdrawal", "client": 1, "tx": 3, "amount": 2.5}
+"#;
+
+    let mut ledger = Ledger::new();
+    let report = process_records(
+        &mut JsonlReader::new(data.as_bytes()),
+        &mut ledger,
+        ProcessingOptions::default(),
+    )?;
+    assert_eq!(report.accepted, 2);
+    assert_eq!(report.errors.len(), 2);
+    assert!(matches!(report.errors[0].error, RecordError::Ledger(_)));
+    assert!(matches!(report.errors[1].error, RecordError::Parse(_)));
+
+    let mut output = Vec::new();
+    export(&ledger, Format::Jsonl, &mut output)?;
+    assert_eq!(
+        String::from_utf8(output)?,
+        "{\"client\":1,\"available\":\"7.5\",\"held\":\"0\",\"total\":\"7.5\",\"locked\":false}\n"
+    );
+
+    Ok(())
+}
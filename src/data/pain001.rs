@@ -0,0 +1,126 @@
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use rust_decimal::Decimal;
+
+use super::statement::{elements, fields, hash, Entry};
+use crate::accounting::{ClientId, Map};
+
+#[cfg(test)]
+#[path = "pain001_tests.rs"]
+mod pain001_tests;
+
+#[derive(Default)]
+struct Instruction<'a> {
+    line: u64,
+    id: Option<&'a str>,
+    end_to_end_id: Option<&'a str>,
+    amount: Option<&'a str>,
+    currency: Option<&'a str>,
+}
+
+/// The credit transfers of an ISO 20022 pain.001 customer credit transfer
+/// initiation, of any version, as withdrawals of the client. The requested
+/// execution date of their payment is their timestamp.
+pub(super) fn pain001_entries(text: &str, client_id: ClientId) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut seen: Map<String, usize> = Map::default();
+
+    let mut message_id = "";
+    let mut payment_id = "";
+    let mut execution_date: Option<&str> = None;
+    let mut in_execution_date = false;
+    let mut instruction: Option<Instruction> = None;
+    for (line, tag, value) in elements(text) {
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        // Namespace prefixes, e.g. `<p:Document>`, don't matter.
+        let closing = name.starts_with('/');
+        let name = name.trim_start_matches('/');
+        let name = name.rsplit(':').next().unwrap_or(name);
+
+        match (name, closing, &mut instruction) {
+            ("MsgId", false, _) => message_id = value,
+            ("PmtInfId", false, _) => payment_id = value,
+            // Just a date before version 8 of pain.001, a Dt or DtTm
+            // element after.
+            ("ReqdExctnDt", false, _) => {
+                execution_date = Some(value).filter(|value| !value.is_empty());
+                in_execution_date = true;
+            },
+            ("ReqdExctnDt", true, _) => in_execution_date = false,
+            ("Dt" | "DtTm", false, _) if in_execution_date => execution_date = Some(value),
+            ("PmtInf", false, _) => execution_date = None,
+            ("CdtTrfTxInf", false, _) => {
+                instruction = Some(Instruction {
+                    line,
+                    ..Instruction::default()
+                })
+            },
+            ("CdtTrfTxInf", true, _) => {
+                if let Some(instruction) = instruction.take() {
+                    // Instructions are told apart by their ids within the
+                    // message and payment, which may be NOTPROVIDED.
+                    let key = format!(
+                        "{}\n{}\n{}\n{}",
+                        message_id,
+                        payment_id,
+                        instruction.id.unwrap_or_default(),
+                        instruction.end_to_end_id.unwrap_or_default(),
+                    );
+                    let occurrence = seen.entry(key.clone()).or_default();
+                    *occurrence += 1;
+                    let id = hash(format!("{}\n{}", key, occurrence).as_bytes());
+
+                    let fields = (|| {
+                        let amount: Decimal = instruction
+                            .amount
+                            .ok_or("credit transfer without an instructed amount")?
+                            .parse()
+                            .map_err(|_| "invalid amount")?;
+                        if amount <= Decimal::ZERO {
+                            return Err("invalid amount");
+                        }
+                        let timestamp = execution_date
+                            .map(|date| parse_iso_date(date).ok_or("invalid date"))
+                            .transpose()?;
+
+                        // Paid out of the client's account.
+                        Ok(fields(client_id, id, -amount, instruction.currency, timestamp))
+                    })();
+                    entries.push(Entry {
+                        line: instruction.line,
+                        fields,
+                    });
+                }
+            },
+            ("InstrId", false, Some(instruction)) => instruction.id = Some(value),
+            ("EndToEndId", false, Some(instruction)) => instruction.end_to_end_id = Some(value),
+            ("InstdAmt", false, Some(instruction)) => {
+                instruction.amount = Some(value);
+                instruction.currency = attribute(attributes, "Ccy");
+            },
+            _ => {},
+        }
+    }
+
+    entries
+}
+
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    attributes.split_whitespace().find_map(|attribute| {
+        let (key, value) = attribute.split_once('=')?;
+        (key == name).then(|| value.trim_end_matches('/').trim_matches(['"', '\'']))
+    })
+}
+
+// E.g. `2024-05-01` or `2024-05-01T12:00:00+02:00`, in UTC without an
+// offset.
+fn parse_iso_date(value: &str) -> Option<i64> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Some(date_time.timestamp());
+    }
+    if let Ok(date_time) = value.parse::<chrono::NaiveDateTime>() {
+        return Some(date_time.and_utc().timestamp());
+    }
+
+    let date: NaiveDate = value.parse().ok()?;
+    Some(date.and_time(NaiveTime::MIN).and_utc().timestamp())
+}
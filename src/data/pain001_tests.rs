@@ -0,0 +1,101 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::{Deposit, Transaction};
+use crate::data::{
+    process_records, ProcessingOptions, RecordReader, StatementFormat, StatementReader, TransactionType,
+};
+
+const PAIN_001: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.09">
+  <CstmrCdtTrfInitn>
+    <GrpHdr>
+      <MsgId>MSG-1</MsgId>
+      <CreDtTm>2024-05-01T08:00:00</CreDtTm>
+      <NbOfTxs>4</NbOfTxs>
+    </GrpHdr>
+    <PmtInf>
+      <PmtInfId>PMT-1</PmtInfId>
+      <PmtMtd>TRF</PmtMtd>
+      <ReqdExctnDt><Dt>2024-05-02</Dt></ReqdExctnDt>
+      <Dbtr><Nm>Acme &amp; Co</Nm></Dbtr>
+      <CdtTrfTxInf>
+        <PmtId><InstrId>1</InstrId><EndToEndId>INV-1</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="EUR">100.25</InstdAmt></Amt>
+        <Cdtr><Nm>Supplier</Nm></Cdtr>
+      </CdtTrfTxInf>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>NOTPROVIDED</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="EUR">10</InstdAmt></Amt>
+      </CdtTrfTxInf>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>NOTPROVIDED</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="EUR">10</InstdAmt></Amt>
+      </CdtTrfTxInf>
+    </PmtInf>
+    <PmtInf>
+      <PmtInfId>PMT-2</PmtInfId>
+      <ReqdExctnDt>2024-05-03</ReqdExctnDt>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>INV-2</EndToEndId></PmtId>
+        <Amt><EqvtAmt><Amt Ccy="USD">5</Amt><CcyOfTrf>EUR</CcyOfTrf></EqvtAmt></Amt>
+      </CdtTrfTxInf>
+    </PmtInf>
+  </CstmrCdtTrfInitn>
+</Document>
+"#;
+
+#[test]
+fn test_pain001() -> Result<()> {
+    let mut reader = StatementReader::new(PAIN_001.as_bytes(), StatementFormat::Pain001, 7)?;
+    let mut records = Vec::new();
+    while let Some(record) = reader.read_record() {
+        records.push((reader.line(), record.map_err(|err| err.to_string())));
+    }
+    assert_eq!(records.len(), 4);
+
+    let (line, transfer) = &records[0];
+    let transfer = transfer.as_ref().unwrap();
+    assert_eq!(*line, 14);
+    assert_eq!(transfer.type_, TransactionType::Withdrawal);
+    assert_eq!(transfer.client_id, 7);
+    assert_eq!(transfer.amount, Some(dec!(100.25)));
+    assert_eq!(transfer.currency, Some("EUR".parse()?));
+    assert_eq!(transfer.timestamp, Some("2024-05-02T00:00:00Z".parse()?));
+
+    // Transfers without ids of their own are still told apart.
+    let first = records[1].1.as_ref().unwrap();
+    let second = records[2].1.as_ref().unwrap();
+    assert_ne!(first.id, second.id);
+    assert_ne!(first.id, transfer.id);
+
+    assert_eq!(
+        records[3].1.as_ref().unwrap_err(),
+        "credit transfer without an instructed amount"
+    );
+
+    assert_eq!(parse_iso_date("2024-05-03"), Some(1714694400));
+    assert_eq!(parse_iso_date("2024-05-03T02:00:00+02:00"), Some(1714694400));
+    assert_eq!(parse_iso_date("2024-05-03T00:00:00"), Some(1714694400));
+    assert_eq!(parse_iso_date("03.05.2024"), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_process_pain001() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 7, dec!(500))?))?;
+    for _ in 0..2 {
+        let mut reader = StatementReader::new(PAIN_001.as_bytes(), StatementFormat::Pain001, 7)?;
+        process_records(&mut reader, &mut ledger, ProcessingOptions::default())?;
+    }
+
+    // Delivering the instructions twice didn't pay them twice.
+    assert_eq!(ledger.clients.get(&7).unwrap().available(), dec!(379.75));
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+
+use anyhow::Result;
+use csv::ByteRecord;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, Int32Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::SerializedFileReader;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::record::reader::RowIter;
+use parquet::record::Field;
+use parquet::schema::parser::parse_message_type;
+
+use super::{ExportRecord, ParseError, RecordReader, TransactionRecord, EXPORT_CHUNK_SIZE, HEADERS};
+
+#[cfg(test)]
+#[path = "parquet_tests.rs"]
+mod parquet_tests;
+
+// Amounts are kept as strings, so they round trip exactly. Wide client ids
+// (see `ClientId`) still fit in an INT32, unsigned.
+#[cfg(not(feature = "wide-ids"))]
+const ACCOUNTS_SCHEMA: &str = "
+message accounts {
+    REQUIRED INT32 client (INTEGER(16, false));
+    REQUIRED BINARY available (UTF8);
+    REQUIRED BINARY held (UTF8);
+    REQUIRED BINARY total (UTF8);
+    REQUIRED BOOLEAN locked;
+}
+";
+#[cfg(feature = "wide-ids")]
+const ACCOUNTS_SCHEMA: &str = "
+message accounts {
+    REQUIRED INT32 client (INTEGER(32, false));
+    REQUIRED BINARY available (UTF8);
+    REQUIRED BINARY held (UTF8);
+    REQUIRED BINARY total (UTF8);
+    REQUIRED BOOLEAN locked;
+}
+";
+
+/// Reads `TransactionRecord`s from a parquet file with `type`, `client`,
+/// `tx` and `amount` columns. Like `JsonlReader`, each row is turned into a
+/// csv row and parsed like one, so the columns can be of any type that reads
+/// as the right value (e.g. a decimal, a double or a string amount).
+pub struct ParquetReader {
+    rows: RowIter<'static>,
+    headers: ByteRecord,
+    record: ByteRecord,
+    line: u64,
+}
+
+impl ParquetReader {
+    pub fn new(file: File) -> Result<ParquetReader, ParseError> {
+        Ok(ParquetReader {
+            rows: SerializedFileReader::new(file)?.into_iter(),
+            headers: ByteRecord::from(HEADERS.to_vec()),
+            record: ByteRecord::new(),
+            line: 0,
+        })
+    }
+}
+
+impl RecordReader for ParquetReader {
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>> {
+        let row = match self.rows.next()? {
+            Ok(row) => row,
+            Err(err) => return Some(Err(err.into())),
+        };
+
+        self.line += 1;
+        self.record.clear();
+
+        for header in HEADERS {
+            match row.get_column_iter().find(|(name, _field)| *name == header) {
+                None | Some((_, Field::Null)) => self.record.push_field(b""),
+                Some((_, Field::Str(field))) => self.record.push_field(field.trim().as_bytes()),
+                // Read as seconds, see `Timestamp`.
+                Some((_, Field::TimestampMillis(millis))) => {
+                    self.record.push_field(millis.div_euclid(1_000).to_string().as_bytes())
+                },
+                Some((_, Field::TimestampMicros(micros))) => self
+                    .record
+                    .push_field(micros.div_euclid(1_000_000).to_string().as_bytes()),
+                Some((_, field)) => self.record.push_field(field.to_string().as_bytes()),
+            }
+        }
+
+        Some(self.record.deserialize(Some(&self.headers)).map_err(ParseError::from))
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn headers(&self) -> &ByteRecord {
+        &self.headers
+    }
+
+    fn raw_record(&self) -> &ByteRecord {
+        &self.record
+    }
+}
+
+pub(super) fn write_records(records: impl Iterator<Item = ExportRecord>, writer: impl Write + Send) -> Result<()> {
+    write_row_groups(records, writer, EXPORT_CHUNK_SIZE)
+}
+
+// Writes a row group per `chunk_size` records, so that no more than that are
+// held at once.
+fn write_row_groups<W: Write + Send>(
+    mut records: impl Iterator<Item = ExportRecord>,
+    writer: W,
+    chunk_size: usize,
+) -> Result<()> {
+    let schema = Arc::new(parse_message_type(ACCOUNTS_SCHEMA)?);
+    let mut writer = SerializedFileWriter::new(writer, schema, Arc::new(WriterProperties::builder().build()))?;
+
+    let mut chunk = Vec::new();
+    loop {
+        chunk.clear();
+        chunk.extend(records.by_ref().take(chunk_size));
+        if chunk.is_empty() {
+            break;
+        }
+
+        write_row_group(&mut writer, &chunk)?;
+    }
+
+    writer.close()?;
+
+    Ok(())
+}
+
+fn write_row_group<W: Write + Send>(writer: &mut SerializedFileWriter<W>, records: &[ExportRecord]) -> Result<()> {
+    let decimals = |f: fn(&ExportRecord) -> String| -> Vec<ByteArray> {
+        records
+            .iter()
+            .map(|record| ByteArray::from(f(record).as_str()))
+            .collect()
+    };
+
+    let clients: Vec<i32> = records.iter().map(|record| record.id as i32).collect();
+    let available = decimals(|record| record.available.to_string());
+    let held = decimals(|record| record.held.to_string());
+    let total = decimals(|record| record.total.to_string());
+    let locked: Vec<bool> = records.iter().map(|record| record.locked).collect();
+
+    let mut row_group = writer.next_row_group()?;
+
+    let mut column = row_group.next_column()?.expect("client column");
+    column.typed::<Int32Type>().write_batch(&clients, None, None)?;
+    column.close()?;
+
+    for values in [available, held, total] {
+        let mut column = row_group.next_column()?.expect("amount column");
+        column.typed::<ByteArrayType>().write_batch(&values, None, None)?;
+        column.close()?;
+    }
+
+    let mut column = row_group.next_column()?.expect("locked column");
+    column.typed::<BoolType>().write_batch(&locked, None, None)?;
+    column.close()?;
+
+    row_group.close()?;
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::sync::Arc;
+
+use anyhow::Result;
+use parquet::data_type::{ByteArray, DoubleType, Int32Type, Int64Type};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::data::{export, process_records, ClientRecord, Format, ProcessingOptions};
+
+fn write_transactions(file: File) -> Result<()> {
+    let schema = parse_message_type(
+        "
+        message transactions {
+            REQUIRED BINARY type (UTF8);
+            REQUIRED INT32 client;
+            REQUIRED INT64 tx;
+            OPTIONAL DOUBLE amount;
+        }
+        ",
+    )?;
+
+    let types: Vec<ByteArray> = ["deposit", "deposit", "dispute", "withdrawal"]
+        .into_iter()
+        .map(ByteArray::from)
+        .collect();
+
+    let mut writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(WriterProperties::builder().build()))?;
+    let mut row_group = writer.next_row_group()?;
+
+    let mut column = row_group.next_column()?.unwrap();
+    column.typed::<ByteArrayType>().write_batch(&types, None, None)?;
+    column.close()?;
+
+    let mut column = row_group.next_column()?.unwrap();
+    column.typed::<Int32Type>().write_batch(&[1, 1, 1, 1], None, None)?;
+    column.close()?;
+
+    let mut column = row_group.next_column()?.unwrap();
+    column.typed::<Int64Type>().write_batch(&[1, 2, 1, 3], None, None)?;
+    column.close()?;
+
+    let mut column = row_group.next_column()?.unwrap();
+    column
+        .typed::<DoubleType>()
+        .write_batch(&[10.5, 2.25, 1.0], Some(&[1, 1, 0, 1]), None)?;
+    column.close()?;
+
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_process_and_export() -> Result<()> {
+    let transactions = tempfile::NamedTempFile::new()?;
+    write_transactions(transactions.reopen()?)?;
+
+    let mut ledger = Ledger::new();
+    let mut reader = ParquetReader::new(transactions.reopen()?)?;
+    let report = process_records(&mut reader, &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 4);
+
+    let client = ledger.clients.get(&1).unwrap();
+    assert_eq!(client.available(), dec!(1.25));
+    assert_eq!(client.held(), dec!(10.5));
+
+    let accounts = tempfile::NamedTempFile::new()?;
+    export(&ledger, Format::Parquet, accounts.reopen()?)?;
+
+    let rows = SerializedFileReader::new(accounts.reopen()?)?
+        .into_iter()
+        .map(|row| Ok(row?.to_string()))
+        .collect::<Result<Vec<String>>>()?;
+    assert_eq!(
+        rows,
+        vec![r#"{client: 1, available: "1.25", held: "10.5", total: "11.75", locked: false}"#]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_row_groups() -> Result<()> {
+    let records = (1..=5).map(|id| {
+        ClientRecord {
+            id,
+            available: dec!(1),
+            held: dec!(0),
+            total: dec!(1),
+            locked: false,
+            currency: None,
+            total_deposited: None,
+            total_withdrawn: None,
+            dispute_count: None,
+            chargeback_count: None,
+        }
+        .with_places(None)
+    });
+    let accounts = tempfile::NamedTempFile::new()?;
+    write_row_groups(records, accounts.reopen()?, 2)?;
+
+    let reader = SerializedFileReader::new(accounts.reopen()?)?;
+    let row_groups: Vec<i64> = reader
+        .metadata()
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows())
+        .collect();
+    assert_eq!(row_groups, vec![2, 2, 1]);
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use thiserror::Error;
+
+use super::{stored_amount, ClientRecord, LedgerEvent, TransactionDataError, TransactionRecord, TransactionType};
+use crate::accounting::ledger::Ledger;
+use crate::accounting::transactions::Transaction;
+use crate::accounting::{ClientId, TransactionError};
+
+#[cfg(test)]
+#[path = "replay_tests.rs"]
+mod replay_tests;
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("failed to read event, err={0}")]
+    Read(#[from] serde_json::Error),
+    #[error("event on line {line} is invalid, err={error}")]
+    Invalid { line: u64, error: TransactionDataError },
+    #[error("event on line {line} failed to execute, err={error}")]
+    Failed { line: u64, error: TransactionError },
+    #[error("event on line {line} doesn't match the account of client {client} after executing it")]
+    Diverged { line: u64, client: ClientId },
+}
+
+/// A client whose account differs from the expected one, see
+/// `Ledger::verify_accounts`. Either side is `None` if the client is missing
+/// from it.
+#[derive(Debug, PartialEq)]
+pub struct AccountMismatch {
+    pub client: ClientId,
+    pub expected: Option<ClientRecord>,
+    pub actual: Option<ClientRecord>,
+}
+
+impl Ledger {
+    /// Rebuilds a ledger from the events of a run, see
+    /// `ProcessingOptions::events`. Every transaction is executed again, in
+    /// the same order, and the client's account after it has to match the
+    /// one in its event. Otherwise the log was changed, or written by a
+    /// ledger with different rules, and replaying fails with Diverged.
+    pub fn replay(events: impl IntoIterator<Item = LedgerEvent>) -> Result<Ledger, ReplayError> {
+        let mut ledger = Ledger::new();
+        for event in events {
+            ledger.replay_event(&event)?;
+        }
+
+        Ok(ledger)
+    }
+
+    /// Same as `replay`, reading the events as JSON lines.
+    pub fn replay_reader(reader: impl Read) -> Result<Ledger, ReplayError> {
+        let mut ledger = Ledger::new();
+        for event in serde_json::Deserializer::from_reader(reader).into_iter::<LedgerEvent>() {
+            ledger.replay_event(&event?)?;
+        }
+
+        Ok(ledger)
+    }
+
+    /// Compares the accounts with the expected ones, e.g. an export read with
+    /// `read_accounts`. Returns every client that doesn't match, ordered by
+    /// id.
+    pub fn verify_accounts(&self, expected: impl IntoIterator<Item = ClientRecord>) -> Vec<AccountMismatch> {
+        let mut actual: BTreeMap<ClientId, ClientRecord> = self
+            .clients_iter()
+            .map(|(id, client)| (*id, ClientRecord::from(client)))
+            .collect();
+
+        let mut mismatches = Vec::new();
+        for mut expected in expected {
+            // They aren't balances, and a replayed ledger doesn't keep them.
+            expected.set_lifetime_totals(None);
+            let actual = actual.remove(&expected.id);
+            if actual.as_ref() != Some(&expected) {
+                mismatches.push(AccountMismatch {
+                    client: expected.id,
+                    expected: Some(expected),
+                    actual,
+                });
+            }
+        }
+        mismatches.extend(actual.into_values().map(|actual| AccountMismatch {
+            client: actual.id,
+            expected: None,
+            actual: Some(actual),
+        }));
+
+        mismatches.sort_by_key(|mismatch| mismatch.client);
+        mismatches
+    }
+
+    fn replay_event(&mut self, event: &LedgerEvent) -> Result<(), ReplayError> {
+        let amount = match event.type_ {
+            // Events about all of a deposit carry its amount, see `LedgerEvent`.
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback
+                if stored_amount(self, event.tx) == Ok(event.amount) =>
+            {
+                None
+            },
+            _ => event.amount,
+        };
+        let record = TransactionRecord {
+            id: event.tx,
+            type_: event.type_,
+            client_id: event.client,
+            amount,
+            currency: event.currency,
+            timestamp: event.timestamp,
+        };
+        let transaction = Transaction::try_from(record).map_err(|error| ReplayError::Invalid {
+            line: event.line,
+            error,
+        })?;
+
+        self.set_position(event.line);
+        self.execute_transaction(transaction)
+            .map_err(|error| ReplayError::Failed {
+                line: event.line,
+                error,
+            })?;
+
+        if LedgerEvent::new(event.line, &record, self).as_ref() != Ok(event) {
+            return Err(ReplayError::Diverged {
+                line: event.line,
+                client: event.client,
+            });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::data::{export, process_reader, read_accounts, Format, ProcessingOptions};
+
+const DATA: &str = "type,client,tx,amount
+deposit,1,1,10
+deposit,2,2,5
+withdrawal,1,3,4
+dispute,1,1,
+withdrawal,2,4,50
+deposit,2,2,5
+resolve,1,1,
+dispute,2,2,
+chargeback,2,2,
+";
+
+// Processes the data, returning the event log and the exported accounts.
+fn run(data: &str) -> Result<(String, Vec<u8>)> {
+    let events = tempfile::NamedTempFile::new()?;
+    let options = ProcessingOptions {
+        events: Some(Box::new(events.reopen()?)),
+        ..ProcessingOptions::default()
+    };
+    let mut ledger = Ledger::new();
+    process_reader(data.as_bytes(), &mut ledger, options)?;
+
+    let mut accounts = Vec::new();
+    export(&ledger, Format::Csv, &mut accounts)?;
+
+    Ok((std::fs::read_to_string(events.path())?, accounts))
+}
+
+#[test]
+fn test_replay() -> Result<()> {
+    let (events, accounts) = run(DATA)?;
+
+    let ledger = Ledger::replay_reader(events.as_bytes())?;
+    assert_eq!(
+        ledger.verify_accounts(read_accounts(accounts.as_slice(), Format::Csv)?),
+        vec![]
+    );
+    assert_eq!(ledger.position(), 10);
+
+    let events = events
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<serde_json::Result<Vec<LedgerEvent>>>()?;
+    assert_eq!(events.len(), 7);
+    let replayed = Ledger::replay(events)?;
+    assert_eq!(replayed.stats(), ledger.stats());
+
+    Ok(())
+}
+
+#[test]
+fn test_replay_diverged() -> Result<()> {
+    let (events, _) = run(DATA)?;
+
+    // The withdrawal's amount is changed, the balances after it aren't.
+    let tampered = events.replacen(r#""amount":"4""#, r#""amount":"3""#, 1);
+    assert_ne!(tampered, events);
+    if let Err(err) = Ledger::replay_reader(tampered.as_bytes()) {
+        assert!(matches!(err, ReplayError::Diverged { line: 4, client: 1 }));
+    } else {
+        bail!("tampered log replayed");
+    }
+
+    // A dispute of a deposit that isn't in the log.
+    let missing = events.lines().skip(1).collect::<Vec<_>>().join("\n");
+    if let Err(err) = Ledger::replay_reader(missing.as_bytes()) {
+        assert!(matches!(err, ReplayError::Failed { line: 4, .. }));
+    } else {
+        bail!("incomplete log replayed");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_accounts() -> Result<()> {
+    let (events, accounts) = run(DATA)?;
+    let ledger = Ledger::replay_reader(events.as_bytes())?;
+
+    let mut expected = read_accounts(accounts.as_slice(), Format::Csv)?;
+    expected.sort_by_key(|account| account.id);
+    let actual = expected.clone();
+    expected[0].available = dec!(7);
+    expected.remove(1);
+    expected.push(ClientRecord {
+        id: 3,
+        available: dec!(1),
+        held: dec!(0),
+        total: dec!(1),
+        locked: false,
+        currency: None,
+        total_deposited: None,
+        total_withdrawn: None,
+        dispute_count: None,
+        chargeback_count: None,
+    });
+
+    let mismatches = ledger.verify_accounts(expected.clone());
+    assert_eq!(
+        mismatches,
+        vec![
+            AccountMismatch {
+                client: 1,
+                expected: Some(expected[0].clone()),
+                actual: Some(actual[0].clone()),
+            },
+            AccountMismatch {
+                client: 2,
+                expected: None,
+                actual: Some(actual[1].clone()),
+            },
+            AccountMismatch {
+                client: 3,
+                expected: Some(expected[1].clone()),
+                actual: None,
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_replay_partial_disputes() -> Result<()> {
+    let (events, accounts) = run("type,client,tx,amount
+deposit,1,1,10
+dispute,1,1,4
+resolve,1,1,1
+chargeback,1,1,
+dispute,1,1,
+resolve,1,1,
+")?;
+
+    let ledger = Ledger::replay_reader(events.as_bytes())?;
+    assert_eq!(
+        ledger.verify_accounts(read_accounts(accounts.as_slice(), Format::Csv)?),
+        vec![]
+    );
+    assert_eq!(ledger.clients[&1].available(), dec!(7));
+
+    Ok(())
+}
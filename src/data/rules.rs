@@ -0,0 +1,100 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use super::{TransactionRecord, TransactionType};
+use crate::accounting::client::Client;
+use crate::accounting::{ClientId, Map};
+
+#[cfg(test)]
+#[path = "rules_tests.rs"]
+mod rules_tests;
+
+/// A fraud rule, loaded from the `[[rules]]` of the config file and checked
+/// against every record before it's executed, see `ProcessingOptions::rules`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Shows up in the rejections and logs of the records it matches.
+    pub name: String,
+    pub when: Condition,
+    pub action: RuleAction,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Condition {
+    /// Records of these types with an amount over `amount`. All types if
+    /// `types` is empty.
+    AmountOver {
+        amount: Decimal,
+        #[serde(default)]
+        types: Vec<TransactionType>,
+    },
+    /// Disputes of a client that already had `count` disputes, in this run.
+    DisputesOver { count: usize },
+    /// Withdrawals of a client with an open dispute, i.e. held funds.
+    WithdrawalDuringDispute,
+}
+
+/// What happens to a record that matches a rule.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Rejected with `RuleRejected`, it isn't executed.
+    Reject,
+    /// Executed anyway, but logged and counted in
+    /// `ProcessingReport::flagged`.
+    Flag,
+    /// The client's account is frozen before the record is executed, see
+    /// `Freeze`.
+    Freeze,
+}
+
+/// Checks records against the rules, keeping track of what the rules need
+/// to know about the records before.
+#[derive(Debug, Default)]
+pub struct RuleEngine {
+    rules: Vec<Rule>,
+    disputes: Map<ClientId, usize>,
+}
+
+impl RuleEngine {
+    pub fn new(rules: Vec<Rule>) -> RuleEngine {
+        RuleEngine {
+            rules,
+            disputes: Map::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The rules the record matches, in the order they were given. `client`
+    /// is the record's client as it is before the record is executed, if it
+    /// exists.
+    pub fn evaluate(&mut self, record: &TransactionRecord, client: Option<&Client>) -> Vec<&Rule> {
+        let disputes = if record.type_ == TransactionType::Dispute {
+            let count = self.disputes.entry(record.client_id).or_default();
+            *count += 1;
+            *count - 1
+        } else {
+            0
+        };
+
+        self.rules
+            .iter()
+            .filter(|rule| match &rule.when {
+                Condition::AmountOver { amount, types } => {
+                    record.amount.map_or(false, |record_amount| record_amount > *amount)
+                        && (types.is_empty() || types.contains(&record.type_))
+                },
+                Condition::DisputesOver { count } => record.type_ == TransactionType::Dispute && disputes >= *count,
+                Condition::WithdrawalDuringDispute => {
+                    record.type_ == TransactionType::Withdrawal
+                        && client.map_or(false, |client| client.held() > Decimal::ZERO)
+                },
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+
+fn record(type_: TransactionType, client_id: ClientId, amount: Option<Decimal>) -> TransactionRecord {
+    TransactionRecord {
+        id: 1,
+        type_,
+        client_id,
+        amount,
+        currency: None,
+        timestamp: None,
+    }
+}
+
+fn names(rules: Vec<&Rule>) -> Vec<&str> {
+    rules.iter().map(|rule| rule.name.as_str()).collect()
+}
+
+#[test]
+fn test_amount_over() {
+    let mut engine = RuleEngine::new(vec![
+        Rule {
+            name: "any".to_string(),
+            when: Condition::AmountOver {
+                amount: dec!(100),
+                types: vec![],
+            },
+            action: RuleAction::Flag,
+        },
+        Rule {
+            name: "withdrawal".to_string(),
+            when: Condition::AmountOver {
+                amount: dec!(100),
+                types: vec![TransactionType::Withdrawal],
+            },
+            action: RuleAction::Reject,
+        },
+    ]);
+
+    let deposit = record(TransactionType::Deposit, 1, Some(dec!(100.01)));
+    assert_eq!(names(engine.evaluate(&deposit, None)), vec!["any"]);
+    let withdrawal = record(TransactionType::Withdrawal, 1, Some(dec!(101)));
+    assert_eq!(names(engine.evaluate(&withdrawal, None)), vec!["any", "withdrawal"]);
+    let withdrawal = record(TransactionType::Withdrawal, 1, Some(dec!(100)));
+    assert!(engine.evaluate(&withdrawal, None).is_empty());
+}
+
+#[test]
+fn test_disputes_over() {
+    let mut engine = RuleEngine::new(vec![Rule {
+        name: "disputes".to_string(),
+        when: Condition::DisputesOver { count: 2 },
+        action: RuleAction::Freeze,
+    }]);
+
+    let dispute = record(TransactionType::Dispute, 1, None);
+    assert!(engine.evaluate(&dispute, None).is_empty());
+    assert!(engine.evaluate(&dispute, None).is_empty());
+    // Counted per client.
+    assert!(engine
+        .evaluate(&record(TransactionType::Dispute, 2, None), None)
+        .is_empty());
+    assert_eq!(names(engine.evaluate(&dispute, None)), vec!["disputes"]);
+}
+
+#[test]
+fn test_withdrawal_during_dispute() -> Result<()> {
+    let mut engine = RuleEngine::new(vec![Rule {
+        name: "pattern".to_string(),
+        when: Condition::WithdrawalDuringDispute,
+        action: RuleAction::Reject,
+    }]);
+
+    let mut client = Client::new(1);
+    client.deposit(dec!(10))?;
+    let withdrawal = record(TransactionType::Withdrawal, 1, Some(dec!(1)));
+    assert!(engine.evaluate(&withdrawal, Some(&client)).is_empty());
+    client.hold_funds(dec!(5))?;
+    assert_eq!(names(engine.evaluate(&withdrawal, Some(&client))), vec!["pattern"]);
+    assert!(engine.evaluate(&withdrawal, None).is_empty());
+
+    Ok(())
+}
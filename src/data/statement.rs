@@ -0,0 +1,343 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveTime};
+use csv::ByteRecord;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+use super::pain001::pain001_entries;
+use super::{ParseError, RecordReader, TransactionRecord, HEADERS};
+use crate::accounting::{ClientId, Map, TxId};
+
+#[cfg(test)]
+#[path = "statement_tests.rs"]
+mod statement_tests;
+
+/// The bank statement formats read by `StatementReader`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatementFormat {
+    /// OFX, both the SGML of version 1 and the XML of version 2, and QFX.
+    Ofx,
+    /// QIF of bank, cash and credit card accounts.
+    Qif,
+    /// ISO 20022 pain.001 credit transfer initiations, whose transfers are
+    /// withdrawals.
+    Pain001,
+}
+
+impl StatementFormat {
+    /// The format matching the file's extension, if any.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<StatementFormat> {
+        match path.as_ref().extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "ofx" | "qfx" => Some(StatementFormat::Ofx),
+            "qif" => Some(StatementFormat::Qif),
+            "xml" => Some(StatementFormat::Pain001),
+            _ => None,
+        }
+    }
+}
+
+// A transaction of the statement as the fields of a csv row, or why it
+// can't be one.
+pub(super) struct Entry {
+    pub(super) line: u64,
+    pub(super) fields: Result<[String; 6], &'static str>,
+}
+
+/// Reads the transactions of a bank statement as those of a single client:
+/// credits are deposits and debits withdrawals, of the amount without its
+/// sign. Statements are small, so they're read in one go. Payment
+/// instructions are read the same way, see `StatementFormat::Pain001`.
+///
+/// The ids are the OFX FITIDs that are numbers, or else a hash of the
+/// FITID, or of the QIF record and how many of the same came before it. The
+/// same statement, or an overlapping one, can be read again without
+/// executing its transactions twice, as long as they don't happen to have
+/// the ids of other transactions of the ledger. The ids of pain.001
+/// transfers are a hash of their message, payment and instruction ids. QIF dates are read month
+/// first, e.g. `12/31/2024` or `12/31'24`, unless they start with the year.
+pub struct StatementReader {
+    entries: std::vec::IntoIter<Entry>,
+    headers: ByteRecord,
+    record: ByteRecord,
+    line: u64,
+}
+
+impl StatementReader {
+    pub fn new(mut reader: impl Read, format: StatementFormat, client_id: ClientId) -> io::Result<StatementReader> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        // OFX 1 files are often in a Windows code page rather than UTF-8,
+        // which only matters to the ids and payees.
+        let text = String::from_utf8_lossy(&bytes);
+
+        let entries = match format {
+            StatementFormat::Ofx => ofx_entries(&text, client_id),
+            StatementFormat::Qif => qif_entries(&text, client_id),
+            StatementFormat::Pain001 => pain001_entries(&text, client_id),
+        };
+
+        Ok(StatementReader {
+            entries: entries.into_iter(),
+            headers: ByteRecord::from(HEADERS.to_vec()),
+            record: ByteRecord::new(),
+            line: 0,
+        })
+    }
+}
+
+impl RecordReader for StatementReader {
+    fn read_record(&mut self) -> Option<Result<TransactionRecord, ParseError>> {
+        let entry = self.entries.next()?;
+        self.line = entry.line;
+        self.record.clear();
+
+        let fields = match entry.fields {
+            Ok(fields) => fields,
+            Err(err) => return Some(Err(ParseError::Invalid(err))),
+        };
+        for field in &fields {
+            self.record.push_field(field.as_bytes());
+        }
+
+        Some(self.record.deserialize(Some(&self.headers)).map_err(ParseError::from))
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn headers(&self) -> &ByteRecord {
+        &self.headers
+    }
+
+    fn raw_record(&self) -> &ByteRecord {
+        &self.record
+    }
+}
+
+pub(super) fn fields(
+    client_id: ClientId,
+    id: TxId,
+    amount: Decimal,
+    currency: Option<&str>,
+    timestamp: Option<i64>,
+) -> [String; 6] {
+    let type_ = match amount.is_sign_negative() {
+        true => "withdrawal",
+        false => "deposit",
+    };
+
+    [
+        type_.to_string(),
+        client_id.to_string(),
+        id.to_string(),
+        amount.abs().to_string(),
+        currency.unwrap_or_default().to_string(),
+        timestamp.map(|seconds| seconds.to_string()).unwrap_or_default(),
+    ]
+}
+
+// FNV-1a, which unlike the standard library's hashers is the same in every
+// build, so that the ids don't change.
+pub(super) fn hash(bytes: &[u8]) -> TxId {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Default)]
+struct OfxTransaction<'a> {
+    line: u64,
+    id: Option<&'a str>,
+    amount: Option<&'a str>,
+    posted: Option<&'a str>,
+}
+
+impl OfxTransaction<'_> {
+    fn entry(&self, client_id: ClientId, currency: Option<&str>) -> Entry {
+        let fields = (|| {
+            let id = self.id.ok_or("statement transaction without an id")?;
+            let id = id.parse().unwrap_or_else(|_| hash(id.as_bytes()));
+            let amount = self.amount.ok_or("statement transaction without an amount")?;
+            // Some banks write a decimal comma.
+            let amount = amount.replace(',', ".").parse().map_err(|_| "invalid amount")?;
+            let timestamp = self
+                .posted
+                .map(|posted| parse_ofx_date(posted).ok_or("invalid date"))
+                .transpose()?;
+
+            Ok(fields(client_id, id, amount, currency, timestamp))
+        })();
+
+        Entry {
+            line: self.line,
+            fields,
+        }
+    }
+}
+
+fn ofx_entries(text: &str, client_id: ClientId) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut currency = None;
+    let mut transaction: Option<OfxTransaction> = None;
+    for (line, tag, value) in elements(text) {
+        match (tag, &mut transaction) {
+            ("CURDEF", _) => currency = Some(value),
+            ("STMTTRN", _) => {
+                transaction = Some(OfxTransaction {
+                    line,
+                    ..OfxTransaction::default()
+                })
+            },
+            ("/STMTTRN", _) => {
+                if let Some(transaction) = transaction.take() {
+                    entries.push(transaction.entry(client_id, currency));
+                }
+            },
+            ("FITID", Some(transaction)) => transaction.id = Some(value),
+            ("TRNAMT", Some(transaction)) => transaction.amount = Some(value),
+            ("DTPOSTED", Some(transaction)) => transaction.posted = Some(value),
+            _ => {},
+        }
+    }
+
+    entries
+}
+
+// Every tag with the text after it, up to the next tag, and the line it's
+// on. That's all there is to both the SGML of OFX 1, where most elements
+// aren't closed, and the XML of OFX 2 and ISO 20022. Tags keep their
+// attributes.
+pub(super) fn elements(text: &str) -> Vec<(u64, &str, &str)> {
+    let mut parts = text.split('<');
+    let mut line = 1 + parts.next().map_or(0, |header| header.matches('\n').count() as u64);
+
+    let mut elements = Vec::new();
+    for part in parts {
+        let (tag, value) = part.split_once('>').unwrap_or((part, ""));
+        elements.push((line, tag.trim(), value.trim()));
+        line += part.matches('\n').count() as u64;
+    }
+
+    elements
+}
+
+// E.g. `20240501`, `20240501120000` or `20240501120000.000[-5:EST]`, in UTC
+// without an offset.
+fn parse_ofx_date(value: &str) -> Option<i64> {
+    let (date_time, offset) = match value.split_once('[') {
+        Some((date_time, zone)) => (date_time, zone.trim_end_matches(']').split(':').next()),
+        None => (value, None),
+    };
+    let digits = date_time.split('.').next()?;
+    if !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+
+    let number = |range: std::ops::Range<usize>| digits.get(range)?.parse::<u32>().ok();
+    let date = NaiveDate::from_ymd_opt(number(0..4)? as i32, number(4..6)?, number(6..8)?)?;
+    let time = match digits.len() {
+        8 => NaiveTime::MIN,
+        12 => NaiveTime::from_hms_opt(number(8..10)?, number(10..12)?, 0)?,
+        14 => NaiveTime::from_hms_opt(number(8..10)?, number(10..12)?, number(12..14)?)?,
+        _ => return None,
+    };
+    let offset_hours = match offset {
+        Some(hours) => hours.parse::<Decimal>().ok()?,
+        None => Decimal::ZERO,
+    };
+
+    Some(date.and_time(time).and_utc().timestamp() - (offset_hours * Decimal::from(3600)).to_i64()?)
+}
+
+fn qif_entries(text: &str, client_id: ClientId) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut seen: Map<String, usize> = Map::default();
+
+    let mut start = None;
+    let mut record = String::new();
+    let mut date = None;
+    let mut amount = None;
+    let mut end_record = |start: u64, record: &mut String, date: Option<&str>, amount: Option<&str>| {
+        let occurrence = seen.entry(record.clone()).or_default();
+        *occurrence += 1;
+        record.push_str(&occurrence.to_string());
+        let id = hash(record.as_bytes());
+        record.clear();
+
+        let fields = (|| {
+            let amount = amount.ok_or("statement transaction without an amount")?;
+            let amount = amount.replace(',', "").parse().map_err(|_| "invalid amount")?;
+            let timestamp = date
+                .map(|date| parse_qif_date(date).ok_or("invalid date"))
+                .transpose()?
+                .map(|date| date.and_time(NaiveTime::MIN).and_utc().timestamp());
+
+            Ok(fields(client_id, id, amount, None, timestamp))
+        })();
+        entries.push(Entry { line: start, fields });
+    };
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        // Headers, e.g. `!Type:Bank`.
+        if line.is_empty() || line.starts_with('!') {
+            continue;
+        }
+        if line == "^" {
+            if let Some(start) = start.take() {
+                end_record(start, &mut record, date.take(), amount.take());
+            }
+            continue;
+        }
+
+        start.get_or_insert(i as u64 + 1);
+        record.push_str(line);
+        record.push('\n');
+        let Some(code) = line.chars().next() else {
+            continue;
+        };
+        let value = &line[code.len_utf8()..];
+        match code {
+            'D' => date = Some(value),
+            // U is the same amount, written by newer versions.
+            'T' => amount = Some(value),
+            'U' => {
+                amount.get_or_insert(value);
+            },
+            _ => {},
+        }
+    }
+    // The last record may not be ended.
+    if let Some(start) = start {
+        end_record(start, &mut record, date, amount);
+    }
+
+    entries
+}
+
+// E.g. `12/31/2024`, `12/31'24` for 2024, `12/31/99` for 1999 or
+// `2024-12-31`.
+fn parse_qif_date(value: &str) -> Option<NaiveDate> {
+    let (value, century) = match value.split_once('\'') {
+        Some((month_day, year)) => (format!("{}/{}", month_day, year), 2000),
+        None => (value.to_string(), 1900),
+    };
+
+    let parts: Vec<&str> = value.split(['/', '-', '.']).map(str::trim).collect();
+    let [first, second, third] = parts[..] else {
+        return None;
+    };
+    let (year, month, day) = match first.len() {
+        4 => (first, second, third),
+        _ => (third, first, second),
+    };
+
+    let mut year: i32 = year.parse().ok()?;
+    if year < 100 {
+        year += century;
+    }
+    NaiveDate::from_ymd_opt(year, month.parse().ok()?, day.parse().ok()?)
+}
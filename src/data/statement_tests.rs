@@ -0,0 +1,174 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::data::{process_records, ProcessingOptions, TransactionType};
+
+const OFX_SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>USD
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240501120000.000[-5:EST]
+<TRNAMT>100.50
+<FITID>1001
+<NAME>Salary
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240502
+<TRNAMT>-20,25
+<FITID>A-2
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<FITID>3
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+";
+
+const OFX_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>EUR</CURDEF><BANKTRANLIST>
+<STMTTRN><TRNTYPE>CREDIT</TRNTYPE><DTPOSTED>20240501</DTPOSTED><TRNAMT>7.00</TRNAMT><FITID>9</FITID></STMTTRN>
+</BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>
+"#;
+
+const QIF: &str = "!Type:Bank
+D12/31'23
+T1,000.00
+PEmployer
+^
+D1/ 2/2024
+T-4.50
+PCoffee
+^
+D1/ 2/2024
+T-4.50
+PCoffee
+^
+D2024-13-01
+T5
+^
+D01/03/99
+U-1.00
+";
+
+fn read_all(reader: &mut StatementReader) -> Vec<(u64, Result<TransactionRecord, String>)> {
+    let mut records = Vec::new();
+    while let Some(record) = reader.read_record() {
+        records.push((reader.line(), record.map_err(|err| err.to_string())));
+    }
+
+    records
+}
+
+#[test]
+fn test_ofx() -> Result<()> {
+    let mut reader = StatementReader::new(OFX_SGML.as_bytes(), StatementFormat::Ofx, 7)?;
+    let records = read_all(&mut reader);
+    assert_eq!(records.len(), 3);
+
+    let (line, deposit) = &records[0];
+    let deposit = deposit.as_ref().unwrap();
+    assert_eq!(*line, 9);
+    assert_eq!(deposit.type_, TransactionType::Deposit);
+    assert_eq!(deposit.client_id, 7);
+    assert_eq!(deposit.id, 1001);
+    assert_eq!(deposit.amount, Some(dec!(100.50)));
+    assert_eq!(deposit.currency, Some("USD".parse()?));
+    assert_eq!(deposit.timestamp, Some("2024-05-01T17:00:00Z".parse()?));
+
+    let withdrawal = records[1].1.as_ref().unwrap();
+    assert_eq!(withdrawal.type_, TransactionType::Withdrawal);
+    assert_eq!(withdrawal.id, hash(b"A-2"));
+    assert_eq!(withdrawal.amount, Some(dec!(20.25)));
+    assert_eq!(withdrawal.timestamp, Some("2024-05-02T00:00:00Z".parse()?));
+
+    assert_eq!(
+        records[2].1.as_ref().unwrap_err(),
+        "statement transaction without an amount"
+    );
+
+    let mut reader = StatementReader::new(OFX_XML.as_bytes(), StatementFormat::Ofx, 7)?;
+    let records = read_all(&mut reader);
+    let deposit = records[0].1.as_ref().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(deposit.id, 9);
+    assert_eq!(deposit.amount, Some(dec!(7)));
+    assert_eq!(deposit.currency, Some("EUR".parse()?));
+
+    assert_eq!(parse_ofx_date("20240501120000[+5.5:IST]"), Some(1714545000));
+    assert_eq!(parse_ofx_date("202405"), None);
+    assert_eq!(parse_ofx_date("20241301"), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_qif() -> Result<()> {
+    let mut reader = StatementReader::new(QIF.as_bytes(), StatementFormat::Qif, 7)?;
+    let records = read_all(&mut reader);
+    assert_eq!(records.len(), 5);
+
+    let (line, deposit) = &records[0];
+    let deposit = deposit.as_ref().unwrap();
+    assert_eq!(*line, 2);
+    assert_eq!(deposit.type_, TransactionType::Deposit);
+    assert_eq!(deposit.amount, Some(dec!(1000)));
+    assert_eq!(deposit.timestamp, Some("2023-12-31T00:00:00Z".parse()?));
+
+    // The same coffee twice on a day is two transactions.
+    let first = records[1].1.as_ref().unwrap();
+    let second = records[2].1.as_ref().unwrap();
+    assert_eq!(first.type_, TransactionType::Withdrawal);
+    assert_eq!(first.amount, Some(dec!(4.50)));
+    assert_eq!(first.timestamp, Some("2024-01-02T00:00:00Z".parse()?));
+    assert_ne!(first.id, second.id);
+
+    assert_eq!(records[3].1.as_ref().unwrap_err(), "invalid date");
+
+    let (line, last) = &records[4];
+    let last = last.as_ref().unwrap();
+    assert_eq!(*line, 17);
+    assert_eq!(last.amount, Some(dec!(1)));
+    assert_eq!(last.timestamp, Some("1999-01-03T00:00:00Z".parse()?));
+
+    // Reading it again gives the same ids.
+    let mut reader = StatementReader::new(QIF.as_bytes(), StatementFormat::Qif, 7)?;
+    let again = read_all(&mut reader);
+    assert_eq!(again[2].1.as_ref().unwrap().id, second.id);
+
+    Ok(())
+}
+
+#[test]
+fn test_process_statement() -> Result<()> {
+    let mut ledger = Ledger::new();
+    for _ in 0..2 {
+        let mut reader = StatementReader::new(QIF.as_bytes(), StatementFormat::Qif, 7)?;
+        process_records(&mut reader, &mut ledger, ProcessingOptions::default())?;
+    }
+
+    // Reading the statement twice didn't execute it twice.
+    assert_eq!(ledger.clients.get(&7).unwrap().available(), dec!(990));
+
+    Ok(())
+}
+
+#[test]
+fn test_from_path() {
+    assert_eq!(StatementFormat::from_path("may.OFX"), Some(StatementFormat::Ofx));
+    assert_eq!(StatementFormat::from_path("may.qfx"), Some(StatementFormat::Ofx));
+    assert_eq!(StatementFormat::from_path("may.qif"), Some(StatementFormat::Qif));
+    assert_eq!(StatementFormat::from_path("may.csv"), None);
+}
@@ -0,0 +1,85 @@
+use std::io::Write;
+
+use anyhow::Result;
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+#[cfg(test)]
+#[path = "table_tests.rs"]
+mod table_tests;
+
+const LOCKED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Writes csv rows as a table with aligned columns, the numbers on the
+/// right, and the header underlined. With `color` the rows whose locked
+/// column is true are red.
+pub(super) fn write_table(csv: &[u8], color: bool, mut writer: impl Write) -> Result<()> {
+    let rows = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(csv)
+        .into_records()
+        .collect::<Result<Vec<StringRecord>, _>>()?;
+    let Some((header, records)) = rows.split_first() else {
+        return Ok(());
+    };
+
+    let columns: Vec<Column> = (0..header.len())
+        .map(|i| {
+            Column::of(
+                rows.iter().filter_map(|row| row.get(i)),
+                records.iter().map(|row| row.get(i)),
+            )
+        })
+        .collect();
+    let locked = header.iter().position(|name| name == "locked");
+
+    write_row(&mut writer, header, &columns)?;
+    writeln!(writer)?;
+    let underline: Vec<String> = columns.iter().map(|column| "-".repeat(column.width)).collect();
+    writeln!(writer, "{}", underline.join("  "))?;
+    for record in records {
+        let is_locked = color && locked.and_then(|i| record.get(i)) == Some("true");
+        if is_locked {
+            write!(writer, "{}", LOCKED)?;
+        }
+        write_row(&mut writer, record, &columns)?;
+        if is_locked {
+            // Before the line break, so the color ends with the row.
+            write!(writer, "{}", RESET)?;
+        }
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+struct Column {
+    width: usize,
+    numeric: bool,
+}
+
+impl Column {
+    fn of<'a>(cells: impl Iterator<Item = &'a str>, mut values: impl Iterator<Item = Option<&'a str>>) -> Column {
+        Column {
+            width: cells.map(|cell| cell.chars().count()).max().unwrap_or_default(),
+            // Empty cells, e.g. of a missing display name, don't count.
+            numeric: values
+                .all(|value| value.map_or(true, |value| value.is_empty() || value.parse::<Decimal>().is_ok())),
+        }
+    }
+}
+
+fn write_row(writer: &mut impl Write, row: &StringRecord, columns: &[Column]) -> Result<()> {
+    let cells: Vec<String> = row
+        .iter()
+        .zip(columns)
+        .map(|(cell, column)| match column.numeric {
+            true => format!("{:>width$}", cell, width = column.width),
+            false => format!("{:<width$}", cell, width = column.width),
+        })
+        .collect();
+    write!(writer, "{}", cells.join("  ").trim_end())?;
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn test_write_table() -> Result<()> {
+    let csv = "client,available,locked,display_name
+1,10.5,false,Alice
+12,-3,true,
+";
+    let mut output = Vec::new();
+    write_table(csv.as_bytes(), false, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "client  available  locked  display_name
+------  ---------  ------  ------------
+     1       10.5  false   Alice
+    12         -3  true
+"
+    );
+
+    let mut output = Vec::new();
+    write_table(csv.as_bytes(), true, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?.lines().nth(3),
+        Some("\x1b[31m    12         -3  true\x1b[0m")
+    );
+
+    let mut output = Vec::new();
+    write_table(b"", true, &mut output)?;
+    assert!(output.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use std::io::Write;
+
+use anyhow::Result;
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+#[cfg(test)]
+#[path = "xlsx_tests.rs"]
+mod xlsx_tests;
+
+/// Most rows a worksheet can have.
+const MAX_ROWS: usize = 1_048_576;
+
+const CONTENT_TYPES: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml";
+const RELATIONSHIPS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const PACKAGE_RELATIONSHIPS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const SPREADSHEET: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+
+/// A worksheet of a workbook, from csv rows.
+pub(super) struct Sheet<'a> {
+    pub(super) name: &'a str,
+    pub(super) csv: &'a [u8],
+}
+
+/// Writes the sheets as an XLSX workbook. Numbers that are written the same
+/// way by Excel are number cells and true and false are booleans, anything
+/// else is text, e.g. client ids with leading zeros.
+pub(super) fn write_workbook(sheets: &[Sheet], writer: impl Write) -> Result<()> {
+    let mut content_types = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+         <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+         <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+         <Override PartName=\"/xl/workbook.xml\" ContentType=\"{}.sheet.main+xml\"/>",
+        CONTENT_TYPES
+    );
+    let mut workbook = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <workbook xmlns=\"{}\" xmlns:r=\"{}\"><sheets>",
+        SPREADSHEET, RELATIONSHIPS
+    );
+    let mut workbook_relationships = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<Relationships xmlns=\"{}\">",
+        PACKAGE_RELATIONSHIPS
+    );
+    for (i, sheet) in sheets.iter().enumerate() {
+        let n = i + 1;
+        content_types.push_str(&format!(
+            "<Override PartName=\"/xl/worksheets/sheet{}.xml\" ContentType=\"{}.worksheet+xml\"/>",
+            n, CONTENT_TYPES
+        ));
+        workbook.push_str(&format!(
+            "<sheet name=\"{}\" sheetId=\"{}\" r:id=\"rId{}\"/>",
+            escape(sheet.name),
+            n,
+            n
+        ));
+        workbook_relationships.push_str(&format!(
+            "<Relationship Id=\"rId{}\" Type=\"{}/worksheet\" Target=\"worksheets/sheet{}.xml\"/>",
+            n, RELATIONSHIPS, n
+        ));
+    }
+    content_types.push_str("</Types>");
+    workbook.push_str("</sheets></workbook>");
+    workbook_relationships.push_str("</Relationships>");
+    let relationships = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <Relationships xmlns=\"{}\">\
+         <Relationship Id=\"rId1\" Type=\"{}/officeDocument\" Target=\"xl/workbook.xml\"/>\
+         </Relationships>",
+        PACKAGE_RELATIONSHIPS, RELATIONSHIPS
+    );
+
+    let mut zip = ZipWriter::new(writer);
+    zip.add("[Content_Types].xml", content_types.as_bytes())?;
+    zip.add("_rels/.rels", relationships.as_bytes())?;
+    zip.add("xl/workbook.xml", workbook.as_bytes())?;
+    zip.add("xl/_rels/workbook.xml.rels", workbook_relationships.as_bytes())?;
+    for (i, sheet) in sheets.iter().enumerate() {
+        zip.add(&format!("xl/worksheets/sheet{}.xml", i + 1), &worksheet(sheet.csv)?)?;
+    }
+    zip.finish()
+}
+
+fn worksheet(csv: &[u8]) -> Result<Vec<u8>> {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<worksheet xmlns=\"{}\"><sheetData>",
+        SPREADSHEET
+    );
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(csv);
+    let mut record = StringRecord::new();
+    let mut row = 0;
+    while reader.read_record(&mut record)? {
+        row += 1;
+        anyhow::ensure!(row <= MAX_ROWS, "a worksheet can't have more than {} rows", MAX_ROWS);
+
+        xml.push_str(&format!("<row r=\"{}\">", row));
+        for (column, value) in record.iter().enumerate() {
+            let reference = format!("{}{}", column_name(column), row);
+            match value {
+                "true" | "false" => xml.push_str(&format!(
+                    "<c r=\"{}\" t=\"b\"><v>{}</v></c>",
+                    reference,
+                    u8::from(value == "true")
+                )),
+                _ if is_number(value) => xml.push_str(&format!("<c r=\"{}\"><v>{}</v></c>", reference, value)),
+                "" => {},
+                _ => xml.push_str(&format!(
+                    "<c r=\"{}\" t=\"inlineStr\"><is><t>{}</t></is></c>",
+                    reference,
+                    escape(value)
+                )),
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+
+    Ok(xml.into_bytes())
+}
+
+fn is_number(value: &str) -> bool {
+    value
+        .parse::<Decimal>()
+        .map_or(false, |number| number.to_string() == value)
+}
+
+/// A, B, ..., Z, AA, AB, ...
+fn column_name(mut column: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (column % 26) as u8);
+        if column < 26 {
+            break;
+        }
+        column = column / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("column names are ASCII")
+}
+
+// Leaves out the control characters XML can't have.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            _ if c.is_control() => {},
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Writes a zip archive with the files stored as they are, which is all a
+/// workbook needs. Without zip64 every file, and the archive, must be
+/// smaller than 4 GiB.
+struct ZipWriter<W> {
+    writer: W,
+    offset: u32,
+    // Name, CRC-32, size and offset of every file.
+    entries: Vec<(String, u32, u32, u32)>,
+}
+
+// 1980-01-01, the earliest date a zip archive can have.
+const DOS_DATE: u16 = 0x21;
+
+impl<W: Write> ZipWriter<W> {
+    fn new(writer: W) -> ZipWriter<W> {
+        ZipWriter {
+            writer,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let size = u32::try_from(data.len())?;
+        let crc = crc32fast::hash(data);
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // Version needed.
+        header.extend_from_slice(&0u16.to_le_bytes()); // Flags.
+        header.extend_from_slice(&0u16.to_le_bytes()); // Stored.
+        header.extend_from_slice(&0u16.to_le_bytes()); // Time.
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes()); // Compressed.
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&u16::try_from(name.len())?.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // Extra field.
+        header.extend_from_slice(name.as_bytes());
+        self.writer.write_all(&header)?;
+        self.writer.write_all(data)?;
+
+        self.entries.push((name.to_string(), crc, size, self.offset));
+        self.offset = u32::try_from(self.offset as u64 + header.len() as u64 + size as u64)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        let mut directory = Vec::new();
+        for (name, crc, size, offset) in &self.entries {
+            directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes()); // Version made by.
+            directory.extend_from_slice(&20u16.to_le_bytes()); // Version needed.
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Flags.
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Stored.
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Time.
+            directory.extend_from_slice(&DOS_DATE.to_le_bytes());
+            directory.extend_from_slice(&crc.to_le_bytes());
+            directory.extend_from_slice(&size.to_le_bytes()); // Compressed.
+            directory.extend_from_slice(&size.to_le_bytes());
+            directory.extend_from_slice(&u16::try_from(name.len())?.to_le_bytes());
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Extra field.
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Comment.
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Disk.
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Internal attributes.
+            directory.extend_from_slice(&0u32.to_le_bytes()); // External attributes.
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let entries = u16::try_from(self.entries.len())?;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // Disk.
+        end.extend_from_slice(&0u16.to_le_bytes()); // Disk of the directory.
+        end.extend_from_slice(&entries.to_le_bytes()); // On this disk.
+        end.extend_from_slice(&entries.to_le_bytes());
+        end.extend_from_slice(&u32::try_from(directory.len())?.to_le_bytes());
+        end.extend_from_slice(&self.offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // Comment.
+
+        self.writer.write_all(&directory)?;
+        self.writer.write_all(&end)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
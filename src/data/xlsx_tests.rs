@@ -0,0 +1,90 @@
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+// The files of a zip archive written by `ZipWriter`, checking its central
+// directory along the way.
+fn unzip(archive: &[u8]) -> Result<Vec<(String, String)>> {
+    let u16_at = |at: usize| u16::from_le_bytes([archive[at], archive[at + 1]]) as usize;
+    let u32_at = |at: usize| u32::from_le_bytes(archive[at..at + 4].try_into().unwrap()) as usize;
+
+    let mut files = Vec::new();
+    let mut at = 0;
+    while u32_at(at) == 0x04034b50 {
+        let size = u32_at(at + 18);
+        let name_len = u16_at(at + 26);
+        let name = std::str::from_utf8(&archive[at + 30..at + 30 + name_len])?;
+        let data = &archive[at + 30 + name_len..at + 30 + name_len + size];
+        if crc32fast::hash(data) as usize != u32_at(at + 14) {
+            bail!("bad CRC-32 of {}", name);
+        }
+
+        files.push((name.to_string(), String::from_utf8(data.to_vec())?));
+        at += 30 + name_len + size;
+    }
+
+    let end = archive.len() - 22;
+    assert_eq!(u32_at(end), 0x06054b50);
+    assert_eq!(u16_at(end + 10), files.len());
+    assert_eq!(u32_at(end + 16), at);
+    assert_eq!(u32_at(at), 0x02014b50);
+
+    Ok(files)
+}
+
+#[test]
+fn test_write_workbook() -> Result<()> {
+    let accounts = "client,available,locked,display_name
+007,10.5,false,Fish & Chips
+12,-3,true,
+";
+    let mut output = Vec::new();
+    write_workbook(
+        &[
+            Sheet {
+                name: "Accounts",
+                csv: accounts.as_bytes(),
+            },
+            Sheet {
+                name: "Summary",
+                csv: b"figure,value\nAccounts,2\n",
+            },
+        ],
+        &mut output,
+    )?;
+
+    let files = unzip(&output)?;
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "[Content_Types].xml",
+            "_rels/.rels",
+            "xl/workbook.xml",
+            "xl/_rels/workbook.xml.rels",
+            "xl/worksheets/sheet1.xml",
+            "xl/worksheets/sheet2.xml",
+        ]
+    );
+    assert!(files[2]
+        .1
+        .contains(r#"<sheets><sheet name="Accounts" sheetId="1" r:id="rId1"/><sheet name="Summary" sheetId="2" r:id="rId2"/></sheets>"#));
+    assert!(files[4].1.contains(
+        r#"<row r="2"><c r="A2" t="inlineStr"><is><t>007</t></is></c><c r="B2"><v>10.5</v></c><c r="C2" t="b"><v>0</v></c><c r="D2" t="inlineStr"><is><t>Fish &amp; Chips</t></is></c></row>"#
+    ));
+    assert!(files[4]
+        .1
+        .contains(r#"<row r="3"><c r="A3"><v>12</v></c><c r="B3"><v>-3</v></c><c r="C3" t="b"><v>1</v></c></row>"#));
+
+    Ok(())
+}
+
+#[test]
+fn test_column_name() {
+    let names: Vec<String> = [0, 1, 25, 26, 27, 51, 52, 701, 702]
+        .into_iter()
+        .map(column_name)
+        .collect();
+    assert_eq!(names, vec!["A", "B", "Z", "AA", "AB", "AZ", "BA", "ZZ", "AAA"]);
+}
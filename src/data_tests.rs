@@ -0,0 +1,1368 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::policy::LedgerPolicy;
+use crate::accounting::store::spill::SpillStore;
+
+fn record(type_: TransactionType, client_id: ClientId, id: TxId, amount: Option<Decimal>) -> TransactionRecord {
+    TransactionRecord {
+        id,
+        type_,
+        client_id,
+        amount,
+        currency: None,
+        timestamp: None,
+    }
+}
+
+#[test]
+fn test_reader() -> Result<()> {
+    let data = "type, client, tx, amount
+deposit, 1, 1, 1.0
+withdrawal, 2, 5, 3.1415926535
+dispute, 1, 1,
+resolve, 1, 1,
+chargeback, 1, 1,
+";
+
+    let records = TransactionReader::new(data.as_bytes())?.collect::<csv::Result<Vec<_>>>()?;
+    assert_eq!(
+        records,
+        vec![
+            record(TransactionType::Deposit, 1, 1, Some(dec!(1.0))),
+            record(TransactionType::Withdrawal, 2, 5, Some(dec!(3.1415926535))),
+            record(TransactionType::Dispute, 1, 1, None),
+            record(TransactionType::Resolve, 1, 1, None),
+            record(TransactionType::Chargeback, 1, 1, None),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_reader_wide_tx_ids() -> Result<()> {
+    let data = "type, client, tx, amount
+deposit, 1, 5000000000, 2
+dispute, 1, 5000000000,
+";
+
+    let records = TransactionReader::new(data.as_bytes())?.collect::<csv::Result<Vec<_>>>()?;
+    assert_eq!(
+        records,
+        vec![
+            record(TransactionType::Deposit, 1, 5_000_000_000, Some(dec!(2))),
+            record(TransactionType::Dispute, 1, 5_000_000_000, None),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_reader_timestamps() -> Result<()> {
+    let data = "type, client, tx, amount, timestamp
+deposit, 1, 1, 2, 2024-05-01T12:00:00+02:00
+withdrawal, 1, 2, 1, 1714557660
+dispute, 1, 1,,
+deposit, 1, 3, 2, yesterday
+";
+
+    let records: Vec<_> = TransactionReader::new(data.as_bytes())?.collect();
+    let timestamps: Vec<_> = records[..3].iter().flatten().map(|record| record.timestamp).collect();
+    assert_eq!(
+        timestamps,
+        vec![Timestamp::from_unix(1714557600), Timestamp::from_unix(1714557660), None]
+    );
+    assert!(records[3].is_err());
+
+    let mut ledger = Ledger::new();
+    process_reader(data.as_bytes(), &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(ledger.transactions[&2].timestamp(), Timestamp::from_unix(1714557660));
+
+    Ok(())
+}
+
+#[test]
+fn test_reader_falls_back_to_serde() -> Result<()> {
+    // Columns in a different order and an amount the fast path doesn't parse.
+    let data = "tx,amount,client,type
+1,1e2,3,deposit
+";
+
+    let records = TransactionReader::new(data.as_bytes())?.collect::<csv::Result<Vec<_>>>()?;
+    assert_eq!(records, vec![record(TransactionType::Deposit, 3, 1, Some(dec!(100)))]);
+
+    Ok(())
+}
+
+#[test]
+fn test_reader_skips_invalid_rows() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,2
+transfer,1,2,2
+deposit,4294967296,3,2
+deposit,1,4
+deposit,1,5,2.5
+";
+
+    let records: Vec<_> = TransactionReader::new(data.as_bytes())?.collect();
+    assert_eq!(records.len(), 5);
+    assert_eq!(records.iter().filter(|record| record.is_err()).count(), 3);
+    assert_eq!(
+        records.into_iter().filter_map(|record| record.ok()).collect::<Vec<_>>(),
+        vec![
+            record(TransactionType::Deposit, 1, 1, Some(dec!(2))),
+            record(TransactionType::Deposit, 1, 5, Some(dec!(2.5))),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_processing_report() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+deposit,1,1,10
+withdrawal,1,2,
+withdrawal,1,3,50
+transfer,1,4,1
+dispute,1,1,
+dispute,1,9,
+";
+
+    let mut ledger = Ledger::new();
+    let report = process_reader(data.as_bytes(), &mut ledger, ProcessingOptions::default())?;
+
+    assert_eq!(report.accepted, 3);
+    assert_eq!(report.parse_errors, 1);
+    assert_eq!(report.validation_errors, 1);
+    assert_eq!(report.ledger_errors, 2);
+    assert_eq!(report.rejected(), 4);
+    assert_eq!(
+        report.accepted_by_type,
+        TransactionCounts {
+            deposits: 2,
+            disputes: 1,
+            ..TransactionCounts::default()
+        }
+    );
+    assert_eq!(
+        report.rejected_by_code.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("insufficient_funds", 1),
+            ("malformed_record", 1),
+            ("missing_amount", 1),
+            ("transaction_not_found", 1)
+        ]
+    );
+
+    let lines: Vec<u64> = report.errors.iter().map(|rejected| rejected.line).collect();
+    assert_eq!(lines, vec![4, 5, 6, 8]);
+    assert!(matches!(
+        report.errors[0].error,
+        RecordError::Validation(TransactionDataError::MissingAmount)
+    ));
+    assert!(matches!(
+        report.errors[1].error,
+        RecordError::Ledger(TransactionError::InsufficientFunds)
+    ));
+    assert!(matches!(report.errors[2].error, RecordError::Parse(_)));
+
+    Ok(())
+}
+
+#[test]
+fn test_rejects_file() -> Result<()> {
+    let data = "type, client, tx, amount
+deposit, 1, 1, 10
+withdrawal, 1, 2,
+withdrawal, 1, 3, 50
+transfer, 1, 4, 1
+deposit, 1, 5
+";
+
+    let rejects = tempfile::NamedTempFile::new()?;
+    let options = ProcessingOptions {
+        rejects: Some(Box::new(rejects.reopen()?)),
+        ..ProcessingOptions::default()
+    };
+    process_reader(data.as_bytes(), &mut Ledger::new(), options)?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(rejects.path())?;
+    assert_eq!(reader.headers()?, vec!["type", "client", "tx", "amount", "error"]);
+
+    let rows = reader.records().collect::<csv::Result<Vec<_>>>()?;
+    let rows: Vec<Vec<&str>> = rows.iter().map(|row| row.iter().collect()).collect();
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0][..4], ["withdrawal", "1", "2", ""]);
+    assert_eq!(rows[0][4], "invalid transaction, err=transaction requires amount");
+    assert_eq!(rows[1][..4], ["withdrawal", "1", "3", "50"]);
+    assert_eq!(rows[1][4], "failed to execute transaction, err=insufficient funds");
+    assert_eq!(rows[2][..4], ["transfer", "1", "4", "1"]);
+    assert!(rows[2][4].starts_with("failed to deserialize record"));
+    assert_eq!(rows[3][..3], ["deposit", "1", "5"]);
+    assert!(rows[3][3].starts_with("failed to deserialize record"));
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_mode() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,50
+deposit,1,3,10
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        error_policy: ErrorPolicy::strict(),
+        ..ProcessingOptions::default()
+    };
+
+    let err = process_reader(data.as_bytes(), &mut ledger, options).unwrap_err();
+    let aborted = err.downcast_ref::<ProcessingAborted>().unwrap();
+    assert_eq!(aborted.line, 3);
+    assert!(matches!(
+        aborted.error,
+        RecordError::Ledger(TransactionError::InsufficientFunds)
+    ));
+
+    // Nothing after the failed record was executed.
+    assert_eq!(ledger.clients.get(&1).unwrap().available(), dec!(10));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_policy() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,50
+deposit,1,1,10
+dispute,1,9,
+withdrawal,1,3,
+deposit,1,4,5
+deposit,1,x,5
+";
+
+    let policy = ErrorPolicy {
+        parse: ErrorAction::Abort,
+        validation: ErrorAction::Warn,
+        ..ErrorPolicy::default()
+    }
+    .with_ledger_error(TransactionError::InsufficientFunds, ErrorAction::Ignore);
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        error_policy: policy.clone(),
+        ..ProcessingOptions::default()
+    };
+
+    let err = process_reader(data.as_bytes(), &mut ledger, options).unwrap_err();
+    let aborted = err.downcast_ref::<ProcessingAborted>().unwrap();
+    assert_eq!(aborted.line, 8);
+    assert_eq!(ledger.clients.get(&1).unwrap().available(), dec!(15));
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        error_policy: ErrorPolicy {
+            parse: ErrorAction::Collect,
+            ..policy
+        },
+        ..ProcessingOptions::default()
+    };
+
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 3);
+    assert_eq!(report.parse_errors, 1);
+    assert_eq!(report.validation_errors, 1);
+    assert_eq!(report.ledger_errors, 1);
+    assert_eq!(
+        report.errors.iter().map(|rejected| rejected.line).collect::<Vec<_>>(),
+        vec![5, 8]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_events() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,50
+deposit,1,1,10
+dispute,1,1,
+withdrawal,2,3,1
+resolve,1,1,
+";
+
+    let events = tempfile::NamedTempFile::new()?;
+    let options = ProcessingOptions {
+        events: Some(Box::new(events.reopen()?)),
+        ..ProcessingOptions::default()
+    };
+    process_reader(data.as_bytes(), &mut Ledger::new(), options)?;
+
+    let events: Vec<LedgerEvent> = std::fs::read_to_string(events.path())?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<serde_json::Result<_>>()?;
+
+    // The failed withdrawals and the repeated deposit are left out.
+    assert_eq!(
+        events,
+        vec![
+            LedgerEvent {
+                line: 2,
+                type_: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(dec!(10)),
+                available: dec!(10),
+                held: dec!(0),
+                locked: false,
+                currency: None,
+                timestamp: None,
+            },
+            LedgerEvent {
+                line: 5,
+                type_: TransactionType::Dispute,
+                client: 1,
+                tx: 1,
+                amount: Some(dec!(10)),
+                available: dec!(0),
+                held: dec!(10),
+                locked: false,
+                currency: None,
+                timestamp: None,
+            },
+            LedgerEvent {
+                line: 7,
+                type_: TransactionType::Resolve,
+                client: 1,
+                tx: 1,
+                amount: Some(dec!(10)),
+                available: dec!(10),
+                held: dec!(0),
+                locked: false,
+                currency: None,
+                timestamp: None,
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_balances() -> Result<()> {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,10,2024-05-01T09:00:00Z
+withdrawal,1,2,50,
+deposit,1,1,10,
+dispute,1,1,,
+withdrawal,1,3,1,
+";
+
+    let balances = tempfile::NamedTempFile::new()?;
+    let options = ProcessingOptions {
+        balances: Some(Box::new(balances.reopen()?)),
+        ..ProcessingOptions::default()
+    };
+    process_reader(data.as_bytes(), &mut Ledger::new(), options)?;
+
+    // Like the events, the failed withdrawals and the repeated deposit are
+    // left out.
+    assert_eq!(
+        std::fs::read_to_string(balances.path())?.lines().collect::<Vec<_>>(),
+        vec![
+            "line,type,client,tx,amount,available,held,total,locked,currency,timestamp",
+            "2,deposit,1,1,10,10,0,10,false,,2024-05-01T09:00:00Z",
+            "5,dispute,1,1,10,0,10,10,false,,",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_error_events() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,50
+withdrawal,1,3,
+transfer,x,4,1
+";
+
+    let events = tempfile::NamedTempFile::new()?;
+    let options = ProcessingOptions {
+        error_events: Some(Box::new(events.reopen()?)),
+        ..ProcessingOptions::default()
+    };
+    process_reader(data.as_bytes(), &mut Ledger::new(), options)?;
+
+    let events: Vec<serde_json::Value> = std::fs::read_to_string(events.path())?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<serde_json::Result<_>>()?;
+
+    assert_eq!(events.len(), 3);
+    assert_eq!(
+        events[0],
+        serde_json::json!({
+            "line": 3,
+            "tx": 2,
+            "client": 1,
+            "code": "insufficient_funds",
+            "message": "failed to execute transaction, err=insufficient funds",
+        })
+    );
+    assert_eq!(events[1]["code"], "missing_amount");
+    assert_eq!(events[2]["line"], 5);
+    assert_eq!(events[2]["tx"], 4);
+    assert_eq!(events[2]["client"], serde_json::Value::Null);
+    assert_eq!(events[2]["code"], "malformed_record");
+
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,50
+deposit,1,1,10
+dispute,1,1,
+withdrawal,1,3,
+deposit,1,x,5
+";
+
+    let report = validate_reader(data.as_bytes(), ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 3);
+    assert_eq!(report.parse_errors, 1);
+    assert_eq!(report.validation_errors, 2);
+    assert_eq!(report.ledger_errors, 0);
+
+    let errors: Vec<(u64, &str)> = report
+        .errors
+        .iter()
+        .map(|rejected| (rejected.line, rejected.error.code()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![(4, "duplicate_id"), (6, "missing_amount"), (7, "malformed_record")]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_resume() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,3
+dispute,1,1,
+resolve,1,1,
+withdrawal,1,3,2
+";
+
+    // Interrupted after the dispute.
+    let mut ledger = Ledger::new();
+    let interrupted = &data[..data.match_indices('\n').nth(3).unwrap().0 + 1];
+    process_reader(interrupted.as_bytes(), &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(ledger.position(), 4);
+
+    let options = ProcessingOptions {
+        resume_after: Some(ledger.position()),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.skipped, 3);
+    assert_eq!(report.accepted, 2);
+    assert_eq!(report.rejected(), 0);
+    assert_eq!(ledger.position(), 6);
+
+    let client = ledger.clients.get(&1).unwrap();
+    assert_eq!(client.available(), dec!(5));
+    assert_eq!(client.held(), dec!(0));
+
+    Ok(())
+}
+
+#[test]
+fn test_velocity() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+deposit,2,2,10
+withdrawal,1,3,1
+withdrawal,1,4,1
+withdrawal,2,5,1
+withdrawal,1,6,1
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        max_transactions_per_client: Some(2),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 4);
+    assert_eq!(report.velocity_violations, 2);
+    assert_eq!(report.rejected_by_code.get("velocity_exceeded"), Some(&2));
+    assert!(matches!(
+        report.errors[0].error,
+        RecordError::Validation(TransactionDataError::VelocityExceeded(1))
+    ));
+    assert_eq!(ledger.clients.get(&1).unwrap().available(), dec!(9));
+    assert_eq!(ledger.clients.get(&2).unwrap().available(), dec!(9));
+
+    Ok(())
+}
+
+#[test]
+fn test_velocity_window() -> Result<()> {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,10,2024-05-01T10:00:00Z
+deposit,1,2,10,2024-05-01T10:00:30Z
+deposit,1,3,10,2024-05-01T10:00:59Z
+deposit,2,4,10,2024-05-01T10:00:59Z
+deposit,1,5,10,2024-05-01T10:01:00Z
+deposit,1,6,10,
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        velocity_window: Some(VelocityWindow {
+            max_transactions: 2,
+            seconds: 60,
+        }),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    // The third within a minute is rejected, the one a minute after the
+    // first isn't, nor is the one without a timestamp.
+    assert_eq!(report.accepted, 5);
+    assert_eq!(report.velocity_violations, 1);
+    assert_eq!(report.errors[0].line, 4);
+    assert!(matches!(
+        report.errors[0].error,
+        RecordError::Validation(TransactionDataError::VelocityExceeded(1))
+    ));
+    assert_eq!(ledger.clients.get(&1).unwrap().available(), dec!(40));
+
+    Ok(())
+}
+
+#[test]
+fn test_velocity_limits_together() -> Result<()> {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,10,2024-05-01T10:00:00Z
+deposit,1,2,10,2024-05-01T10:00:10Z
+deposit,1,3,10,2024-05-01T10:00:20Z
+deposit,1,4,10,2024-05-01T10:01:30Z
+deposit,1,5,10,2024-05-01T10:02:00Z
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        max_transactions_per_client: Some(3),
+        velocity_window: Some(VelocityWindow {
+            max_transactions: 2,
+            seconds: 60,
+        }),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    // The third is over the window, but doesn't count towards the run's
+    // limit, so the fourth is still accepted.
+    assert_eq!(report.accepted, 3);
+    assert_eq!(report.velocity_violations, 2);
+    assert_eq!(
+        report.errors.iter().map(|error| error.line).collect::<Vec<_>>(),
+        vec![4, 6]
+    );
+    assert_eq!(ledger.clients.get(&1).unwrap().available(), dec!(30));
+
+    Ok(())
+}
+
+#[test]
+fn test_ordering() -> Result<()> {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,10,2024-05-01T10:00:00Z
+deposit,1,2,10,2024-05-01T10:10:00Z
+deposit,1,3,10,2024-05-01T10:06:00Z
+deposit,1,4,10,2024-05-01T10:04:00Z
+deposit,1,5,10,
+deposit,1,6,10,2024-05-01T10:20:00Z
+";
+
+    let mut ordering = OrderingCheck {
+        tolerance_seconds: 300,
+        strict: false,
+    };
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        ordering: Some(ordering),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 6);
+    assert_eq!(report.out_of_order, 1);
+
+    ordering.strict = true;
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        ordering: Some(ordering),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 5);
+    assert_eq!(report.out_of_order, 1);
+    assert_eq!(report.rejected_by_code.get("out_of_order"), Some(&1));
+    assert_eq!(
+        report.errors[0].error.to_string(),
+        "invalid transaction, err=timestamp 2024-05-01T10:04:00Z is too far before 2024-05-01T10:10:00Z, out of time order"
+    );
+    assert_eq!(ledger.clients.get(&1).unwrap().available(), dec!(50));
+
+    Ok(())
+}
+
+#[test]
+fn test_close_day() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+close_day,0,0,
+withdrawal,1,2,4
+";
+
+    let mut ledger = Ledger::new();
+    ledger.use_settlement();
+    let options = ProcessingOptions {
+        risk_metrics: true,
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 3);
+    assert_eq!(report.accepted_by_type.day_closes, 1);
+    assert_eq!(report.risk.keys().collect::<Vec<_>>(), vec![&1]);
+    ledger.close_day();
+
+    let mut output = Vec::new();
+    export_settlements(&ledger.take_settlements(), Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "period,date,client,net_deposits,net_withdrawals,chargebacks\n1,,1,10,0,0\n2,,1,0,4,0\n"
+    );
+
+    let report = validate_reader(data.as_bytes(), ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_daily_activity() -> Result<()> {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,10,2024-05-01T09:00:00Z
+deposit,1,1,10,2024-05-01T09:00:00Z
+withdrawal,1,2,4,2024-05-01T12:00:00Z
+deposit,2,3,5,2024-05-01T13:00:00Z
+dispute,1,1,,2024-05-02T08:00:00Z
+chargeback,1,1,,2024-05-02T09:00:00Z
+deposit,1,4,1,
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        daily_activity: true,
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 7);
+
+    let mut output = Vec::new();
+    export_daily_activity(&report.daily, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?.lines().collect::<Vec<_>>(),
+        vec![
+            "client,date,deposits,withdrawals,disputes,resolves,chargebacks,reversals,deposited,withdrawn,net",
+            "1,2024-05-01,2,1,0,0,0,0,10,4,6",
+            "1,2024-05-02,0,0,1,0,1,0,0,0,-10",
+            "2,2024-05-01,1,0,0,0,0,0,5,0,5",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_daily_activity_saturates() -> Result<()> {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,79228162514264337593543950335,2024-05-01T09:00:00Z
+withdrawal,1,2,79228162514264337593543950335,2024-05-01T10:00:00Z
+deposit,1,3,79228162514264337593543950335,2024-05-01T11:00:00Z
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        daily_activity: true,
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 3);
+
+    let mut output = Vec::new();
+    export_daily_activity(&report.daily, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?.lines().nth(1),
+        Some(
+            "1,2024-05-01,2,1,0,0,0,0,79228162514264337593543950335,79228162514264337593543950335,\
+             79228162514264337593543950335"
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_client_activity() -> Result<()> {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,10,2024-05-01T09:00:00Z
+deposit,1,1,10,2024-05-01T09:00:00Z
+withdrawal,1,2,4,
+dispute,1,1,,
+chargeback,1,1,,
+deposit,2,3,5,
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        client_activity: true,
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    let entry = |line, type_, tx, amount, timestamp: Option<&str>| -> Result<ActivityEntry> {
+        Ok(ActivityEntry {
+            line,
+            type_,
+            tx,
+            amount,
+            timestamp: timestamp.map(str::parse).transpose()?,
+        })
+    };
+    // Repeated transactions and disputes don't change the funds.
+    assert_eq!(
+        report.activity.get(&1),
+        Some(&ClientActivity {
+            opening: dec!(0),
+            entries: vec![
+                entry(2, TransactionType::Deposit, 1, dec!(10), Some("2024-05-01T09:00:00Z"))?,
+                entry(4, TransactionType::Withdrawal, 2, dec!(-4), None)?,
+                entry(6, TransactionType::Chargeback, 1, dec!(-10), None)?,
+            ],
+        })
+    );
+    assert_eq!(report.activity.len(), 2);
+
+    let mut output = Vec::new();
+    export_camt053(
+        &ledger,
+        &report.activity,
+        "USD".parse()?,
+        "2024-05-02T00:00:00Z".parse()?,
+        &mut output,
+    )?;
+    let output = String::from_utf8(output)?;
+    assert_eq!(output.matches("<Stmt>").count(), 2);
+    assert_eq!(output.matches("<Ntry>").count(), 4);
+    assert!(output.contains("<Id>TRANSACTO-1714608000-2</Id>"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rules() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+deposit,2,2,5000
+dispute,1,1,
+withdrawal,1,3,1
+withdrawal,2,4,2000
+";
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        rules: vec![
+            rules::Rule {
+                name: "large".to_string(),
+                when: rules::Condition::AmountOver {
+                    amount: dec!(1000),
+                    types: vec![],
+                },
+                action: RuleAction::Flag,
+            },
+            rules::Rule {
+                name: "large withdrawal".to_string(),
+                when: rules::Condition::AmountOver {
+                    amount: dec!(1000),
+                    types: vec![TransactionType::Withdrawal],
+                },
+                action: RuleAction::Reject,
+            },
+            rules::Rule {
+                name: "pattern".to_string(),
+                when: rules::Condition::WithdrawalDuringDispute,
+                action: RuleAction::Freeze,
+            },
+        ],
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 3);
+    assert_eq!(report.flagged, 2);
+    assert!(matches!(
+        report.errors[0].error,
+        RecordError::Ledger(TransactionError::AccountFrozen)
+    ));
+    assert!(matches!(
+        &report.errors[1].error,
+        RecordError::Validation(TransactionDataError::RuleRejected(name)) if name == "large withdrawal"
+    ));
+    assert!(ledger.clients.get(&1).unwrap().frozen());
+    assert_eq!(ledger.clients.get(&2).unwrap().available(), dec!(5000));
+
+    Ok(())
+}
+
+#[test]
+fn test_accrue_events() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,200
+accrue,1,0,
+";
+
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        interest_rate: Some(dec!(0.5)),
+        ..LedgerPolicy::default()
+    });
+    let events = tempfile::NamedTempFile::new()?;
+    let options = ProcessingOptions {
+        events: Some(Box::new(events.reopen()?)),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted_by_type.accruals, 1);
+
+    let events: Vec<LedgerEvent> = std::fs::read_to_string(events.path())?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<serde_json::Result<_>>()?;
+    assert_eq!(
+        events[1],
+        LedgerEvent {
+            line: 3,
+            type_: TransactionType::Accrue,
+            client: 1,
+            tx: 0,
+            amount: Some(dec!(1)),
+            available: dec!(201),
+            held: dec!(0),
+            locked: false,
+            currency: None,
+            timestamp: None,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_currencies() -> Result<()> {
+    let data = "type,client,tx,amount,currency
+deposit,1,1,10,
+deposit,1,2,20,eur
+withdrawal,1,3,15,
+dispute,1,2,,usd
+dispute,1,2,,EUR
+";
+
+    // A single ledger takes the first currency, and rejects the dispute in
+    // another.
+    let mut ledger = Ledger::new();
+    let report = process_reader(data.as_bytes(), &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 4);
+    assert_eq!(ledger.currency(), Some("EUR".parse()?));
+    assert!(matches!(
+        report.errors[0].error,
+        RecordError::Validation(TransactionDataError::CurrencyMismatch { .. })
+    ));
+    assert_eq!(ledger.clients.get(&1).map(Client::held), Some(dec!(20)));
+
+    let mut ledger = Ledger::new();
+    ledger.use_currency("USD".parse()?);
+    let report = process_reader(data.as_bytes(), &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 1);
+    assert!(matches!(
+        report.errors[0].error,
+        RecordError::Validation(TransactionDataError::CurrencyMismatch { .. })
+    ));
+
+    let mut ledgers = CurrencyLedgers::new("USD".parse()?);
+    let report = process_reader(data.as_bytes(), &mut ledgers, ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 3);
+    assert!(matches!(
+        report.errors[0].error,
+        RecordError::Ledger(TransactionError::InsufficientFunds)
+    ));
+    assert!(matches!(
+        report.errors[1].error,
+        RecordError::Ledger(TransactionError::TransactionNotFound)
+    ));
+
+    let mut output = Vec::new();
+    export_currencies(&ledgers, Format::Csv, &mut output)?;
+    assert_eq!(
+        String::from_utf8(output)?,
+        "client,available,held,total,locked,currency
+1,0,20,20,false,EUR
+1,10,0,10,false,USD
+"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_risk_metrics() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+deposit,1,2,10
+dispute,1,1,
+chargeback,1,1,
+withdrawal,1,3,50
+deposit,2,4,5
+withdrawal,2,5,1
+";
+
+    let mut ledger = Ledger::new();
+    let report = process_reader(data.as_bytes(), &mut ledger, ProcessingOptions::default())?;
+    assert!(report.risk.is_empty());
+
+    let mut ledger = Ledger::new();
+    let options = ProcessingOptions {
+        risk_metrics: true,
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(
+        report.risk[&1],
+        ClientRisk {
+            deposits: 2,
+            disputes: 1,
+            chargebacks: 1,
+            rejected_withdrawals: 1,
+        }
+    );
+    assert_eq!(report.risk[&1].dispute_ratio(), 0.5);
+    assert_eq!(report.risk[&2].dispute_ratio(), 0.0);
+
+    let mut output = Vec::new();
+    export_risk(&report.risk, Format::Csv, &mut output)?;
+    assert_eq!(
+        String::from_utf8(output)?,
+        "client,deposits,disputes,chargebacks,rejected_withdrawals,dispute_ratio
+1,2,1,1,1,0.5
+2,1,0,0,0,0.0
+"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_export_tenants() -> Result<()> {
+    let mut ledgers = LedgerSet::new();
+    ledgers.execute_transaction("alpha", Transaction::Deposit(Deposit::new(1, 1, dec!(3))?))?;
+    ledgers.execute_transaction("beta", Transaction::Deposit(Deposit::new(1, 2, dec!(4))?))?;
+
+    let dir = tempfile::tempdir()?;
+    export_tenants(&ledgers, dir.path(), Format::Csv)?;
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("alpha.csv"))?,
+        "client,available,held,total,locked\n1,3,0,3,false\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("beta.csv"))?,
+        "client,available,held,total,locked\n2,4,0,4,false\n"
+    );
+
+    ledgers.ledger("../gamma");
+    assert!(export_tenants(&ledgers, dir.path(), Format::Csv).is_err());
+    assert!(!dir.path().join("../gamma.csv").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_client_metadata() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(3))?))?;
+
+    let clients = "client, display_name, external_ref, opened_at\n1, Ada Lovelace, CRM-1, 2024-01-02\n2,,CRM-2,\n";
+    assert_eq!(load_client_metadata(&mut ledger, clients.as_bytes(), Format::Csv)?, 2);
+    assert_eq!(
+        ledger.clients[&1].metadata().display_name.as_deref(),
+        Some("Ada Lovelace")
+    );
+
+    let mut output = Vec::new();
+    export_with_metadata(&ledger, Format::Csv, &mut output)?;
+    let mut lines: Vec<&str> = std::str::from_utf8(&output)?.lines().collect();
+    lines[1..].sort();
+    assert_eq!(
+        lines,
+        vec![
+            "client,available,held,total,locked,display_name,external_ref,opened_at",
+            "1,3,0,3,false,Ada Lovelace,CRM-1,2024-01-02",
+            "2,0,0,0,false,,CRM-2,",
+        ]
+    );
+
+    let mut output = Vec::new();
+    export(&ledger, Format::Csv, &mut output)?;
+    assert!(std::str::from_utf8(&output)?.starts_with("client,available,held,total,locked\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_export_filters() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,1,1,10
+deposit,2,2,5
+dispute,2,2,
+chargeback,2,2,
+deposit,3,3,7
+deposit,4,4,1
+dispute,4,4,
+chargeback,4,4,
+deposit,5,5,2
+";
+
+    let mut ledger = Ledger::new();
+    process_reader(data.as_bytes(), &mut ledger, ProcessingOptions::default())?;
+
+    let exported = |options: &ExportOptions| -> Result<Vec<ClientId>> {
+        let mut output = Vec::new();
+        export_with(&ledger, options, Format::Jsonl, &mut output)?;
+        let mut clients = std::str::from_utf8(&output)?
+            .lines()
+            .map(|line| Ok(serde_json::from_str::<ClientRecord>(line)?.id))
+            .collect::<Result<Vec<_>>>()?;
+        clients.sort();
+        Ok(clients)
+    };
+
+    assert_eq!(exported(&ExportOptions::default())?, vec![1, 2, 3, 4, 5]);
+    let locked_only = ExportOptions {
+        locked_only: true,
+        ..ExportOptions::default()
+    };
+    assert_eq!(exported(&locked_only)?, vec![2, 4]);
+    let non_zero_only = ExportOptions {
+        non_zero_only: true,
+        ..ExportOptions::default()
+    };
+    assert_eq!(exported(&non_zero_only)?, vec![1, 3, 5]);
+    let clients = ExportOptions {
+        non_zero_only: true,
+        clients: Some([1, 2, 5].into_iter().collect()),
+        ..ExportOptions::default()
+    };
+    assert_eq!(exported(&clients)?, vec![1, 5]);
+
+    Ok(())
+}
+
+#[test]
+fn test_sorted_export() -> Result<()> {
+    let mut ledger = Ledger::new();
+    for client_id in (0..100).rev() {
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(
+            client_id.into(),
+            client_id,
+            dec!(1),
+        )?))?;
+    }
+
+    let options = ExportOptions {
+        clients: Some((0..50).collect()),
+        sort: Some(SortKey::Client),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Csv, &mut output)?;
+    let clients: Vec<String> = std::str::from_utf8(&output)?
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').next().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(clients, (0..50).map(|id| id.to_string()).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[test]
+fn test_sorted_clients_in_chunks() -> Result<()> {
+    let mut ledger = Ledger::new();
+    for client_id in [7, 3, 9, 1, 5, 8, 2] {
+        ledger.execute_transaction(Transaction::Deposit(Deposit::new(
+            client_id.into(),
+            client_id,
+            dec!(1),
+        )?))?;
+    }
+
+    for chunk_size in [1, 3, 7, 10] {
+        let ids: Vec<ClientId> = SortedClients::new(&ledger.clients, chunk_size)
+            .map(|client| client.map(|client| client.id()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(ids, vec![1, 2, 3, 5, 7, 8, 9]);
+    }
+    assert_eq!(SortedClients::new(&Map::<ClientId, Client>::default(), 3).count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_other_stores() -> Result<()> {
+    let data = "type,client,tx,amount
+deposit,2,1,10
+deposit,1,2,5
+deposit,1,3,1
+dispute,1,2,
+deposit,1,2,7
+";
+
+    // Everything but the latest transaction is spilled, to be read back. The
+    // repeated deposit is accepted, but discarded without an event.
+    let dir = tempfile::tempdir()?;
+    let mut ledger = Ledger::with_store(SpillStore::new(dir.path().join("spill"), 1)?);
+    let events = tempfile::NamedTempFile::new()?;
+    let options = ProcessingOptions {
+        events: Some(Box::new(events.reopen()?)),
+        ..ProcessingOptions::default()
+    };
+    let report = process_reader(data.as_bytes(), &mut ledger, options)?;
+    assert_eq!(report.accepted, 5);
+
+    let events: Vec<LedgerEvent> = std::fs::read_to_string(events.path())?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<serde_json::Result<_>>()?;
+    assert_eq!(events[3].amount, Some(dec!(5)));
+    assert_eq!(events.len(), 4);
+
+    let options = ExportOptions {
+        sort: Some(SortKey::Client),
+        columns: Some(
+            "client,available,held,open_disputes"
+                .parse()
+                .map_err(anyhow::Error::msg)?,
+        ),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "client,available,held,open_disputes\n1,1,5,1\n2,10,0,0\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_export_columns() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 1)))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(3, 2, dec!(1))?))?;
+
+    let options = ExportOptions {
+        sort: Some(SortKey::Client),
+        columns: Some("total, client,open_disputes".parse().map_err(anyhow::Error::msg)?),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "total,client,open_disputes\n15,1,1\n1,2,0\n"
+    );
+
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Jsonl, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?.lines().next(),
+        Some(r#"{"total":"15","client":1,"open_disputes":1}"#)
+    );
+
+    let mut ledgers = CurrencyLedgers::new("USD".parse()?);
+    process_reader(
+        "type,client,tx,amount\ndeposit,1,1,2\n".as_bytes(),
+        &mut ledgers,
+        ProcessingOptions::default(),
+    )?;
+    let mut output = Vec::new();
+    let spec = ExportSpec::new(vec![Column::Client, Column::Currency]).map_err(anyhow::Error::msg)?;
+    export_currencies_with(&ledgers, &spec, Format::Csv, &mut output)?;
+    assert_eq!(std::str::from_utf8(&output)?, "client,currency\n1,USD\n");
+
+    // Whether an account is closed is only exported when asked for.
+    ledger.execute_transaction(Transaction::CloseAccount(CloseAccount::new(2)))?;
+    let mut output = Vec::new();
+    export(&ledger, Format::Csv, &mut output)?;
+    assert!(std::str::from_utf8(&output)?.starts_with("client,available,held,total,locked\n"));
+    let closed = ExportOptions {
+        sort: Some(SortKey::Client),
+        columns: Some("client,closed".parse().map_err(anyhow::Error::msg)?),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &closed, Format::Csv, &mut output)?;
+    assert_eq!(std::str::from_utf8(&output)?, "client,closed\n1,false\n2,true\n");
+
+    assert!("client,held,client".parse::<ExportSpec>().is_err());
+    assert!("client,balance".parse::<ExportSpec>().is_err());
+    assert!("".parse::<ExportSpec>().is_err());
+    let options = ExportOptions {
+        with_metadata: true,
+        ..options
+    };
+    assert!(export_with(&ledger, &options, Format::Csv, Vec::new()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_export_lifetime_totals() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_lifetime_totals();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(10))?))?;
+    ledger.execute_transaction(Transaction::Withdrawal(Withdrawal::new(2, 1, dec!(3))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(1, 1)))?;
+
+    let mut output = Vec::new();
+    export(&ledger, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "client,available,held,total,locked,total_deposited,total_withdrawn,dispute_count,chargeback_count
+1,-3,10,7,false,10,3,1,0
+"
+    );
+
+    let options = ExportOptions {
+        columns: Some(
+            ExportSpec::new(vec![Column::Client, Column::DisputeCount, Column::TotalDeposited])
+                .map_err(anyhow::Error::msg)?,
+        ),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "client,dispute_count,total_deposited\n1,1,10\n"
+    );
+
+    // Lifetime totals in the expected accounts are left out of verifying.
+    let expected = read_accounts(
+        "client,available,held,total,locked,total_deposited\n1,-3,10,7,false,10\n".as_bytes(),
+        Format::Csv,
+    )?;
+    assert_eq!(ledger.verify_accounts(expected), vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn test_decimal_places() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(5))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 1, dec!(0.9))?))?;
+    ledger.execute_transaction(Transaction::Dispute(Dispute::new(2, 1)))?;
+
+    let options = ExportOptions {
+        decimal_places: Some(4),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "client,available,held,total,locked\n1,5.0000,0.9000,5.9000,false\n"
+    );
+
+    let options = ExportOptions {
+        decimal_places: Some(0),
+        with_metadata: true,
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Jsonl, &mut output)?;
+    assert!(std::str::from_utf8(&output)?.starts_with(r#"{"client":1,"available":"5","held":"1","total":"6","#));
+
+    let options = ExportOptions {
+        decimal_places: Some(2),
+        columns: Some(ExportSpec::new(vec![Column::Total, Column::OpenDisputes]).map_err(anyhow::Error::msg)?),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Csv, &mut output)?;
+    assert_eq!(std::str::from_utf8(&output)?, "total,open_disputes\n5.90,1\n");
+
+    // Amounts are rounded the policy's way, and get every place asked for
+    // even when a decimal couldn't hold them.
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        rounding: Rounding::Truncate,
+        ..LedgerPolicy::default()
+    });
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(1, 1, dec!(0.95))?))?;
+    ledger.execute_transaction(Transaction::Deposit(Deposit::new(2, 2, Decimal::MAX)?))?;
+    let options = ExportOptions {
+        decimal_places: Some(1),
+        sort: Some(SortKey::Client),
+        ..ExportOptions::default()
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Csv, &mut output)?;
+    assert_eq!(
+        std::str::from_utf8(&output)?,
+        "client,available,held,total,locked\n1,0.9,0.0,0.9,false\n2,79228162514264337593543950335.0,0.0,\
+         79228162514264337593543950335.0,false\n"
+    );
+
+    let options = ExportOptions {
+        decimal_places: Some(4),
+        ..options
+    };
+    let mut output = Vec::new();
+    export_with(&ledger, &options, Format::Jsonl, &mut output)?;
+    assert!(std::str::from_utf8(&output)?.contains(r#""available":"79228162514264337593543950335.0000""#));
+
+    Ok(())
+}
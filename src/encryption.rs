@@ -0,0 +1,213 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305};
+use thiserror::Error;
+
+#[cfg(test)]
+#[path = "encryption_tests.rs"]
+mod encryption_tests;
+
+/// Encrypted files start with these bytes, followed by the random nonce
+/// prefix of the stream.
+const MAGIC: &[u8; 4] = b"TXEN";
+
+const NONCE_PREFIX_SIZE: usize = 19;
+const TAG_SIZE: usize = 16;
+
+/// Data is encrypted in chunks of this size, so neither side ever has to
+/// hold a whole ledger in memory.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Set in the length of the last chunk. Changing it makes the chunk fail to
+/// decrypt, so a file can't be truncated at a chunk boundary unnoticed.
+const LAST_CHUNK: u32 = 1 << 31;
+
+#[derive(Debug, Error)]
+pub enum KeyError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("key must be 32 bytes, found {0}")]
+    InvalidLength(usize),
+}
+
+/// A 256 bit key for XChaCha20-Poly1305. A key file holds the 32 raw bytes,
+/// e.g. `head -c 32 /dev/urandom > transacto.key`.
+#[derive(Clone)]
+pub struct Key([u8; 32]);
+
+/// Encrypts everything written to it. Call `finish` once done, without it
+/// the last chunk is missing and the output can't be decrypted.
+pub struct EncryptedWriter<W: Write> {
+    writer: W,
+    encryptor: EncryptorBE32<XChaCha20Poly1305>,
+    buffer: Vec<u8>,
+}
+
+/// Decrypts what an `EncryptedWriter` wrote with the same key. Reading fails
+/// if any of it was changed or cut off.
+pub struct EncryptedReader<R: Read> {
+    reader: R,
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Key {
+    pub fn new(bytes: [u8; 32]) -> Key {
+        Key(bytes)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Key, KeyError> {
+        let bytes = fs::read(path)?;
+        let bytes: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| KeyError::InvalidLength(bytes.len()))?;
+
+        Ok(Key(bytes))
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+/// Whether the data, of which this is the beginning, was written by an
+/// `EncryptedWriter`.
+pub fn is_encrypted(header: &[u8]) -> bool {
+    header.starts_with(MAGIC)
+}
+
+impl<W: Write> EncryptedWriter<W> {
+    pub fn new(key: &Key, mut writer: W) -> io::Result<EncryptedWriter<W>> {
+        let mut nonce_prefix = [0; NONCE_PREFIX_SIZE];
+        getrandom::getrandom(&mut nonce_prefix).map_err(io::Error::from)?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&nonce_prefix)?;
+
+        Ok(EncryptedWriter {
+            writer,
+            encryptor: EncryptorBE32::from_aead(key.cipher(), &nonce_prefix.into()),
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    /// Encrypts what is left and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let chunk = self
+            .encryptor
+            .encrypt_last(self.buffer.as_slice())
+            .map_err(|_| encryption_failed())?;
+        write_chunk(&mut self.writer, &chunk, LAST_CHUNK)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for EncryptedWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() == CHUNK_SIZE {
+            let chunk = self
+                .encryptor
+                .encrypt_next(self.buffer.as_slice())
+                .map_err(|_| encryption_failed())?;
+            write_chunk(&mut self.writer, &chunk, 0)?;
+            self.buffer.clear();
+        }
+
+        let size = data.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..size]);
+
+        Ok(size)
+    }
+
+    /// Only flushes the inner writer, a chunk is never written before it's
+    /// full.
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<R: Read> EncryptedReader<R> {
+    pub fn new(key: &Key, mut reader: R) -> io::Result<EncryptedReader<R>> {
+        let mut header = [0; MAGIC.len() + NONCE_PREFIX_SIZE];
+        reader.read_exact(&mut header)?;
+        if !is_encrypted(&header) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "data is not encrypted"));
+        }
+
+        let nonce_prefix: [u8; NONCE_PREFIX_SIZE] = header[MAGIC.len()..].try_into().unwrap();
+
+        Ok(EncryptedReader {
+            reader,
+            decryptor: Some(DecryptorBE32::from_aead(key.cipher(), &nonce_prefix.into())),
+            chunk: Vec::new(),
+            position: 0,
+        })
+    }
+
+    /// Reads and decrypts the next chunk, returns false after the last one.
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        if self.decryptor.is_none() {
+            return Ok(false);
+        }
+
+        let mut length = [0; 4];
+        self.reader.read_exact(&mut length)?;
+        let length = u32::from_le_bytes(length);
+        let size = (length & !LAST_CHUNK) as usize;
+        if size > CHUNK_SIZE + TAG_SIZE {
+            return Err(decryption_failed());
+        }
+
+        let mut ciphertext = vec![0; size];
+        self.reader.read_exact(&mut ciphertext)?;
+
+        let chunk = if length & LAST_CHUNK != 0 {
+            self.decryptor.take().unwrap().decrypt_last(ciphertext.as_slice())
+        } else {
+            self.decryptor.as_mut().unwrap().decrypt_next(ciphertext.as_slice())
+        };
+        self.chunk = chunk.map_err(|_| decryption_failed())?;
+        self.position = 0;
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for EncryptedReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            if !self.next_chunk()? {
+                return Ok(0);
+            }
+        }
+
+        let size = buffer.len().min(self.chunk.len() - self.position);
+        buffer[..size].copy_from_slice(&self.chunk[self.position..self.position + size]);
+        self.position += size;
+
+        Ok(size)
+    }
+}
+
+fn write_chunk(writer: &mut impl Write, chunk: &[u8], flags: u32) -> io::Result<()> {
+    writer.write_all(&(chunk.len() as u32 | flags).to_le_bytes())?;
+    writer.write_all(chunk)
+}
+
+fn encryption_failed() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "failed to encrypt")
+}
+
+fn decryption_failed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "failed to decrypt, wrong key or corrupt data",
+    )
+}
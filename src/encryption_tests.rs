@@ -0,0 +1,67 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn encrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>> {
+    let mut writer = EncryptedWriter::new(key, Vec::new())?;
+    writer.write_all(data)?;
+    Ok(writer.finish()?)
+}
+
+fn decrypt(key: &Key, data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decrypted = Vec::new();
+    EncryptedReader::new(key, data)?.read_to_end(&mut decrypted)?;
+    Ok(decrypted)
+}
+
+#[test]
+fn test_round_trip() -> Result<()> {
+    let key = Key::new([7; 32]);
+
+    for size in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, 3 * CHUNK_SIZE + 5] {
+        let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        let encrypted = encrypt(&key, &data)?;
+        assert_eq!(is_encrypted(&encrypted), true);
+        assert_eq!(decrypt(&key, &encrypted)?, data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_tampering() -> Result<()> {
+    let key = Key::new([7; 32]);
+    let data = vec![1; 2 * CHUNK_SIZE + 10];
+    let encrypted = encrypt(&key, &data)?;
+
+    assert!(decrypt(&Key::new([8; 32]), &encrypted).is_err());
+
+    let mut changed = encrypted.clone();
+    let last = changed.len() - 1;
+    changed[last] ^= 1;
+    assert!(decrypt(&key, &changed).is_err());
+
+    // Cut off after the first chunk, which on its own decrypts fine.
+    let header = MAGIC.len() + NONCE_PREFIX_SIZE;
+    let truncated = &encrypted[..header + 4 + CHUNK_SIZE + TAG_SIZE];
+    assert!(decrypt(&key, truncated).is_err());
+
+    assert!(decrypt(&key, &data).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_key_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("transacto.key");
+
+    std::fs::write(&path, [1; 32])?;
+    assert_eq!(Key::load(&path)?.0, [1; 32]);
+
+    std::fs::write(&path, "too short")?;
+    assert!(matches!(Key::load(&path), Err(KeyError::InvalidLength(9))));
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use std::io::Write;
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+
+use crate::accounting::{ClientId, TxId};
+use crate::data::{TransactionRecord, TransactionType};
+
+#[cfg(test)]
+#[path = "generate_tests.rs"]
+mod generate_tests;
+
+// Amounts are generated in units of 0.0001, up to 1000.
+const AMOUNT_SCALE: u32 = 4;
+const MAX_AMOUNT: u64 = 10_000_000;
+
+#[derive(Clone, Debug)]
+pub struct GeneratorConfig {
+    /// The same seed (and settings) always generates the same transactions.
+    pub seed: u64,
+    pub rows: usize,
+    pub clients: ClientId,
+    /// Share of rows that are withdrawals.
+    pub withdrawal_rate: f64,
+    /// Share of rows that open a dispute. About as many rows close one.
+    pub dispute_rate: f64,
+    /// Share of disputes that end in a chargeback rather than a resolve.
+    pub chargeback_rate: f64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            seed: 0,
+            rows: 1000,
+            clients: 1000,
+            withdrawal_rate: 0.3,
+            dispute_rate: 0.02,
+            chargeback_rate: 0.2,
+        }
+    }
+}
+
+/// Generates synthetic transactions. They are all valid: withdrawals never
+/// exceed the client's available funds (nor come from a locked account), and
+/// disputes, resolves and chargebacks refer to deposits that can take them.
+/// Executed in order, every transaction is accepted by a ledger.
+pub struct Generator {
+    config: GeneratorConfig,
+    rng: SplitMix64,
+    rows: usize,
+    next_id: TxId,
+    available: Vec<u64>,
+    locked: Vec<bool>,
+    // Deposits that can still be disputed, and those under a dispute, as
+    // (tx, client, amount).
+    deposits: Vec<(TxId, ClientId, u64)>,
+    disputes: Vec<(TxId, ClientId, u64)>,
+}
+
+impl Generator {
+    pub fn new(config: GeneratorConfig) -> Generator {
+        let clients = config.clients.max(1) as usize;
+
+        Generator {
+            rng: SplitMix64(config.seed),
+            rows: 0,
+            next_id: 0,
+            available: vec![0; clients],
+            locked: vec![false; clients],
+            deposits: Vec::new(),
+            disputes: Vec::new(),
+            config,
+        }
+    }
+
+    fn deposit(&mut self) -> TransactionRecord {
+        let client_id = self.rng.below(self.available.len() as u64) as ClientId;
+        let amount = self.rng.below(MAX_AMOUNT) + 1;
+        let id = self.next_id();
+
+        self.available[client_id as usize] += amount;
+        self.deposits.push((id, client_id, amount));
+
+        record(TransactionType::Deposit, client_id, id, Some(amount))
+    }
+
+    fn withdrawal(&mut self) -> Option<TransactionRecord> {
+        let client_id = self.rng.below(self.available.len() as u64) as ClientId;
+        let available = self.available[client_id as usize];
+        if available == 0 || self.locked[client_id as usize] {
+            return None;
+        }
+
+        let amount = self.rng.below(available) + 1;
+        self.available[client_id as usize] -= amount;
+
+        Some(record(
+            TransactionType::Withdrawal,
+            client_id,
+            self.next_id(),
+            Some(amount),
+        ))
+    }
+
+    fn dispute(&mut self) -> Option<TransactionRecord> {
+        if self.deposits.is_empty() {
+            return None;
+        }
+
+        let index = self.rng.below(self.deposits.len() as u64) as usize;
+        let (id, client_id, amount) = self.deposits[index];
+        if self.available[client_id as usize] < amount {
+            return None;
+        }
+
+        self.deposits.swap_remove(index);
+        self.available[client_id as usize] -= amount;
+        self.disputes.push((id, client_id, amount));
+
+        Some(record(TransactionType::Dispute, client_id, id, None))
+    }
+
+    fn close_dispute(&mut self) -> Option<TransactionRecord> {
+        if self.disputes.is_empty() {
+            return None;
+        }
+
+        let index = self.rng.below(self.disputes.len() as u64) as usize;
+        let (id, client_id, amount) = self.disputes.swap_remove(index);
+
+        if self.rng.chance(self.config.chargeback_rate) {
+            self.locked[client_id as usize] = true;
+            Some(record(TransactionType::Chargeback, client_id, id, None))
+        } else {
+            self.available[client_id as usize] += amount;
+            Some(record(TransactionType::Resolve, client_id, id, None))
+        }
+    }
+
+    fn next_id(&mut self) -> TxId {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+impl Iterator for Generator {
+    type Item = TransactionRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rows >= self.config.rows {
+            return None;
+        }
+        self.rows += 1;
+
+        let dispute_rate = self.config.dispute_rate;
+        let roll = self.rng.unit();
+
+        // Anything that can't be generated right now (e.g. a withdrawal from
+        // an empty account) becomes a deposit.
+        let record = if roll < dispute_rate {
+            self.close_dispute()
+        } else if roll < 2.0 * dispute_rate {
+            self.dispute()
+        } else if roll < 2.0 * dispute_rate + self.config.withdrawal_rate {
+            self.withdrawal()
+        } else {
+            None
+        };
+
+        Some(record.unwrap_or_else(|| self.deposit()))
+    }
+}
+
+fn record(type_: TransactionType, client_id: ClientId, id: TxId, amount: Option<u64>) -> TransactionRecord {
+    TransactionRecord {
+        id,
+        type_,
+        client_id,
+        amount: amount.map(|amount| Decimal::new(amount as i64, AMOUNT_SCALE)),
+        currency: None,
+        timestamp: None,
+    }
+}
+
+/// Writes generated transactions as csv, in the format `data::process_csv`
+/// reads.
+pub fn write_csv(config: GeneratorConfig, writer: impl Write) -> Result<()> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(["type", "client", "tx", "amount"])?;
+
+    for record in Generator::new(config) {
+        let type_ = record.type_.name();
+        let amount = record.amount.map(|amount| amount.to_string()).unwrap_or_default();
+
+        csv_writer.write_record([type_, &record.client_id.to_string(), &record.id.to_string(), &amount])?;
+    }
+
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+// SplitMix64, small and fast, and its output doesn't depend on any crate's
+// version so a seed keeps generating the same file.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    // Uniform in [0, 1).
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::data::{process_reader, ProcessingOptions};
+
+fn config() -> GeneratorConfig {
+    GeneratorConfig {
+        seed: 42,
+        rows: 10_000,
+        clients: 20,
+        dispute_rate: 0.05,
+        chargeback_rate: 0.3,
+        ..GeneratorConfig::default()
+    }
+}
+
+#[test]
+fn test_deterministic() -> Result<()> {
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    write_csv(config(), &mut first)?;
+    write_csv(config(), &mut second)?;
+    assert_eq!(first, second);
+
+    let mut other = Vec::new();
+    write_csv(GeneratorConfig { seed: 7, ..config() }, &mut other)?;
+    assert!(first != other);
+
+    Ok(())
+}
+
+#[test]
+fn test_every_transaction_is_accepted() -> Result<()> {
+    let mut data = Vec::new();
+    write_csv(config(), &mut data)?;
+
+    let mut ledger = Ledger::new();
+    let report = process_reader(data.as_slice(), &mut ledger, ProcessingOptions::default())?;
+    assert_eq!(report.accepted, 10_000);
+    assert_eq!(report.rejected(), 0);
+
+    let counts = report.accepted_by_type;
+    assert!(counts.withdrawals > 0);
+    assert!(counts.disputes > 0);
+    assert!(counts.resolves > 0);
+    assert!(counts.chargebacks > 0);
+    assert!(ledger.clients.len() <= 20);
+
+    Ok(())
+}
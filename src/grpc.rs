@@ -0,0 +1,173 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use serde_json::{json, Value};
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+
+use crate::accounting::client::Client;
+use crate::accounting::shared::SharedLedger;
+use crate::accounting::ClientId;
+use crate::data::{self, JsonlReader, ProcessingOptions, TransactionType};
+
+use self::proto::ledger_server::{Ledger, LedgerServer};
+use self::proto::{Account, GetClientRequest, StreamAccountsRequest, SubmitTransactionResponse, Transaction};
+
+#[cfg(test)]
+#[path = "grpc_tests.rs"]
+mod grpc_tests;
+
+/// The messages and service of `proto/transacto.proto`.
+pub mod proto {
+    tonic::include_proto!("transacto");
+}
+
+/// The gRPC service of `proto/transacto.proto` in front of a shared ledger.
+/// Transactions go through `data::process_records` like those of the HTTP
+/// `Server`, so both can serve the same ledger.
+#[derive(Clone)]
+pub struct Service {
+    ledger: SharedLedger,
+}
+
+impl Service {
+    pub fn new(ledger: SharedLedger) -> Service {
+        Service { ledger }
+    }
+
+    /// Serves connections on `address` until that fails, on a runtime of
+    /// its own.
+    pub fn run(self, address: SocketAddr) -> anyhow::Result<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(
+            tonic::transport::Server::builder()
+                .add_service(LedgerServer::new(self))
+                .serve(address),
+        )?;
+
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Ledger for Service {
+    async fn submit_transaction(
+        &self,
+        request: Request<Transaction>,
+    ) -> Result<Response<SubmitTransactionResponse>, Status> {
+        let transaction = request.into_inner();
+        let Some(type_) = transaction_type(transaction.r#type()) else {
+            return Err(Status::invalid_argument("transaction type is unspecified"));
+        };
+
+        // Written as a JSON line, for `JsonlReader`.
+        let mut record = json!({ "type": type_.name(), "client": transaction.client, "tx": transaction.tx });
+        if let Some(amount) = transaction.amount {
+            record["amount"] = Value::from(amount);
+        }
+        if let Some(currency) = transaction.currency {
+            record["currency"] = Value::from(currency);
+        }
+        if let Some(timestamp) = transaction.timestamp {
+            record["timestamp"] = Value::from(timestamp);
+        }
+        let line = record.to_string();
+
+        let ledger = self.ledger.clone();
+        let report = blocking(move || {
+            ledger.write(|ledger| {
+                data::process_records(
+                    &mut JsonlReader::new(line.as_bytes()),
+                    ledger,
+                    ProcessingOptions::default(),
+                )
+            })
+        })
+        .await?
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+        let response = match report.errors.first() {
+            Some(rejected) => SubmitTransactionResponse {
+                accepted: false,
+                error_code: rejected.error.code().to_string(),
+                error_message: rejected.error.to_string(),
+            },
+            None => SubmitTransactionResponse {
+                accepted: true,
+                ..SubmitTransactionResponse::default()
+            },
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn get_client(&self, request: Request<GetClientRequest>) -> Result<Response<Account>, Status> {
+        let Ok(id) = ClientId::try_from(request.into_inner().client) else {
+            return Err(Status::not_found("client not found"));
+        };
+
+        let ledger = self.ledger.clone();
+        match blocking(move || ledger.client(id)).await? {
+            Some(client) => Ok(Response::new(Account::from(&client))),
+            None => Err(Status::not_found("client not found")),
+        }
+    }
+
+    type StreamAccountsStream = Pin<Box<dyn Stream<Item = Result<Account, Status>> + Send>>;
+
+    async fn stream_accounts(
+        &self,
+        _request: Request<StreamAccountsRequest>,
+    ) -> Result<Response<Self::StreamAccountsStream>, Status> {
+        let ledger = self.ledger.clone();
+        let mut accounts: Vec<Account> =
+            blocking(move || ledger.snapshot().iter().map(Account::from).collect()).await?;
+        accounts.sort_unstable_by_key(|account| account.client);
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(
+            accounts.into_iter().map(Ok),
+        ))))
+    }
+}
+
+// Runs `f` on tokio's blocking threads. It waits for the ledger's lock, and
+// executing transactions can take a while, which would hold up the other
+// requests served by the same worker.
+async fn blocking<R: Send + 'static>(f: impl FnOnce() -> R + Send + 'static) -> Result<R, Status> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| Status::internal(err.to_string()))
+}
+
+impl From<&Client> for Account {
+    fn from(client: &Client) -> Self {
+        // Already a u32 with wide-ids.
+        #[allow(clippy::useless_conversion)]
+        let id = u32::from(client.id());
+        Account {
+            client: id,
+            available: client.available().to_string(),
+            held: client.held().to_string(),
+            total: client.get_total().to_string(),
+            locked: client.locked(),
+        }
+    }
+}
+
+fn transaction_type(type_: proto::TransactionType) -> Option<TransactionType> {
+    match type_ {
+        proto::TransactionType::Unspecified => None,
+        proto::TransactionType::Deposit => Some(TransactionType::Deposit),
+        proto::TransactionType::Withdrawal => Some(TransactionType::Withdrawal),
+        proto::TransactionType::Dispute => Some(TransactionType::Dispute),
+        proto::TransactionType::Resolve => Some(TransactionType::Resolve),
+        proto::TransactionType::Chargeback => Some(TransactionType::Chargeback),
+        proto::TransactionType::Reversal => Some(TransactionType::Reversal),
+        proto::TransactionType::Freeze => Some(TransactionType::Freeze),
+        proto::TransactionType::Unfreeze => Some(TransactionType::Unfreeze),
+        proto::TransactionType::CloseAccount => Some(TransactionType::CloseAccount),
+        proto::TransactionType::CreditLimit => Some(TransactionType::CreditLimit),
+        proto::TransactionType::Accrue => Some(TransactionType::Accrue),
+        proto::TransactionType::CloseDay => Some(TransactionType::CloseDay),
+    }
+}
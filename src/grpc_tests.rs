@@ -0,0 +1,117 @@
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+use tokio::runtime::Runtime;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_stream::StreamExt;
+use tonic::Code;
+
+use super::proto::ledger_client::LedgerClient;
+use super::*;
+
+fn transaction(type_: proto::TransactionType, client: u32, tx: u64, amount: Option<&str>) -> Transaction {
+    Transaction {
+        r#type: type_.into(),
+        client,
+        tx,
+        amount: amount.map(str::to_string),
+        ..Transaction::default()
+    }
+}
+
+fn account(client: u32, available: &str, held: &str, total: &str) -> Account {
+    Account {
+        client,
+        available: available.to_string(),
+        held: held.to_string(),
+        total: total.to_string(),
+        locked: false,
+    }
+}
+
+#[test]
+fn test_service() -> Result<()> {
+    let service = Service::new(SharedLedger::new());
+    Runtime::new()?.block_on(async {
+        let submit = |transaction| service.submit_transaction(Request::new(transaction));
+
+        let response = submit(transaction(proto::TransactionType::Deposit, 2, 1, Some("10"))).await?;
+        assert_eq!(
+            response.into_inner(),
+            SubmitTransactionResponse {
+                accepted: true,
+                ..SubmitTransactionResponse::default()
+            }
+        );
+        submit(transaction(proto::TransactionType::Deposit, 1, 2, Some("1.5"))).await?;
+        submit(transaction(proto::TransactionType::Dispute, 2, 1, None)).await?;
+
+        let response = submit(transaction(proto::TransactionType::Withdrawal, 1, 3, Some("5"))).await?;
+        assert_eq!(
+            response.into_inner(),
+            SubmitTransactionResponse {
+                accepted: false,
+                error_code: "insufficient_funds".to_string(),
+                error_message: "failed to execute transaction, err=insufficient funds".to_string(),
+            }
+        );
+        let response = submit(transaction(proto::TransactionType::Withdrawal, 1, 4, None)).await?;
+        assert_eq!(response.into_inner().error_code, "missing_amount");
+        let status = submit(transaction(proto::TransactionType::Unspecified, 1, 5, None))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let response = service.get_client(Request::new(GetClientRequest { client: 2 })).await?;
+        assert_eq!(response.into_inner(), account(2, "0", "10", "10"));
+        for client in [7, u32::MAX] {
+            let status = service
+                .get_client(Request::new(GetClientRequest { client }))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), Code::NotFound);
+        }
+
+        let accounts: Vec<Account> = service
+            .stream_accounts(Request::new(StreamAccountsRequest {}))
+            .await?
+            .into_inner()
+            .collect::<Result<_, _>>()
+            .await?;
+        assert_eq!(
+            accounts,
+            vec![account(1, "1.5", "0", "1.5"), account(2, "0", "10", "10")]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn test_connection() -> Result<()> {
+    let ledger = SharedLedger::new();
+    let service = Service::new(ledger.clone());
+    Runtime::new()?.block_on(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(LedgerServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = LedgerClient::connect(format!("http://{}", address)).await?;
+        let response = client
+            .submit_transaction(transaction(proto::TransactionType::Deposit, 1, 1, Some("2.25")))
+            .await?;
+        assert!(response.into_inner().accepted);
+
+        let response = client.get_client(GetClientRequest { client: 1 }).await?;
+        assert_eq!(response.into_inner(), account(1, "2.25", "0", "2.25"));
+
+        anyhow::Ok(())
+    })?;
+    assert_eq!(ledger.client(1).map(|client| client.available()), Some(dec!(2.25)));
+
+    Ok(())
+}
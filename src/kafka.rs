@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::debug;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer};
+use rdkafka::error::{KafkaError, KafkaResult};
+use rdkafka::message::Message;
+use rdkafka::{Offset, TopicPartitionList};
+
+use crate::accounting::ledger::Ledger;
+use crate::data::{self, PayloadFormat, ProcessingOptions};
+
+#[cfg(test)]
+#[path = "kafka_tests.rs"]
+mod kafka_tests;
+
+// How long a poll waits for a message. A checkpoint that's only due by time
+// is saved when none came.
+const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What a `KafkaSource` consumes.
+#[derive(Clone, Debug)]
+pub struct KafkaOptions {
+    /// The bootstrap servers, e.g. `localhost:9092`.
+    pub brokers: String,
+    pub topic: String,
+    /// The consumer group, whose committed offsets are where consuming
+    /// starts. A group that hasn't committed any starts from the beginning.
+    pub group: String,
+    pub format: PayloadFormat,
+    /// Other librdkafka settings, e.g. `security.protocol`.
+    pub properties: Vec<(String, String)>,
+    /// Stop once every assigned partition is read to its end, rather than
+    /// waiting for more.
+    pub until_end: bool,
+}
+
+/// What `KafkaSource::run` did.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConsumeReport {
+    pub messages: u64,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub commits: u64,
+}
+
+/// Executes the transactions of a Kafka topic, one message after the other,
+/// with at least once semantics: the group's offsets are only committed
+/// once a checkpoint has everything executed before them, see
+/// `Ledger::is_checkpointed`. After a crash the messages since the last
+/// commit are consumed again, on top of the last checkpoint, and the ledger
+/// ignores or rejects what it already executed. So the ledger needs
+/// checkpoints, without them nothing is ever committed.
+///
+/// Rejected records are logged and skipped, like those of a file. The
+/// ledger is a single one, so the group should only have this consumer.
+pub struct KafkaSource {
+    consumer: BaseConsumer,
+    options: KafkaOptions,
+    offsets: Offsets,
+    ended: BTreeSet<i32>,
+}
+
+impl KafkaSource {
+    /// Subscribes to the topic. Connecting to the brokers happens in the
+    /// background, so only invalid options fail here.
+    pub fn new(options: KafkaOptions) -> Result<KafkaSource> {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &options.brokers)
+            .set("group.id", &options.group)
+            // Committed by `run`, after checkpoints.
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .set("enable.partition.eof", options.until_end.to_string());
+        for (key, value) in &options.properties {
+            config.set(key, value);
+        }
+
+        let consumer: BaseConsumer = config.create()?;
+        consumer.subscribe(&[&options.topic])?;
+
+        Ok(KafkaSource {
+            consumer,
+            options,
+            offsets: Offsets::default(),
+            ended: BTreeSet::new(),
+        })
+    }
+
+    /// Consumes until the end of the topic with `until_end`, or else until
+    /// something fails. At the end a checkpoint is saved and the last
+    /// offsets are committed.
+    pub fn run(&mut self, ledger: &mut Ledger) -> Result<ConsumeReport> {
+        let mut report = ConsumeReport::default();
+        loop {
+            match self.consumer.poll(POLL_TIMEOUT) {
+                Some(Ok(message)) => {
+                    let (partition, offset) = (message.partition(), message.offset());
+                    self.ended.remove(&partition);
+
+                    // Tombstones carry nothing to execute.
+                    if let Some(payload) = message.payload() {
+                        let processed =
+                            data::process_payload(payload, self.options.format, ledger, ProcessingOptions::default())?;
+                        for rejected in &processed.errors {
+                            debug!(
+                                "rejected record, partition={}, offset={}, record={}, err={}",
+                                partition, offset, rejected.line, rejected.error
+                            );
+                        }
+                        report.accepted += processed.accepted;
+                        report.rejected += processed.rejected();
+                    }
+
+                    report.messages += 1;
+                    self.offsets.consumed(partition, offset);
+                },
+                Some(Err(KafkaError::PartitionEOF(partition))) => {
+                    self.ended.insert(partition);
+                    if self.options.until_end && self.is_at_end()? {
+                        break;
+                    }
+                },
+                // librdkafka logs these and retries on its own, e.g. while the
+                // brokers are down, waiting here keeps it from spinning.
+                Some(Err(err)) => {
+                    debug!("failed to consume, topic={}, err={}", self.options.topic, err);
+                    thread::sleep(POLL_TIMEOUT);
+                    ledger.checkpoint_if_due()?;
+                },
+                None => {
+                    ledger.checkpoint_if_due()?;
+                },
+            }
+
+            if ledger.is_checkpointed() && self.commit(CommitMode::Async)? {
+                report.commits += 1;
+            }
+        }
+
+        ledger.checkpoint()?;
+        if ledger.is_checkpointed() && self.commit(CommitMode::Sync)? {
+            report.commits += 1;
+        }
+
+        Ok(report)
+    }
+
+    // Whether every assigned partition was read to its end.
+    fn is_at_end(&self) -> KafkaResult<bool> {
+        let assignment = self.consumer.assignment()?;
+        Ok(assignment.count() > 0
+            && assignment
+                .elements()
+                .iter()
+                .all(|element| self.ended.contains(&element.partition())))
+    }
+
+    // Commits the offsets consumed since the last commit. Returns whether
+    // there were any.
+    fn commit(&mut self, mode: CommitMode) -> KafkaResult<bool> {
+        if self.offsets.is_empty() {
+            return Ok(false);
+        }
+
+        self.consumer.commit(&self.offsets.list(&self.options.topic)?, mode)?;
+        self.offsets = Offsets::default();
+
+        Ok(true)
+    }
+}
+
+// The offsets to commit for the messages consumed since the last commit, by
+// partition: the one after the last message.
+#[derive(Debug, Default)]
+struct Offsets(BTreeMap<i32, i64>);
+
+impl Offsets {
+    fn consumed(&mut self, partition: i32, offset: i64) {
+        self.0.insert(partition, offset + 1);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn list(&self, topic: &str) -> KafkaResult<TopicPartitionList> {
+        let mut list = TopicPartitionList::new();
+        for (&partition, &offset) in &self.0 {
+            list.add_partition_offset(topic, partition, Offset::Offset(offset))?;
+        }
+
+        Ok(list)
+    }
+}
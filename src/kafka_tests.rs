@@ -0,0 +1,55 @@
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn options(properties: Vec<(String, String)>) -> KafkaOptions {
+    KafkaOptions {
+        brokers: "127.0.0.1:9".to_string(),
+        topic: "transactions".to_string(),
+        group: "transacto".to_string(),
+        format: PayloadFormat::Json,
+        properties,
+        until_end: true,
+    }
+}
+
+#[test]
+fn test_offsets() -> Result<()> {
+    let mut offsets = Offsets::default();
+    assert!(offsets.is_empty());
+    offsets.consumed(1, 10);
+    offsets.consumed(0, 4);
+    offsets.consumed(1, 11);
+
+    let list = offsets.list("transactions")?;
+    let committed: Vec<_> = list
+        .elements()
+        .iter()
+        .map(|element| (element.topic().to_string(), element.partition(), element.offset()))
+        .collect();
+    assert_eq!(
+        committed,
+        vec![
+            ("transactions".to_string(), 0, Offset::Offset(5)),
+            ("transactions".to_string(), 1, Offset::Offset(12)),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_new() -> Result<()> {
+    let source = KafkaSource::new(options(Vec::new()))?;
+    assert_eq!(source.is_at_end()?, false);
+
+    let invalid = options(vec![("no.such.property".to_string(), "1".to_string())]);
+    if let Err(err) = KafkaSource::new(invalid) {
+        assert!(err.to_string().contains("no.such.property"), "{}", err);
+    } else {
+        bail!("expected an invalid property to fail");
+    }
+
+    Ok(())
+}
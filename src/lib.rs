@@ -1,2 +1,10 @@
 pub mod accounting;
+pub mod bench;
 pub mod data;
+pub mod encryption;
+pub mod generate;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod server;
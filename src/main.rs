@@ -1,28 +1,1629 @@
 use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use log::error;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use log::{debug, error, warn};
 
-use transacto::accounting::ledger::Ledger;
-use transacto::data;
+use transacto::accounting::audit::{verify_audit_log, AuditError, AuditLog};
+use transacto::accounting::checkpoint::CheckpointPolicy;
+use transacto::accounting::currency::Currency;
+use transacto::accounting::ledger::{Ledger, LedgerStats};
+use transacto::accounting::merge::{ClientPolicy, DuplicatePolicy, MergePolicy};
+use transacto::accounting::monitoring::ChargebackAlert;
+use transacto::accounting::shared::SharedLedger;
+use transacto::accounting::snapshot::{SnapshotEncoding, SnapshotOptions};
+use transacto::accounting::store::bloom::BloomFilter;
+use transacto::accounting::timestamp::Timestamp;
+use transacto::accounting::transactions::Transaction;
+use transacto::accounting::wal::Wal;
+use transacto::accounting::{ClientId, TxId};
+use transacto::bench;
+use transacto::data::{
+    self, ClientIds, ErrorPolicy, ExportOptions, ExportSpec, Format, PayloadFormat, ProcessingOptions,
+    ProcessingReport, SortKey, StatementFormat,
+};
+use transacto::encryption::{is_encrypted, EncryptedReader, EncryptedWriter, Key};
+use transacto::generate::{self, GeneratorConfig};
+#[cfg(feature = "kafka")]
+use transacto::kafka::{KafkaOptions, KafkaSource};
+use transacto::server::Server;
 
-fn main() {
+use crate::config::Config;
+
+mod config;
+
+/// Processes transactions and prints the resulting client accounts as csv.
+///
+/// Without a subcommand it behaves like `process`, e.g.
+/// `transacto transactions.csv > accounts.csv`.
+#[derive(Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    process: Option<ProcessArgs>,
+
+    /// Read settings from this TOML file. Flags and environment variables
+    /// take precedence over it.
+    #[arg(long = "config", env = "TRANSACTO_CONFIG", value_name = "CONFIG_FILE", global = true)]
+    config_file: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Process a file of transactions and print the client accounts.
+    Process(Box<ProcessArgs>),
+    /// Check a file of transactions without executing them.
+    Validate(ValidateArgs),
+    /// Serve a ledger over the network.
+    Serve(ServeArgs),
+    /// Execute the transactions of a Kafka topic as they come in.
+    Consume(ConsumeArgs),
+    /// Query a saved ledger.
+    Inspect(InspectArgs),
+    /// Compare the accounts of two saved ledgers.
+    Diff(DiffArgs),
+    /// Combine saved ledgers into one.
+    Merge(MergeArgs),
+    /// Print the contents of an encrypted file.
+    Decrypt(DecryptArgs),
+    /// Rebuild the client accounts from an event log, see `--events`.
+    Replay(ReplayArgs),
+    /// Check that an audit log wasn't altered, see `--audit-log`.
+    VerifyAudit(VerifyAuditArgs),
+    /// Write synthetic transactions as csv.
+    Generate(GenerateArgs),
+    /// Measure how fast the ledger executes synthetic transactions.
+    Bench(BenchArgs),
+    /// Print a shell completion script.
+    Completions(CompletionsArgs),
+    /// Print the man page.
+    Man,
+}
+
+#[derive(Args)]
+struct ProcessArgs {
+    input_file: String,
+
+    /// Write every rejected row, with the reason, to this csv file.
+    #[arg(long = "rejects", env = "TRANSACTO_REJECTS", value_name = "REJECTS_FILE")]
+    rejects_file: Option<String>,
+
+    /// Append every executed transaction, with the client's account after
+    /// it, to this file as one JSON object per line.
+    #[arg(long = "events", env = "TRANSACTO_EVENTS", value_name = "EVENTS_FILE")]
+    events_file: Option<String>,
+
+    /// Write every executed transaction, with the client's available, held
+    /// and total funds after it, to this csv file, e.g. to reconcile against
+    /// bank statements.
+    #[arg(long = "balances", env = "TRANSACTO_BALANCES", value_name = "BALANCES_FILE")]
+    balances_file: Option<String>,
+
+    /// Write the disputes, chargebacks and rejected withdrawals of every
+    /// client, and their dispute to deposit ratio, to this csv or jsonl file.
+    #[arg(long = "risk-report", env = "TRANSACTO_RISK_REPORT", value_name = "RISK_FILE")]
+    risk_file: Option<String>,
+
+    /// Write a page for a browser to this file, with a summary of the run,
+    /// the exported accounts and the locked ones. Needs a build with the
+    /// html feature.
+    #[arg(long = "html-report", env = "TRANSACTO_HTML_REPORT", value_name = "HTML_FILE")]
+    html_file: Option<String>,
+
+    /// Write the exported accounts to an Excel workbook in this file too.
+    /// Needs a build with the xlsx feature.
+    #[arg(long = "xlsx", env = "TRANSACTO_XLSX", value_name = "XLSX_FILE")]
+    xlsx_file: Option<String>,
+
+    /// Add a sheet with a summary of the run to the workbook of `--xlsx`.
+    #[arg(long, env = "TRANSACTO_XLSX_SUMMARY", requires = "xlsx_file")]
+    xlsx_summary: bool,
+
+    /// Write what every client did by day, going by the timestamp column,
+    /// to this csv or jsonl file: the records of every type and the net
+    /// change of its funds.
+    #[arg(long = "daily-report", env = "TRANSACTO_DAILY_REPORT", value_name = "DAILY_FILE")]
+    daily_file: Option<String>,
+
+    /// Write an ISO 20022 camt.053 statement of every client to this file,
+    /// with what its transactions of the run did to its funds and its
+    /// opening and closing balances.
+    #[arg(long = "camt053", env = "TRANSACTO_CAMT053", value_name = "CAMT053_FILE")]
+    camt053_file: Option<String>,
+
+    /// The currency of the input, e.g. USD. Records in another are
+    /// rejected. By default it's the first currency in the input.
+    #[arg(long, env = "TRANSACTO_CURRENCY", value_name = "CURRENCY")]
+    currency: Option<Currency>,
+
+    /// The currency of the camt.053 statements, that of the input, or XXX
+    /// (no currency) without one, by default.
+    #[arg(long, env = "TRANSACTO_CAMT053_CURRENCY", value_name = "CURRENCY")]
+    camt053_currency: Option<Currency>,
+
+    /// Settle the deposits, withdrawals and chargebacks of every client by
+    /// day, closed by a close_day record or the first transaction of the
+    /// next day, and write them to this csv or jsonl file. The last day is
+    /// closed at the end of the input.
+    #[arg(long = "settlements", env = "TRANSACTO_SETTLEMENTS", value_name = "SETTLEMENTS_FILE")]
+    settlements_file: Option<String>,
+
+    /// Stop at the first rejected row and exit with an error.
+    #[arg(long, env = "TRANSACTO_STRICT")]
+    strict: bool,
+
+    /// csv, jsonl or parquet. Detected from the input file's extension by
+    /// default, falling back to csv.
+    #[arg(long, env = "TRANSACTO_INPUT_FORMAT")]
+    input_format: Option<Format>,
+
+    /// Write the accounts to this file instead of stdout.
+    #[arg(long = "output", env = "TRANSACTO_OUTPUT", value_name = "OUTPUT_FILE")]
+    output_file: Option<String>,
+
+    /// csv, jsonl or parquet. Detected from the output file's extension by
+    /// default, falling back to csv.
+    #[arg(long, env = "TRANSACTO_OUTPUT_FORMAT")]
+    output_format: Option<Format>,
+
+    /// Print the accounts to stdout as a table with aligned columns, for
+    /// people to read, with the locked accounts in red on a terminal unless
+    /// NO_COLOR is set. The table isn't encrypted with a key.
+    #[arg(long, env = "TRANSACTO_PRETTY", conflicts_with_all = ["output_file", "output_format"])]
+    pretty: bool,
+
+    /// Read the input file as a bank statement of this client, OFX (.ofx
+    /// or .qfx) or QIF (.qif) going by its extension. Credits are deposits
+    /// and debits withdrawals. Payment instructions in ISO 20022 pain.001
+    /// (.xml) are withdrawals.
+    #[arg(
+        long,
+        env = "TRANSACTO_STATEMENT_CLIENT",
+        value_name = "CLIENT",
+        conflicts_with_all = ["input_format", "client_ids_file"]
+    )]
+    statement_client: Option<ClientId>,
+
+    /// Read the input file as FIX messages, one per line. The tags of the
+    /// columns can be set in the `[fix]` section of the config file, by
+    /// default Side (buys are withdrawals and sells deposits), Account,
+    /// ExecID, NetMoney, Currency and TransactTime.
+    #[arg(
+        long,
+        env = "TRANSACTO_FIX",
+        conflicts_with_all = ["input_format", "client_ids_file", "statement_client"]
+    )]
+    fix: bool,
+
+    /// Read the metadata of clients from this csv or jsonl file before
+    /// processing, with a client column and any of display_name,
+    /// external_ref and opened_at.
+    #[arg(long = "clients", env = "TRANSACTO_CLIENTS", value_name = "CLIENTS_FILE")]
+    clients_file: Option<String>,
+
+    /// Add the metadata of every client to the exported accounts.
+    #[arg(long, env = "TRANSACTO_WITH_METADATA")]
+    with_metadata: bool,
+
+    /// Only export the locked accounts.
+    #[arg(long, env = "TRANSACTO_LOCKED_ONLY")]
+    locked_only: bool,
+
+    /// Only export the accounts with funds, i.e. whose total isn't 0.
+    #[arg(long, env = "TRANSACTO_NON_ZERO_ONLY")]
+    non_zero_only: bool,
+
+    /// Only export the accounts of these clients, e.g. `1,2,3`. They're the
+    /// internal ids with `--client-ids`.
+    #[arg(long, env = "TRANSACTO_ONLY_CLIENTS", value_name = "CLIENTS", value_delimiter = ',')]
+    only_clients: Option<Vec<ClientId>>,
+
+    /// Export the accounts in this order, e.g. `client` for ascending client
+    /// ids. They're in no particular order by default.
+    #[arg(long, env = "TRANSACTO_SORT", value_name = "KEY")]
+    sort: Option<SortKey>,
+
+    /// Export these columns, in this order, e.g. `client,total,open_disputes`.
+    /// Besides those of the accounts there are currency, open_disputes,
+    /// the columns of `--lifetime-totals`, display_name, external_ref and
+    /// opened_at.
+    #[arg(long, env = "TRANSACTO_COLUMNS", value_name = "COLUMNS")]
+    columns: Option<ExportSpec>,
+
+    /// Add what every client deposited, withdrew, disputed and charged back
+    /// in this run to the exported accounts, as total_deposited,
+    /// total_withdrawn, dispute_count and chargeback_count.
+    #[arg(long, env = "TRANSACTO_LIFETIME_TOTALS")]
+    lifetime_totals: bool,
+
+    /// Write the exported amounts with exactly this many decimal places, e.g.
+    /// 4 for `5.0000` instead of `5`.
+    #[arg(
+        long,
+        env = "TRANSACTO_DECIMAL_PLACES",
+        value_name = "PLACES",
+        value_parser = clap::value_parser!(u32).range(0..=28)
+    )]
+    decimal_places: Option<u32>,
+
+    /// Read the client column of the input as external ids, e.g.
+    /// alphanumeric customer ids, mapped to internal ids in this csv file.
+    /// The file is created if missing and updated with the new ids, and the
+    /// exported accounts have the external ids.
+    #[arg(long = "client-ids", env = "TRANSACTO_CLIENT_IDS", value_name = "CLIENT_IDS_FILE")]
+    client_ids_file: Option<String>,
+
+    /// Print statistics about the run to stderr.
+    #[arg(long, env = "TRANSACTO_SUMMARY")]
+    summary: bool,
+
+    /// Start from the ledger saved in this file, instead of an empty one.
+    #[arg(
+        long = "load-snapshot",
+        env = "TRANSACTO_LOAD_SNAPSHOT",
+        value_name = "SNAPSHOT_FILE"
+    )]
+    load_snapshot_file: Option<String>,
+
+    /// Save the ledger to this file after processing, see `inspect`.
+    #[arg(
+        long = "save-snapshot",
+        env = "TRANSACTO_SAVE_SNAPSHOT",
+        value_name = "SNAPSHOT_FILE"
+    )]
+    save_snapshot_file: Option<String>,
+
+    /// bincode or cbor, used for the saved snapshot. Loading detects it.
+    #[arg(long, env = "TRANSACTO_SNAPSHOT_ENCODING")]
+    snapshot_encoding: Option<SnapshotEncoding>,
+
+    /// Journal every transaction to a write-ahead log in this directory
+    /// before executing it.
+    #[arg(long = "wal", env = "TRANSACTO_WAL", value_name = "WAL_DIR")]
+    wal_dir: Option<String>,
+
+    /// Append every applied transaction to this hash-chained audit log, see
+    /// `verify-audit`.
+    #[arg(long = "audit-log", env = "TRANSACTO_AUDIT_LOG", value_name = "AUDIT_LOG_FILE")]
+    audit_log_file: Option<String>,
+
+    /// Save a checkpoint of the ledger to this file while processing. With a
+    /// write-ahead log, it's truncated after every checkpoint.
+    #[arg(long = "checkpoint", env = "TRANSACTO_CHECKPOINT", value_name = "CHECKPOINT_FILE")]
+    checkpoint_file: Option<String>,
+
+    /// Save a checkpoint every N transactions, with an optional K, M or G
+    /// suffix. Defaults to 100K unless there is an interval.
+    #[arg(long, env = "TRANSACTO_CHECKPOINT_EVERY", value_name = "N", value_parser = parse_count)]
+    checkpoint_every: Option<usize>,
+
+    /// Save a checkpoint every this many seconds.
+    #[arg(long, env = "TRANSACTO_CHECKPOINT_INTERVAL", value_name = "SECONDS")]
+    checkpoint_interval: Option<u64>,
+
+    /// Encrypt the saved snapshot, the accounts and the reports with the 32
+    /// byte key in this file. A loaded snapshot or client ids file is
+    /// decrypted with it. The rejects, events and balances files are written
+    /// as the input is processed and can't be encrypted, so they can't be
+    /// used with a key.
+    #[arg(long = "key-file", env = "TRANSACTO_KEY_FILE", value_name = "KEY_FILE")]
+    key_file: Option<String>,
+
+    /// Continue an interrupted run over the same file, skipping what the
+    /// loaded snapshot, or else the checkpoint, already executed. With a
+    /// write-ahead log, the transactions journaled since are executed first.
+    #[arg(long, env = "TRANSACTO_RESUME")]
+    resume: bool,
+
+    /// Process the file without exporting anything. The summary goes to
+    /// stderr and the rejected rows to stdout, instead of the accounts.
+    #[arg(long, env = "TRANSACTO_DRY_RUN")]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct ValidateArgs {
+    input_file: String,
+
+    /// Write every invalid row, with the reason, to this csv file.
+    #[arg(long = "rejects", env = "TRANSACTO_REJECTS", value_name = "REJECTS_FILE")]
+    rejects_file: Option<String>,
+
+    /// csv, jsonl or parquet. Detected from the input file's extension by
+    /// default, falling back to csv.
+    #[arg(long, env = "TRANSACTO_INPUT_FORMAT")]
+    input_format: Option<Format>,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[arg(long, env = "TRANSACTO_LISTEN", default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Start from the ledger saved in this snapshot rather than an empty
+    /// one.
+    #[arg(long = "snapshot", env = "TRANSACTO_SNAPSHOT", value_name = "SNAPSHOT_FILE")]
+    snapshot_file: Option<String>,
+
+    /// Decrypt the snapshot with the 32 byte key in this file.
+    #[arg(long = "key-file", env = "TRANSACTO_KEY_FILE", value_name = "KEY_FILE")]
+    key_file: Option<String>,
+
+    /// Write the accounts to this csv, jsonl or parquet file on a
+    /// `POST /export`.
+    #[arg(long = "export-file", env = "TRANSACTO_EXPORT_FILE", value_name = "EXPORT_FILE")]
+    export_file: Option<String>,
+
+    /// Serve the gRPC service of `proto/transacto.proto` on this address
+    /// too, with the same ledger. Needs a build with the grpc feature.
+    #[arg(long, env = "TRANSACTO_GRPC_LISTEN", value_name = "ADDR")]
+    grpc_listen: Option<SocketAddr>,
+}
+
+#[derive(Args)]
+struct ConsumeArgs {
+    /// The Kafka bootstrap servers, e.g. `localhost:9092`.
+    #[arg(long, env = "TRANSACTO_KAFKA_BROKERS")]
+    brokers: String,
+
+    #[arg(long, env = "TRANSACTO_KAFKA_TOPIC")]
+    topic: String,
+
+    /// The consumer group, whose committed offsets are where consuming
+    /// starts.
+    #[arg(long, env = "TRANSACTO_KAFKA_GROUP", default_value = "transacto")]
+    group: String,
+
+    /// json (JSON lines) or avro (records of `avro/transaction.avsc`).
+    #[arg(long, env = "TRANSACTO_PAYLOAD_FORMAT", default_value = "json")]
+    payload_format: PayloadFormat,
+
+    /// Set a librdkafka property, e.g. `security.protocol=ssl`. Can be
+    /// repeated.
+    #[arg(long = "property", value_name = "KEY=VALUE", value_parser = parse_property)]
+    properties: Vec<(String, String)>,
+
+    /// Save a checkpoint of the ledger to this file, and start from it if
+    /// it exists. Offsets are only committed after a checkpoint, so one is
+    /// required.
+    #[arg(long = "checkpoint", env = "TRANSACTO_CHECKPOINT", value_name = "CHECKPOINT_FILE")]
+    checkpoint_file: Option<String>,
+
+    /// Save a checkpoint every N transactions, with an optional K, M or G
+    /// suffix. Defaults to 100K unless there is an interval.
+    #[arg(long, env = "TRANSACTO_CHECKPOINT_EVERY", value_name = "N", value_parser = parse_count)]
+    checkpoint_every: Option<usize>,
+
+    /// Save a checkpoint every this many seconds.
+    #[arg(long, env = "TRANSACTO_CHECKPOINT_INTERVAL", value_name = "SECONDS")]
+    checkpoint_interval: Option<u64>,
+
+    /// bincode or cbor, used for the checkpoints.
+    #[arg(long, env = "TRANSACTO_SNAPSHOT_ENCODING")]
+    snapshot_encoding: Option<SnapshotEncoding>,
+
+    /// Encrypt the checkpoints with the 32 byte key in this file.
+    #[arg(long = "key-file", env = "TRANSACTO_KEY_FILE", value_name = "KEY_FILE")]
+    key_file: Option<String>,
+
+    /// Stop once the whole topic is executed, rather than waiting for more.
+    #[arg(long, env = "TRANSACTO_UNTIL_END")]
+    until_end: bool,
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    snapshot_file: String,
+
+    /// Print this client's account.
+    #[arg(long)]
+    client: Option<ClientId>,
+
+    /// Print this transaction and its dispute status.
+    #[arg(long)]
+    tx: Option<TxId>,
+
+    /// Decrypt the snapshot with the 32 byte key in this file.
+    #[arg(long = "key-file", env = "TRANSACTO_KEY_FILE", value_name = "KEY_FILE")]
+    key_file: Option<String>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    before_file: String,
+    after_file: String,
+
+    /// Decrypt the snapshots with the 32 byte key in this file.
+    #[arg(long = "key-file", env = "TRANSACTO_KEY_FILE", value_name = "KEY_FILE")]
+    key_file: Option<String>,
+}
+
+#[derive(Args)]
+struct MergeArgs {
+    /// Merged in this order, into the first one.
+    #[arg(required = true, num_args = 2..)]
+    snapshot_files: Vec<String>,
+
+    /// Save the merged ledger to this file.
+    #[arg(long = "save-snapshot", value_name = "SNAPSHOT_FILE")]
+    save_snapshot_file: String,
+
+    /// bincode or cbor, used for the saved snapshot.
+    #[arg(long, env = "TRANSACTO_SNAPSHOT_ENCODING")]
+    snapshot_encoding: Option<SnapshotEncoding>,
+
+    /// Keep the first of transactions with the same id instead of failing.
+    /// The accounts still include both.
+    #[arg(long)]
+    keep_duplicates: bool,
+
+    /// Fail if a client is in more than one ledger, instead of adding up
+    /// its funds.
+    #[arg(long)]
+    disjoint_clients: bool,
+
+    /// Decrypt the snapshots, and encrypt the saved one, with the 32 byte
+    /// key in this file.
+    #[arg(long = "key-file", env = "TRANSACTO_KEY_FILE", value_name = "KEY_FILE")]
+    key_file: Option<String>,
+}
+
+#[derive(Args)]
+struct DecryptArgs {
+    input_file: String,
+
+    #[arg(long = "key-file", env = "TRANSACTO_KEY_FILE", value_name = "KEY_FILE")]
+    key_file: String,
+}
+
+#[derive(Args)]
+struct VerifyAuditArgs {
+    audit_log_file: String,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    events_file: String,
+
+    /// Check the accounts against this export instead of printing them, and
+    /// exit with an error if any of them differ.
+    #[arg(long = "expected", value_name = "ACCOUNTS_FILE")]
+    expected_file: Option<String>,
+
+    /// csv or jsonl. Detected from the expected file's extension by default,
+    /// falling back to csv.
+    #[arg(long)]
+    expected_format: Option<Format>,
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    #[arg(long, default_value_t = 1000)]
+    rows: usize,
+
+    #[arg(long, default_value_t = 1000)]
+    clients: ClientId,
+
+    /// The same seed always generates the same file.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Share of rows that are withdrawals.
+    #[arg(long, default_value_t = 0.3)]
+    withdrawal_rate: f64,
+
+    /// Share of rows that open a dispute, about as many close one.
+    #[arg(long, default_value_t = 0.02)]
+    dispute_rate: f64,
+
+    /// Share of disputes that end in a chargeback.
+    #[arg(long, default_value_t = 0.2)]
+    chargeback_rate: f64,
+
+    /// Write to this file instead of stdout.
+    #[arg(long = "output", value_name = "OUTPUT_FILE")]
+    output_file: Option<String>,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    /// Number of transactions, with an optional K, M or G suffix.
+    #[arg(long, default_value = "1M", value_parser = parse_count)]
+    rows: usize,
+
+    #[arg(long, default_value_t = 1000)]
+    clients: ClientId,
+
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(Args)]
+struct CompletionsArgs {
+    shell: Shell,
+}
+
+fn parse_count(count: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match count.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&count[..index], 1_000),
+        Some((index, 'm' | 'M')) => (&count[..index], 1_000_000),
+        Some((index, 'g' | 'G')) => (&count[..index], 1_000_000_000),
+        _ => (count, 1),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|count| count.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid count {}", count))
+}
+
+fn parse_property(property: &str) -> Result<(String, String), String> {
+    match property.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("invalid property {}, expected KEY=VALUE", property)),
+    }
+}
+
+fn checkpoint_policy(every: Option<usize>, interval: Option<u64>, config: &config::Checkpoint) -> CheckpointPolicy {
+    let policy = CheckpointPolicy {
+        every_transactions: every.map(|every| every as u64).or(config.every_transactions),
+        every: interval.or(config.every_seconds).map(Duration::from_secs),
+    };
+    if policy.every_transactions.is_none() && policy.every.is_none() {
+        return CheckpointPolicy::default();
+    }
+
+    policy
+}
+
+fn main() -> ExitCode {
     env_logger::init();
 
-    let args: Vec<String> = env::args().collect();
+    let cli = Cli::parse();
+    let config = match &cli.config_file {
+        Some(config_file) => match Config::load(config_file) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("failed to load config, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        },
+        None => Config::default(),
+    };
+
+    match cli.command {
+        Some(Command::Process(args)) => process(*args, config),
+        Some(Command::Validate(args)) => validate(args, config),
+        Some(Command::Serve(args)) => serve(args, config),
+        Some(Command::Consume(args)) => consume(args, config),
+        Some(Command::Inspect(args)) => inspect(args, config),
+        Some(Command::Diff(args)) => diff(args, config),
+        Some(Command::Merge(args)) => merge(args, config),
+        Some(Command::Decrypt(args)) => decrypt(args),
+        Some(Command::Replay(args)) => replay(args),
+        Some(Command::VerifyAudit(args)) => verify_audit(args),
+        Some(Command::Generate(args)) => generate(args),
+        Some(Command::Bench(args)) => bench(args),
+        Some(Command::Completions(args)) => {
+            // Generated in memory, as clap_complete panics on write errors.
+            let mut script = Vec::new();
+            clap_complete::generate(args.shell, &mut Cli::command(), "transacto", &mut script);
+            match io::stdout().write_all(&script) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    error!("failed to write completions, err={}", err);
+                    ExitCode::FAILURE
+                },
+            }
+        },
+        Some(Command::Man) => match clap_mangen::Man::new(Cli::command()).render(&mut io::stdout()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                error!("failed to render man page, err={}", err);
+                ExitCode::FAILURE
+            },
+        },
+        None => match cli.process {
+            Some(args) => process(args, config),
+            None => {
+                let _ = Cli::command().print_help();
+                ExitCode::FAILURE
+            },
+        },
+    }
+}
+
+fn format_of(format: Option<Format>, file_path: &str) -> Format {
+    format.or_else(|| Format::from_path(file_path)).unwrap_or_default()
+}
+
+fn serve(args: ServeArgs, config: Config) -> ExitCode {
+    let key = match args.key_file.or(config.key_file).map(Key::load).transpose() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("failed to load key, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let mut ledger = match args.snapshot_file {
+        Some(snapshot_file) => match Ledger::load_with(snapshot_file, key.as_ref()) {
+            Ok(ledger) => ledger,
+            Err(err) => {
+                error!("failed to read snapshot, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        },
+        None => Ledger::new(),
+    };
+
+    // Neither is saved in snapshots, they're configuration.
+    ledger.use_policy(config.policy);
+    if let Some(currency) = config.currency {
+        ledger.use_currency(currency);
+    }
+
+    let listener = match TcpListener::bind(&args.listen) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("failed to listen, address={}, err={}", args.listen, err);
+            return ExitCode::FAILURE;
+        },
+    };
+    debug!("listening, address={}", args.listen);
+
+    let ledger = SharedLedger::from(ledger);
+    if let Some(address) = args.grpc_listen {
+        let ledger = ledger.clone();
+        thread::spawn(move || {
+            debug!("listening for grpc, address={}", address);
+            if let Err(err) = serve_grpc(ledger, address) {
+                error!("failed to serve grpc, address={}, err={}", address, err);
+                process::exit(1);
+            }
+        });
+    }
+
+    let server = Server::new(ledger, args.export_file.map(PathBuf::from));
+    match server.run(listener) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("failed to accept connection, err={}", err);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(ledger: SharedLedger, address: SocketAddr) -> anyhow::Result<()> {
+    transacto::grpc::Service::new(ledger).run(address)
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_ledger: SharedLedger, _address: SocketAddr) -> anyhow::Result<()> {
+    anyhow::bail!("grpc is not enabled, build with the grpc feature")
+}
+
+fn consume(args: ConsumeArgs, config: Config) -> ExitCode {
+    let key = match args.key_file.clone().or(config.key_file).map(Key::load).transpose() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("failed to load key, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let Some(checkpoint_file) = args.checkpoint_file.clone().or(config.checkpoint.path.clone()) else {
+        error!("a checkpoint is required, offsets are only committed after one");
+        return ExitCode::FAILURE;
+    };
 
-    if args.len() != 2 {
-        error!("Usage: cargo run -- <input_file>");
-        return;
+    // What was consumed since the last commit is consumed again, on top of
+    // the last checkpoint.
+    let mut ledger = if Path::new(&checkpoint_file).exists() {
+        match Ledger::load_with(&checkpoint_file, key.as_ref()) {
+            Ok(ledger) => ledger,
+            Err(err) => {
+                error!("failed to load checkpoint, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        }
+    } else {
+        Ledger::new()
+    };
+
+    ledger.use_policy(config.policy);
+    if let Some(currency) = config.currency {
+        ledger.use_currency(currency);
+    }
+    let policy = checkpoint_policy(args.checkpoint_every, args.checkpoint_interval, &config.checkpoint);
+    let snapshot_options = SnapshotOptions {
+        encoding: args.snapshot_encoding.or(config.snapshot_encoding).unwrap_or_default(),
+        key,
+    };
+    ledger.use_checkpoints(checkpoint_file, policy, snapshot_options);
+
+    match consume_topic(args, &mut ledger) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("failed to consume, err={}", err);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+#[cfg(feature = "kafka")]
+fn consume_topic(args: ConsumeArgs, ledger: &mut Ledger) -> anyhow::Result<()> {
+    let mut source = KafkaSource::new(KafkaOptions {
+        brokers: args.brokers,
+        topic: args.topic,
+        group: args.group,
+        format: args.payload_format,
+        properties: args.properties,
+        until_end: args.until_end,
+    })?;
+    let report = source.run(ledger)?;
+    eprintln!(
+        "consumed: {} messages, {} accepted, {} rejected, {} commits",
+        report.messages, report.accepted, report.rejected, report.commits
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "kafka"))]
+fn consume_topic(_args: ConsumeArgs, _ledger: &mut Ledger) -> anyhow::Result<()> {
+    anyhow::bail!("kafka is not enabled, build with the kafka feature")
+}
+
+fn process(args: ProcessArgs, config: Config) -> ExitCode {
+    let mut options = ProcessingOptions {
+        error_events: Some(Box::new(io::stderr())),
+        error_policy: config.error_policy,
+        max_transactions_per_client: config.velocity.max_transactions_per_client,
+        velocity_window: config.velocity.window,
+        ordering: config.ordering,
+        rules: config.rules,
+        ..ProcessingOptions::default()
+    };
+    if args.strict || config.strict {
+        options.error_policy = ErrorPolicy::strict();
+    }
+    let risk_file = args.risk_file.or(config.risk_report).filter(|_| !args.dry_run);
+    options.risk_metrics = risk_file.is_some();
+    let daily_file = args.daily_file.or(config.daily_report).filter(|_| !args.dry_run);
+    options.daily_activity = daily_file.is_some();
+    let camt053_file = args.camt053_file.or(config.camt053).filter(|_| !args.dry_run);
+    options.client_activity = camt053_file.is_some();
+
+    let key = match args.key_file.or(config.key_file).map(Key::load).transpose() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("failed to load key, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+    let rejects_file = args.rejects_file.or(config.rejects).filter(|_| !args.dry_run);
+    let events_file = args.events_file.or(config.events).filter(|_| !args.dry_run);
+    let balances_file = args.balances_file.or(config.balances).filter(|_| !args.dry_run);
+    // These are written as the file is processed and never finished, so
+    // they can't be encrypted.
+    if key.is_some() && (rejects_file.is_some() || events_file.is_some() || balances_file.is_some()) {
+        error!("the rejects, events and balances files can't be encrypted, they can't be used with a key");
+        return ExitCode::FAILURE;
+    }
+
+    if args.dry_run {
+        options.error_events = None;
+        options.rejects = Some(Box::new(io::stdout()));
+    } else if let Some(rejects_file) = rejects_file {
+        match File::create(rejects_file) {
+            Ok(file) => options.rejects = Some(Box::new(file)),
+            Err(err) => {
+                error!("failed to create rejects file, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    if let Some(events_file) = events_file {
+        match OpenOptions::new().create(true).append(true).open(events_file) {
+            Ok(file) => options.events = Some(Box::new(file)),
+            Err(err) => {
+                error!("failed to open events file, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    if let Some(balances_file) = balances_file {
+        match File::create(balances_file) {
+            Ok(file) => options.balances = Some(Box::new(file)),
+            Err(err) => {
+                error!("failed to create balances file, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    let checkpoint = config.checkpoint;
+    let checkpoint_file = args.checkpoint_file.or(checkpoint.path.clone());
+    let wal_dir = args.wal_dir.or(config.wal);
+    let audit_log_file = args.audit_log_file.or(config.audit_log);
+
+    let loaded = match (args.load_snapshot_file, args.resume) {
+        (Some(snapshot_file), false) => Ledger::load_with(snapshot_file, key.as_ref()).map(Some),
+        (snapshot_file, true) => {
+            let Some(resume_file) = snapshot_file.or_else(|| checkpoint_file.clone()) else {
+                error!("nothing to resume from, a snapshot or a checkpoint is required");
+                return ExitCode::FAILURE;
+            };
+
+            // The journal has whatever happened after the last checkpoint.
+            match &wal_dir {
+                Some(wal_dir) => {
+                    Ledger::recover_with(resume_file, wal_dir, key.as_ref(), config.policy).map(|(ledger, report)| {
+                        eprintln!(
+                            "recovered: {} wal entries replayed, {} already in the checkpoint, {} skipped",
+                            report.replayed, report.in_checkpoint, report.skipped
+                        );
+                        Some(ledger)
+                    })
+                },
+                None => Ledger::load_with(resume_file, key.as_ref()).map(Some),
+            }
+        },
+        (None, false) => Ok(None),
+    };
+
+    // A loaded ledger keeps the way it remembers settled ids.
+    let mut ledger = match loaded {
+        Ok(Some(ledger)) => ledger,
+        Ok(None) => {
+            let mut ledger = Ledger::new();
+            if let Some(id_filter) = config.performance.id_filter {
+                ledger.use_id_filter(BloomFilter::new(id_filter.expected_ids, id_filter.false_positive_rate));
+            }
+            ledger
+        },
+        Err(err) => {
+            error!("failed to load snapshot, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    ledger.use_policy(config.policy);
+    if let Some(currency) = args.currency.or(config.currency) {
+        ledger.use_currency(currency);
+    }
+    let settlements_file = args.settlements_file.or(config.settlements).filter(|_| !args.dry_run);
+    if settlements_file.is_some() {
+        ledger.use_settlement();
+    }
+    if args.lifetime_totals || config.export.lifetime_totals {
+        ledger.use_lifetime_totals();
+    }
+    if let Some(threshold) = config.chargeback_alerts {
+        ledger.use_chargeback_monitor(threshold, |alert: ChargebackAlert| match alert.client_id {
+            Some(client_id) => warn!(
+                "chargeback rate over the threshold, client={}, rate={}",
+                client_id,
+                alert.rate.rate()
+            ),
+            None => warn!("global chargeback rate over the threshold, rate={}", alert.rate.rate()),
+        });
+    }
+
+    if let Some(clients_file) = args.clients_file.or(config.clients) {
+        let loaded = File::open(&clients_file).map_err(anyhow::Error::from).and_then(|file| {
+            data::load_client_metadata(&mut ledger, BufReader::new(file), format_of(None, &clients_file))
+        });
+        if let Err(err) = loaded {
+            error!("failed to load clients, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let client_ids_file = args.client_ids_file.or(config.client_ids);
+    let mut client_ids = match &client_ids_file {
+        Some(client_ids_file) => match File::open(client_ids_file) {
+            Ok(file) => match read_client_ids(BufReader::new(file), key.as_ref()) {
+                Ok(client_ids) => Some(client_ids),
+                Err(err) => {
+                    error!("failed to load client ids, err={}", err);
+                    return ExitCode::FAILURE;
+                },
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Some(ClientIds::new()),
+            Err(err) => {
+                error!("failed to open client ids file, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        },
+        None => None,
+    };
+
+    let snapshot_options = SnapshotOptions {
+        encoding: args.snapshot_encoding.or(config.snapshot_encoding).unwrap_or_default(),
+        key: key.clone(),
+    };
+
+    // A dry run doesn't leave any state behind.
+    if !args.dry_run {
+        if let Some(wal_dir) = wal_dir {
+            match Wal::open(wal_dir) {
+                Ok(wal) => ledger.use_wal(wal),
+                Err(err) => {
+                    error!("failed to open wal, err={}", err);
+                    return ExitCode::FAILURE;
+                },
+            }
+        }
+
+        if let Some(audit_log_file) = audit_log_file {
+            match AuditLog::open(audit_log_file) {
+                Ok(audit) => ledger.use_audit_log(audit),
+                Err(err) => {
+                    error!("failed to open audit log, err={}", err);
+                    return ExitCode::FAILURE;
+                },
+            }
+        }
+
+        if let Some(checkpoint_file) = checkpoint_file {
+            let policy = checkpoint_policy(args.checkpoint_every, args.checkpoint_interval, &checkpoint);
+            ledger.use_checkpoints(checkpoint_file, policy, snapshot_options.clone());
+        }
+    }
+
+    if args.resume {
+        options.resume_after = Some(ledger.position());
+    }
+
+    let started = Instant::now();
+    let stats_before = ledger.stats();
+
+    let input_format = format_of(args.input_format.or(config.input_format), &args.input_file);
+    let processed = match (&mut client_ids, args.statement_client, args.fix) {
+        (_, Some(client_id), _) => StatementFormat::from_path(&args.input_file)
+            .ok_or_else(|| anyhow::anyhow!("unknown bank statement format, expected an ofx, qfx, qif or xml file"))
+            .and_then(|format| data::process_statement(&args.input_file, format, client_id, &mut ledger, options)),
+        (_, None, true) => data::process_fix(&args.input_file, config.fix, &mut ledger, options),
+        (Some(client_ids), None, false) => {
+            data::process_file_with_client_ids(&args.input_file, input_format, &mut ledger, client_ids, options)
+        },
+        (None, None, false) => data::process_file(&args.input_file, input_format, &mut ledger, options),
+    };
+    let report = match processed {
+        Ok(report) => report,
+        Err(err) => {
+            error!("failed to process file, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+    debug!(
+        "processed csv, accepted={}, rejected={}",
+        report.accepted,
+        report.rejected()
+    );
+
+    if args.summary || config.summary || args.dry_run {
+        print_summary(&report, &stats_before, &ledger.stats(), started.elapsed());
+    }
+
+    if args.dry_run {
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(risk_file) = risk_file {
+        let written = write_file(&risk_file, key.as_ref(), |writer| {
+            data::export_risk(&report.risk, format_of(None, &risk_file), writer)
+        });
+        if let Err(err) = written {
+            error!("failed to write risk report, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(daily_file) = daily_file {
+        let written = write_file(&daily_file, key.as_ref(), |writer| {
+            data::export_daily_activity(&report.daily, format_of(None, &daily_file), writer)
+        });
+        if let Err(err) = written {
+            error!("failed to write daily report, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(camt053_file) = camt053_file {
+        let currency = args
+            .camt053_currency
+            .or(config.camt053_currency)
+            .or(ledger.currency())
+            .unwrap_or(Currency::NONE);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let created = Timestamp::from_unix(now.as_secs() as i64).expect("now is a valid timestamp");
+        let written = write_file(camt053_file, key.as_ref(), |writer| {
+            data::export_camt053(&ledger, &report.activity, currency, created, writer)
+        });
+        if let Err(err) = written {
+            error!("failed to write camt.053 statements, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(settlements_file) = settlements_file {
+        // The open period isn't saved, see `Ledger::use_settlement`.
+        ledger.close_day();
+        let written = write_file(&settlements_file, key.as_ref(), |writer| {
+            data::export_settlements(&ledger.take_settlements(), format_of(None, &settlements_file), writer)
+        });
+        if let Err(err) = written {
+            error!("failed to write settlements, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Err(err) = ledger.checkpoint() {
+        error!("failed to save checkpoint, err={}", err);
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(snapshot_file) = &args.save_snapshot_file {
+        if let Err(err) = ledger.save_with(snapshot_file, &snapshot_options) {
+            error!("failed to save snapshot, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let (Some(client_ids_file), Some(client_ids)) = (&client_ids_file, &client_ids) {
+        let written = write_file(client_ids_file, key.as_ref(), |writer| Ok(client_ids.write(writer)?));
+        if let Err(err) = written {
+            error!("failed to write client ids, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let output_format = args.output_format.or(config.output_format);
+    let export_options = ExportOptions {
+        with_metadata: args.with_metadata || config.with_metadata,
+        client_ids: client_ids.as_ref(),
+        locked_only: args.locked_only || config.export.locked_only,
+        non_zero_only: args.non_zero_only || config.export.non_zero_only,
+        clients: args
+            .only_clients
+            .or(config.export.clients)
+            .map(|clients| clients.into_iter().collect()),
+        sort: args.sort.or(config.export.sort),
+        columns: args.columns.or(config.export.columns),
+        decimal_places: args.decimal_places.or(config.export.decimal_places),
+    };
+    let exported = match args.output_file.or(config.output) {
+        _ if args.pretty => {
+            let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+            data::export_table(&ledger, &export_options, color, io::stdout())
+        },
+        Some(output_file) => File::create(&output_file)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                export(
+                    &ledger,
+                    &export_options,
+                    format_of(output_format, &output_file),
+                    file,
+                    key.as_ref(),
+                )
+            }),
+        None => export(
+            &ledger,
+            &export_options,
+            output_format.unwrap_or_default(),
+            io::stdout(),
+            key.as_ref(),
+        ),
+    };
+    if let Err(err) = exported {
+        error!("failed to export accounts, err={}", err);
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(html_file) = args.html_file.or(config.html_report) {
+        let written = write_file(html_file, key.as_ref(), |writer| {
+            data::export_html(&ledger, &report, &export_options, writer)
+        });
+        if let Err(err) = written {
+            error!("failed to write html report, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(xlsx_file) = args.xlsx_file.or(config.xlsx) {
+        let summary = (args.xlsx_summary || config.xlsx_summary).then_some(&report);
+        let written = write_file(xlsx_file, key.as_ref(), |writer| {
+            data::export_xlsx(&ledger, &export_options, summary, writer)
+        });
+        if let Err(err) = written {
+            error!("failed to write xlsx workbook, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Creates the file and writes it with `write`, encrypted if there is a key.
+fn write_file(
+    path: impl AsRef<Path>,
+    key: Option<&Key>,
+    write: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let Some(key) = key else {
+        write(&mut file)?;
+        file.flush()?;
+        return Ok(());
+    };
+
+    let mut writer = EncryptedWriter::new(key, file)?;
+    write(&mut writer)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Reads the client ids, decrypting them with the key if they're encrypted.
+fn read_client_ids(mut reader: impl BufRead, key: Option<&Key>) -> anyhow::Result<ClientIds> {
+    if !is_encrypted(reader.fill_buf()?) {
+        return Ok(ClientIds::read(reader)?);
+    }
+
+    let Some(key) = key else {
+        anyhow::bail!("the client ids are encrypted, a key is required");
+    };
+    Ok(ClientIds::read(EncryptedReader::new(key, reader)?)?)
+}
+
+/// Exports the accounts, encrypted if there is a key.
+fn export(
+    ledger: &Ledger,
+    options: &ExportOptions,
+    format: Format,
+    writer: impl Write + Send,
+    key: Option<&Key>,
+) -> anyhow::Result<()> {
+    let export = |writer: &mut (dyn Write + Send)| data::export_with(ledger, options, format, writer);
+    let Some(key) = key else {
+        let mut writer = writer;
+        return export(&mut writer);
+    };
+
+    let mut writer = EncryptedWriter::new(key, writer)?;
+    export(&mut writer)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+fn validate(args: ValidateArgs, config: Config) -> ExitCode {
+    let mut options = ProcessingOptions::default();
+    let rejects_file = args.rejects_file.or(config.rejects);
+    if config.key_file.is_some() && rejects_file.is_some() {
+        error!("the rejects file can't be encrypted, it can't be used with a key");
+        return ExitCode::FAILURE;
+    }
+    if let Some(rejects_file) = rejects_file {
+        match File::create(rejects_file) {
+            Ok(file) => options.rejects = Some(Box::new(file)),
+            Err(err) => {
+                error!("failed to create rejects file, err={}", err);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    let input_format = format_of(args.input_format.or(config.input_format), &args.input_file);
+    let report = match data::validate_file(&args.input_file, input_format, options) {
+        Ok(report) => report,
+        Err(err) => {
+            error!("failed to validate file, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    println!("valid records: {}", report.accepted);
+    println!(
+        "invalid records: {} (parse errors: {}, validation errors: {})",
+        report.rejected(),
+        report.parse_errors,
+        report.validation_errors
+    );
+    for rejected in &report.errors {
+        println!("line {}: {}", rejected.line, rejected.error);
+    }
+    if report.rejected() > report.errors.len() {
+        println!("... and {} more", report.rejected() - report.errors.len());
+    }
+
+    if report.rejected() > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_summary(report: &ProcessingReport, before: &LedgerStats, after: &LedgerStats, elapsed: Duration) {
+    let counts = &report.accepted_by_type;
+    let records = report.accepted + report.rejected();
+
+    if report.skipped > 0 {
+        eprintln!("records: {} skipped, already executed", report.skipped);
+    }
+    eprintln!("records: {} accepted, {} rejected", report.accepted, report.rejected());
+    eprintln!(
+        "accepted: {} deposits, {} withdrawals, {} disputes, {} resolves, {} chargebacks, {} reversals, {} freezes, \
+         {} unfreezes, {} closures, {} credit limits, {} accruals, {} day closes",
+        counts.deposits,
+        counts.withdrawals,
+        counts.disputes,
+        counts.resolves,
+        counts.chargebacks,
+        counts.reversals,
+        counts.freezes,
+        counts.unfreezes,
+        counts.closures,
+        counts.credit_limits,
+        counts.accruals,
+        counts.day_closes
+    );
+    for (code, count) in &report.rejected_by_code {
+        eprintln!("rejected: {} {}", count, code);
+    }
+    if report.flagged > 0 {
+        eprintln!("flagged: {} records", report.flagged);
+    }
+    if report.out_of_order > 0 {
+        eprintln!("out of order: {} records", report.out_of_order);
+    }
+    eprintln!(
+        "clients: {} created, {} locked",
+        after.clients.saturating_sub(before.clients),
+        after.locked_clients.saturating_sub(before.locked_clients)
+    );
+    eprintln!(
+        "elapsed: {:.3}s, {:.0} records/s",
+        elapsed.as_secs_f64(),
+        records as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+}
+
+fn inspect(args: InspectArgs, config: Config) -> ExitCode {
+    let key = match args.key_file.or(config.key_file).map(Key::load).transpose() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("failed to load key, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let ledger: Ledger = match Ledger::load_with(&args.snapshot_file, key.as_ref()) {
+        Ok(ledger) => ledger,
+        Err(err) => {
+            error!("failed to read snapshot, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    if args.client.is_none() && args.tx.is_none() {
+        println!("{:#?}", ledger.stats());
+        println!("state digest {}", ledger.state_digest().root());
+        return ExitCode::SUCCESS;
     }
 
-    let mut ledger = Ledger::new();
+    let mut found = true;
+
+    if let Some(client_id) = args.client {
+        match ledger.clients.get(&client_id) {
+            Some(client) => println!(
+                "client {}: available {}, held {}, total {}, locked {}",
+                client.id(),
+                client.available(),
+                client.held(),
+                client.get_total(),
+                client.locked()
+            ),
+            None => {
+                println!("client {}: not found", client_id);
+                found = false;
+            },
+        }
+    }
 
-    if let Err(err) = data::process_csv(&args[1], &mut ledger) {
-        error!("failed to process csv, err={}", err);
-        return;
+    if let Some(id) = args.tx {
+        match ledger.transactions.get(&id) {
+            Some(Transaction::Deposit(deposit)) => println!(
+                "tx {}: deposit of {} by client {}, dispute status {:?}",
+                id,
+                deposit.amount(),
+                deposit.client_id(),
+                deposit.dispute_status()
+            ),
+            Some(Transaction::Withdrawal(withdrawal)) => println!(
+                "tx {}: withdrawal of {} by client {}",
+                id,
+                withdrawal.amount(),
+                withdrawal.client_id()
+            ),
+            Some(Transaction::DisputedWithdrawal(withdrawal)) => println!(
+                "tx {}: withdrawal of {} by client {}, dispute status {:?}",
+                id,
+                withdrawal.amount(),
+                withdrawal.client_id(),
+                withdrawal.dispute_status()
+            ),
+            Some(_) => println!("tx {}: not a deposit or withdrawal", id),
+            None if ledger.is_settled(id) => println!("tx {}: settled, no longer stored", id),
+            None => {
+                println!("tx {}: not found", id);
+                found = false;
+            },
+        }
     }
 
-    if let Err(err) = data::export_csv(&ledger) {
-        error!("failed to export csv, err={}", err);
+    if found {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
     }
 }
+
+fn diff(args: DiffArgs, config: Config) -> ExitCode {
+    let key = match args.key_file.or(config.key_file).map(Key::load).transpose() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("failed to load key, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let loaded = Ledger::load_with(&args.before_file, key.as_ref())
+        .and_then(|before: Ledger| Ok((before, Ledger::load_with(&args.after_file, key.as_ref())?)));
+    let (before, after): (Ledger, Ledger) = match loaded {
+        Ok(ledgers) => ledgers,
+        Err(err) => {
+            error!("failed to read snapshot, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    println!("{}", before.diff(&after));
+
+    ExitCode::SUCCESS
+}
+
+fn merge(args: MergeArgs, config: Config) -> ExitCode {
+    let key = match args.key_file.or(config.key_file).map(Key::load).transpose() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("failed to load key, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let policy = MergePolicy {
+        duplicate_ids: if args.keep_duplicates {
+            DuplicatePolicy::KeepExisting
+        } else {
+            DuplicatePolicy::Fail
+        },
+        clients: if args.disjoint_clients {
+            ClientPolicy::Fail
+        } else {
+            ClientPolicy::Sum
+        },
+    };
+
+    let mut merged: Option<Ledger> = None;
+    for snapshot_file in &args.snapshot_files {
+        let ledger = match Ledger::load_with(snapshot_file, key.as_ref()) {
+            Ok(ledger) => ledger,
+            Err(err) => {
+                error!("failed to read snapshot {}, err={}", snapshot_file, err);
+                return ExitCode::FAILURE;
+            },
+        };
+
+        let Some(merged) = &mut merged else {
+            merged = Some(ledger);
+            continue;
+        };
+        match merged.merge(ledger, &policy) {
+            Ok(report) => println!(
+                "{}: {} clients added, {} merged, {} transactions added, {} duplicate ids",
+                snapshot_file,
+                report.clients_added,
+                report.clients_merged,
+                report.transactions_added,
+                report.duplicate_ids.len()
+            ),
+            Err(err) => {
+                error!("failed to merge snapshot {}, err={}", snapshot_file, err);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    let options = SnapshotOptions {
+        encoding: args.snapshot_encoding.or(config.snapshot_encoding).unwrap_or_default(),
+        key,
+    };
+    if let Some(merged) = merged {
+        if let Err(err) = merged.save_with(&args.save_snapshot_file, &options) {
+            error!("failed to save snapshot, err={}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn decrypt(args: DecryptArgs) -> ExitCode {
+    let key = match Key::load(&args.key_file) {
+        Ok(key) => key,
+        Err(err) => {
+            error!("failed to load key, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let decrypted = File::open(&args.input_file)
+        .and_then(|file| EncryptedReader::new(&key, BufReader::new(file)))
+        .and_then(|mut reader| io::copy(&mut reader, &mut io::stdout().lock()));
+    if let Err(err) = decrypted {
+        error!("failed to decrypt file, err={}", err);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn verify_audit(args: VerifyAuditArgs) -> ExitCode {
+    let verified = File::open(args.audit_log_file)
+        .map_err(AuditError::from)
+        .and_then(|file| verify_audit_log(BufReader::new(file)));
+    match verified {
+        Ok(head) => {
+            println!("{} entries, head {}", head.entries, head.hash);
+            ExitCode::SUCCESS
+        },
+        Err(err) => {
+            error!("audit log failed verification, err={}", err);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn replay(args: ReplayArgs) -> ExitCode {
+    let ledger = match File::open(&args.events_file)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(Ledger::replay_reader(BufReader::new(file))?))
+    {
+        Ok(ledger) => ledger,
+        Err(err) => {
+            error!("failed to replay events, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let Some(expected_file) = args.expected_file else {
+        if let Err(err) = data::export(&ledger, Format::Csv, io::stdout()) {
+            error!("failed to export accounts, err={}", err);
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    };
+
+    let expected_format = format_of(args.expected_format, &expected_file);
+    let expected = match File::open(&expected_file)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| data::read_accounts(BufReader::new(file), expected_format))
+    {
+        Ok(expected) => expected,
+        Err(err) => {
+            error!("failed to read expected accounts, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let accounts = expected.len();
+    let mismatches = ledger.verify_accounts(expected);
+    for mismatch in &mismatches {
+        match (&mismatch.expected, &mismatch.actual) {
+            (Some(expected), Some(actual)) => println!(
+                "client {}: expected available {}, held {}, locked {}, replayed available {}, held {}, locked {}",
+                mismatch.client,
+                expected.available,
+                expected.held,
+                expected.locked,
+                actual.available,
+                actual.held,
+                actual.locked
+            ),
+            (Some(_), None) => println!("client {}: not in the events", mismatch.client),
+            (None, _) => println!("client {}: not in the expected accounts", mismatch.client),
+        }
+    }
+
+    if mismatches.is_empty() {
+        println!("verified: {} accounts match", accounts);
+        ExitCode::SUCCESS
+    } else {
+        println!("{} accounts don't match", mismatches.len());
+        ExitCode::FAILURE
+    }
+}
+
+fn generate(args: GenerateArgs) -> ExitCode {
+    let config = GeneratorConfig {
+        seed: args.seed,
+        rows: args.rows,
+        clients: args.clients,
+        withdrawal_rate: args.withdrawal_rate,
+        dispute_rate: args.dispute_rate,
+        chargeback_rate: args.chargeback_rate,
+    };
+
+    let generated = match &args.output_file {
+        Some(output_file) => File::create(output_file)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| generate::write_csv(config, BufWriter::new(file))),
+        None => generate::write_csv(config, io::stdout().lock()),
+    };
+    if let Err(err) = generated {
+        error!("failed to generate transactions, err={}", err);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn bench(args: BenchArgs) -> ExitCode {
+    let config = GeneratorConfig {
+        seed: args.seed,
+        rows: args.rows,
+        clients: args.clients,
+        ..GeneratorConfig::default()
+    };
+
+    let report = match bench::run(config) {
+        Ok(report) => report,
+        Err(err) => {
+            error!("failed to run benchmark, err={}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    println!("transactions: {}", report.transactions);
+    println!("elapsed: {:.3}s", report.elapsed.as_secs_f64());
+    println!("throughput: {:.0} transactions/s", report.throughput());
+    println!(
+        "latency: p50 {:?}, p90 {:?}, p99 {:?}, p99.9 {:?}, max {:?}",
+        report.p50, report.p90, report.p99, report.p999, report.max
+    );
+    match report.peak_memory {
+        Some(bytes) => println!("peak memory: {:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+        None => println!("peak memory: unknown"),
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,376 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, warn};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::accounting::shared::SharedLedger;
+use crate::accounting::ClientId;
+use crate::data::{self, ClientRecord, Format, JsonlReader, ProcessingOptions, ProcessingReport, TransactionReader};
+
+#[cfg(test)]
+#[path = "server_tests.rs"]
+mod server_tests;
+
+/// The largest request body accepted, larger ones are rejected with a 413.
+pub const MAX_BODY: usize = 16 * 1024 * 1024;
+
+const MAX_HEADERS: usize = 100;
+/// The longest request line or header accepted, newline included.
+const MAX_LINE: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A request as far as the server cares, see `Server::handle`.
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Without the `?`, empty without one.
+    pub query: String,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: &impl Serialize) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            // Only ever given values that serialize.
+            body: serde_json::to_vec(value).expect("response is serializable"),
+        }
+    }
+
+    fn error(status: u16, code: &str, message: impl ToString) -> Response {
+        Response::json(status, &json!({ "code": code, "message": message.to_string() }))
+    }
+}
+
+/// A small HTTP/1.1 service in front of a shared ledger, one thread per
+/// connection and one request per connection. The endpoints are:
+///
+/// - `POST /transactions`, a transaction as a JSON object with the csv
+///   columns, answered with a 422 if it's rejected.
+/// - `POST /transactions/batch`, transactions as JSON lines, or as csv with
+///   a `text/csv` content type, answered with how many were accepted and
+///   the first `data::MAX_REPORTED_ERRORS` rejected.
+/// - `GET /clients/{id}`, the client's account.
+/// - `GET /clients/{id}/disputes` and `GET /disputes`, the open disputes.
+/// - `GET /clients/{id}/withdrawals`, what the client withdrew by day, see
+///   `Ledger::daily_withdrawals`.
+/// - `GET /accounts`, every account as csv, or as JSON lines with
+///   `?format=jsonl`.
+/// - `POST /export`, writes the accounts to the export file, if there is
+///   one.
+///
+/// Errors are JSON objects with a `code` and a `message`.
+#[derive(Clone)]
+pub struct Server {
+    ledger: SharedLedger,
+    export_file: Option<PathBuf>,
+}
+
+impl Server {
+    pub fn new(ledger: SharedLedger, export_file: Option<PathBuf>) -> Server {
+        Server { ledger, export_file }
+    }
+
+    /// Serves connections until accepting one fails.
+    pub fn run(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || {
+                if let Err(err) = server.connection(stream) {
+                    warn!("failed to serve connection, err={}", err);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    fn connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let response = match read_request(&mut BufReader::new(&stream)) {
+            Ok(Ok(request)) => {
+                debug!("request, method={}, path={}", request.method, request.path);
+                self.handle(&request)
+            },
+            Ok(Err(response)) => response,
+            // Closed before sending anything.
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        write_response(BufWriter::new(&stream), &response)
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        let method = request.method.as_str();
+        match (segments.as_slice(), method) {
+            (["transactions"], "POST") => self.submit(&request.body),
+            (["transactions", "batch"], "POST") => {
+                let csv = request
+                    .content_type
+                    .as_deref()
+                    .map_or(false, |content_type| content_type.starts_with("text/csv"));
+                self.submit_batch(&request.body, csv)
+            },
+            (["clients", id], "GET") => match id.parse::<ClientId>() {
+                Ok(id) => self.client(id),
+                Err(_) => Response::error(404, "client_not_found", "client not found"),
+            },
+            (["clients", id, "disputes"], "GET") => match id.parse::<ClientId>() {
+                Ok(id) => Response::json(
+                    200,
+                    &self
+                        .ledger
+                        .read(|ledger| ledger.open_disputes_for(id).collect::<Vec<_>>()),
+                ),
+                Err(_) => Response::error(404, "client_not_found", "client not found"),
+            },
+            (["clients", id, "withdrawals"], "GET") => match id.parse::<ClientId>() {
+                Ok(id) => Response::json(
+                    200,
+                    &self.ledger.read(|ledger| {
+                        ledger
+                            .daily_withdrawals_for(id)
+                            .map(|(date, withdrawn)| json!({ "date": date, "withdrawn": withdrawn }))
+                            .collect::<Vec<_>>()
+                    }),
+                ),
+                Err(_) => Response::error(404, "client_not_found", "client not found"),
+            },
+            (["disputes"], "GET") => {
+                let mut disputes = self.ledger.read(|ledger| ledger.open_disputes().collect::<Vec<_>>());
+                disputes.sort_unstable_by_key(|dispute| dispute.tx);
+                Response::json(200, &disputes)
+            },
+            (["accounts"], "GET") => self.accounts(&request.query),
+            (["export"], "POST") => self.export(),
+            (
+                ["transactions"]
+                | ["transactions", "batch"]
+                | ["clients", _]
+                | ["clients", _, "disputes"]
+                | ["clients", _, "withdrawals"]
+                | ["disputes"]
+                | ["accounts"]
+                | ["export"],
+                _,
+            ) => Response::error(405, "method_not_allowed", "method not allowed"),
+            _ => Response::error(404, "not_found", "not found"),
+        }
+    }
+
+    fn submit(&self, body: &[u8]) -> Response {
+        // Written again on a single line, for `JsonlReader`.
+        let line = match serde_json::from_slice::<Value>(body) {
+            Ok(object @ Value::Object(_)) => object.to_string(),
+            Ok(_) => return Response::error(400, "bad_request", "transaction is not an object"),
+            Err(err) => return Response::error(400, "bad_request", err),
+        };
+
+        match self.process(&mut JsonlReader::new(line.as_bytes())) {
+            Ok(report) => match report.errors.first() {
+                Some(rejected) => Response::error(422, rejected.error.code(), &rejected.error),
+                None => Response::json(200, &json!({ "accepted": report.accepted })),
+            },
+            Err(err) => Response::error(500, "internal_error", err),
+        }
+    }
+
+    fn submit_batch(&self, body: &[u8], csv: bool) -> Response {
+        let processed = match csv {
+            true => TransactionReader::new(body)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut reader| self.process(&mut reader)),
+            false => self.process(&mut JsonlReader::new(body)),
+        };
+
+        match processed {
+            Ok(report) => {
+                let errors: Vec<Value> = report
+                    .errors
+                    .iter()
+                    .map(|rejected| {
+                        json!({
+                            "line": rejected.line,
+                            "code": rejected.error.code(),
+                            "message": rejected.error.to_string(),
+                        })
+                    })
+                    .collect();
+                Response::json(
+                    200,
+                    &json!({
+                        "accepted": report.accepted,
+                        "rejected": report.rejected(),
+                        "errors": errors,
+                    }),
+                )
+            },
+            Err(err) => Response::error(400, "bad_request", err),
+        }
+    }
+
+    fn process(&self, reader: &mut impl data::RecordReader) -> anyhow::Result<ProcessingReport> {
+        self.ledger
+            .write(|ledger| data::process_records(reader, ledger, ProcessingOptions::default()))
+    }
+
+    fn client(&self, id: ClientId) -> Response {
+        match self.ledger.client(id) {
+            Some(client) => Response::json(200, &ClientRecord::from(&client)),
+            None => Response::error(404, "client_not_found", "client not found"),
+        }
+    }
+
+    fn accounts(&self, query: &str) -> Response {
+        let format = query.split('&').find_map(|pair| pair.strip_prefix("format="));
+        let (format, content_type) = match format {
+            None | Some("csv") => (Format::Csv, "text/csv"),
+            Some("jsonl") => (Format::Jsonl, "application/x-ndjson"),
+            Some(_) => return Response::error(400, "bad_request", "unknown format, expected csv or jsonl"),
+        };
+
+        let mut body = Vec::new();
+        match self.ledger.read(|ledger| data::export(ledger, format, &mut body)) {
+            Ok(()) => Response {
+                status: 200,
+                content_type,
+                body,
+            },
+            Err(err) => Response::error(500, "internal_error", err),
+        }
+    }
+
+    fn export(&self) -> Response {
+        let Some(path) = &self.export_file else {
+            return Response::error(409, "no_export_file", "the server has no export file");
+        };
+
+        let format = Format::from_path(path).unwrap_or_default();
+        let exported = File::create(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| self.ledger.read(|ledger| data::export(ledger, format, file)));
+        match exported {
+            Ok(()) => Response::json(200, &json!({ "path": path })),
+            Err(err) => Response::error(500, "internal_error", err),
+        }
+    }
+}
+
+// The request, or the response to send if it's not one the server can
+// handle.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Result<Request, Response>> {
+    let mut line = String::new();
+    match read_line(reader, &mut line)? {
+        Some(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+        Some(_) => {},
+        None => return Ok(Err(Response::error(400, "bad_request", "request line is too long"))),
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Ok(Err(Response::error(400, "bad_request", "invalid request line")));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        ..Request::default()
+    };
+
+    let mut content_length = 0;
+    let mut chunked = false;
+    for _ in 0..=MAX_HEADERS {
+        line.clear();
+        if read_line(reader, &mut line)?.is_none() {
+            return Ok(Err(Response::error(431, "header_too_large", "header is too long")));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            if chunked {
+                return Ok(Err(Response::error(
+                    411,
+                    "length_required",
+                    "chunked bodies aren't supported",
+                )));
+            }
+            if content_length > MAX_BODY {
+                return Ok(Err(Response::error(413, "payload_too_large", "body is too large")));
+            }
+
+            request.body.resize(content_length, 0);
+            reader.read_exact(&mut request.body)?;
+            return Ok(Ok(request));
+        }
+
+        let Some((name, value)) = header.split_once(':') else {
+            return Ok(Err(Response::error(400, "bad_request", "invalid header")));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => match value.parse() {
+                Ok(length) => content_length = length,
+                Err(_) => return Ok(Err(Response::error(400, "bad_request", "invalid content length"))),
+            },
+            "content-type" => request.content_type = Some(value.to_string()),
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            _ => {},
+        }
+    }
+
+    Ok(Err(Response::error(400, "bad_request", "too many headers")))
+}
+
+// Reads a line of at most `MAX_LINE` bytes, None if it's longer, so that a
+// client can't make the server buffer an endless line.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<Option<usize>> {
+    let read = reader.by_ref().take(MAX_LINE as u64).read_line(line)?;
+    match read == MAX_LINE && !line.ends_with('\n') {
+        true => Ok(None),
+        false => Ok(Some(read)),
+    }
+}
+
+fn write_response(mut writer: impl Write, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    };
+
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
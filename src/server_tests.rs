@@ -0,0 +1,232 @@
+use std::io::Read;
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use super::*;
+use crate::accounting::ledger::Ledger;
+use crate::accounting::policy::LedgerPolicy;
+
+fn request(method: &str, path: &str, body: &str) -> Request {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        content_type: None,
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+fn json_body(response: &Response) -> Result<Value> {
+    Ok(serde_json::from_slice(&response.body)?)
+}
+
+#[test]
+fn test_handle() -> Result<()> {
+    let ledger = SharedLedger::new();
+    let server = Server::new(ledger.clone(), None);
+
+    let response = server.handle(&request(
+        "POST",
+        "/transactions",
+        r#"{
+            "type": "deposit",
+            "client": 1,
+            "tx": 1,
+            "amount": "10"
+        }"#,
+    ));
+    assert_eq!(response.status, 200);
+    assert_eq!(json_body(&response)?, json!({ "accepted": 1 }));
+
+    let response = server.handle(&request(
+        "POST",
+        "/transactions",
+        r#"{"type": "withdrawal", "client": 1, "tx": 2, "amount": "50"}"#,
+    ));
+    assert_eq!(response.status, 422);
+    assert_eq!(
+        json_body(&response)?,
+        json!({
+            "code": "insufficient_funds",
+            "message": "failed to execute transaction, err=insufficient funds",
+        })
+    );
+    assert_eq!(server.handle(&request("POST", "/transactions", "[1]")).status, 400);
+
+    let response = server.handle(&request(
+        "POST",
+        "/transactions/batch",
+        "{\"type\": \"deposit\", \"client\": 2, \"tx\": 3, \"amount\": 5}\n\
+         {\"type\": \"dispute\", \"client\": 2, \"tx\": 3}\n\
+         {\"type\": \"dispute\", \"client\": 2, \"tx\": 9}\n",
+    ));
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        json_body(&response)?,
+        json!({
+            "accepted": 2,
+            "rejected": 1,
+            "errors": [{
+                "line": 3,
+                "code": "transaction_not_found",
+                "message": "failed to execute transaction, err=transaction not found",
+            }],
+        })
+    );
+
+    let mut batch = request("POST", "/transactions/batch", "type,client,tx,amount\ndeposit,1,4,1\n");
+    batch.content_type = Some("text/csv; charset=utf-8".to_string());
+    assert_eq!(json_body(&server.handle(&batch))?["accepted"], json!(1));
+    assert_eq!(ledger.client(1).map(|client| client.available()), Some(dec!(11)));
+
+    let response = server.handle(&request("GET", "/clients/2", ""));
+    assert_eq!(
+        json_body(&response)?,
+        json!({
+            "client": 2,
+            "available": "0",
+            "held": "5",
+            "total": "5",
+            "locked": false,
+        })
+    );
+    assert_eq!(server.handle(&request("GET", "/clients/7", "")).status, 404);
+    assert_eq!(server.handle(&request("GET", "/clients/x", "")).status, 404);
+
+    let disputes = json!([{ "tx": 3, "client": 2, "held": "5" }]);
+    assert_eq!(json_body(&server.handle(&request("GET", "/disputes", "")))?, disputes);
+    assert_eq!(
+        json_body(&server.handle(&request("GET", "/clients/2/disputes", "")))?,
+        disputes
+    );
+    assert_eq!(
+        json_body(&server.handle(&request("GET", "/clients/1/disputes", "")))?,
+        json!([])
+    );
+
+    let response = server.handle(&request("GET", "/accounts?format=jsonl", ""));
+    assert_eq!(response.content_type, "application/x-ndjson");
+    assert_eq!(std::str::from_utf8(&response.body)?.lines().count(), 2);
+    assert_eq!(server.handle(&request("GET", "/accounts?format=xml", "")).status, 400);
+
+    assert_eq!(server.handle(&request("POST", "/export", "")).status, 409);
+    assert_eq!(server.handle(&request("DELETE", "/clients/1", "")).status, 405);
+    assert_eq!(server.handle(&request("GET", "/nothing", "")).status, 404);
+
+    Ok(())
+}
+
+#[test]
+fn test_withdrawals() -> Result<()> {
+    let mut ledger = Ledger::new();
+    ledger.use_policy(LedgerPolicy {
+        daily_withdrawal_limit: Some(dec!(10)),
+        ..LedgerPolicy::default()
+    });
+    let server = Server::new(SharedLedger::from(ledger), None);
+    let response = server.handle(&request(
+        "POST",
+        "/transactions/batch",
+        "{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": 50}
+{\"type\": \"withdrawal\", \"client\": 1, \"tx\": 2, \"amount\": 4, \"timestamp\": \"2024-05-01T09:00:00Z\"}
+{\"type\": \"withdrawal\", \"client\": 1, \"tx\": 3, \"amount\": 7, \"timestamp\": \"2024-05-01T10:00:00Z\"}
+",
+    ));
+    assert_eq!(json_body(&response)?["accepted"], json!(2));
+
+    let response = server.handle(&request("GET", "/clients/1/withdrawals", ""));
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        json_body(&response)?,
+        json!([{ "date": "2024-05-01", "withdrawn": "4" }])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_export() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("transacto-server-{}.csv", std::process::id()));
+    let server = Server::new(SharedLedger::new(), Some(path.clone()));
+    server.handle(&request(
+        "POST",
+        "/transactions",
+        r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "10"}"#,
+    ));
+
+    assert_eq!(server.handle(&request("POST", "/export", "")).status, 200);
+    let exported = std::fs::read_to_string(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(exported, "client,available,held,total,locked\n1,10,0,10,false\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_read_request_limits() -> Result<()> {
+    let long = "a".repeat(MAX_LINE);
+    let read = |request: String| -> Result<Result<Request, Response>> {
+        Ok(read_request(&mut io::Cursor::new(request.into_bytes()))?)
+    };
+
+    let Err(response) = read(format!("GET /{} HTTP/1.1\r\n\r\n", long))? else {
+        panic!("request line wasn't rejected");
+    };
+    assert_eq!(response.status, 400);
+
+    let Err(response) = read(format!("GET /accounts HTTP/1.1\r\nX-Long: {}\r\n\r\n", long))? else {
+        panic!("header wasn't rejected");
+    };
+    assert_eq!(response.status, 431);
+
+    let Ok(request) = read(format!("GET /accounts HTTP/1.1\r\nX-Long: {}\r\n\r\n", &long[..100]))? else {
+        panic!("request was rejected");
+    };
+    assert_eq!(request.path, "/accounts");
+
+    Ok(())
+}
+
+#[test]
+fn test_connection() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let server = Server::new(SharedLedger::new(), None);
+    thread::spawn(move || server.run(listener));
+
+    let send = |request: &str| -> Result<String> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.write_all(request.as_bytes())?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    };
+
+    let body = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "10"}"#;
+    let response = send(&format!(
+        "POST /transactions HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    ))?;
+    assert_eq!(
+        response,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 14\r\nConnection: close\r\n\r\n{\"accepted\":1}"
+    );
+
+    let response = send("GET /accounts HTTP/1.1\r\n\r\n")?;
+    assert!(response.ends_with("\r\n\r\nclient,available,held,total,locked\n1,10,0,10,false\n"));
+
+    let response = send(&format!(
+        "POST /transactions/batch HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+        MAX_BODY + 1
+    ))?;
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+    let response = send("nonsense\r\n\r\n")?;
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+    Ok(())
+}